@@ -1,29 +1,37 @@
-use crate::{expression::Expression, function::Arguments, terminal::Name};
+use crate::{expression::Expression, function::Arguments, span::Span, terminal::Name};
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", content = "value"))]
 pub enum Prefix {
-	ParenExpression(Expression),
+	ParenExpression(Expression, Span),
 	Name(Name),
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", content = "value"))]
 #[repr(u8)]
 pub enum Index {
 	/// Index using the `'[' exp ']'` syntax
-	Expression(Expression),
+	Expression(Expression, Span),
 	/// Index using the `'.' Name` syntax
 	Member(Name),
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Call {
 	/// If this member is `Some`, the function is defined and called as a method
 	pub oname: Option<Name>,
 	/// Function arguments
 	pub argu: Arguments,
+	pub span: Span,
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", content = "value"))]
 #[repr(u8)]
 pub enum Suffix {
 	Call(Call),
@@ -33,15 +41,17 @@ pub enum Suffix {
 impl Suffix {
 	pub fn to_u8(&self) -> u8 {
 		match self {
-			Suffix::Call(_) => Suffix::Call as u8,
-			Suffix::Index(_) => Suffix::Index as u8,
+			Suffix::Call(_) => 0,
+			Suffix::Index(_) => 1,
 		}
 	}
 }
 
 /// A series of names, beginning with a [Name] or [Expression].
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Affix {
 	pub pfix: Prefix,
 	pub suflist: Vec<Suffix>,
+	pub span: Span,
 }
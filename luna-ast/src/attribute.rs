@@ -1,18 +1,23 @@
-use crate::terminal::Name;
+use crate::{span::Span, terminal::Name};
 
 /// Grammar: `Name attrib {',' Name attrib}`
 pub type AttributeNameList = Vec<AttributeName>;
 
 /// See [AttributeNameList].
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AttributeName {
 	pub name: Name,
 	pub attr: Attribute,
+	pub span: Span,
 }
 
 /// A [variable](crate::variable::Variable) attribute. The attribute can
 /// either be `const` or `close` as denoted by the Lua specification.
 ///
 /// Grammar: `['<' Name '>']`
+///
+/// Without an attribute, the span is empty, where the next token starts.
 #[derive(Clone, Debug, PartialEq)]
-pub struct Attribute(pub Option<Name>);
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Attribute(pub Option<Name>, pub Span);
@@ -0,0 +1,463 @@
+//! # Building Trees
+//!
+//! Short constructors for syntax trees, for tests and for tools that write
+//! Lua code. Every node gets an empty span, so built trees are compared to
+//! parsed ones with [assert_spanless_eq](crate::assert_spanless_eq), and
+//! turned into source with the [printer](crate::print). The parser's scope
+//! resolution and compiler tell nodes apart by identity rather than by span,
+//! so they take built trees too; every instruction is then on line 1.
+//!
+//! ```
+//! use luna_ast::{build::*, operation::BinaryOperation::*};
+//!
+//! // local x = f(1 + 2, "a").y
+//! let f = call(var("f"), vec![bin(num(1), Add, num(2)), string("a")]);
+//! let chunk = chunk(vec![local(&["x"], vec![field(f, "y")])]);
+//! ```
+//!
+//! Expressions are built from the inside out. Suffixes like [field] and
+//! [call] extend the expression they're given when it's a variable or a
+//! call, and put it in parentheses otherwise, as `("s"):upper()` needs.
+
+use crate::{
+	affix::{Affix, Call, Index, Prefix, Suffix},
+	attribute::{Attribute, AttributeName},
+	expression::{AnonFunctionDefinition, BinaryExpression, Expression, UnaryExpression, Value},
+	function::{Arguments, FunctionBody, FunctionCall, FunctionName, ParameterList, VarArgs},
+	operation::{BinaryOperation, UnaryOperation},
+	span::Span,
+	statement::{
+		Assignment, ForExpression, ForList, IfBlock, IfTree, Label, LocalDefinitionWithAttribute,
+		LocalFunctionDefinition, NamedFunctionDefinition, RepeatUntil, Statement, While,
+	},
+	table::{BracketField, Field, NameField, TableConstructor},
+	terminal::{LiteralString, Name, Numeral},
+	variable::Variable,
+	Block, Chunk, ReturnStatement,
+};
+
+pub fn name(value: &str) -> Name {
+	Name {
+		value: value.to_owned(),
+		span: Span::default(),
+	}
+}
+
+fn names(values: &[&str]) -> Vec<Name> {
+	values.iter().map(|value| name(value)).collect()
+}
+
+pub fn nil() -> Expression {
+	Value::Nil(Span::default()).into()
+}
+
+pub fn boolean(value: bool) -> Expression {
+	match value {
+		false => Value::False(Span::default()),
+		true => Value::True(Span::default()),
+	}
+	.into()
+}
+
+/// An integer numeral. Negative integers print as negations, so `-1` is
+/// better built with [un] to parse back as the same tree.
+pub fn num(value: i64) -> Expression {
+	Value::Numeral(Numeral::Integer(value), Span::default()).into()
+}
+
+pub fn float(value: f64) -> Expression {
+	Value::Numeral(Numeral::Float(value), Span::default()).into()
+}
+
+pub fn string(value: impl Into<Vec<u8>>) -> Expression {
+	Value::from(literal(value)).into()
+}
+
+fn literal(value: impl Into<Vec<u8>>) -> LiteralString {
+	LiteralString {
+		value: value.into(),
+		span: Span::default(),
+	}
+}
+
+/// `...`
+pub fn varargs() -> Expression {
+	Value::from(VarArgs {
+		span: Span::default(),
+	})
+	.into()
+}
+
+/// The variable `value`.
+pub fn var(value: &str) -> Expression {
+	Value::from(Variable::Name(name(value))).into()
+}
+
+pub fn bin(left: Expression, op: BinaryOperation, right: Expression) -> Expression {
+	BinaryExpression {
+		left: Box::new(left),
+		op,
+		right: Box::new(right),
+		span: Span::default(),
+	}
+	.into()
+}
+
+pub fn un(op: UnaryOperation, ex: Expression) -> Expression {
+	UnaryExpression {
+		op,
+		ex: Box::new(ex),
+		span: Span::default(),
+	}
+	.into()
+}
+
+pub fn paren(ex: Expression) -> Expression {
+	Value::ParenExpression(ex, Span::default()).into()
+}
+
+/// An anonymous function. Its parameters end with `...` if `vararg` is set.
+pub fn function(params: &[&str], vararg: bool, bl: Block) -> Expression {
+	Value::from(AnonFunctionDefinition {
+		fbody: body(params, vararg, bl),
+		span: Span::default(),
+	})
+	.into()
+}
+
+fn body(params: &[&str], vararg: bool, bl: Block) -> FunctionBody {
+	let nlist = names(params);
+	let oplist = match (nlist.is_empty(), vararg) {
+		(true, false) => None,
+		(false, false) => Some(ParameterList::NameList(nlist)),
+		(false, true) => Some(ParameterList::NameListWithVarArgs(
+			nlist,
+			VarArgs {
+				span: Span::default(),
+			},
+		)),
+		(true, true) => Some(ParameterList::VarArgs(VarArgs {
+			span: Span::default(),
+		})),
+	};
+	FunctionBody {
+		oplist,
+		bl,
+		span: Span::default(),
+	}
+}
+
+pub fn table(fields: Vec<Field>) -> Expression {
+	Value::from(constructor(fields)).into()
+}
+
+fn constructor(fields: Vec<Field>) -> TableConstructor {
+	TableConstructor {
+		oflist: (!fields.is_empty()).then_some(fields),
+		span: Span::default(),
+	}
+}
+
+/// A positional field, `{val}`.
+pub fn item(val: Expression) -> Field {
+	val.into()
+}
+
+/// `{key = val}`
+pub fn keyed(key: &str, val: Expression) -> Field {
+	NameField {
+		tabname: name(key),
+		val,
+		span: Span::default(),
+	}
+	.into()
+}
+
+/// `{[key] = val}`
+pub fn bracket(key: Expression, val: Expression) -> Field {
+	BracketField {
+		tabexp: key,
+		val,
+		span: Span::default(),
+	}
+	.into()
+}
+
+/// `ex.key`
+pub fn field(ex: Expression, key: &str) -> Expression {
+	suffix(ex, Suffix::Index(Index::Member(name(key))))
+}
+
+/// `ex[key]`
+pub fn index(ex: Expression, key: Expression) -> Expression {
+	suffix(ex, Suffix::Index(Index::Expression(key, Span::default())))
+}
+
+/// `ex(args)`
+pub fn call(ex: Expression, args: Vec<Expression>) -> Expression {
+	suffix(ex, Suffix::Call(arguments(None, args)))
+}
+
+/// `ex:method(args)`
+pub fn method(ex: Expression, method: &str, args: Vec<Expression>) -> Expression {
+	suffix(ex, Suffix::Call(arguments(Some(name(method)), args)))
+}
+
+/// `ex "s"`
+pub fn call_string(ex: Expression, s: impl Into<Vec<u8>>) -> Expression {
+	suffix(ex, Suffix::Call(call_with(None, literal(s).into())))
+}
+
+/// `ex {fields}`
+pub fn call_table(ex: Expression, fields: Vec<Field>) -> Expression {
+	suffix(
+		ex,
+		Suffix::Call(call_with(None, constructor(fields).into())),
+	)
+}
+
+fn arguments(oname: Option<Name>, args: Vec<Expression>) -> Call {
+	let argu = Arguments::ClosedExpressionList((!args.is_empty()).then_some(args), Span::default());
+	call_with(oname, argu)
+}
+
+fn call_with(oname: Option<Name>, argu: Arguments) -> Call {
+	Call {
+		oname,
+		argu,
+		span: Span::default(),
+	}
+}
+
+/// Adds `suf` to `ex`, the way the parser would have read it.
+fn suffix(ex: Expression, suf: Suffix) -> Expression {
+	let mut affix = match ex {
+		Expression::Value(val) => match *val {
+			Value::Variable(Variable::Name(name)) => affix(Prefix::Name(name)),
+			Value::Variable(Variable::Affixed(affix)) => affix,
+			Value::FunctionCall(FunctionCall {
+				mut affix, call, ..
+			}) => {
+				affix.suflist.push(Suffix::Call(call));
+				affix
+			}
+			Value::ParenExpression(ex, span) => affix(Prefix::ParenExpression(ex, span)),
+			val => affix(Prefix::ParenExpression(val.into(), Span::default())),
+		},
+		ex => affix(Prefix::ParenExpression(ex, Span::default())),
+	};
+	match suf {
+		Suffix::Call(call) => Value::FunctionCall(FunctionCall {
+			affix,
+			call,
+			span: Span::default(),
+		}),
+		suf => {
+			affix.suflist.push(suf);
+			Value::Variable(Variable::Affixed(affix))
+		}
+	}
+	.into()
+}
+
+fn affix(pfix: Prefix) -> Affix {
+	Affix {
+		pfix,
+		suflist: Vec::new(),
+		span: Span::default(),
+	}
+}
+
+pub fn chunk(stlist: Vec<Statement>) -> Chunk {
+	Chunk(block(stlist))
+}
+
+pub fn block(stlist: Vec<Statement>) -> Block {
+	Block {
+		stlist,
+		oret: None,
+		span: Span::default(),
+	}
+}
+
+/// A block that ends with `return values`.
+pub fn block_return(stlist: Vec<Statement>, values: Vec<Expression>) -> Block {
+	Block {
+		oret: Some(ReturnStatement {
+			oelist: (!values.is_empty()).then_some(values),
+			span: Span::default(),
+		}),
+		..block(stlist)
+	}
+}
+
+/// `local names = values`, without attributes.
+pub fn local(names: &[&str], values: Vec<Expression>) -> Statement {
+	let atlist = names.iter().map(|value| (*value, None)).collect::<Vec<_>>();
+	local_attributes(&atlist, values)
+}
+
+/// `local names = values`, where each name may have an attribute, like
+/// `("x", Some("const"))` for `x <const>`.
+pub fn local_attributes(names: &[(&str, Option<&str>)], values: Vec<Expression>) -> Statement {
+	let atlist = names
+		.iter()
+		.map(|(value, attr)| AttributeName {
+			name: name(value),
+			attr: Attribute(attr.map(name), Span::default()),
+			span: Span::default(),
+		})
+		.collect();
+	LocalDefinitionWithAttribute {
+		atlist,
+		oelist: (!values.is_empty()).then_some(values),
+		span: Span::default(),
+	}
+	.into()
+}
+
+/// `targets = values`
+///
+/// # Panics
+///
+/// If a target isn't a variable.
+pub fn assign(targets: Vec<Expression>, values: Vec<Expression>) -> Statement {
+	let vlist = targets
+		.into_iter()
+		.map(|ex| match ex {
+			Expression::Value(val) => match *val {
+				Value::Variable(var) => var,
+				val => panic!("{val:?} can't be assigned to"),
+			},
+			ex => panic!("{ex:?} can't be assigned to"),
+		})
+		.collect();
+	Assignment {
+		vlist,
+		elist: values,
+		span: Span::default(),
+	}
+	.into()
+}
+
+/// A call, as a statement.
+///
+/// # Panics
+///
+/// If `ex` isn't a call.
+pub fn call_statement(ex: Expression) -> Statement {
+	match ex {
+		Expression::Value(val) => match *val {
+			Value::FunctionCall(call) => call.into(),
+			val => panic!("{val:?} isn't a call"),
+		},
+		ex => panic!("{ex:?} isn't a call"),
+	}
+}
+
+pub fn do_block(bl: Block) -> Statement {
+	Statement::Do(Box::new(bl), Span::default())
+}
+
+pub fn while_do(cond: Expression, bl: Block) -> Statement {
+	While {
+		cond,
+		bl,
+		span: Span::default(),
+	}
+	.into()
+}
+
+pub fn repeat_until(bl: Block, cond: Expression) -> Statement {
+	RepeatUntil {
+		cond,
+		bl,
+		span: Span::default(),
+	}
+	.into()
+}
+
+/// An `if` statement with a branch for each condition, and an `else`
+/// branch if `otherwise` is given.
+///
+/// # Panics
+///
+/// If there are no branches.
+pub fn if_then(branches: Vec<(Expression, Block)>, otherwise: Option<Block>) -> Statement {
+	let mut branches = branches.into_iter().map(|(cond, bl)| IfBlock {
+		cond,
+		bl,
+		span: Span::default(),
+	});
+	let initial = branches.next().expect("an if statement needs a branch");
+	IfTree {
+		initial,
+		elseifs: branches.collect(),
+		otherwise,
+		span: Span::default(),
+	}
+	.into()
+}
+
+/// `for var = start, limit, step do bl end`
+pub fn for_range(
+	var: &str, start: Expression, limit: Expression, step: Option<Expression>, bl: Block,
+) -> Statement {
+	ForExpression {
+		name: name(var),
+		range: start..=limit,
+		step,
+		bl,
+		span: Span::default(),
+	}
+	.into()
+}
+
+/// `for names in values do bl end`
+pub fn for_in(names: &[&str], values: Vec<Expression>, bl: Block) -> Statement {
+	ForList {
+		nlist: self::names(names),
+		elist: values,
+		bl,
+		span: Span::default(),
+	}
+	.into()
+}
+
+/// `function path:method(params) bl end`, where `path` is a name followed
+/// by its fields, like `["a", "b"]` for `a.b`.
+pub fn function_statement(
+	path: &[&str], method: Option<&str>, params: &[&str], vararg: bool, bl: Block,
+) -> Statement {
+	NamedFunctionDefinition {
+		fname: FunctionName {
+			nlist: names(path),
+			objname: method.map(name),
+			span: Span::default(),
+		},
+		fbody: body(params, vararg, bl),
+		span: Span::default(),
+	}
+	.into()
+}
+
+pub fn local_function(value: &str, params: &[&str], vararg: bool, bl: Block) -> Statement {
+	LocalFunctionDefinition {
+		name: name(value),
+		fbody: body(params, vararg, bl),
+		span: Span::default(),
+	}
+	.into()
+}
+
+pub fn label(value: &str) -> Statement {
+	Label(name(value), Span::default()).into()
+}
+
+pub fn goto(label: &str) -> Statement {
+	Statement::Goto(name(label), Span::default())
+}
+
+pub fn break_loop() -> Statement {
+	Statement::Break(Span::default())
+}
@@ -0,0 +1,91 @@
+//! # Comments
+//!
+//! Comments aren't part of the syntax tree, but tools like formatters and
+//! documentation generators need them. When asked to, the parser collects
+//! them into [Comments], which attaches each comment to the tokens around it
+//! so it can be looked up from any [Spanned] node.
+
+use crate::span::{Span, Spanned};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", content = "value"))]
+pub enum CommentKind {
+	/// `-- ...` up to the end of the line
+	Line,
+	/// `--[[ ... ]]`, with the number of `=` signs in its brackets
+	Long(usize),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Comment {
+	pub kind: CommentKind,
+	/// The text between the delimiters, as written.
+	pub text: Vec<u8>,
+	pub span: Span,
+}
+
+/// A comment, along with where it sits between two tokens.
+#[derive(Clone, Debug, PartialEq)]
+struct Attached {
+	comment: Comment,
+	/// The end of the token before the comment (0 if there is none)
+	prev: usize,
+	/// The start of the token after the comment
+	next: usize,
+	/// Whether the comment is on the same line as the token before it
+	trailing: bool,
+}
+
+/// The comments of a chunk, in source order.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Comments {
+	list: Vec<Attached>,
+}
+
+impl Comments {
+	/// Records `comment`, which follows a token ending at `prev` and precedes
+	/// one starting at `next`.
+	///
+	/// `trailing` comments share a line with the token before them.
+	pub fn push(&mut self, comment: Comment, prev: usize, next: usize, trailing: bool) {
+		self.list.push(Attached {
+			comment,
+			prev,
+			next,
+			trailing,
+		});
+	}
+
+	/// All comments, in source order.
+	pub fn iter(&self) -> impl Iterator<Item = &Comment> {
+		self.list.iter().map(|a| &a.comment)
+	}
+
+	pub fn len(&self) -> usize {
+		self.list.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.list.is_empty()
+	}
+
+	/// The comments on their own lines directly before `node`.
+	pub fn leading(&self, node: &impl Spanned) -> impl Iterator<Item = &Comment> {
+		let start = node.span().start;
+		self.list
+			.iter()
+			.filter(move |a| a.next == start && !a.trailing)
+			.map(|a| &a.comment)
+	}
+
+	/// The comments on the same line directly after `node`.
+	pub fn trailing(&self, node: &impl Spanned) -> impl Iterator<Item = &Comment> {
+		let end = node.span().end;
+		self.list
+			.iter()
+			.filter(move |a| a.prev == end && a.trailing)
+			.map(|a| &a.comment)
+	}
+}
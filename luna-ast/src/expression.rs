@@ -1,6 +1,7 @@
 use crate::{
 	function::{FunctionBody, FunctionCall, VarArgs},
 	operation::{BinaryOperation, UnaryOperation},
+	span::Span,
 	table::{Field, TableConstructor},
 	terminal::{LiteralString, Numeral},
 	variable::Variable,
@@ -15,7 +16,11 @@ pub type ExpressionList = Vec<Expression>;
 /// ```
 /// Grammar (functiondef): `<function> funcbody`
 #[derive(Clone, Debug, PartialEq)]
-pub struct AnonFunctionDefinition(pub FunctionBody);
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AnonFunctionDefinition {
+	pub fbody: FunctionBody,
+	pub span: Span,
+}
 
 impl From<AnonFunctionDefinition> for Value {
 	fn from(value: AnonFunctionDefinition) -> Self {
@@ -24,14 +29,16 @@ impl From<AnonFunctionDefinition> for Value {
 }
 
 /// An infix expression with two parameters.
+///
+/// Operator precedence and associativity are already resolved by the
+/// shape of the tree: `1 - 2 - 3` has `1 - 2` as its `left` operand.
 #[derive(Clone, Debug, PartialEq)]
-pub enum BinaryExpression {
-	AsValue(Box<Value>),
-	AsExpression {
-		left: Box<Value>,
-		op: BinaryOperation,
-		right: Box<Expression>,
-	},
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BinaryExpression {
+	pub left: Box<Expression>,
+	pub op: BinaryOperation,
+	pub right: Box<Expression>,
+	pub span: Span,
 }
 
 impl From<BinaryExpression> for Expression {
@@ -41,9 +48,11 @@ impl From<BinaryExpression> for Expression {
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct UnaryExpression {
 	pub op: UnaryOperation,
 	pub ex: Box<Expression>,
+	pub span: Span,
 }
 
 impl From<UnaryExpression> for Expression {
@@ -53,34 +62,42 @@ impl From<UnaryExpression> for Expression {
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", content = "value"))]
 pub enum Value {
-	Nil,
-	False,
-	True,
-	Numeral(Numeral),
+	Nil(Span),
+	False(Span),
+	True(Span),
+	Numeral(Numeral, Span),
 	LiteralString(LiteralString),
 	VarArgs(VarArgs),
 	AnonFunctionDefinition(AnonFunctionDefinition),
 	Variable(Variable),
 	FunctionCall(FunctionCall),
-	ParenExpression(Expression),
+	ParenExpression(Expression, Span),
 	TableConstructor(TableConstructor),
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", content = "value"))]
 pub enum Expression {
+	/// A single operand
+	Value(Box<Value>),
 	BinaryExpression(BinaryExpression),
 	UnaryExpression(UnaryExpression),
+	/// An expression that couldn't be parsed, left by error recovery
+	Error(Span),
 }
 
-impl From<Expression> for Field {
-	fn from(value: Expression) -> Self {
-		Self::Expression(value)
+impl From<Value> for Expression {
+	fn from(value: Value) -> Self {
+		Self::Value(Box::new(value))
 	}
 }
 
-impl From<Expression> for Value {
+impl From<Expression> for Field {
 	fn from(value: Expression) -> Self {
-		Self::ParenExpression(value)
+		Self::Expression(value)
 	}
 }
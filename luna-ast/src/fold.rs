@@ -0,0 +1,398 @@
+//! # Constant Folding
+//!
+//! Evaluates operations on literals ahead of time, like `constfolding` in
+//! `lcode.c`, and removes `if` and `while` branches whose conditions are
+//! always false.
+//!
+//! Folding never changes what a program does. Operations that could raise
+//! an error, like dividing by zero or a bitwise operation on a float without
+//! an integer value, are left for the program to run, and so are results Lua
+//! doesn't fold either: NaN and floating-point zeroes, whose sign is easy to
+//! lose. Besides arithmetic, comparisons and concatenations whose result
+//! can't depend on metamethods or the locale are folded too.
+
+use std::mem;
+
+use crate::{
+	expression::{Expression, Value},
+	operation::{BinaryOperation, UnaryOperation},
+	span::{Span, Spanned},
+	statement::{IfTree, Statement},
+	terminal::{LiteralString, Numeral},
+	visit_mut::{walk_block_mut, walk_expression_mut, VisitMut},
+	Block, Chunk,
+};
+
+/// Folds the constants of `chunk`, and removes its dead branches.
+pub fn fold_chunk(chunk: &mut Chunk) {
+	Folder.visit_chunk_mut(chunk);
+}
+
+/// Folds the constants of `ex`.
+pub fn fold_expression(ex: &mut Expression) {
+	Folder.visit_expression_mut(ex);
+}
+
+/// The value of a literal.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Constant {
+	Nil,
+	Boolean(bool),
+	Integer(i64),
+	Float(f64),
+	String(Vec<u8>),
+}
+
+impl Constant {
+	/// The value of `ex`, if it's a literal, possibly in parentheses.
+	pub fn of(ex: &Expression) -> Option<Self> {
+		let Expression::Value(val) = ex else {
+			return None;
+		};
+		Some(match &**val {
+			Value::Nil(_) => Self::Nil,
+			Value::False(_) => Self::Boolean(false),
+			Value::True(_) => Self::Boolean(true),
+			Value::Numeral(Numeral::Integer(i), _) => Self::Integer(*i),
+			Value::Numeral(Numeral::Float(f), _) => Self::Float(*f),
+			Value::LiteralString(s) => Self::String(s.value.clone()),
+			Value::ParenExpression(ex, _) => return Self::of(ex),
+			_ => return None,
+		})
+	}
+
+	/// A literal for this value at `span`.
+	pub fn to_expression(&self, span: Span) -> Expression {
+		let value = match self {
+			Self::Nil => Value::Nil(span),
+			Self::Boolean(false) => Value::False(span),
+			Self::Boolean(true) => Value::True(span),
+			Self::Integer(i) => Value::Numeral(Numeral::Integer(*i), span),
+			Self::Float(f) => Value::Numeral(Numeral::Float(*f), span),
+			Self::String(s) => Value::LiteralString(LiteralString {
+				value: s.clone(),
+				span,
+			}),
+		};
+		value.into()
+	}
+
+	/// Whether the value counts as true in a condition.
+	pub fn is_truthy(&self) -> bool {
+		!matches!(self, Self::Nil | Self::Boolean(false))
+	}
+
+	/// The integer with this value (`luaV_tointegerns` with `F2Ieq`). Strings
+	/// aren't converted, since `lcode.c` only folds numerals.
+	fn to_integer(&self) -> Option<i64> {
+		match self {
+			Self::Integer(i) => Some(*i),
+			Self::Float(f) if f.floor() == *f => float_to_integer(*f),
+			_ => None,
+		}
+	}
+
+	fn to_float(&self) -> Option<f64> {
+		match self {
+			Self::Integer(i) => Some(*i as f64),
+			Self::Float(f) => Some(*f),
+			_ => None,
+		}
+	}
+}
+
+struct Folder;
+
+impl VisitMut for Folder {
+	fn visit_expression_mut(&mut self, ex: &mut Expression) {
+		walk_expression_mut(self, ex);
+		if let Some(folded) = fold(ex) {
+			*ex = folded;
+		}
+	}
+
+	fn visit_block_mut(&mut self, bl: &mut Block) {
+		walk_block_mut(self, bl);
+		let stlist = mem::take(&mut bl.stlist);
+		for stat in stlist {
+			match stat {
+				Statement::While(w) if is_false(&w.cond) => {}
+				Statement::IfTree(tree) => bl.stlist.extend(prune(tree)),
+				stat => bl.stlist.push(stat),
+			}
+		}
+	}
+}
+
+fn is_false(ex: &Expression) -> bool {
+	Constant::of(ex).is_some_and(|c| !c.is_truthy())
+}
+
+/// Removes the branches of `tree` that can't be taken. A branch that's always
+/// taken becomes the `else` branch, or a `do` block if it's the only one left.
+fn prune(mut tree: IfTree) -> Option<Statement> {
+	let mut branches = Vec::new();
+	for ifb in std::iter::once(tree.initial).chain(tree.elseifs) {
+		match Constant::of(&ifb.cond) {
+			Some(c) if c.is_truthy() => {
+				tree.otherwise = Some(ifb.bl);
+				break;
+			}
+			Some(_) => {}
+			None => branches.push(ifb),
+		}
+	}
+
+	let mut branches = branches.into_iter();
+	match branches.next() {
+		Some(initial) => Some(Statement::IfTree(IfTree {
+			initial,
+			elseifs: branches.collect(),
+			otherwise: tree.otherwise,
+			span: tree.span,
+		})),
+		// The `else` block is left, where the whole statement was
+		None => tree
+			.otherwise
+			.map(|bl| Statement::Do(Box::new(bl), tree.span)),
+	}
+}
+
+/// The folded value of `ex`, whose operands have been folded already.
+fn fold(ex: &Expression) -> Option<Expression> {
+	let span = ex.span();
+	let value = match ex {
+		Expression::Value(val) => match &**val {
+			// Parentheses don't change a single value
+			Value::ParenExpression(..) => Constant::of(ex)?,
+			_ => return None,
+		},
+		Expression::UnaryExpression(ex) => fold_unary(&ex.op, &Constant::of(&ex.ex)?)?,
+		Expression::BinaryExpression(ex) => {
+			let left = Constant::of(&ex.left)?;
+			match ex.op {
+				// Short-circuiting only needs the left operand. The right one is
+				// kept as it is, to keep a call or `...` from being truncated.
+				BinaryOperation::And if !left.is_truthy() => left,
+				BinaryOperation::Or if left.is_truthy() => left,
+				_ => fold_binary(&ex.op, &left, &Constant::of(&ex.right)?)?,
+			}
+		}
+		Expression::Error(_) => return None,
+	};
+	Some(value.to_expression(span))
+}
+
+/// The value of `op` applied to `v`, if it's known while compiling. Code
+/// generators fold their own operands with this.
+pub fn fold_unary(op: &UnaryOperation, v: &Constant) -> Option<Constant> {
+	match op {
+		UnaryOperation::Negate => match v {
+			Constant::Integer(i) => Some(Constant::Integer(i.wrapping_neg())),
+			Constant::Float(f) => float(-f),
+			_ => None,
+		},
+		UnaryOperation::BitwiseNot => Some(Constant::Integer(!v.to_integer()?)),
+		UnaryOperation::Not => Some(Constant::Boolean(!v.is_truthy())),
+		// Like `lcode.c`, which leaves `#` to the VM
+		UnaryOperation::Length => None,
+	}
+}
+
+/// The value of `a op b`, if it's known while compiling. `and` and `or`
+/// always are, since both operands are constants.
+pub fn fold_binary(op: &BinaryOperation, a: &Constant, b: &Constant) -> Option<Constant> {
+	use BinaryOperation::*;
+
+	match op {
+		Add | Subtract | Multiply | Divide | FloorDivide | Power | Modulo => arith(op, a, b),
+		BitwiseAnd | BitwiseXor | BitwiseOr | BitwiseLeftShift | BitwiseRightShift => {
+			let (x, y) = (a.to_integer()?, b.to_integer()?);
+			Some(Constant::Integer(match op {
+				BitwiseAnd => x & y,
+				BitwiseXor => x ^ y,
+				BitwiseOr => x | y,
+				BitwiseLeftShift => shift_left(x, y),
+				_ => shift_left(x, y.wrapping_neg()),
+			}))
+		}
+		Concat => {
+			let mut s = tostring(a)?;
+			s.extend(tostring(b)?);
+			Some(Constant::String(s))
+		}
+		IsEqual => Some(Constant::Boolean(equal(a, b))),
+		IsNotEqual => Some(Constant::Boolean(!equal(a, b))),
+		LessThan => Some(Constant::Boolean(less(a, b, false)?)),
+		LessEqual => Some(Constant::Boolean(less(a, b, true)?)),
+		// `a > b` is `b < a`
+		GreaterThan => Some(Constant::Boolean(less(b, a, false)?)),
+		GreaterEqual => Some(Constant::Boolean(less(b, a, true)?)),
+		// Both operands are constants
+		And => Some(if a.is_truthy() { b.clone() } else { a.clone() }),
+		Or => Some(if a.is_truthy() { a.clone() } else { b.clone() }),
+	}
+}
+
+/// An arithmetic operation on numerals (`luaO_rawarith`), unless it divides
+/// by zero (`validop`).
+fn arith(op: &BinaryOperation, a: &Constant, b: &Constant) -> Option<Constant> {
+	use BinaryOperation::*;
+
+	let (x, y) = (a.to_float()?, b.to_float()?);
+	if matches!(op, Divide | FloorDivide | Modulo) && y == 0.0 {
+		return None;
+	}
+	if let (Constant::Integer(m), Constant::Integer(n)) = (a, b) {
+		let i = match op {
+			Add => Some(m.wrapping_add(*n)),
+			Subtract => Some(m.wrapping_sub(*n)),
+			Multiply => Some(m.wrapping_mul(*n)),
+			FloorDivide => Some(idiv(*m, *n)),
+			Modulo => Some(imod(*m, *n)),
+			// `/` and `^` always give floats
+			_ => None,
+		};
+		if let Some(i) = i {
+			return Some(Constant::Integer(i));
+		}
+	}
+	float(match op {
+		Add => x + y,
+		Subtract => x - y,
+		Multiply => x * y,
+		Divide => x / y,
+		FloorDivide => (x / y).floor(),
+		Power if y == 2.0 => x * x,
+		Power => x.powf(y),
+		Modulo => fmod(x, y),
+		_ => unreachable!("not an arithmetic operation"),
+	})
+}
+
+/// A float result, unless it's NaN or a zero, which `constfolding` leaves alone.
+fn float(f: f64) -> Option<Constant> {
+	match f.is_nan() || f == 0.0 {
+		true => None,
+		false => Some(Constant::Float(f)),
+	}
+}
+
+/// Integer floor division (`luaV_idiv`), for a nonzero `n`.
+fn idiv(m: i64, n: i64) -> i64 {
+	if n == -1 {
+		// Avoids overflowing with the smallest integer
+		return m.wrapping_neg();
+	}
+	let q = m / n;
+	match (m ^ n) < 0 && m % n != 0 {
+		true => q - 1,
+		false => q,
+	}
+}
+
+/// Integer modulo (`luaV_mod`), for a nonzero `n`.
+fn imod(m: i64, n: i64) -> i64 {
+	if n == -1 {
+		return 0;
+	}
+	let r = m % n;
+	match r != 0 && (r ^ n) < 0 {
+		true => r + n,
+		false => r,
+	}
+}
+
+/// Float modulo (`luai_nummod`).
+fn fmod(a: f64, b: f64) -> f64 {
+	let m = a % b;
+	match if m > 0.0 { b < 0.0 } else { m < 0.0 && b != m } {
+		true => m + b,
+		false => m,
+	}
+}
+
+/// `x << y`, shifting right for a negative `y` (`luaV_shiftl`).
+fn shift_left(x: i64, y: i64) -> i64 {
+	match y {
+		..=-64 | 64.. => 0,
+		..=-1 => ((x as u64) >> -y) as i64,
+		_ => ((x as u64) << y) as i64,
+	}
+}
+
+/// The integer `f` is, if it's in range. `f` must have an integer value.
+fn float_to_integer(f: f64) -> Option<i64> {
+	// -2^63 is exact, and 2^63 is the first float past the largest integer
+	match (-9223372036854775808.0..9223372036854775808.0).contains(&f) {
+		true => Some(f as i64),
+		false => None,
+	}
+}
+
+/// A value as a string for concatenation. Floats aren't folded, since their
+/// formatting is up to the C library.
+fn tostring(v: &Constant) -> Option<Vec<u8>> {
+	match v {
+		Constant::String(s) => Some(s.clone()),
+		Constant::Integer(i) => Some(i.to_string().into_bytes()),
+		_ => None,
+	}
+}
+
+/// Raw equality (`luaV_rawequalobj`).
+fn equal(a: &Constant, b: &Constant) -> bool {
+	match (a, b) {
+		(Constant::Integer(i), Constant::Float(f)) | (Constant::Float(f), Constant::Integer(i)) => {
+			Constant::Float(*f).to_integer() == Some(*i)
+		}
+		_ => a == b,
+	}
+}
+
+/// `a < b`, or `a <= b` if `or_equal` is set, for numbers (`LTnum` and
+/// `LEnum`). Strings compare by the locale, so they aren't folded.
+fn less(a: &Constant, b: &Constant, or_equal: bool) -> Option<bool> {
+	Some(match (a, b) {
+		(Constant::Integer(x), Constant::Integer(y)) => match or_equal {
+			true => x <= y,
+			false => x < y,
+		},
+		(Constant::Float(x), Constant::Float(y)) => match or_equal {
+			true => x <= y,
+			false => x < y,
+		},
+		(Constant::Integer(i), Constant::Float(f)) => {
+			if fits_float(*i) {
+				let x = *i as f64;
+				return Some(if or_equal { x <= *f } else { x < *f });
+			}
+			// `i < f` is `i < ceil(f)`, and `i <= f` is `i <= floor(f)`
+			let bound = if or_equal { f.floor() } else { f.ceil() };
+			match float_to_integer(bound) {
+				Some(fi) if or_equal => *i <= fi,
+				Some(fi) => *i < fi,
+				None => *f > 0.0,
+			}
+		}
+		(Constant::Float(f), Constant::Integer(i)) => {
+			if fits_float(*i) {
+				let y = *i as f64;
+				return Some(if or_equal { *f <= y } else { *f < y });
+			}
+			// `f < i` is `floor(f) < i`, and `f <= i` is `ceil(f) <= i`
+			let bound = if or_equal { f.ceil() } else { f.floor() };
+			match float_to_integer(bound) {
+				Some(fi) if or_equal => fi <= *i,
+				Some(fi) => fi < *i,
+				None => *f < 0.0,
+			}
+		}
+		_ => return None,
+	})
+}
+
+/// Whether `i` converts to a float exactly (`l_intfitsf`).
+fn fits_float(i: i64) -> bool {
+	(-(1 << 53)..=(1 << 53)).contains(&i)
+}
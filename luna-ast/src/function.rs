@@ -1,6 +1,7 @@
 use crate::{
 	affix::{Affix, Call},
 	expression::{ExpressionList, Value},
+	span::Span,
 	statement::Statement,
 	table::TableConstructor,
 	terminal::{LiteralString, Name, NameList},
@@ -8,7 +9,10 @@ use crate::{
 };
 
 #[derive(Clone, Debug, PartialEq)]
-pub struct VarArgs;
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VarArgs {
+	pub span: Span,
+}
 
 impl From<VarArgs> for Value {
 	fn from(value: VarArgs) -> Self {
@@ -17,24 +21,30 @@ impl From<VarArgs> for Value {
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FunctionName {
 	/// Names that refer to a single element or elements of subtables
 	pub nlist: Vec<Name>,
 	/// Names that refer to table functions that take `self`
 	/// as the first parameter.
 	pub objname: Option<Name>,
+	pub span: Span,
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FunctionBody {
 	pub oplist: Option<ParameterList>,
 	pub bl: Block,
+	pub span: Span,
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FunctionCall {
 	pub affix: Affix,
 	pub call: Call,
+	pub span: Span,
 }
 
 impl From<FunctionCall> for Statement {
@@ -44,22 +54,20 @@ impl From<FunctionCall> for Statement {
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", content = "value"))]
 pub enum Arguments {
-	ClosedExpressionList(Option<ExpressionList>),
+	ClosedExpressionList(Option<ExpressionList>, Span),
 	TableConstructor(TableConstructor),
 	LiteralString(LiteralString),
 }
 
-impl From<Option<ExpressionList>> for Arguments {
-	fn from(value: Option<ExpressionList>) -> Self {
-		Self::ClosedExpressionList(value)
-	}
-}
-
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", content = "value"))]
 pub enum ParameterList {
 	NameList(NameList),
-	NameListWithVarArgs(NameList),
+	NameListWithVarArgs(NameList, VarArgs),
 	VarArgs(VarArgs),
 }
 
@@ -71,13 +79,13 @@ impl From<NameList> for ParameterList {
 
 impl From<(NameList, VarArgs)> for ParameterList {
 	fn from(value: (NameList, VarArgs)) -> Self {
-		Self::NameListWithVarArgs(value.0)
+		Self::NameListWithVarArgs(value.0, value.1)
 	}
 }
 
 impl From<(VarArgs, NameList)> for ParameterList {
 	fn from(value: (VarArgs, NameList)) -> Self {
-		Self::NameListWithVarArgs(value.1)
+		Self::NameListWithVarArgs(value.1, value.0)
 	}
 }
 
@@ -0,0 +1,217 @@
+//! # Core Language
+//!
+//! A smaller language than the syntax tree, for code generators and
+//! interpreters. [lower](crate::lower::lower) translates a [Chunk](crate::Chunk)
+//! into it, spelling out the syntactic sugar of Lua:
+//!
+//! - `a.b` and `a[b]` are both an [Index].
+//! - A method call `o:m(...)` evaluates `o` once into a [Temp], and calls its
+//!   `m` field with the temporary as the first argument (see [Let]).
+//! - `function a.b:f() end` assigns a [Function] with an explicit `self`
+//!   parameter to `a.b.f`.
+//! - `f'str'` and `f{...}` are calls with a single argument.
+//! - Fields of table constructors are either positional or keyed.
+//! - `return` is an ordinary statement, which ends its block.
+//!
+//! Names still carry the spans they were read from, so the scopes found by a
+//! resolver over the syntax tree apply to the lowered code too.
+
+use crate::{
+	attribute::AttributeName,
+	operation::{BinaryOperation, UnaryOperation},
+	span::Span,
+	terminal::{LiteralString, Name, Numeral},
+};
+
+/// The main function of a chunk, which takes no parameters besides `...`.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Chunk(pub Function);
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Function {
+	/// The named parameters, starting with `self` for methods
+	pub params: Vec<Name>,
+	pub vararg: bool,
+	pub body: Block,
+	/// The number of temporaries the function uses, numbered from 0
+	pub temps: usize,
+	pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Block {
+	pub stats: Vec<Statement>,
+	pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", content = "value"))]
+pub enum Statement {
+	Assign {
+		targets: Vec<Target>,
+		values: Vec<Expression>,
+		span: Span,
+	},
+	/// `local` variables, with their attributes
+	Local {
+		names: Vec<AttributeName>,
+		values: Vec<Expression>,
+		span: Span,
+	},
+	/// A local that's visible in its own function
+	LocalFunction {
+		name: Name,
+		function: Box<Function>,
+		span: Span,
+	},
+	/// A call whose results are discarded: an [Expression::Call], or the
+	/// [Expression::Let] of a method call
+	Call(Expression),
+	Do(Block),
+	While {
+		cond: Expression,
+		body: Block,
+		span: Span,
+	},
+	/// The condition can see the locals of the body.
+	Repeat {
+		body: Block,
+		cond: Expression,
+		span: Span,
+	},
+	/// Each condition in turn, then `otherwise` if none were true
+	If {
+		branches: Vec<(Expression, Block)>,
+		otherwise: Option<Block>,
+		span: Span,
+	},
+	NumericFor {
+		var: Name,
+		start: Expression,
+		limit: Expression,
+		step: Option<Expression>,
+		body: Block,
+		span: Span,
+	},
+	GenericFor {
+		names: Vec<Name>,
+		values: Vec<Expression>,
+		body: Block,
+		span: Span,
+	},
+	Goto(Name),
+	Label(Name),
+	Break(Span),
+	/// Always the last statement of its block
+	Return {
+		values: Vec<Expression>,
+		span: Span,
+	},
+	/// Source that couldn't be parsed
+	Error(Span),
+}
+
+/// Something assigned to.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", content = "value"))]
+pub enum Target {
+	Name(Name),
+	Index(Index),
+}
+
+/// A temporary value, only visible in the [Let] that holds it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Temp(pub usize);
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", content = "value"))]
+pub enum Expression {
+	Nil(Span),
+	False(Span),
+	True(Span),
+	Numeral(Numeral, Span),
+	String(LiteralString),
+	VarArgs(Span),
+	Function(Box<Function>),
+	Name(Name),
+	Temp(Temp),
+	Index(Box<Index>),
+	Call(Box<Call>),
+	Let(Box<Let>),
+	Binary(Box<Binary>),
+	Unary(Box<Unary>),
+	Table(Table),
+	/// Only the first value of a call or `...`, which was in parentheses
+	Truncate(Box<Expression>, Span),
+	/// Source that couldn't be parsed
+	Error(Span),
+}
+
+/// `table[key]`
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Index {
+	pub table: Expression,
+	pub key: Expression,
+	pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Call {
+	pub function: Expression,
+	pub args: Vec<Expression>,
+	pub span: Span,
+}
+
+/// Evaluates `value` into `temp`, then `body`, which is the value of the `Let`.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Let {
+	pub temp: Temp,
+	pub value: Expression,
+	pub body: Expression,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Binary {
+	pub op: BinaryOperation,
+	pub left: Expression,
+	pub right: Expression,
+	pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Unary {
+	pub op: UnaryOperation,
+	pub operand: Expression,
+	pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Table {
+	pub fields: Vec<TableField>,
+	pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", content = "value"))]
+pub enum TableField {
+	/// The next array item. The last one keeps all the values of a call or `...`.
+	Positional(Expression),
+	Keyed {
+		key: Expression,
+		value: Expression,
+	},
+}
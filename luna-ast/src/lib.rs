@@ -1,28 +1,75 @@
+//! # Lua Syntax Trees
+//!
+//! The types here describe Lua source as the parser reads it, starting from a
+//! [Chunk]. Every node keeps the [Span] of the source it came from.
+//!
+//! ## Serialization
+//!
+//! With the `serde` feature, every node can be serialized and deserialized.
+//! The layout is stable:
+//!
+//! - Structs are maps of their fields, by their names in Rust. Tuple structs
+//!   with one field, like [Chunk], are just that field.
+//! - Enums that carry data are maps with the variant name under `"type"` and
+//!   its data under `"value"`. Variants without data have no `"value"`.
+//! - Enums that never carry data, like the operators, are their variant name.
+//! - A [Span] is a map of its `start` and `end` byte offsets, and a range
+//!   (the bounds of a numeric `for`) is a map of its `start` and `end`.
+//! - Names are strings, but string literals are arrays of bytes, since they
+//!   need not be UTF-8.
+//!
+//! For example, in JSON the expression `-x` is
+//!
+//! ```json
+//! {"type": "UnaryExpression", "value": {
+//!     "op": "Negate",
+//!     "ex": {"type": "Value", "value": {"type": "Variable", "value":
+//!         {"type": "Name", "value": {"value": "x", "span": {"start": 1, "end": 2}}}}},
+//!     "span": {"start": 0, "end": 2}}}
+//! ```
+//!
+//! Formats without infinities and NaN, like JSON, can't hold every float.
+
 use expression::ExpressionList;
+use span::Span;
 use statement::Statement;
 
 pub mod affix;
 pub mod attribute;
+pub mod build;
+pub mod comment;
 pub mod expression;
+pub mod fold;
 pub mod function;
+pub mod ir;
+pub mod lower;
 pub mod operation;
+pub mod print;
+pub mod span;
 pub mod statement;
 pub mod table;
 pub mod terminal;
 pub mod variable;
+pub mod visit;
+pub mod visit_mut;
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Chunk(pub Block);
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Block {
 	/// The statements within this block.
 	pub stlist: Vec<Statement>,
 	/// The return statement, if any. Void if `None`.
 	pub oret: Option<ReturnStatement>,
+	pub span: Span,
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ReturnStatement {
 	pub oelist: Option<ExpressionList>,
+	pub span: Span,
 }
@@ -0,0 +1,347 @@
+//! # Lowering
+//!
+//! Translates a syntax tree into the [core language](crate::ir).
+
+use crate::{
+	affix::{Affix, Call, Index, Prefix, Suffix},
+	expression::{Expression, Value},
+	function::{Arguments, FunctionBody, FunctionCall, ParameterList},
+	ir,
+	span::{Span, Spanned},
+	statement::Statement,
+	table::{Field, TableConstructor},
+	terminal::{LiteralString, Name},
+	variable::Variable,
+	Block, Chunk,
+};
+
+/// Lowers `chunk` into the core language.
+pub fn lower(chunk: &Chunk) -> ir::Chunk {
+	let mut lowerer = Lowerer::default();
+	let body = lowerer.block(&chunk.0);
+	ir::Chunk(ir::Function {
+		params: Vec::new(),
+		vararg: true,
+		body,
+		temps: lowerer.temps,
+		span: chunk.0.span,
+	})
+}
+
+#[derive(Default)]
+struct Lowerer {
+	/// The number of temporaries of the function being lowered
+	temps: usize,
+}
+
+impl Lowerer {
+	fn temp(&mut self) -> ir::Temp {
+		self.temps += 1;
+		ir::Temp(self.temps - 1)
+	}
+
+	/// Lowers a function, whose temporaries are numbered on their own. A
+	/// method gets an explicit `self`, at the span of the method's name.
+	fn function(&mut self, fbody: &FunctionBody, method: Option<&Name>) -> ir::Function {
+		let mut params = Vec::new();
+		if let Some(name) = method {
+			params.push(Name {
+				value: "self".to_owned(),
+				span: name.span,
+			});
+		}
+		let vararg = match &fbody.oplist {
+			None => false,
+			Some(ParameterList::NameList(nlist)) => {
+				params.extend_from_slice(nlist);
+				false
+			}
+			Some(ParameterList::NameListWithVarArgs(nlist, _)) => {
+				params.extend_from_slice(nlist);
+				true
+			}
+			Some(ParameterList::VarArgs(_)) => true,
+		};
+
+		let outer = std::mem::take(&mut self.temps);
+		let body = self.block(&fbody.bl);
+		let temps = std::mem::replace(&mut self.temps, outer);
+		ir::Function {
+			params,
+			vararg,
+			body,
+			temps,
+			span: fbody.span,
+		}
+	}
+
+	fn block(&mut self, bl: &Block) -> ir::Block {
+		let mut stats: Vec<_> = bl
+			.stlist
+			.iter()
+			.filter_map(|stat| self.statement(stat))
+			.collect();
+		if let Some(ret) = &bl.oret {
+			stats.push(ir::Statement::Return {
+				values: self.exps(ret.oelist.iter().flatten()),
+				span: ret.span,
+			});
+		}
+		ir::Block {
+			stats,
+			span: bl.span,
+		}
+	}
+
+	fn statement(&mut self, stat: &Statement) -> Option<ir::Statement> {
+		Some(match stat {
+			Statement::End(_) => return None,
+			Statement::Assignment(assign) => {
+				let mut targets = Vec::new();
+				for var in &assign.vlist {
+					match self.target(var) {
+						Some(target) => targets.push(target),
+						None => return Some(ir::Statement::Error(assign.span)),
+					}
+				}
+				ir::Statement::Assign {
+					targets,
+					values: self.exps(&assign.elist),
+					span: assign.span,
+				}
+			}
+			Statement::FunctionCall(call) => ir::Statement::Call(self.function_call(call)),
+			Statement::Label(label) => ir::Statement::Label(label.0.clone()),
+			Statement::Break(span) => ir::Statement::Break(*span),
+			Statement::Goto(name, _) => ir::Statement::Goto(name.clone()),
+			Statement::Do(bl, _) => ir::Statement::Do(self.block(bl)),
+			Statement::While(w) => ir::Statement::While {
+				cond: self.exp(&w.cond),
+				body: self.block(&w.bl),
+				span: w.span,
+			},
+			Statement::RepeatUntil(r) => ir::Statement::Repeat {
+				body: self.block(&r.bl),
+				cond: self.exp(&r.cond),
+				span: r.span,
+			},
+			Statement::IfTree(tree) => {
+				let branches = std::iter::once(&tree.initial)
+					.chain(&tree.elseifs)
+					.map(|ifb| (self.exp(&ifb.cond), self.block(&ifb.bl)))
+					.collect();
+				ir::Statement::If {
+					branches,
+					otherwise: tree.otherwise.as_ref().map(|bl| self.block(bl)),
+					span: tree.span,
+				}
+			}
+			Statement::ForExpression(f) => ir::Statement::NumericFor {
+				var: f.name.clone(),
+				start: self.exp(f.range.start()),
+				limit: self.exp(f.range.end()),
+				step: f.step.as_ref().map(|step| self.exp(step)),
+				body: self.block(&f.bl),
+				span: f.span,
+			},
+			Statement::ForList(f) => ir::Statement::GenericFor {
+				names: f.nlist.clone(),
+				values: self.exps(&f.elist),
+				body: self.block(&f.bl),
+				span: f.span,
+			},
+			Statement::FunctionDefinition(def) => {
+				// `function a.b:m()` assigns to the field `m` of `a.b`
+				let fname = &def.fname;
+				let mut target = ir::Target::Name(fname.nlist[0].clone());
+				let keys = fname.nlist[1..].iter().chain(&fname.objname);
+				for key in keys {
+					let table = match target {
+						ir::Target::Name(name) => ir::Expression::Name(name),
+						ir::Target::Index(index) => ir::Expression::Index(Box::new(index)),
+					};
+					target = ir::Target::Index(ir::Index {
+						table,
+						key: field_key(key),
+						span: Span::new(fname.span.start, key.span.end),
+					});
+				}
+				let function = self.function(&def.fbody, fname.objname.as_ref());
+				ir::Statement::Assign {
+					targets: vec![target],
+					values: vec![ir::Expression::Function(Box::new(function))],
+					span: def.span,
+				}
+			}
+			Statement::LocalFunctionDefinition(def) => ir::Statement::LocalFunction {
+				name: def.name.clone(),
+				function: Box::new(self.function(&def.fbody, None)),
+				span: def.span,
+			},
+			Statement::LocalDefinitionWithAttribute(def) => ir::Statement::Local {
+				names: def.atlist.clone(),
+				values: self.exps(def.oelist.iter().flatten()),
+				span: def.span,
+			},
+			Statement::Error(span) => ir::Statement::Error(*span),
+		})
+	}
+
+	/// The target of an assignment, unless it ends in a call, which the parser
+	/// only leaves in trees it recovered.
+	fn target(&mut self, var: &Variable) -> Option<ir::Target> {
+		match var {
+			Variable::Name(name) => Some(ir::Target::Name(name.clone())),
+			Variable::Affixed(affix) => match self.affix(affix) {
+				ir::Expression::Index(index) => Some(ir::Target::Index(*index)),
+				_ => None,
+			},
+		}
+	}
+
+	fn exps<'e>(&mut self, elist: impl IntoIterator<Item = &'e Expression>) -> Vec<ir::Expression> {
+		elist.into_iter().map(|ex| self.exp(ex)).collect()
+	}
+
+	fn exp(&mut self, ex: &Expression) -> ir::Expression {
+		match ex {
+			Expression::Value(val) => self.value(val),
+			Expression::BinaryExpression(ex) => ir::Expression::Binary(Box::new(ir::Binary {
+				op: ex.op.clone(),
+				left: self.exp(&ex.left),
+				right: self.exp(&ex.right),
+				span: ex.span,
+			})),
+			Expression::UnaryExpression(ex) => ir::Expression::Unary(Box::new(ir::Unary {
+				op: ex.op.clone(),
+				operand: self.exp(&ex.ex),
+				span: ex.span,
+			})),
+			Expression::Error(span) => ir::Expression::Error(*span),
+		}
+	}
+
+	fn value(&mut self, val: &Value) -> ir::Expression {
+		match val {
+			Value::Nil(span) => ir::Expression::Nil(*span),
+			Value::False(span) => ir::Expression::False(*span),
+			Value::True(span) => ir::Expression::True(*span),
+			Value::Numeral(num, span) => ir::Expression::Numeral(num.clone(), *span),
+			Value::LiteralString(s) => ir::Expression::String(s.clone()),
+			Value::VarArgs(va) => ir::Expression::VarArgs(va.span),
+			Value::AnonFunctionDefinition(def) => {
+				ir::Expression::Function(Box::new(self.function(&def.fbody, None)))
+			}
+			Value::Variable(Variable::Name(name)) => ir::Expression::Name(name.clone()),
+			Value::Variable(Variable::Affixed(affix)) => self.affix(affix),
+			Value::FunctionCall(call) => self.function_call(call),
+			Value::ParenExpression(ex, _) => self.paren(ex),
+			Value::TableConstructor(table) => ir::Expression::Table(self.table(table)),
+		}
+	}
+
+	/// Parentheses only matter around expressions with several values.
+	fn paren(&mut self, ex: &Expression) -> ir::Expression {
+		match self.exp(ex) {
+			lowered @ (ir::Expression::Call(_)
+			| ir::Expression::Let(_)
+			| ir::Expression::VarArgs(_)) => ir::Expression::Truncate(Box::new(lowered), ex.span()),
+			lowered => lowered,
+		}
+	}
+
+	fn table(&mut self, table: &TableConstructor) -> ir::Table {
+		let fields = table
+			.oflist
+			.iter()
+			.flatten()
+			.map(|field| match field {
+				Field::BracketField(field) => ir::TableField::Keyed {
+					key: self.exp(&field.tabexp),
+					value: self.exp(&field.val),
+				},
+				Field::NameField(field) => ir::TableField::Keyed {
+					key: field_key(&field.tabname),
+					value: self.exp(&field.val),
+				},
+				Field::Expression(ex) => ir::TableField::Positional(self.exp(ex)),
+			})
+			.collect();
+		ir::Table {
+			fields,
+			span: table.span,
+		}
+	}
+
+	fn affix(&mut self, affix: &Affix) -> ir::Expression {
+		let mut ex = match &affix.pfix {
+			Prefix::Name(name) => ir::Expression::Name(name.clone()),
+			Prefix::ParenExpression(ex, _) => self.paren(ex),
+		};
+		for suffix in &affix.suflist {
+			let span = Span::new(affix.span.start, suffix.span().end);
+			ex = match suffix {
+				Suffix::Index(index) => {
+					let key = match index {
+						Index::Expression(key, _) => self.exp(key),
+						Index::Member(name) => field_key(name),
+					};
+					ir::Expression::Index(Box::new(ir::Index {
+						table: ex,
+						key,
+						span,
+					}))
+				}
+				Suffix::Call(call) => self.call(ex, call, span),
+			};
+		}
+		ex
+	}
+
+	fn function_call(&mut self, call: &FunctionCall) -> ir::Expression {
+		let function = self.affix(&call.affix);
+		self.call(function, &call.call, call.span)
+	}
+
+	/// Calls `callee`, or its method if the call names one.
+	fn call(&mut self, callee: ir::Expression, call: &Call, span: Span) -> ir::Expression {
+		let mut args = match &call.argu {
+			Arguments::ClosedExpressionList(elist, _) => self.exps(elist.iter().flatten()),
+			Arguments::TableConstructor(table) => vec![ir::Expression::Table(self.table(table))],
+			Arguments::LiteralString(s) => vec![ir::Expression::String(s.clone())],
+		};
+
+		let Some(method) = &call.oname else {
+			return ir::Expression::Call(Box::new(ir::Call {
+				function: callee,
+				args,
+				span,
+			}));
+		};
+		// `o:m(...)` is `o.m(o, ...)`, evaluating `o` once
+		let temp = self.temp();
+		args.insert(0, ir::Expression::Temp(temp));
+		let function = ir::Expression::Index(Box::new(ir::Index {
+			table: ir::Expression::Temp(temp),
+			key: field_key(method),
+			span: Span::new(span.start, method.span.end),
+		}));
+		ir::Expression::Let(Box::new(ir::Let {
+			temp,
+			value: callee,
+			body: ir::Expression::Call(Box::new(ir::Call {
+				function,
+				args,
+				span,
+			})),
+		}))
+	}
+}
+
+/// The string key of a field written as a name, like `a.name` or `{name = v}`.
+fn field_key(name: &Name) -> ir::Expression {
+	ir::Expression::String(LiteralString {
+		value: name.value.clone().into_bytes(),
+		span: name.span,
+	})
+}
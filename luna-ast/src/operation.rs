@@ -3,6 +3,7 @@
 /// | '<<' | '..' | '<' | ‘<=' | '>' | ‘>='
 /// | '==' | ‘~=' | <and> | <or>`
 #[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BinaryOperation {
 	Add,
 	Subtract,
@@ -27,8 +28,36 @@ pub enum BinaryOperation {
 	Or,
 }
 
+/// The binding power of unary operators. Only `^` binds tighter.
+pub const UNARY_PRIORITY: u8 = 12;
+
+impl BinaryOperation {
+	/// The left and right binding power of this operator, as in `lparser.c`.
+	///
+	/// An operator is right associative when its right priority is lower
+	/// than its left priority.
+	pub const fn priority(&self) -> (u8, u8) {
+		use BinaryOperation::*;
+
+		match self {
+			Or => (1, 1),
+			And => (2, 2),
+			LessThan | LessEqual | GreaterThan | GreaterEqual | IsEqual | IsNotEqual => (3, 3),
+			BitwiseOr => (4, 4),
+			BitwiseXor => (5, 5),
+			BitwiseAnd => (6, 6),
+			BitwiseLeftShift | BitwiseRightShift => (7, 7),
+			Concat => (9, 8),
+			Add | Subtract => (10, 10),
+			Multiply | Divide | FloorDivide | Modulo => (11, 11),
+			Power => (14, 13),
+		}
+	}
+}
+
 /// Grammar (unop): `'-' | <not> | '#' | '~'`
 #[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum UnaryOperation {
 	Negate,
 	Not,
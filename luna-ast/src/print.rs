@@ -0,0 +1,858 @@
+//! # Printing
+//!
+//! Turns a syntax tree back into Lua source. The output parses back into an
+//! equal tree (see [SpanlessEq](crate::span::SpanlessEq)), with parentheses
+//! only where the tree has them or where precedence needs them. Negative
+//! integers, from folding or from hexadecimal numerals that wrap around, are
+//! the exception: Lua has no negative numerals, so they're printed as
+//! negations like `-1`.
+//!
+//! [print_chunk] prints any tree. [format_source] prints a tree along with
+//! the source it was parsed from, keeping its comments and blank lines.
+
+use std::fmt::Write;
+
+use crate::{
+	affix::{Affix, Call, Index, Prefix, Suffix},
+	attribute::AttributeName,
+	comment::{Comment, CommentKind, Comments},
+	expression::{Expression, Value},
+	function::{Arguments, FunctionBody, FunctionCall, FunctionName, ParameterList},
+	operation::{BinaryOperation, UnaryOperation, UNARY_PRIORITY},
+	span::Spanned,
+	statement::{IfBlock, Statement},
+	table::{Field, TableConstructor},
+	terminal::{Name, Numeral},
+	variable::Variable,
+	Block, Chunk, ReturnStatement,
+};
+
+/// The quotes around printed strings.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Quote {
+	#[default]
+	Double,
+	Single,
+}
+
+impl Quote {
+	fn char(self) -> char {
+		match self {
+			Self::Double => '"',
+			Self::Single => '\'',
+		}
+	}
+}
+
+/// How a tree is printed. The defaults indent with tabs and quote strings
+/// with `"`.
+#[derive(Clone, Debug)]
+pub struct PrintOptions {
+	indent: String,
+	quote: Quote,
+	trailing_separators: bool,
+	/// The column table constructors are split past
+	width: usize,
+}
+
+impl Default for PrintOptions {
+	fn default() -> Self {
+		Self {
+			indent: "\t".to_owned(),
+			quote: Quote::default(),
+			trailing_separators: false,
+			width: 80,
+		}
+	}
+}
+
+impl PrintOptions {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Indents each nested block with `indent`, like four spaces.
+	pub fn indent(mut self, indent: impl Into<String>) -> Self {
+		self.indent = indent.into();
+		self
+	}
+
+	pub fn quote(mut self, quote: Quote) -> Self {
+		self.quote = quote;
+		self
+	}
+
+	/// Ends each field of a table constructor that spans several lines with
+	/// a `,`, including the last one.
+	pub fn trailing_separators(mut self) -> Self {
+		self.trailing_separators = true;
+		self
+	}
+
+	/// Table constructors that would make a line longer than `width` columns
+	/// are split into one field per line (80 by default). Nothing else is
+	/// split, so other lines can still be longer.
+	pub fn width(mut self, width: usize) -> Self {
+		self.width = width;
+		self
+	}
+}
+
+/// Prints `chunk` as Lua source.
+///
+/// Error nodes left by error recovery have no source to print: statements
+/// are left out, and expressions are printed as `nil`.
+pub fn print_chunk(chunk: &Chunk, options: &PrintOptions) -> String {
+	let mut printer = Printer::new(options, None);
+	printer.chunk(chunk);
+	printer.out
+}
+
+/// Prints `exp` as a Lua expression.
+pub fn print_expression(exp: &Expression, options: &PrintOptions) -> String {
+	let mut printer = Printer::new(options, None);
+	printer.exp(exp, 0, 0);
+	printer.out
+}
+
+/// Prints `chunk`, which was parsed from `src`, keeping the `comments` of the
+/// source and the blank lines between its statements.
+///
+/// Comments stay before the statement or table field that follows them, or
+/// after one on the same line. Comments anywhere else, like between the
+/// arguments of a call, move before the next statement.
+pub fn format_source(
+	chunk: &Chunk, comments: &Comments, src: &[u8], options: &PrintOptions,
+) -> String {
+	let source = Source {
+		src,
+		comments: comments.iter().collect(),
+		next: 0,
+		last_end: 0,
+	};
+	let mut printer = Printer::new(options, Some(source));
+	printer.chunk(chunk);
+	printer.out
+}
+
+/// The source a tree was parsed from, and the comments left to print.
+struct Source<'a> {
+	src: &'a [u8],
+	comments: Vec<&'a Comment>,
+	/// The first comment that hasn't been printed
+	next: usize,
+	/// The end of the last thing printed
+	last_end: usize,
+}
+
+impl Source<'_> {
+	/// Whether the source between the last thing printed and `pos` has a
+	/// blank line.
+	fn blank_line_before(&self, pos: usize) -> bool {
+		let gap = self.src.get(self.last_end..pos).unwrap_or_default();
+		gap.iter().filter(|&&c| c == b'\n').count() > 1
+	}
+
+	/// Whether the next comment follows `end` on the same line, with only
+	/// separators in between.
+	fn trails(&self, end: usize) -> bool {
+		let Some(comment) = self.comments.get(self.next) else {
+			return false;
+		};
+		let gap = self.src.get(end..comment.span.start).unwrap_or_default();
+		gap.iter().all(|c| matches!(c, b' ' | b'\t' | b',' | b';'))
+	}
+}
+
+struct Printer<'a> {
+	options: &'a PrintOptions,
+	out: String,
+	level: usize,
+	source: Option<Source<'a>>,
+	/// Whether nothing has been printed in the current block yet
+	block_start: bool,
+}
+
+impl<'a> Printer<'a> {
+	fn new(options: &'a PrintOptions, source: Option<Source<'a>>) -> Self {
+		Self {
+			options,
+			out: String::new(),
+			level: 0,
+			source,
+			block_start: true,
+		}
+	}
+
+	/// Starts a new line at the current indentation.
+	fn line(&mut self) {
+		if self.out.is_empty() {
+			return;
+		}
+		self.out.push('\n');
+		for _ in 0..self.level {
+			self.out.push_str(&self.options.indent);
+		}
+	}
+
+	fn column(&self) -> usize {
+		let start = self.out.rfind('\n').map_or(0, |i| i + 1);
+		self.out[start..].chars().count()
+	}
+
+	fn comment(&mut self, comment: &Comment) {
+		match comment.kind {
+			CommentKind::Line => {
+				self.out.push_str("--");
+				self.out.push_str(&String::from_utf8_lossy(&comment.text));
+			}
+			CommentKind::Long(level) => {
+				let eqs = "=".repeat(level);
+				write!(
+					self.out,
+					"--[{eqs}[{}]{eqs}]",
+					String::from_utf8_lossy(&comment.text)
+				)
+				.unwrap();
+			}
+		}
+	}
+
+	/// Prints the comments before `pos` on their own lines, and starts a new
+	/// line for whatever is at `pos`.
+	fn leading(&mut self, pos: usize) {
+		let Some(source) = &mut self.source else {
+			self.line();
+			return;
+		};
+
+		let mut comments = Vec::new();
+		while let Some(&comment) = source.comments.get(source.next) {
+			if comment.span.start >= pos {
+				break;
+			}
+			let blank = !self.block_start && source.blank_line_before(comment.span.start);
+			comments.push((comment, blank));
+			source.next += 1;
+			source.last_end = comment.span.end;
+			self.block_start = false;
+		}
+		let blank = !self.block_start && source.blank_line_before(pos);
+
+		for (comment, blank) in comments {
+			if blank {
+				self.out.push('\n');
+			}
+			self.line();
+			self.comment(comment);
+		}
+		if blank {
+			self.out.push('\n');
+		}
+		self.line();
+	}
+
+	/// Prints the comments on the same line after something that ends at `end`.
+	fn trailing(&mut self, end: usize) {
+		let Some(source) = &mut self.source else {
+			return;
+		};
+		source.last_end = source.last_end.max(end);
+		let mut comments = Vec::new();
+		while source.trails(source.last_end) {
+			let comment = source.comments[source.next];
+			comments.push(comment);
+			source.next += 1;
+			source.last_end = source.last_end.max(comment.span.end);
+			// Nothing can follow a line comment
+			if comment.kind == CommentKind::Line {
+				break;
+			}
+		}
+		for comment in comments {
+			self.out.push(' ');
+			self.comment(comment);
+		}
+	}
+
+	fn chunk(&mut self, chunk: &Chunk) {
+		self.block(&chunk.0);
+		// Comments at the end of the source
+		if let Some(source) = &self.source {
+			if source.next < source.comments.len() {
+				self.leading(usize::MAX);
+				self.out.truncate(self.out.trim_end().len());
+			}
+		}
+		if !self.out.is_empty() {
+			self.out.push('\n');
+		}
+	}
+
+	/// Prints the statements of `bl`, each on its own line.
+	fn block(&mut self, bl: &Block) {
+		self.block_start = true;
+		let mut stats = bl.stlist.iter().peekable();
+		while let Some(stat) = stats.next() {
+			match stat {
+				Statement::Error(_) => continue,
+				// A `;` ends the statement before it, if there is one
+				Statement::End(_) if !self.block_start => self.out.push(';'),
+				_ => {
+					self.leading(stat.span().start);
+					self.statement(stat);
+				}
+			}
+			self.block_start = false;
+			match stats.peek() {
+				Some(Statement::End(_)) => continue,
+				// Otherwise the next statement would call the end of this one
+				Some(next) if starts_with_paren(next) && ends_with_prefix(stat) => {
+					self.out.push(';')
+				}
+				_ => {}
+			}
+			self.trailing(stat.span().end);
+		}
+		if let Some(ret) = &bl.oret {
+			self.leading(ret.span.start);
+			self.retstat(ret);
+			self.block_start = false;
+			self.trailing(ret.span.end);
+		}
+	}
+
+	/// Prints `bl` indented, followed by the keyword that closes it.
+	fn body(&mut self, bl: &Block, close: &str) {
+		let empty = self.out.len();
+		self.level += 1;
+		self.block(bl);
+		// Comments before the closing keyword
+		if let Some(source) = &self.source {
+			let next = source.comments.get(source.next);
+			if next.is_some_and(|c| c.span.start < close_position(source, bl)) {
+				self.leading(close_position(source, bl));
+				self.out.truncate(self.out.trim_end().len());
+			}
+		}
+		self.level -= 1;
+
+		if self.out.len() == empty {
+			self.out.push(' ');
+		} else {
+			self.line();
+		}
+		self.out.push_str(close);
+	}
+
+	fn statement(&mut self, stat: &Statement) {
+		match stat {
+			Statement::End(_) => self.out.push(';'),
+			Statement::Assignment(assign) => {
+				self.list(&assign.vlist, Self::variable);
+				self.out.push_str(" = ");
+				self.explist(&assign.elist);
+			}
+			Statement::FunctionCall(call) => self.function_call(call),
+			Statement::Label(label) => {
+				self.out.push_str("::");
+				self.name(&label.0);
+				self.out.push_str("::");
+			}
+			Statement::Break(_) => self.out.push_str("break"),
+			Statement::Goto(name, _) => {
+				self.out.push_str("goto ");
+				self.name(name);
+			}
+			Statement::Do(bl, _) => {
+				self.out.push_str("do");
+				self.body(bl, "end");
+			}
+			Statement::While(whl) => {
+				self.out.push_str("while ");
+				self.exp(&whl.cond, 0, 0);
+				self.out.push_str(" do");
+				self.body(&whl.bl, "end");
+			}
+			Statement::RepeatUntil(rep) => {
+				self.out.push_str("repeat");
+				self.body(&rep.bl, "until ");
+				self.exp(&rep.cond, 0, 0);
+			}
+			Statement::IfTree(tree) => {
+				self.out.push_str("if ");
+				self.if_block(&tree.initial);
+				for ifb in &tree.elseifs {
+					self.out.push_str("elseif ");
+					self.if_block(ifb);
+				}
+				if let Some(bl) = &tree.otherwise {
+					self.out.push_str("else");
+					self.body(bl, "");
+				}
+				self.out.push_str("end");
+			}
+			Statement::ForExpression(fexp) => {
+				self.out.push_str("for ");
+				self.name(&fexp.name);
+				self.out.push_str(" = ");
+				self.exp(fexp.range.start(), 0, 0);
+				self.out.push_str(", ");
+				self.exp(fexp.range.end(), 0, 0);
+				if let Some(step) = &fexp.step {
+					self.out.push_str(", ");
+					self.exp(step, 0, 0);
+				}
+				self.out.push_str(" do");
+				self.body(&fexp.bl, "end");
+			}
+			Statement::ForList(flist) => {
+				self.out.push_str("for ");
+				self.list(&flist.nlist, Self::name);
+				self.out.push_str(" in ");
+				self.explist(&flist.elist);
+				self.out.push_str(" do");
+				self.body(&flist.bl, "end");
+			}
+			Statement::FunctionDefinition(def) => {
+				self.out.push_str("function ");
+				self.function_name(&def.fname);
+				self.function_body(&def.fbody);
+			}
+			Statement::LocalFunctionDefinition(def) => {
+				self.out.push_str("local function ");
+				self.name(&def.name);
+				self.function_body(&def.fbody);
+			}
+			Statement::LocalDefinitionWithAttribute(def) => {
+				self.out.push_str("local ");
+				self.list(&def.atlist, Self::attribute_name);
+				if let Some(elist) = &def.oelist {
+					self.out.push_str(" = ");
+					self.explist(elist);
+				}
+			}
+			Statement::Error(_) => {}
+		}
+	}
+
+	/// Prints the condition and block of an `if` or `elseif`, leaving the
+	/// line ready for the keyword after it.
+	fn if_block(&mut self, ifb: &IfBlock) {
+		self.exp(&ifb.cond, 0, 0);
+		self.out.push_str(" then");
+		self.body(&ifb.bl, "");
+	}
+
+	fn retstat(&mut self, ret: &ReturnStatement) {
+		self.out.push_str("return");
+		if let Some(elist) = &ret.oelist {
+			self.out.push(' ');
+			self.explist(elist);
+		}
+	}
+
+	fn list<T>(&mut self, items: &[T], mut print: impl FnMut(&mut Self, &T)) {
+		for (i, item) in items.iter().enumerate() {
+			if i > 0 {
+				self.out.push_str(", ");
+			}
+			print(self, item);
+		}
+	}
+
+	fn explist(&mut self, elist: &[Expression]) {
+		self.list(elist, |p, exp| p.exp(exp, 0, 0));
+	}
+
+	fn name(&mut self, name: &Name) {
+		self.out.push_str(&name.value);
+	}
+
+	fn attribute_name(&mut self, atname: &AttributeName) {
+		self.name(&atname.name);
+		if let Some(attr) = &atname.attr.0 {
+			self.out.push_str(" <");
+			self.name(attr);
+			self.out.push('>');
+		}
+	}
+
+	fn function_name(&mut self, fname: &FunctionName) {
+		for (i, name) in fname.nlist.iter().enumerate() {
+			if i > 0 {
+				self.out.push('.');
+			}
+			self.name(name);
+		}
+		if let Some(name) = &fname.objname {
+			self.out.push(':');
+			self.name(name);
+		}
+	}
+
+	fn function_body(&mut self, fbody: &FunctionBody) {
+		self.out.push('(');
+		match &fbody.oplist {
+			None => {}
+			Some(ParameterList::NameList(nlist)) => self.list(nlist, Self::name),
+			Some(ParameterList::NameListWithVarArgs(nlist, _)) => {
+				self.list(nlist, Self::name);
+				self.out.push_str(", ...");
+			}
+			Some(ParameterList::VarArgs(_)) => self.out.push_str("..."),
+		}
+		self.out.push(')');
+		self.body(&fbody.bl, "end");
+	}
+
+	/// Prints `exp` where the parser would read it with `subexpr(limit)`,
+	/// followed by a binary operator with left priority `follow` (or 0).
+	fn exp(&mut self, exp: &Expression, limit: u8, follow: u8) {
+		match exp {
+			Expression::Value(val) => match &**val {
+				Value::Numeral(Numeral::Float(n), _) if n.is_sign_negative() && !n.is_nan() => {
+					// Printed like a negation
+					let paren = follow > UNARY_PRIORITY;
+					self.paren(paren, |p| {
+						p.out.push('-');
+						p.float(-n);
+					});
+				}
+				Value::Numeral(Numeral::Integer(n), _) if *n < 0 && *n != i64::MIN => {
+					let paren = follow > UNARY_PRIORITY;
+					self.paren(paren, |p| write!(p.out, "-{}", -n).unwrap());
+				}
+				val => self.value(val),
+			},
+			Expression::BinaryExpression(bexp) => {
+				let (left, right) = bexp.op.priority();
+				let paren = left <= limit || follow > right;
+				let (limit, follow) = if paren { (0, 0) } else { (limit, follow) };
+				self.paren(paren, |p| {
+					p.exp(&bexp.left, limit, left);
+					write!(p.out, " {} ", binop(&bexp.op)).unwrap();
+					p.exp(&bexp.right, right, follow);
+				});
+			}
+			Expression::UnaryExpression(uexp) => {
+				let paren = follow > UNARY_PRIORITY;
+				let follow = if paren { 0 } else { follow };
+				self.paren(paren, |p| {
+					p.out.push_str(unop(&uexp.op));
+					let start = p.out.len();
+					p.exp(&uexp.ex, UNARY_PRIORITY, follow);
+					// `- -x` isn't a comment
+					if p.out[start..].starts_with('-') {
+						p.out.insert(start, ' ');
+					}
+				});
+			}
+			Expression::Error(_) => self.out.push_str("nil"),
+		}
+	}
+
+	fn paren(&mut self, paren: bool, print: impl FnOnce(&mut Self)) {
+		if paren {
+			self.out.push('(');
+		}
+		print(self);
+		if paren {
+			self.out.push(')');
+		}
+	}
+
+	fn value(&mut self, val: &Value) {
+		match val {
+			Value::Nil(_) => self.out.push_str("nil"),
+			Value::False(_) => self.out.push_str("false"),
+			Value::True(_) => self.out.push_str("true"),
+			Value::Numeral(Numeral::Integer(n), _) if *n < 0 => {
+				// The smallest integer has no positive counterpart to negate,
+				// but hexadecimal integers wrap around, so this reads back as it
+				write!(self.out, "0x{:x}", *n as u64).unwrap();
+			}
+			Value::Numeral(Numeral::Integer(n), _) => write!(self.out, "{n}").unwrap(),
+			Value::Numeral(Numeral::Float(n), _) => self.float(*n),
+			Value::LiteralString(string) => self.string(&string.value),
+			Value::VarArgs(_) => self.out.push_str("..."),
+			Value::AnonFunctionDefinition(def) => {
+				self.out.push_str("function");
+				self.function_body(&def.fbody);
+			}
+			Value::Variable(var) => self.variable(var),
+			Value::FunctionCall(call) => self.function_call(call),
+			Value::ParenExpression(exp, _) => self.paren(true, |p| p.exp(exp, 0, 0)),
+			Value::TableConstructor(table) => self.table(table),
+		}
+	}
+
+	/// Prints a float that isn't negative, so that it reads back as a float.
+	fn float(&mut self, n: f64) {
+		if n.is_nan() {
+			self.out.push_str("(0 / 0)");
+		} else if n.is_infinite() {
+			// Too large for a float, which reads as infinity
+			self.out.push_str("1e999");
+		} else {
+			// Always has a `.` or an exponent
+			write!(self.out, "{n:?}").unwrap();
+		}
+	}
+
+	/// Prints a short string, escaping what can't appear in one as is.
+	fn string(&mut self, value: &[u8]) {
+		let quote = self.options.quote.char();
+		self.out.push(quote);
+		let mut rest = value;
+		while !rest.is_empty() {
+			let (text, bad) = match std::str::from_utf8(rest) {
+				Ok(text) => (text, 0),
+				Err(e) => {
+					let text = std::str::from_utf8(&rest[..e.valid_up_to()]).unwrap();
+					(text, e.error_len().unwrap_or(rest.len() - e.valid_up_to()))
+				}
+			};
+			for c in text.chars() {
+				match c {
+					'\\' => self.out.push_str("\\\\"),
+					'\n' => self.out.push_str("\\n"),
+					'\r' => self.out.push_str("\\r"),
+					'\t' => self.out.push_str("\\t"),
+					c if c == quote => {
+						self.out.push('\\');
+						self.out.push(c);
+					}
+					// Three digits, so a digit after it isn't part of the escape
+					c if c.is_ascii_control() => write!(self.out, "\\{:03}", c as u8).unwrap(),
+					c => self.out.push(c),
+				}
+			}
+			let invalid = &rest[text.len()..text.len() + bad];
+			for byte in invalid {
+				write!(self.out, "\\{byte:03}").unwrap();
+			}
+			rest = &rest[text.len() + bad..];
+		}
+		self.out.push(quote);
+	}
+
+	fn variable(&mut self, var: &Variable) {
+		match var {
+			Variable::Name(name) => self.name(name),
+			Variable::Affixed(affix) => self.affix(affix),
+		}
+	}
+
+	fn affix(&mut self, affix: &Affix) {
+		match &affix.pfix {
+			Prefix::ParenExpression(exp, _) => self.paren(true, |p| p.exp(exp, 0, 0)),
+			Prefix::Name(name) => self.name(name),
+		}
+		for suf in &affix.suflist {
+			match suf {
+				Suffix::Call(call) => self.call(call),
+				Suffix::Index(Index::Expression(exp, _)) => {
+					self.out.push('[');
+					self.exp(exp, 0, 0);
+					self.out.push(']');
+				}
+				Suffix::Index(Index::Member(name)) => {
+					self.out.push('.');
+					self.name(name);
+				}
+			}
+		}
+	}
+
+	fn function_call(&mut self, call: &FunctionCall) {
+		self.affix(&call.affix);
+		self.call(&call.call);
+	}
+
+	fn call(&mut self, call: &Call) {
+		if let Some(name) = &call.oname {
+			self.out.push(':');
+			self.name(name);
+		}
+		match &call.argu {
+			Arguments::ClosedExpressionList(oelist, _) => {
+				self.out.push('(');
+				if let Some(elist) = oelist {
+					self.explist(elist);
+				}
+				self.out.push(')');
+			}
+			Arguments::TableConstructor(table) => {
+				self.out.push(' ');
+				self.table(table);
+			}
+			Arguments::LiteralString(string) => {
+				self.out.push(' ');
+				self.string(&string.value);
+			}
+		}
+	}
+
+	fn table(&mut self, table: &TableConstructor) {
+		let Some(flist) = &table.oflist else {
+			self.out.push_str("{}");
+			return;
+		};
+
+		// On one line if it fits, and there are no comments to keep
+		let commented = self.source.as_ref().is_some_and(|source| {
+			let next = source.comments.get(source.next);
+			next.is_some_and(|c| c.span.start < table.span.end)
+		});
+		if !commented {
+			let start = self.out.len();
+			self.out.push_str("{ ");
+			self.list(flist, Self::field);
+			self.out.push_str(" }");
+			if !self.out[start..].contains('\n') && self.column() <= self.options.width {
+				return;
+			}
+			self.out.truncate(start);
+		}
+
+		self.out.push('{');
+		self.level += 1;
+		let block_start = std::mem::replace(&mut self.block_start, true);
+		for (i, field) in flist.iter().enumerate() {
+			self.leading(field.span().start);
+			self.block_start = false;
+			self.field(field);
+			if i + 1 < flist.len() || self.options.trailing_separators {
+				self.out.push(',');
+			}
+			self.trailing(field.span().end);
+		}
+		if let Some(source) = &self.source {
+			if source
+				.comments
+				.get(source.next)
+				.is_some_and(|c| c.span.start < table.span.end)
+			{
+				self.leading(table.span.end);
+				self.out.truncate(self.out.trim_end().len());
+			}
+		}
+		self.block_start = block_start;
+		self.level -= 1;
+		self.line();
+		self.out.push('}');
+	}
+
+	fn field(&mut self, field: &Field) {
+		match field {
+			Field::BracketField(field) => {
+				self.out.push('[');
+				self.exp(&field.tabexp, 0, 0);
+				self.out.push_str("] = ");
+				self.exp(&field.val, 0, 0);
+			}
+			Field::NameField(field) => {
+				self.name(&field.tabname);
+				self.out.push_str(" = ");
+				self.exp(&field.val, 0, 0);
+			}
+			Field::Expression(exp) => self.exp(exp, 0, 0),
+		}
+	}
+}
+
+/// Where the keyword closing `bl` starts: the first token after its last
+/// statement, skipping any comments.
+fn close_position(source: &Source, bl: &Block) -> usize {
+	let mut pos = source.last_end.max(bl.span.end);
+	let mut comments = source.comments[source.next..].iter();
+	loop {
+		while source.src.get(pos).is_some_and(u8::is_ascii_whitespace) {
+			pos += 1;
+		}
+		match comments.next() {
+			Some(c) if c.span.start == pos => pos = c.span.end,
+			_ => return pos,
+		}
+	}
+}
+
+/// Whether `stat` starts with a `(`, which would continue the statement
+/// before it as a call.
+fn starts_with_paren(stat: &Statement) -> bool {
+	let pfix = match stat {
+		Statement::FunctionCall(call) => &call.affix.pfix,
+		Statement::Assignment(assign) => match assign.vlist.first() {
+			Some(Variable::Affixed(affix)) => &affix.pfix,
+			_ => return false,
+		},
+		_ => return false,
+	};
+	matches!(pfix, Prefix::ParenExpression(..))
+}
+
+/// Whether `stat` ends with an expression that a call could follow.
+fn ends_with_prefix(stat: &Statement) -> bool {
+	let last = match stat {
+		Statement::FunctionCall(_) => return true,
+		Statement::Assignment(assign) => assign.elist.last(),
+		Statement::LocalDefinitionWithAttribute(def) => def.oelist.iter().flatten().last(),
+		Statement::RepeatUntil(rep) => Some(&rep.cond),
+		_ => None,
+	};
+	let mut exp = match last {
+		Some(exp) => exp,
+		None => return false,
+	};
+	loop {
+		exp = match exp {
+			Expression::BinaryExpression(bexp) => &bexp.right,
+			Expression::UnaryExpression(uexp) => &uexp.ex,
+			Expression::Value(val) => {
+				return matches!(
+					**val,
+					Value::Variable(_) | Value::FunctionCall(_) | Value::ParenExpression(..)
+				)
+			}
+			Expression::Error(_) => return false,
+		}
+	}
+}
+
+fn binop(op: &BinaryOperation) -> &'static str {
+	use BinaryOperation::*;
+
+	match op {
+		Add => "+",
+		Subtract => "-",
+		Multiply => "*",
+		Divide => "/",
+		FloorDivide => "//",
+		Power => "^",
+		Modulo => "%",
+		BitwiseAnd => "&",
+		BitwiseXor => "~",
+		BitwiseOr => "|",
+		BitwiseRightShift => ">>",
+		BitwiseLeftShift => "<<",
+		Concat => "..",
+		LessThan => "<",
+		LessEqual => "<=",
+		GreaterThan => ">",
+		GreaterEqual => ">=",
+		IsEqual => "==",
+		IsNotEqual => "~=",
+		And => "and",
+		Or => "or",
+	}
+}
+
+fn unop(op: &UnaryOperation) -> &'static str {
+	match op {
+		UnaryOperation::Negate => "-",
+		UnaryOperation::Not => "not ",
+		UnaryOperation::Length => "#",
+		UnaryOperation::BitwiseNot => "~",
+	}
+}
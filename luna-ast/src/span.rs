@@ -0,0 +1,479 @@
+//! # Source Spans
+//!
+//! Locations of syntax within the source text it was read from.
+
+use std::ops::{Range, RangeInclusive};
+
+use crate::{
+	affix::{Affix, Call, Index, Prefix, Suffix},
+	attribute::{Attribute, AttributeName},
+	expression::{AnonFunctionDefinition, BinaryExpression, Expression, UnaryExpression, Value},
+	function::{Arguments, FunctionBody, FunctionCall, FunctionName, ParameterList, VarArgs},
+	operation::{BinaryOperation, UnaryOperation},
+	statement::{
+		Assignment, ForExpression, ForList, IfBlock, IfTree, Label, LocalDefinitionWithAttribute,
+		LocalFunctionDefinition, NamedFunctionDefinition, RepeatUntil, Statement, While,
+	},
+	table::{BracketField, Field, NameField, TableConstructor},
+	terminal::{LiteralString, Name, Numeral},
+	variable::Variable,
+	Block, Chunk, ReturnStatement,
+};
+
+/// A half-open range of byte offsets into a source file.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Span {
+	/// Offset of the first byte in the span
+	pub start: usize,
+	/// Offset one past the last byte in the span
+	pub end: usize,
+}
+
+impl Span {
+	pub const fn new(start: usize, end: usize) -> Self {
+		Self { start, end }
+	}
+
+	/// An empty span at `offset`.
+	pub const fn empty(offset: usize) -> Self {
+		Self::new(offset, offset)
+	}
+
+	pub const fn len(&self) -> usize {
+		self.end - self.start
+	}
+
+	pub const fn is_empty(&self) -> bool {
+		self.start == self.end
+	}
+
+	/// The smallest span that covers both `self` and `other`.
+	pub fn to(self, other: Span) -> Span {
+		Span::new(self.start.min(other.start), self.end.max(other.end))
+	}
+
+	pub fn range(&self) -> Range<usize> {
+		self.start..self.end
+	}
+}
+
+impl From<Range<usize>> for Span {
+	fn from(value: Range<usize>) -> Self {
+		Self::new(value.start, value.end)
+	}
+}
+
+/// Maps byte offsets of a source file to line and column numbers.
+///
+/// Line breaks are counted the same way the lexer counts them: `\n`, `\r`,
+/// `\r\n` and `\n\r` each end a single line.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LineIndex {
+	/// Offset of the first byte of each line
+	starts: Vec<usize>,
+}
+
+impl LineIndex {
+	pub fn new(src: &[u8]) -> Self {
+		let mut starts = vec![0];
+		let mut pos = 0;
+
+		while let Some(&ch) = src.get(pos) {
+			pos += 1;
+			if matches!(ch, b'\n' | b'\r') {
+				if matches!(src.get(pos), Some(&next) if (next == b'\n' || next == b'\r') && next != ch)
+				{
+					pos += 1;
+				}
+				starts.push(pos);
+			}
+		}
+
+		Self { starts }
+	}
+
+	/// The number of lines in the source.
+	pub fn lines(&self) -> usize {
+		self.starts.len()
+	}
+
+	/// The line (starting at 1) that `offset` falls on.
+	pub fn line(&self, offset: usize) -> usize {
+		self.starts.partition_point(|&start| start <= offset)
+	}
+
+	/// The line and column (both starting at 1) of `offset`. Columns are counted in bytes.
+	pub fn line_col(&self, offset: usize) -> (usize, usize) {
+		let line = self.line(offset);
+		(line, offset - self.starts[line - 1] + 1)
+	}
+
+	/// The offset of the first byte of `line`, if the source has that many lines.
+	pub fn line_start(&self, line: usize) -> Option<usize> {
+		self.starts.get(line.checked_sub(1)?).copied()
+	}
+}
+
+/// A syntax tree node that knows where it came from in the source.
+pub trait Spanned {
+	fn span(&self) -> Span;
+}
+
+impl<T: Spanned> Spanned for Box<T> {
+	fn span(&self) -> Span {
+		(**self).span()
+	}
+}
+
+/// Equality that doesn't consider the [Span]s stored in syntax tree nodes.
+///
+/// Two trees parsed from differently formatted sources compare equal with
+/// this trait as long as they have the same structure.
+pub trait SpanlessEq {
+	fn spanless_eq(&self, other: &Self) -> bool;
+}
+
+/// Asserts that two syntax trees are equal, disregarding their spans.
+#[macro_export]
+macro_rules! assert_spanless_eq {
+	($left:expr, $right:expr $(,)?) => {
+		match (&$left, &$right) {
+			(left, right) => {
+				if !$crate::span::SpanlessEq::spanless_eq(left, right) {
+					panic!(
+						"assertion `left.spanless_eq(right)` failed\n  left: {:?}\n right: {:?}",
+						left, right
+					)
+				}
+			}
+		}
+	};
+}
+
+impl SpanlessEq for Span {
+	fn spanless_eq(&self, _other: &Self) -> bool {
+		true
+	}
+}
+
+impl<T: SpanlessEq + ?Sized> SpanlessEq for Box<T> {
+	fn spanless_eq(&self, other: &Self) -> bool {
+		(**self).spanless_eq(other)
+	}
+}
+
+impl<T: SpanlessEq> SpanlessEq for Option<T> {
+	fn spanless_eq(&self, other: &Self) -> bool {
+		match (self, other) {
+			(Some(left), Some(right)) => left.spanless_eq(right),
+			(None, None) => true,
+			_ => false,
+		}
+	}
+}
+
+impl<T: SpanlessEq> SpanlessEq for [T] {
+	fn spanless_eq(&self, other: &Self) -> bool {
+		self.len() == other.len() && self.iter().zip(other).all(|(l, r)| l.spanless_eq(r))
+	}
+}
+
+impl<T: SpanlessEq> SpanlessEq for Vec<T> {
+	fn spanless_eq(&self, other: &Self) -> bool {
+		self.as_slice().spanless_eq(other.as_slice())
+	}
+}
+
+impl<T: SpanlessEq> SpanlessEq for RangeInclusive<T> {
+	fn spanless_eq(&self, other: &Self) -> bool {
+		self.start().spanless_eq(other.start()) && self.end().spanless_eq(other.end())
+	}
+}
+
+impl<T: SpanlessEq, U: SpanlessEq> SpanlessEq for (T, U) {
+	fn spanless_eq(&self, other: &Self) -> bool {
+		self.0.spanless_eq(&other.0) && self.1.spanless_eq(&other.1)
+	}
+}
+
+/// Implements [SpanlessEq] with `==` for types that don't contain spans.
+macro_rules! spanless_by_eq {
+	($($t:ty),* $(,)?) => {
+		$(impl SpanlessEq for $t {
+			fn spanless_eq(&self, other: &Self) -> bool {
+				self == other
+			}
+		})*
+	};
+}
+
+/// Implements [SpanlessEq] for structs by comparing each of the listed fields.
+macro_rules! spanless_struct {
+	($($t:ty { $($field:tt),* }),* $(,)?) => {
+		$(impl SpanlessEq for $t {
+			fn spanless_eq(&self, other: &Self) -> bool {
+				true $(&& self.$field.spanless_eq(&other.$field))*
+			}
+		})*
+	};
+}
+
+/// Implements [SpanlessEq] for enums. Each variant lists pairs of bindings
+/// for the fields of the left and right hand sides.
+macro_rules! spanless_enum {
+	($($t:ty { $($variant:ident $(($($l:ident $r:ident),*))?),* $(,)? }),* $(,)?) => {
+		$(impl SpanlessEq for $t {
+			fn spanless_eq(&self, other: &Self) -> bool {
+				#[allow(unused_variables)]
+				match (self, other) {
+					$((Self::$variant $(($($l),*))?, Self::$variant $(($($r),*))?) => {
+						true $($(&& $l.spanless_eq($r))*)?
+					})*
+					#[allow(unreachable_patterns)]
+					_ => false,
+				}
+			}
+		})*
+	};
+}
+
+/// Implements [Spanned] for structs with a `span` field.
+macro_rules! spanned_struct {
+	($($t:ty),* $(,)?) => {
+		$(impl Spanned for $t {
+			fn span(&self) -> Span {
+				self.span
+			}
+		})*
+	};
+}
+
+spanless_by_eq! { bool, u8, String, Numeral, BinaryOperation, UnaryOperation }
+
+spanless_struct! {
+	Chunk { 0 },
+	Block { stlist, oret },
+	ReturnStatement { oelist },
+	Affix { pfix, suflist },
+	Call { oname, argu },
+	AttributeName { name, attr },
+	Attribute { 0, 1 },
+	AnonFunctionDefinition { fbody },
+	BinaryExpression { left, op, right },
+	UnaryExpression { op, ex },
+	VarArgs { span },
+	FunctionName { nlist, objname },
+	FunctionBody { oplist, bl },
+	FunctionCall { affix, call },
+	Label { 0, 1 },
+	IfBlock { cond, bl },
+	IfTree { initial, elseifs, otherwise },
+	ForExpression { name, range, step, bl },
+	ForList { nlist, elist, bl },
+	While { cond, bl },
+	RepeatUntil { cond, bl },
+	Assignment { vlist, elist },
+	NamedFunctionDefinition { fname, fbody },
+	LocalFunctionDefinition { name, fbody },
+	LocalDefinitionWithAttribute { atlist, oelist },
+	TableConstructor { oflist },
+	BracketField { tabexp, val },
+	NameField { tabname, val },
+	Name { value },
+	LiteralString { value },
+}
+
+spanless_enum! {
+	Prefix { ParenExpression(a b, c d), Name(a b) },
+	Index { Expression(a b, c d), Member(a b) },
+	Suffix { Call(a b), Index(a b) },
+	Value {
+		Nil(a b),
+		False(a b),
+		True(a b),
+		Numeral(a b, c d),
+		LiteralString(a b),
+		VarArgs(a b),
+		AnonFunctionDefinition(a b),
+		Variable(a b),
+		FunctionCall(a b),
+		ParenExpression(a b, c d),
+		TableConstructor(a b),
+	},
+	Expression { Value(a b), BinaryExpression(a b), UnaryExpression(a b), Error(a b) },
+	Arguments { ClosedExpressionList(a b, c d), TableConstructor(a b), LiteralString(a b) },
+	ParameterList { NameList(a b), NameListWithVarArgs(a b, c d), VarArgs(a b) },
+	Statement {
+		End(a b),
+		Assignment(a b),
+		FunctionCall(a b),
+		Label(a b),
+		Break(a b),
+		Goto(a b, c d),
+		Do(a b, c d),
+		While(a b),
+		RepeatUntil(a b),
+		IfTree(a b),
+		ForExpression(a b),
+		ForList(a b),
+		FunctionDefinition(a b),
+		LocalFunctionDefinition(a b),
+		LocalDefinitionWithAttribute(a b),
+		Error(a b),
+	},
+	Field { BracketField(a b), NameField(a b), Expression(a b) },
+	Variable { Name(a b), Affixed(a b) },
+}
+
+spanned_struct! {
+	Block,
+	ReturnStatement,
+	Affix,
+	Call,
+	AttributeName,
+	AnonFunctionDefinition,
+	BinaryExpression,
+	UnaryExpression,
+	VarArgs,
+	FunctionName,
+	FunctionBody,
+	FunctionCall,
+	IfBlock,
+	IfTree,
+	ForExpression,
+	ForList,
+	While,
+	RepeatUntil,
+	Assignment,
+	NamedFunctionDefinition,
+	LocalFunctionDefinition,
+	LocalDefinitionWithAttribute,
+	TableConstructor,
+	BracketField,
+	NameField,
+	Name,
+	LiteralString,
+}
+
+impl Spanned for Chunk {
+	fn span(&self) -> Span {
+		self.0.span
+	}
+}
+
+impl Spanned for Label {
+	fn span(&self) -> Span {
+		self.1
+	}
+}
+
+impl Spanned for Attribute {
+	fn span(&self) -> Span {
+		self.1
+	}
+}
+
+impl Spanned for Prefix {
+	fn span(&self) -> Span {
+		match self {
+			Self::ParenExpression(_, span) => *span,
+			Self::Name(name) => name.span,
+		}
+	}
+}
+
+impl Spanned for Index {
+	fn span(&self) -> Span {
+		match self {
+			Self::Expression(_, span) => *span,
+			Self::Member(name) => name.span,
+		}
+	}
+}
+
+impl Spanned for Suffix {
+	fn span(&self) -> Span {
+		match self {
+			Self::Call(call) => call.span,
+			Self::Index(index) => index.span(),
+		}
+	}
+}
+
+impl Spanned for Value {
+	fn span(&self) -> Span {
+		match self {
+			Self::Nil(span) | Self::False(span) | Self::True(span) | Self::Numeral(_, span) => {
+				*span
+			}
+			Self::LiteralString(string) => string.span,
+			Self::VarArgs(vargs) => vargs.span,
+			Self::AnonFunctionDefinition(func) => func.span,
+			Self::Variable(var) => var.span(),
+			Self::FunctionCall(call) => call.span,
+			Self::ParenExpression(_, span) => *span,
+			Self::TableConstructor(table) => table.span,
+		}
+	}
+}
+
+impl Spanned for Expression {
+	fn span(&self) -> Span {
+		match self {
+			Self::Value(val) => val.span(),
+			Self::BinaryExpression(ex) => ex.span,
+			Self::UnaryExpression(ex) => ex.span,
+			Self::Error(span) => *span,
+		}
+	}
+}
+
+impl Spanned for Arguments {
+	fn span(&self) -> Span {
+		match self {
+			Self::ClosedExpressionList(_, span) => *span,
+			Self::TableConstructor(table) => table.span,
+			Self::LiteralString(string) => string.span,
+		}
+	}
+}
+
+impl Spanned for Statement {
+	fn span(&self) -> Span {
+		match self {
+			Self::End(span) | Self::Break(span) | Self::Error(span) => *span,
+			Self::Assignment(stat) => stat.span,
+			Self::FunctionCall(stat) => stat.span,
+			Self::Label(stat) => stat.span(),
+			Self::Goto(_, span) | Self::Do(_, span) => *span,
+			Self::While(stat) => stat.span,
+			Self::RepeatUntil(stat) => stat.span,
+			Self::IfTree(stat) => stat.span,
+			Self::ForExpression(stat) => stat.span,
+			Self::ForList(stat) => stat.span,
+			Self::FunctionDefinition(stat) => stat.span,
+			Self::LocalFunctionDefinition(stat) => stat.span,
+			Self::LocalDefinitionWithAttribute(stat) => stat.span,
+		}
+	}
+}
+
+impl Spanned for Field {
+	fn span(&self) -> Span {
+		match self {
+			Self::BracketField(field) => field.span,
+			Self::NameField(field) => field.span,
+			Self::Expression(ex) => ex.span(),
+		}
+	}
+}
+
+impl Spanned for Variable {
+	fn span(&self) -> Span {
+		match self {
+			Self::Name(name) => name.span,
+			Self::Affixed(affix) => affix.span,
+		}
+	}
+}
@@ -4,13 +4,15 @@ use crate::{
 	attribute::AttributeNameList,
 	expression::{Expression, ExpressionList},
 	function::{FunctionBody, FunctionCall, FunctionName},
+	span::Span,
 	terminal::{Name, NameList},
 	variable::VariableList,
 	Block,
 };
 
 #[derive(Clone, Debug, PartialEq)]
-pub struct Label(pub Name);
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Label(pub Name, pub Span);
 
 impl From<Label> for Statement {
 	fn from(value: Label) -> Self {
@@ -19,13 +21,16 @@ impl From<Label> for Statement {
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IfBlock {
 	pub cond: Expression,
 	pub bl: Block,
+	pub span: Span,
 }
 
 /// **if** exp **then** block {**elseif** exp **then** block} \[**else** block\] **end**
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IfTree {
 	/// The initial condition (if .. then ..)
 	pub initial: IfBlock,
@@ -33,6 +38,7 @@ pub struct IfTree {
 	pub elseifs: Vec<IfBlock>,
 	/// The last statement to execute of all other conditions are false
 	pub otherwise: Option<Block>,
+	pub span: Span,
 }
 
 impl From<IfTree> for Statement {
@@ -43,12 +49,14 @@ impl From<IfTree> for Statement {
 
 /// **for** `name` **in** `start`, `stop` \[, `step`\] **do** `block` **end**
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ForExpression {
 	/// The name used in this loop context
 	pub name: Name,
 	pub range: RangeInclusive<Expression>,
 	pub step: Option<Expression>,
 	pub bl: Block,
+	pub span: Span,
 }
 
 impl From<ForExpression> for Statement {
@@ -58,10 +66,12 @@ impl From<ForExpression> for Statement {
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ForList {
 	pub nlist: NameList,
 	pub elist: ExpressionList,
 	pub bl: Block,
+	pub span: Span,
 }
 
 impl From<ForList> for Statement {
@@ -71,9 +81,11 @@ impl From<ForList> for Statement {
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct While {
 	pub cond: Expression,
 	pub bl: Block,
+	pub span: Span,
 }
 
 impl From<While> for Statement {
@@ -83,9 +95,11 @@ impl From<While> for Statement {
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RepeatUntil {
 	pub cond: Expression,
 	pub bl: Block,
+	pub span: Span,
 }
 
 impl From<RepeatUntil> for Statement {
@@ -95,9 +109,11 @@ impl From<RepeatUntil> for Statement {
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Assignment {
 	pub vlist: VariableList,
 	pub elist: ExpressionList,
+	pub span: Span,
 }
 
 impl From<Assignment> for Statement {
@@ -107,9 +123,11 @@ impl From<Assignment> for Statement {
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct NamedFunctionDefinition {
 	pub fname: FunctionName,
 	pub fbody: FunctionBody,
+	pub span: Span,
 }
 
 impl From<NamedFunctionDefinition> for Statement {
@@ -119,9 +137,11 @@ impl From<NamedFunctionDefinition> for Statement {
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LocalFunctionDefinition {
 	pub name: Name,
 	pub fbody: FunctionBody,
+	pub span: Span,
 }
 
 impl From<LocalFunctionDefinition> for Statement {
@@ -131,9 +151,11 @@ impl From<LocalFunctionDefinition> for Statement {
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LocalDefinitionWithAttribute {
 	pub atlist: AttributeNameList,
 	pub oelist: Option<ExpressionList>,
+	pub span: Span,
 }
 
 impl From<LocalDefinitionWithAttribute> for Statement {
@@ -143,14 +165,16 @@ impl From<LocalDefinitionWithAttribute> for Statement {
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", content = "value"))]
 pub enum Statement {
-	End,
+	End(Span),
 	Assignment(Assignment),
 	FunctionCall(FunctionCall),
 	Label(Label),
-	Break,
-	Goto(Name),
-	Do(Box<Block>),
+	Break(Span),
+	Goto(Name, Span),
+	Do(Box<Block>, Span),
 	While(While),
 	RepeatUntil(RepeatUntil),
 	IfTree(IfTree),
@@ -159,4 +183,6 @@ pub enum Statement {
 	FunctionDefinition(NamedFunctionDefinition),
 	LocalFunctionDefinition(LocalFunctionDefinition),
 	LocalDefinitionWithAttribute(LocalDefinitionWithAttribute),
+	/// Tokens that couldn't be parsed as a statement, skipped by error recovery
+	Error(Span),
 }
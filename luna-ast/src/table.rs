@@ -1,14 +1,17 @@
 use crate::{
 	expression::{Expression, Value},
 	function::Arguments,
+	span::Span,
 	terminal::Name,
 };
 
 pub type FieldList = Vec<Field>;
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TableConstructor {
 	pub oflist: Option<FieldList>,
+	pub span: Span,
 }
 
 impl From<TableConstructor> for Value {
@@ -24,9 +27,11 @@ impl From<TableConstructor> for Arguments {
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BracketField {
 	pub tabexp: Expression,
 	pub val: Expression,
+	pub span: Span,
 }
 
 impl From<BracketField> for Field {
@@ -36,9 +41,11 @@ impl From<BracketField> for Field {
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct NameField {
 	pub tabname: Name,
 	pub val: Expression,
+	pub span: Span,
 }
 
 impl From<NameField> for Field {
@@ -48,6 +55,8 @@ impl From<NameField> for Field {
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", content = "value"))]
 pub enum Field {
 	BracketField(BracketField),
 	NameField(NameField),
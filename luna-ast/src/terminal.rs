@@ -3,12 +3,16 @@
 //! These are representations of source structure, which are generated
 //! from the parser.
 
-use crate::{expression::Value, function::Arguments, variable::Variable};
+use crate::{expression::Value, function::Arguments, span::Span, variable::Variable};
 
 pub type NameList = Vec<Name>;
 
 #[derive(Clone, Debug, PartialEq)]
-pub struct Name(pub String);
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Name {
+	pub value: String,
+	pub span: Span,
+}
 
 impl From<Name> for Variable {
 	fn from(value: Name) -> Self {
@@ -17,19 +21,20 @@ impl From<Name> for Variable {
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", content = "value"))]
 pub enum Numeral {
-	Integer(isize),
+	Integer(i64),
 	Float(f64),
 }
 
-impl From<Numeral> for Value {
-	fn from(value: Numeral) -> Self {
-		Self::Numeral(value)
-	}
-}
-
 #[derive(Clone, Debug, PartialEq)]
-pub struct LiteralString(pub String);
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LiteralString {
+	/// The decoded contents. Lua strings are byte strings, so this need not be UTF-8.
+	pub value: Vec<u8>,
+	pub span: Span,
+}
 
 impl From<LiteralString> for Value {
 	fn from(value: LiteralString) -> Self {
@@ -3,6 +3,8 @@ use crate::{affix::Affix, expression::Value, terminal::Name};
 pub type VariableList = Vec<Variable>;
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", content = "value"))]
 pub enum Variable {
 	Name(Name),
 	Affixed(Affix),
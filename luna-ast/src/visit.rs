@@ -0,0 +1,468 @@
+//! # Visitors
+//!
+//! [Visit] walks a syntax tree by reference. Each `visit_*` method calls the
+//! matching `walk_*` function by default, which visits the children of the
+//! node in source order. A pass overrides the methods for the nodes it cares
+//! about, and calls `walk_*` itself to keep descending.
+//!
+//! ```
+//! use luna_ast::{terminal::Name, visit::Visit};
+//!
+//! /// Collects every name in a tree.
+//! struct Names(Vec<String>);
+//!
+//! impl Visit for Names {
+//!     fn visit_name(&mut self, name: &Name) {
+//!         self.0.push(name.value.clone());
+//!     }
+//! }
+//! ```
+//!
+//! See [visit_mut](crate::visit_mut) to change the tree in place.
+
+use crate::{
+	affix::{Affix, Call, Index, Prefix, Suffix},
+	attribute::AttributeName,
+	expression::{AnonFunctionDefinition, BinaryExpression, Expression, UnaryExpression, Value},
+	function::{Arguments, FunctionBody, FunctionCall, FunctionName, ParameterList, VarArgs},
+	statement::{
+		Assignment, ForExpression, ForList, IfBlock, IfTree, Label, LocalDefinitionWithAttribute,
+		LocalFunctionDefinition, NamedFunctionDefinition, RepeatUntil, Statement, While,
+	},
+	table::{BracketField, Field, NameField, TableConstructor},
+	terminal::{LiteralString, Name},
+	variable::Variable,
+	Block, Chunk, ReturnStatement,
+};
+
+/// A pass over a syntax tree. See the [module documentation](self).
+pub trait Visit {
+	fn visit_chunk(&mut self, chunk: &Chunk) {
+		walk_chunk(self, chunk);
+	}
+
+	fn visit_block(&mut self, bl: &Block) {
+		walk_block(self, bl);
+	}
+
+	fn visit_return_statement(&mut self, ret: &ReturnStatement) {
+		walk_return_statement(self, ret);
+	}
+
+	fn visit_statement(&mut self, stat: &Statement) {
+		walk_statement(self, stat);
+	}
+
+	fn visit_assignment(&mut self, assign: &Assignment) {
+		walk_assignment(self, assign);
+	}
+
+	fn visit_label(&mut self, label: &Label) {
+		walk_label(self, label);
+	}
+
+	fn visit_while(&mut self, whl: &While) {
+		walk_while(self, whl);
+	}
+
+	fn visit_repeat_until(&mut self, rep: &RepeatUntil) {
+		walk_repeat_until(self, rep);
+	}
+
+	fn visit_if_tree(&mut self, tree: &IfTree) {
+		walk_if_tree(self, tree);
+	}
+
+	fn visit_if_block(&mut self, ifb: &IfBlock) {
+		walk_if_block(self, ifb);
+	}
+
+	fn visit_for_expression(&mut self, fexp: &ForExpression) {
+		walk_for_expression(self, fexp);
+	}
+
+	fn visit_for_list(&mut self, flist: &ForList) {
+		walk_for_list(self, flist);
+	}
+
+	fn visit_named_function_definition(&mut self, def: &NamedFunctionDefinition) {
+		walk_named_function_definition(self, def);
+	}
+
+	fn visit_function_name(&mut self, fname: &FunctionName) {
+		walk_function_name(self, fname);
+	}
+
+	fn visit_local_function_definition(&mut self, def: &LocalFunctionDefinition) {
+		walk_local_function_definition(self, def);
+	}
+
+	fn visit_local_definition_with_attribute(&mut self, def: &LocalDefinitionWithAttribute) {
+		walk_local_definition_with_attribute(self, def);
+	}
+
+	fn visit_attribute_name(&mut self, atname: &AttributeName) {
+		walk_attribute_name(self, atname);
+	}
+
+	fn visit_expression(&mut self, exp: &Expression) {
+		walk_expression(self, exp);
+	}
+
+	fn visit_binary_expression(&mut self, bexp: &BinaryExpression) {
+		walk_binary_expression(self, bexp);
+	}
+
+	fn visit_unary_expression(&mut self, uexp: &UnaryExpression) {
+		walk_unary_expression(self, uexp);
+	}
+
+	fn visit_value(&mut self, val: &Value) {
+		walk_value(self, val);
+	}
+
+	fn visit_anon_function_definition(&mut self, def: &AnonFunctionDefinition) {
+		walk_anon_function_definition(self, def);
+	}
+
+	fn visit_function_body(&mut self, fbody: &FunctionBody) {
+		walk_function_body(self, fbody);
+	}
+
+	fn visit_parameter_list(&mut self, plist: &ParameterList) {
+		walk_parameter_list(self, plist);
+	}
+
+	fn visit_function_call(&mut self, call: &FunctionCall) {
+		walk_function_call(self, call);
+	}
+
+	fn visit_call(&mut self, call: &Call) {
+		walk_call(self, call);
+	}
+
+	fn visit_arguments(&mut self, argu: &Arguments) {
+		walk_arguments(self, argu);
+	}
+
+	fn visit_variable(&mut self, var: &Variable) {
+		walk_variable(self, var);
+	}
+
+	fn visit_affix(&mut self, affix: &Affix) {
+		walk_affix(self, affix);
+	}
+
+	fn visit_prefix(&mut self, pfix: &Prefix) {
+		walk_prefix(self, pfix);
+	}
+
+	fn visit_suffix(&mut self, suf: &Suffix) {
+		walk_suffix(self, suf);
+	}
+
+	fn visit_index(&mut self, index: &Index) {
+		walk_index(self, index);
+	}
+
+	fn visit_table_constructor(&mut self, table: &TableConstructor) {
+		walk_table_constructor(self, table);
+	}
+
+	fn visit_field(&mut self, field: &Field) {
+		walk_field(self, field);
+	}
+
+	fn visit_bracket_field(&mut self, field: &BracketField) {
+		walk_bracket_field(self, field);
+	}
+
+	fn visit_name_field(&mut self, field: &NameField) {
+		walk_name_field(self, field);
+	}
+
+	// Leaves of the tree, which have nothing to walk
+
+	fn visit_name(&mut self, _name: &Name) {}
+
+	fn visit_literal_string(&mut self, _string: &LiteralString) {}
+
+	fn visit_var_args(&mut self, _varargs: &VarArgs) {}
+}
+
+pub fn walk_chunk<V: Visit + ?Sized>(v: &mut V, chunk: &Chunk) {
+	v.visit_block(&chunk.0);
+}
+
+pub fn walk_block<V: Visit + ?Sized>(v: &mut V, bl: &Block) {
+	for stat in &bl.stlist {
+		v.visit_statement(stat);
+	}
+	if let Some(ret) = &bl.oret {
+		v.visit_return_statement(ret);
+	}
+}
+
+pub fn walk_return_statement<V: Visit + ?Sized>(v: &mut V, ret: &ReturnStatement) {
+	for exp in ret.oelist.iter().flatten() {
+		v.visit_expression(exp);
+	}
+}
+
+pub fn walk_statement<V: Visit + ?Sized>(v: &mut V, stat: &Statement) {
+	match stat {
+		Statement::End(_) | Statement::Break(_) | Statement::Error(_) => {}
+		Statement::Assignment(assign) => v.visit_assignment(assign),
+		Statement::FunctionCall(call) => v.visit_function_call(call),
+		Statement::Label(label) => v.visit_label(label),
+		Statement::Goto(name, _) => v.visit_name(name),
+		Statement::Do(bl, _) => v.visit_block(bl),
+		Statement::While(whl) => v.visit_while(whl),
+		Statement::RepeatUntil(rep) => v.visit_repeat_until(rep),
+		Statement::IfTree(tree) => v.visit_if_tree(tree),
+		Statement::ForExpression(fexp) => v.visit_for_expression(fexp),
+		Statement::ForList(flist) => v.visit_for_list(flist),
+		Statement::FunctionDefinition(def) => v.visit_named_function_definition(def),
+		Statement::LocalFunctionDefinition(def) => v.visit_local_function_definition(def),
+		Statement::LocalDefinitionWithAttribute(def) => {
+			v.visit_local_definition_with_attribute(def)
+		}
+	}
+}
+
+pub fn walk_assignment<V: Visit + ?Sized>(v: &mut V, assign: &Assignment) {
+	for var in &assign.vlist {
+		v.visit_variable(var);
+	}
+	for exp in &assign.elist {
+		v.visit_expression(exp);
+	}
+}
+
+pub fn walk_label<V: Visit + ?Sized>(v: &mut V, label: &Label) {
+	v.visit_name(&label.0);
+}
+
+pub fn walk_while<V: Visit + ?Sized>(v: &mut V, whl: &While) {
+	v.visit_expression(&whl.cond);
+	v.visit_block(&whl.bl);
+}
+
+pub fn walk_repeat_until<V: Visit + ?Sized>(v: &mut V, rep: &RepeatUntil) {
+	v.visit_block(&rep.bl);
+	v.visit_expression(&rep.cond);
+}
+
+pub fn walk_if_tree<V: Visit + ?Sized>(v: &mut V, tree: &IfTree) {
+	v.visit_if_block(&tree.initial);
+	for ifb in &tree.elseifs {
+		v.visit_if_block(ifb);
+	}
+	if let Some(bl) = &tree.otherwise {
+		v.visit_block(bl);
+	}
+}
+
+pub fn walk_if_block<V: Visit + ?Sized>(v: &mut V, ifb: &IfBlock) {
+	v.visit_expression(&ifb.cond);
+	v.visit_block(&ifb.bl);
+}
+
+pub fn walk_for_expression<V: Visit + ?Sized>(v: &mut V, fexp: &ForExpression) {
+	v.visit_name(&fexp.name);
+	v.visit_expression(fexp.range.start());
+	v.visit_expression(fexp.range.end());
+	if let Some(step) = &fexp.step {
+		v.visit_expression(step);
+	}
+	v.visit_block(&fexp.bl);
+}
+
+pub fn walk_for_list<V: Visit + ?Sized>(v: &mut V, flist: &ForList) {
+	for name in &flist.nlist {
+		v.visit_name(name);
+	}
+	for exp in &flist.elist {
+		v.visit_expression(exp);
+	}
+	v.visit_block(&flist.bl);
+}
+
+pub fn walk_named_function_definition<V: Visit + ?Sized>(v: &mut V, def: &NamedFunctionDefinition) {
+	v.visit_function_name(&def.fname);
+	v.visit_function_body(&def.fbody);
+}
+
+pub fn walk_function_name<V: Visit + ?Sized>(v: &mut V, fname: &FunctionName) {
+	for name in &fname.nlist {
+		v.visit_name(name);
+	}
+	if let Some(name) = &fname.objname {
+		v.visit_name(name);
+	}
+}
+
+pub fn walk_local_function_definition<V: Visit + ?Sized>(v: &mut V, def: &LocalFunctionDefinition) {
+	v.visit_name(&def.name);
+	v.visit_function_body(&def.fbody);
+}
+
+pub fn walk_local_definition_with_attribute<V: Visit + ?Sized>(
+	v: &mut V, def: &LocalDefinitionWithAttribute,
+) {
+	for atname in &def.atlist {
+		v.visit_attribute_name(atname);
+	}
+	for exp in def.oelist.iter().flatten() {
+		v.visit_expression(exp);
+	}
+}
+
+pub fn walk_attribute_name<V: Visit + ?Sized>(v: &mut V, atname: &AttributeName) {
+	v.visit_name(&atname.name);
+	if let Some(attr) = &atname.attr.0 {
+		v.visit_name(attr);
+	}
+}
+
+pub fn walk_expression<V: Visit + ?Sized>(v: &mut V, exp: &Expression) {
+	match exp {
+		Expression::Value(val) => v.visit_value(val),
+		Expression::BinaryExpression(bexp) => v.visit_binary_expression(bexp),
+		Expression::UnaryExpression(uexp) => v.visit_unary_expression(uexp),
+		Expression::Error(_) => {}
+	}
+}
+
+pub fn walk_binary_expression<V: Visit + ?Sized>(v: &mut V, bexp: &BinaryExpression) {
+	v.visit_expression(&bexp.left);
+	v.visit_expression(&bexp.right);
+}
+
+pub fn walk_unary_expression<V: Visit + ?Sized>(v: &mut V, uexp: &UnaryExpression) {
+	v.visit_expression(&uexp.ex);
+}
+
+pub fn walk_value<V: Visit + ?Sized>(v: &mut V, val: &Value) {
+	match val {
+		Value::Nil(_) | Value::False(_) | Value::True(_) | Value::Numeral(..) => {}
+		Value::LiteralString(string) => v.visit_literal_string(string),
+		Value::VarArgs(varargs) => v.visit_var_args(varargs),
+		Value::AnonFunctionDefinition(def) => v.visit_anon_function_definition(def),
+		Value::Variable(var) => v.visit_variable(var),
+		Value::FunctionCall(call) => v.visit_function_call(call),
+		Value::ParenExpression(exp, _) => v.visit_expression(exp),
+		Value::TableConstructor(table) => v.visit_table_constructor(table),
+	}
+}
+
+pub fn walk_anon_function_definition<V: Visit + ?Sized>(v: &mut V, def: &AnonFunctionDefinition) {
+	v.visit_function_body(&def.fbody);
+}
+
+pub fn walk_function_body<V: Visit + ?Sized>(v: &mut V, fbody: &FunctionBody) {
+	if let Some(plist) = &fbody.oplist {
+		v.visit_parameter_list(plist);
+	}
+	v.visit_block(&fbody.bl);
+}
+
+pub fn walk_parameter_list<V: Visit + ?Sized>(v: &mut V, plist: &ParameterList) {
+	match plist {
+		ParameterList::NameList(nlist) => {
+			for name in nlist {
+				v.visit_name(name);
+			}
+		}
+		ParameterList::NameListWithVarArgs(nlist, varargs) => {
+			for name in nlist {
+				v.visit_name(name);
+			}
+			v.visit_var_args(varargs);
+		}
+		ParameterList::VarArgs(varargs) => v.visit_var_args(varargs),
+	}
+}
+
+pub fn walk_function_call<V: Visit + ?Sized>(v: &mut V, call: &FunctionCall) {
+	v.visit_affix(&call.affix);
+	v.visit_call(&call.call);
+}
+
+pub fn walk_call<V: Visit + ?Sized>(v: &mut V, call: &Call) {
+	if let Some(name) = &call.oname {
+		v.visit_name(name);
+	}
+	v.visit_arguments(&call.argu);
+}
+
+pub fn walk_arguments<V: Visit + ?Sized>(v: &mut V, argu: &Arguments) {
+	match argu {
+		Arguments::ClosedExpressionList(oelist, _) => {
+			for exp in oelist.iter().flatten() {
+				v.visit_expression(exp);
+			}
+		}
+		Arguments::TableConstructor(table) => v.visit_table_constructor(table),
+		Arguments::LiteralString(string) => v.visit_literal_string(string),
+	}
+}
+
+pub fn walk_variable<V: Visit + ?Sized>(v: &mut V, var: &Variable) {
+	match var {
+		Variable::Name(name) => v.visit_name(name),
+		Variable::Affixed(affix) => v.visit_affix(affix),
+	}
+}
+
+pub fn walk_affix<V: Visit + ?Sized>(v: &mut V, affix: &Affix) {
+	v.visit_prefix(&affix.pfix);
+	for suf in &affix.suflist {
+		v.visit_suffix(suf);
+	}
+}
+
+pub fn walk_prefix<V: Visit + ?Sized>(v: &mut V, pfix: &Prefix) {
+	match pfix {
+		Prefix::ParenExpression(exp, _) => v.visit_expression(exp),
+		Prefix::Name(name) => v.visit_name(name),
+	}
+}
+
+pub fn walk_suffix<V: Visit + ?Sized>(v: &mut V, suf: &Suffix) {
+	match suf {
+		Suffix::Call(call) => v.visit_call(call),
+		Suffix::Index(index) => v.visit_index(index),
+	}
+}
+
+pub fn walk_index<V: Visit + ?Sized>(v: &mut V, index: &Index) {
+	match index {
+		Index::Expression(exp, _) => v.visit_expression(exp),
+		Index::Member(name) => v.visit_name(name),
+	}
+}
+
+pub fn walk_table_constructor<V: Visit + ?Sized>(v: &mut V, table: &TableConstructor) {
+	for field in table.oflist.iter().flatten() {
+		v.visit_field(field);
+	}
+}
+
+pub fn walk_field<V: Visit + ?Sized>(v: &mut V, field: &Field) {
+	match field {
+		Field::BracketField(field) => v.visit_bracket_field(field),
+		Field::NameField(field) => v.visit_name_field(field),
+		Field::Expression(exp) => v.visit_expression(exp),
+	}
+}
+
+pub fn walk_bracket_field<V: Visit + ?Sized>(v: &mut V, field: &BracketField) {
+	v.visit_expression(&field.tabexp);
+	v.visit_expression(&field.val);
+}
+
+pub fn walk_name_field<V: Visit + ?Sized>(v: &mut V, field: &NameField) {
+	v.visit_name(&field.tabname);
+	v.visit_expression(&field.val);
+}
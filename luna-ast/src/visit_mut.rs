@@ -0,0 +1,480 @@
+//! # Mutable Visitors
+//!
+//! [VisitMut] is like [Visit](crate::visit::Visit), but walks the tree by
+//! mutable reference so a pass can rewrite the nodes it visits. Methods and
+//! `walk_*` functions end in `_mut`, so a type can implement both traits.
+//!
+//! ```
+//! use luna_ast::{terminal::Name, visit_mut::VisitMut};
+//!
+//! /// Renames every use of a name.
+//! struct Rename<'a>(&'a str, &'a str);
+//!
+//! impl VisitMut for Rename<'_> {
+//!     fn visit_name_mut(&mut self, name: &mut Name) {
+//!         if name.value == self.0 {
+//!             name.value = self.1.to_owned();
+//!         }
+//!     }
+//! }
+//! ```
+
+use crate::{
+	affix::{Affix, Call, Index, Prefix, Suffix},
+	attribute::AttributeName,
+	expression::{AnonFunctionDefinition, BinaryExpression, Expression, UnaryExpression, Value},
+	function::{Arguments, FunctionBody, FunctionCall, FunctionName, ParameterList, VarArgs},
+	span::Span,
+	statement::{
+		Assignment, ForExpression, ForList, IfBlock, IfTree, Label, LocalDefinitionWithAttribute,
+		LocalFunctionDefinition, NamedFunctionDefinition, RepeatUntil, Statement, While,
+	},
+	table::{BracketField, Field, NameField, TableConstructor},
+	terminal::{LiteralString, Name},
+	variable::Variable,
+	Block, Chunk, ReturnStatement,
+};
+
+/// A pass that changes a syntax tree in place. See the [module documentation](self).
+pub trait VisitMut {
+	fn visit_chunk_mut(&mut self, chunk: &mut Chunk) {
+		walk_chunk_mut(self, chunk);
+	}
+
+	fn visit_block_mut(&mut self, bl: &mut Block) {
+		walk_block_mut(self, bl);
+	}
+
+	fn visit_return_statement_mut(&mut self, ret: &mut ReturnStatement) {
+		walk_return_statement_mut(self, ret);
+	}
+
+	fn visit_statement_mut(&mut self, stat: &mut Statement) {
+		walk_statement_mut(self, stat);
+	}
+
+	fn visit_assignment_mut(&mut self, assign: &mut Assignment) {
+		walk_assignment_mut(self, assign);
+	}
+
+	fn visit_label_mut(&mut self, label: &mut Label) {
+		walk_label_mut(self, label);
+	}
+
+	fn visit_while_mut(&mut self, whl: &mut While) {
+		walk_while_mut(self, whl);
+	}
+
+	fn visit_repeat_until_mut(&mut self, rep: &mut RepeatUntil) {
+		walk_repeat_until_mut(self, rep);
+	}
+
+	fn visit_if_tree_mut(&mut self, tree: &mut IfTree) {
+		walk_if_tree_mut(self, tree);
+	}
+
+	fn visit_if_block_mut(&mut self, ifb: &mut IfBlock) {
+		walk_if_block_mut(self, ifb);
+	}
+
+	fn visit_for_expression_mut(&mut self, fexp: &mut ForExpression) {
+		walk_for_expression_mut(self, fexp);
+	}
+
+	fn visit_for_list_mut(&mut self, flist: &mut ForList) {
+		walk_for_list_mut(self, flist);
+	}
+
+	fn visit_named_function_definition_mut(&mut self, def: &mut NamedFunctionDefinition) {
+		walk_named_function_definition_mut(self, def);
+	}
+
+	fn visit_function_name_mut(&mut self, fname: &mut FunctionName) {
+		walk_function_name_mut(self, fname);
+	}
+
+	fn visit_local_function_definition_mut(&mut self, def: &mut LocalFunctionDefinition) {
+		walk_local_function_definition_mut(self, def);
+	}
+
+	fn visit_local_definition_with_attribute_mut(
+		&mut self, def: &mut LocalDefinitionWithAttribute,
+	) {
+		walk_local_definition_with_attribute_mut(self, def);
+	}
+
+	fn visit_attribute_name_mut(&mut self, atname: &mut AttributeName) {
+		walk_attribute_name_mut(self, atname);
+	}
+
+	fn visit_expression_mut(&mut self, exp: &mut Expression) {
+		walk_expression_mut(self, exp);
+	}
+
+	fn visit_binary_expression_mut(&mut self, bexp: &mut BinaryExpression) {
+		walk_binary_expression_mut(self, bexp);
+	}
+
+	fn visit_unary_expression_mut(&mut self, uexp: &mut UnaryExpression) {
+		walk_unary_expression_mut(self, uexp);
+	}
+
+	fn visit_value_mut(&mut self, val: &mut Value) {
+		walk_value_mut(self, val);
+	}
+
+	fn visit_anon_function_definition_mut(&mut self, def: &mut AnonFunctionDefinition) {
+		walk_anon_function_definition_mut(self, def);
+	}
+
+	fn visit_function_body_mut(&mut self, fbody: &mut FunctionBody) {
+		walk_function_body_mut(self, fbody);
+	}
+
+	fn visit_parameter_list_mut(&mut self, plist: &mut ParameterList) {
+		walk_parameter_list_mut(self, plist);
+	}
+
+	fn visit_function_call_mut(&mut self, call: &mut FunctionCall) {
+		walk_function_call_mut(self, call);
+	}
+
+	fn visit_call_mut(&mut self, call: &mut Call) {
+		walk_call_mut(self, call);
+	}
+
+	fn visit_arguments_mut(&mut self, argu: &mut Arguments) {
+		walk_arguments_mut(self, argu);
+	}
+
+	fn visit_variable_mut(&mut self, var: &mut Variable) {
+		walk_variable_mut(self, var);
+	}
+
+	fn visit_affix_mut(&mut self, affix: &mut Affix) {
+		walk_affix_mut(self, affix);
+	}
+
+	fn visit_prefix_mut(&mut self, pfix: &mut Prefix) {
+		walk_prefix_mut(self, pfix);
+	}
+
+	fn visit_suffix_mut(&mut self, suf: &mut Suffix) {
+		walk_suffix_mut(self, suf);
+	}
+
+	fn visit_index_mut(&mut self, index: &mut Index) {
+		walk_index_mut(self, index);
+	}
+
+	fn visit_table_constructor_mut(&mut self, table: &mut TableConstructor) {
+		walk_table_constructor_mut(self, table);
+	}
+
+	fn visit_field_mut(&mut self, field: &mut Field) {
+		walk_field_mut(self, field);
+	}
+
+	fn visit_bracket_field_mut(&mut self, field: &mut BracketField) {
+		walk_bracket_field_mut(self, field);
+	}
+
+	fn visit_name_field_mut(&mut self, field: &mut NameField) {
+		walk_name_field_mut(self, field);
+	}
+
+	// Leaves of the tree, which have nothing to walk
+
+	fn visit_name_mut(&mut self, _name: &mut Name) {}
+
+	fn visit_literal_string_mut(&mut self, _string: &mut LiteralString) {}
+
+	fn visit_var_args_mut(&mut self, _varargs: &mut VarArgs) {}
+}
+
+pub fn walk_chunk_mut<V: VisitMut + ?Sized>(v: &mut V, chunk: &mut Chunk) {
+	v.visit_block_mut(&mut chunk.0);
+}
+
+pub fn walk_block_mut<V: VisitMut + ?Sized>(v: &mut V, bl: &mut Block) {
+	for stat in &mut bl.stlist {
+		v.visit_statement_mut(stat);
+	}
+	if let Some(ret) = &mut bl.oret {
+		v.visit_return_statement_mut(ret);
+	}
+}
+
+pub fn walk_return_statement_mut<V: VisitMut + ?Sized>(v: &mut V, ret: &mut ReturnStatement) {
+	for exp in ret.oelist.iter_mut().flatten() {
+		v.visit_expression_mut(exp);
+	}
+}
+
+pub fn walk_statement_mut<V: VisitMut + ?Sized>(v: &mut V, stat: &mut Statement) {
+	match stat {
+		Statement::End(_) | Statement::Break(_) | Statement::Error(_) => {}
+		Statement::Assignment(assign) => v.visit_assignment_mut(assign),
+		Statement::FunctionCall(call) => v.visit_function_call_mut(call),
+		Statement::Label(label) => v.visit_label_mut(label),
+		Statement::Goto(name, _) => v.visit_name_mut(name),
+		Statement::Do(bl, _) => v.visit_block_mut(bl),
+		Statement::While(whl) => v.visit_while_mut(whl),
+		Statement::RepeatUntil(rep) => v.visit_repeat_until_mut(rep),
+		Statement::IfTree(tree) => v.visit_if_tree_mut(tree),
+		Statement::ForExpression(fexp) => v.visit_for_expression_mut(fexp),
+		Statement::ForList(flist) => v.visit_for_list_mut(flist),
+		Statement::FunctionDefinition(def) => v.visit_named_function_definition_mut(def),
+		Statement::LocalFunctionDefinition(def) => v.visit_local_function_definition_mut(def),
+		Statement::LocalDefinitionWithAttribute(def) => {
+			v.visit_local_definition_with_attribute_mut(def)
+		}
+	}
+}
+
+pub fn walk_assignment_mut<V: VisitMut + ?Sized>(v: &mut V, assign: &mut Assignment) {
+	for var in &mut assign.vlist {
+		v.visit_variable_mut(var);
+	}
+	for exp in &mut assign.elist {
+		v.visit_expression_mut(exp);
+	}
+}
+
+pub fn walk_label_mut<V: VisitMut + ?Sized>(v: &mut V, label: &mut Label) {
+	v.visit_name_mut(&mut label.0);
+}
+
+pub fn walk_while_mut<V: VisitMut + ?Sized>(v: &mut V, whl: &mut While) {
+	v.visit_expression_mut(&mut whl.cond);
+	v.visit_block_mut(&mut whl.bl);
+}
+
+pub fn walk_repeat_until_mut<V: VisitMut + ?Sized>(v: &mut V, rep: &mut RepeatUntil) {
+	v.visit_block_mut(&mut rep.bl);
+	v.visit_expression_mut(&mut rep.cond);
+}
+
+pub fn walk_if_tree_mut<V: VisitMut + ?Sized>(v: &mut V, tree: &mut IfTree) {
+	v.visit_if_block_mut(&mut tree.initial);
+	for ifb in &mut tree.elseifs {
+		v.visit_if_block_mut(ifb);
+	}
+	if let Some(bl) = &mut tree.otherwise {
+		v.visit_block_mut(bl);
+	}
+}
+
+pub fn walk_if_block_mut<V: VisitMut + ?Sized>(v: &mut V, ifb: &mut IfBlock) {
+	v.visit_expression_mut(&mut ifb.cond);
+	v.visit_block_mut(&mut ifb.bl);
+}
+
+pub fn walk_for_expression_mut<V: VisitMut + ?Sized>(v: &mut V, fexp: &mut ForExpression) {
+	v.visit_name_mut(&mut fexp.name);
+	// A range can't be borrowed mutably, so its bounds are taken out of it
+	let empty = || Expression::Error(Span::default());
+	let (mut start, mut end) = std::mem::replace(&mut fexp.range, empty()..=empty()).into_inner();
+	v.visit_expression_mut(&mut start);
+	v.visit_expression_mut(&mut end);
+	fexp.range = start..=end;
+	if let Some(step) = &mut fexp.step {
+		v.visit_expression_mut(step);
+	}
+	v.visit_block_mut(&mut fexp.bl);
+}
+
+pub fn walk_for_list_mut<V: VisitMut + ?Sized>(v: &mut V, flist: &mut ForList) {
+	for name in &mut flist.nlist {
+		v.visit_name_mut(name);
+	}
+	for exp in &mut flist.elist {
+		v.visit_expression_mut(exp);
+	}
+	v.visit_block_mut(&mut flist.bl);
+}
+
+pub fn walk_named_function_definition_mut<V: VisitMut + ?Sized>(
+	v: &mut V, def: &mut NamedFunctionDefinition,
+) {
+	v.visit_function_name_mut(&mut def.fname);
+	v.visit_function_body_mut(&mut def.fbody);
+}
+
+pub fn walk_function_name_mut<V: VisitMut + ?Sized>(v: &mut V, fname: &mut FunctionName) {
+	for name in &mut fname.nlist {
+		v.visit_name_mut(name);
+	}
+	if let Some(name) = &mut fname.objname {
+		v.visit_name_mut(name);
+	}
+}
+
+pub fn walk_local_function_definition_mut<V: VisitMut + ?Sized>(
+	v: &mut V, def: &mut LocalFunctionDefinition,
+) {
+	v.visit_name_mut(&mut def.name);
+	v.visit_function_body_mut(&mut def.fbody);
+}
+
+pub fn walk_local_definition_with_attribute_mut<V: VisitMut + ?Sized>(
+	v: &mut V, def: &mut LocalDefinitionWithAttribute,
+) {
+	for atname in &mut def.atlist {
+		v.visit_attribute_name_mut(atname);
+	}
+	for exp in def.oelist.iter_mut().flatten() {
+		v.visit_expression_mut(exp);
+	}
+}
+
+pub fn walk_attribute_name_mut<V: VisitMut + ?Sized>(v: &mut V, atname: &mut AttributeName) {
+	v.visit_name_mut(&mut atname.name);
+	if let Some(attr) = &mut atname.attr.0 {
+		v.visit_name_mut(attr);
+	}
+}
+
+pub fn walk_expression_mut<V: VisitMut + ?Sized>(v: &mut V, exp: &mut Expression) {
+	match exp {
+		Expression::Value(val) => v.visit_value_mut(val),
+		Expression::BinaryExpression(bexp) => v.visit_binary_expression_mut(bexp),
+		Expression::UnaryExpression(uexp) => v.visit_unary_expression_mut(uexp),
+		Expression::Error(_) => {}
+	}
+}
+
+pub fn walk_binary_expression_mut<V: VisitMut + ?Sized>(v: &mut V, bexp: &mut BinaryExpression) {
+	v.visit_expression_mut(&mut bexp.left);
+	v.visit_expression_mut(&mut bexp.right);
+}
+
+pub fn walk_unary_expression_mut<V: VisitMut + ?Sized>(v: &mut V, uexp: &mut UnaryExpression) {
+	v.visit_expression_mut(&mut uexp.ex);
+}
+
+pub fn walk_value_mut<V: VisitMut + ?Sized>(v: &mut V, val: &mut Value) {
+	match val {
+		Value::Nil(_) | Value::False(_) | Value::True(_) | Value::Numeral(..) => {}
+		Value::LiteralString(string) => v.visit_literal_string_mut(string),
+		Value::VarArgs(varargs) => v.visit_var_args_mut(varargs),
+		Value::AnonFunctionDefinition(def) => v.visit_anon_function_definition_mut(def),
+		Value::Variable(var) => v.visit_variable_mut(var),
+		Value::FunctionCall(call) => v.visit_function_call_mut(call),
+		Value::ParenExpression(exp, _) => v.visit_expression_mut(exp),
+		Value::TableConstructor(table) => v.visit_table_constructor_mut(table),
+	}
+}
+
+pub fn walk_anon_function_definition_mut<V: VisitMut + ?Sized>(
+	v: &mut V, def: &mut AnonFunctionDefinition,
+) {
+	v.visit_function_body_mut(&mut def.fbody);
+}
+
+pub fn walk_function_body_mut<V: VisitMut + ?Sized>(v: &mut V, fbody: &mut FunctionBody) {
+	if let Some(plist) = &mut fbody.oplist {
+		v.visit_parameter_list_mut(plist);
+	}
+	v.visit_block_mut(&mut fbody.bl);
+}
+
+pub fn walk_parameter_list_mut<V: VisitMut + ?Sized>(v: &mut V, plist: &mut ParameterList) {
+	match plist {
+		ParameterList::NameList(nlist) => {
+			for name in nlist {
+				v.visit_name_mut(name);
+			}
+		}
+		ParameterList::NameListWithVarArgs(nlist, varargs) => {
+			for name in nlist {
+				v.visit_name_mut(name);
+			}
+			v.visit_var_args_mut(varargs);
+		}
+		ParameterList::VarArgs(varargs) => v.visit_var_args_mut(varargs),
+	}
+}
+
+pub fn walk_function_call_mut<V: VisitMut + ?Sized>(v: &mut V, call: &mut FunctionCall) {
+	v.visit_affix_mut(&mut call.affix);
+	v.visit_call_mut(&mut call.call);
+}
+
+pub fn walk_call_mut<V: VisitMut + ?Sized>(v: &mut V, call: &mut Call) {
+	if let Some(name) = &mut call.oname {
+		v.visit_name_mut(name);
+	}
+	v.visit_arguments_mut(&mut call.argu);
+}
+
+pub fn walk_arguments_mut<V: VisitMut + ?Sized>(v: &mut V, argu: &mut Arguments) {
+	match argu {
+		Arguments::ClosedExpressionList(oelist, _) => {
+			for exp in oelist.iter_mut().flatten() {
+				v.visit_expression_mut(exp);
+			}
+		}
+		Arguments::TableConstructor(table) => v.visit_table_constructor_mut(table),
+		Arguments::LiteralString(string) => v.visit_literal_string_mut(string),
+	}
+}
+
+pub fn walk_variable_mut<V: VisitMut + ?Sized>(v: &mut V, var: &mut Variable) {
+	match var {
+		Variable::Name(name) => v.visit_name_mut(name),
+		Variable::Affixed(affix) => v.visit_affix_mut(affix),
+	}
+}
+
+pub fn walk_affix_mut<V: VisitMut + ?Sized>(v: &mut V, affix: &mut Affix) {
+	v.visit_prefix_mut(&mut affix.pfix);
+	for suf in &mut affix.suflist {
+		v.visit_suffix_mut(suf);
+	}
+}
+
+pub fn walk_prefix_mut<V: VisitMut + ?Sized>(v: &mut V, pfix: &mut Prefix) {
+	match pfix {
+		Prefix::ParenExpression(exp, _) => v.visit_expression_mut(exp),
+		Prefix::Name(name) => v.visit_name_mut(name),
+	}
+}
+
+pub fn walk_suffix_mut<V: VisitMut + ?Sized>(v: &mut V, suf: &mut Suffix) {
+	match suf {
+		Suffix::Call(call) => v.visit_call_mut(call),
+		Suffix::Index(index) => v.visit_index_mut(index),
+	}
+}
+
+pub fn walk_index_mut<V: VisitMut + ?Sized>(v: &mut V, index: &mut Index) {
+	match index {
+		Index::Expression(exp, _) => v.visit_expression_mut(exp),
+		Index::Member(name) => v.visit_name_mut(name),
+	}
+}
+
+pub fn walk_table_constructor_mut<V: VisitMut + ?Sized>(v: &mut V, table: &mut TableConstructor) {
+	for field in table.oflist.iter_mut().flatten() {
+		v.visit_field_mut(field);
+	}
+}
+
+pub fn walk_field_mut<V: VisitMut + ?Sized>(v: &mut V, field: &mut Field) {
+	match field {
+		Field::BracketField(field) => v.visit_bracket_field_mut(field),
+		Field::NameField(field) => v.visit_name_field_mut(field),
+		Field::Expression(exp) => v.visit_expression_mut(exp),
+	}
+}
+
+pub fn walk_bracket_field_mut<V: VisitMut + ?Sized>(v: &mut V, field: &mut BracketField) {
+	v.visit_expression_mut(&mut field.tabexp);
+	v.visit_expression_mut(&mut field.val);
+}
+
+pub fn walk_name_field_mut<V: VisitMut + ?Sized>(v: &mut V, field: &mut NameField) {
+	v.visit_name_mut(&mut field.tabname);
+	v.visit_expression_mut(&mut field.val);
+}
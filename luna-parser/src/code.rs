@@ -0,0 +1,213 @@
+//! # Code Generation
+//!
+//! Compiles a syntax tree into the [Proto]s of the Lua 5.4 virtual machine,
+//! the way `lparser.c` and `lcode.c` do as they parse. [compile] checks the
+//! chunk with [validate] and resolves its names with [resolve] first, then
+//! walks it once, keeping locals and temporaries in registers and constants
+//! in operands wherever an instruction can take them.
+//!
+//! The code is the same as `luac` would give, but for line numbers: the
+//! syntax tree doesn't keep the position of every token, so instructions are
+//! on the line where their expression or statement starts.
+
+use luna_ast::{
+	function::FunctionBody,
+	span::{LineIndex, Span},
+	terminal::Name,
+	Chunk,
+};
+use luna_vm::{
+	instruction::MAXARG_BX,
+	proto::{Proto, UpvalueDesc, VariableKind},
+	OpCode,
+};
+
+use self::{
+	expdesc::{ExpDesc, ExpKind},
+	func::FuncState,
+};
+use crate::{
+	error::{Error, ErrorKind},
+	resolve::{resolve, Binding, FunctionId, LocalKind, Resolution},
+	validate::validate,
+};
+
+mod block;
+mod expdesc;
+mod expression;
+mod func;
+mod statement;
+
+/// The largest number of upvalues a function can have (`MAXUPVAL`).
+const MAXUPVAL: usize = 255;
+
+/// Compiles `chunk`, parsed from `src`, into the prototype of its main
+/// function. `source` names the chunk in the debug information, like
+/// `@script.lua`.
+///
+/// The first error is returned instead, whether it breaks a rule [validate]
+/// checks or a limit of the virtual machine, like the number of registers.
+pub fn compile(chunk: &Chunk, src: &[u8], source: &str) -> Result<Proto, Error<Span>> {
+	if let Some(e) = validate(chunk).into_iter().next() {
+		return Err(e);
+	}
+
+	let mut compiler = Compiler {
+		res: resolve(chunk),
+		lines: LineIndex::new(src),
+		source,
+		funcs: Vec::new(),
+	};
+	compiler.main(chunk)
+}
+
+struct Compiler<'a> {
+	res: Resolution<'a>,
+	lines: LineIndex,
+	source: &'a str,
+	/// The function being compiled, after the ones it's nested in
+	funcs: Vec<FuncState>,
+}
+
+impl Compiler<'_> {
+	fn fs(&mut self) -> &mut FuncState {
+		self.funcs.last_mut().expect("always inside a function")
+	}
+
+	fn line(&self, offset: usize) -> usize {
+		self.lines.line(offset)
+	}
+
+	/// Puts the instructions added next on the line where `span` starts.
+	fn at(&mut self, span: Span) {
+		let line = self.line(span.start);
+		self.fs().line = line;
+	}
+
+	/// The kind of the local declared by `name`, and whether it's captured.
+	/// A name that doesn't declare a local fails the compilation, and is a
+	/// regular local meanwhile.
+	fn local(&mut self, name: &Name) -> (LocalKind, bool) {
+		match self.res.binding(name) {
+			Some(Binding::Local(id)) => {
+				let local = self.res.local(id);
+				(local.kind, local.captured)
+			}
+			_ => {
+				self.fs().fail(ErrorKind::Unresolved(name.value.clone()));
+				(LocalKind::Regular, false)
+			}
+		}
+	}
+
+	/// Activates the local declared by `name` (`adjustlocalvars`).
+	fn declare(&mut self, name: &Name) {
+		let (kind, captured) = self.local(name);
+		let constant = kind == LocalKind::CompileTimeConstant;
+		self.fs().add_local(&name.value, constant, captured);
+	}
+
+	fn main(&mut self, chunk: &Chunk) -> Result<Proto, Error<Span>> {
+		self.open_func(FunctionId(0), 0);
+		// The main function is always vararg (`setvararg`)
+		let fs = self.fs();
+		fs.f.is_vararg = true;
+		fs.line = 1;
+		fs.code_abc(OpCode::VarArgPrep, 0, 0, 0);
+
+		let bl = &chunk.0;
+		self.statlist(bl, false);
+		let lastline = self.line(bl.span.end.saturating_sub(1));
+		let fs = self.close_func(lastline);
+		match fs.error {
+			Some(e) => Err(e),
+			None => Ok(fs.f),
+		}
+	}
+
+	/// Starts compiling the function `id`, defined at `linedefined`
+	/// (`open_func`).
+	fn open_func(&mut self, id: FunctionId, linedefined: usize) {
+		let mut fs = FuncState::new(linedefined, self.source);
+		let upvalues = &self.res.function(id).upvalues;
+		fs.check_limit(upvalues.len(), MAXUPVAL, "upvalues");
+		fs.f.upvalues = upvalues
+			.iter()
+			.map(|up| UpvalueDesc {
+				name: Some(up.name.clone()),
+				instack: up.instack,
+				idx: up.index as u8,
+				kind: variable_kind(up.kind),
+			})
+			.collect();
+		fs.enter_block(false);
+		self.funcs.push(fs);
+	}
+
+	/// Finishes the function being compiled, whose code ends on `lastline`
+	/// (`close_func`).
+	fn close_func(&mut self, lastline: usize) -> FuncState {
+		let fs = self.fs();
+		fs.line = lastline;
+		let first = fs.nvarstack();
+		fs.ret(first, Some(0));
+		fs.leave_block();
+		fs.finish();
+		self.funcs.pop().expect("always inside a function")
+	}
+
+	/// Compiles a function defined at `line`, and makes a closure of it in the
+	/// next register (`body` and `codeclosure`). The parameters of a method
+	/// start with `self`.
+	fn body(&mut self, fbody: &FunctionBody, line: usize) -> ExpDesc {
+		let id = self.res.body_function(fbody).expect("bodies are resolved");
+		self.open_func(id, line);
+
+		// Parameters are the first locals of the function (`parlist`)
+		let function = self.res.function(id);
+		let vararg = function.vararg;
+		let params: Vec<_> = self.res.scope(function.scope).locals[..function.params]
+			.iter()
+			.map(|&local| {
+				let local = self.res.local(local);
+				(local.name.clone(), local.captured)
+			})
+			.collect();
+		let fs = self.fs();
+		for (name, captured) in &params {
+			fs.add_local(name, false, *captured);
+		}
+		fs.f.numparams = fs.actvar.len() as u8;
+		if vararg {
+			fs.f.is_vararg = true;
+			fs.code_abc(OpCode::VarArgPrep, params.len(), 0, 0);
+		}
+		fs.reserve_regs(params.len());
+
+		self.statlist(&fbody.bl, false);
+		let lastline = self.line(fbody.span.end.saturating_sub(1));
+		self.fs().f.lastlinedefined = lastline;
+		let child = self.close_func(lastline);
+
+		let fs = self.fs();
+		if fs.error.is_none() {
+			fs.error = child.error;
+		}
+		fs.f.protos.push(child.f);
+		let np = fs.f.protos.len();
+		fs.check_limit(np, MAXARG_BX as usize, "functions");
+		fs.line = lastline;
+		let mut e = ExpDesc::new(ExpKind::Reloc(fs.code_abx(OpCode::Closure, 0, np - 1)));
+		fs.exp_to_next_reg(&mut e);
+		e
+	}
+}
+
+fn variable_kind(kind: LocalKind) -> VariableKind {
+	match kind {
+		LocalKind::Regular => VariableKind::Regular,
+		LocalKind::Const => VariableKind::Const,
+		LocalKind::ToClose => VariableKind::ToClose,
+		LocalKind::CompileTimeConstant => VariableKind::CompileTimeConstant,
+	}
+}
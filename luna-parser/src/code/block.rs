@@ -0,0 +1,205 @@
+//! ## Blocks, Locals and Labels
+//!
+//! Keeps track of the scopes of a function being compiled, like the parts of
+//! `lparser.c` that handle `BlockCnt` and `Labeldesc`. Jumps that leave the
+//! scope of a captured or to-be-closed local close it on the way.
+
+use luna_vm::{proto::LocVar, OpCode};
+
+use super::func::{FuncState, JumpList};
+use crate::error::ErrorKind;
+
+/// The largest number of active locals in a function (`MAXVARS`).
+const MAXVARS: usize = 200;
+
+/// An active local (`Vardesc`).
+#[derive(Clone, Copy, Debug)]
+pub(super) struct ActVar {
+	/// The register of the local, unless it's a compile-time constant
+	pub ridx: Option<usize>,
+	/// The index of the local in [Proto::locvars](luna_vm::proto::Proto::locvars)
+	pub pidx: Option<usize>,
+}
+
+/// A block being compiled (`BlockCnt`).
+#[derive(Clone, Copy, Debug)]
+pub(super) struct BlockCnt {
+	/// The first label of the block
+	pub firstlabel: usize,
+	/// The first pending goto of the block
+	pub firstgoto: usize,
+	/// The number of active locals outside the block
+	pub nactvar: usize,
+	/// Whether some local of the block is captured or to be closed
+	pub upval: bool,
+	pub isloop: bool,
+	/// Whether the block is in the scope of a to-be-closed local
+	pub insidetbc: bool,
+}
+
+/// A label, or a pending `goto` (`Labeldesc`).
+#[derive(Clone, Debug)]
+pub(super) struct LabelDesc {
+	pub name: String,
+	/// The position of the label, or the jumps of the `goto`, which are
+	/// none for a `break` whose condition is never true
+	pub pc: JumpList,
+	/// The number of active locals where it appears
+	pub nactvar: usize,
+	/// Whether the `goto` leaves the scope of a local that needs closing
+	pub close: bool,
+}
+
+impl FuncState {
+	pub fn enter_block(&mut self, isloop: bool) {
+		let insidetbc = self.blocks.last().is_some_and(|bl| bl.insidetbc);
+		self.blocks.push(BlockCnt {
+			firstlabel: self.labels.len(),
+			firstgoto: self.gotos.len(),
+			nactvar: self.actvar.len(),
+			upval: false,
+			isloop,
+			insidetbc,
+		});
+	}
+
+	pub fn leave_block(&mut self) {
+		let bl = *self.blocks.last().expect("block was entered");
+		// The level outside the block
+		let stklevel = self.reglevel(bl.nactvar);
+		// The levels inside it, which pending gotos still need once its locals
+		// are gone (`movegotosout` reads them from the removed `Vardesc`s)
+		let mut levels = vec![stklevel];
+		for var in &self.actvar[bl.nactvar..] {
+			let last = levels[levels.len() - 1];
+			levels.push(var.ridx.map_or(last, |ridx| ridx + 1));
+		}
+		self.remove_vars(bl.nactvar);
+		// Pending breaks jump to the end of the loop
+		let hasclose = bl.isloop && self.create_label("break", false);
+		if !hasclose && self.blocks.len() > 1 && bl.upval {
+			self.code_abc(OpCode::Close, stklevel, 0, 0);
+		}
+		self.freereg = stklevel;
+		self.labels.truncate(bl.firstlabel);
+		self.blocks.pop();
+
+		if self.blocks.is_empty() {
+			// `validate` reports these with their locations (`undefgoto`)
+			if let Some(gt) = self.gotos.get(bl.firstgoto) {
+				let kind = match gt.name.as_str() {
+					"break" => ErrorKind::BreakOutsideLoop,
+					name => ErrorKind::UndefinedGoto(name.to_owned()),
+				};
+				self.fail(kind);
+			}
+			return;
+		}
+		// Pending gotos leave the scope of the block's locals (`movegotosout`)
+		for gt in &mut self.gotos[bl.firstgoto..] {
+			if levels[gt.nactvar - bl.nactvar] > stklevel {
+				gt.close |= bl.upval;
+			}
+			gt.nactvar = bl.nactvar;
+		}
+	}
+
+	/// Activates a new local (`adjustlocalvars`). Compile-time constants don't
+	/// take a register, and aren't in the debug information.
+	pub fn add_local(&mut self, name: &str, constant: bool, captured: bool) {
+		self.check_limit(self.actvar.len() + 1, MAXVARS, "local variables");
+		if constant {
+			self.actvar.push(ActVar {
+				ridx: None,
+				pidx: None,
+			});
+			return;
+		}
+		let ridx = self.nvarstack();
+		self.f.locvars.push(LocVar {
+			varname: name.to_owned(),
+			startpc: self.pc(),
+			endpc: 0,
+		});
+		self.actvar.push(ActVar {
+			ridx: Some(ridx),
+			pidx: Some(self.f.locvars.len() - 1),
+		});
+		if captured {
+			// Leaving the block closes the upvalue (`markupval`)
+			self.blocks.last_mut().expect("always inside a block").upval = true;
+			self.needclose = true;
+		}
+	}
+
+	/// Deactivates the locals past the first `tolevel` (`removevars`).
+	pub fn remove_vars(&mut self, tolevel: usize) {
+		let pc = self.pc();
+		for var in self.actvar.drain(tolevel..) {
+			if let Some(pidx) = var.pidx {
+				self.f.locvars[pidx].endpc = pc;
+			}
+		}
+	}
+
+	/// Marks the current block as having a to-be-closed local (`marktobeclosed`).
+	pub fn mark_to_be_closed(&mut self) {
+		let bl = self.blocks.last_mut().expect("always inside a block");
+		bl.upval = true;
+		bl.insidetbc = true;
+		self.needclose = true;
+	}
+
+	/// Adds a pending `goto` for the jumps of `pc` (`newgotoentry`).
+	pub fn new_goto(&mut self, name: &str, pc: JumpList) {
+		self.gotos.push(LabelDesc {
+			name: name.to_owned(),
+			pc,
+			nactvar: self.actvar.len(),
+			close: false,
+		});
+	}
+
+	/// The visible label called `name` (`findlabel`).
+	pub fn find_label(&self, name: &str) -> Option<&LabelDesc> {
+		self.labels.iter().find(|lb| lb.name == name)
+	}
+
+	/// Adds a label at the next instruction, and points the pending gotos of
+	/// the block to it. A label at the end of its block is outside the scope of
+	/// the block's locals. Returns whether it had to close any of them
+	/// (`createlabel`).
+	pub fn create_label(&mut self, name: &str, last: bool) -> bool {
+		let pc = self.get_label();
+		let bl = self.blocks.last().expect("always inside a block");
+		let nactvar = match last {
+			true => bl.nactvar,
+			false => self.actvar.len(),
+		};
+		let firstgoto = bl.firstgoto;
+		self.labels.push(LabelDesc {
+			name: name.to_owned(),
+			pc: Some(pc),
+			nactvar,
+			close: false,
+		});
+
+		// Solve the pending gotos of the block (`solvegotos`)
+		let mut needsclose = false;
+		let mut i = firstgoto;
+		while i < self.gotos.len() {
+			if self.gotos[i].name == name {
+				let gt = self.gotos.remove(i);
+				needsclose |= gt.close;
+				self.patch_list(gt.pc, pc);
+			} else {
+				i += 1;
+			}
+		}
+		if needsclose {
+			let level = self.nvarstack();
+			self.code_abc(OpCode::Close, level, 0, 0);
+		}
+		needsclose
+	}
+}
@@ -0,0 +1,990 @@
+//! ## Expression Descriptors
+//!
+//! An expression being compiled is described by an [ExpDesc], which says where
+//! its value is, or how to get it. Values are only put in registers when they
+//! have to be, so that constants can be operands of instructions, and
+//! conditions can jump without producing a value. This is the upper half of
+//! `lcode.c`.
+
+use std::mem;
+
+use luna_ast::{
+	fold::{fold_binary, fold_unary, Constant},
+	operation::{BinaryOperation, UnaryOperation},
+};
+use luna_vm::{
+	instruction::{MAXARG_B, MAXARG_C},
+	proto, OpCode,
+};
+
+use super::func::{fits_c, float_to_int, int_to_sc, FuncState, JumpList, MAXINDEXRK, NO_REG};
+
+/// The longest strings that are interned, which are the only ones that can be
+/// field names of `GETFIELD` and friends (`LUAI_MAXSHORTLEN`).
+const MAXSHORTLEN: usize = 40;
+
+/// Where the value of an expression is (`expkind`).
+#[derive(Clone, Debug, PartialEq)]
+pub(super) enum ExpKind {
+	/// No value, like an empty list of expressions
+	Void,
+	Nil,
+	True,
+	False,
+	/// A constant, by its index in the constant table
+	K(usize),
+	KFlt(f64),
+	KInt(i64),
+	/// A string not in the constant table yet
+	KStr(Vec<u8>),
+	/// A value in a register that can't change
+	NonReloc(usize),
+	/// A local, in its register
+	Local(usize),
+	/// An upvalue, by its index
+	Upval(usize),
+	/// A table in register `t`, indexed by the value in register `idx`
+	Indexed {
+		t: usize,
+		idx: usize,
+	},
+	/// An upvalue table `t`, indexed by the string constant `idx`
+	IndexUp {
+		t: usize,
+		idx: usize,
+	},
+	/// A table in register `t`, indexed by the integer `idx`
+	IndexI {
+		t: usize,
+		idx: usize,
+	},
+	/// A table in register `t`, indexed by the string constant `idx`
+	IndexStr {
+		t: usize,
+		idx: usize,
+	},
+	/// A test, and the jump at this pc taken when it's true
+	Jmp(usize),
+	/// The result of the instruction at this pc, which can still be put in any
+	/// register
+	Reloc(usize),
+	/// A call, by the pc of its instruction
+	Call(usize),
+	/// `...`, by the pc of its instruction
+	Vararg(usize),
+}
+
+/// An expression being compiled (`expdesc`).
+#[derive(Clone, Debug, PartialEq)]
+pub(super) struct ExpDesc {
+	pub k: ExpKind,
+	/// The jumps taken when the expression is true
+	pub t: JumpList,
+	/// The jumps taken when the expression is false
+	pub f: JumpList,
+}
+
+impl ExpDesc {
+	pub fn new(k: ExpKind) -> Self {
+		Self {
+			k,
+			t: None,
+			f: None,
+		}
+	}
+
+	/// An expression for a compile-time constant (`const2exp`).
+	pub fn constant(c: &Constant) -> Self {
+		Self::new(match c {
+			Constant::Nil => ExpKind::Nil,
+			Constant::Boolean(true) => ExpKind::True,
+			Constant::Boolean(false) => ExpKind::False,
+			Constant::Integer(i) => ExpKind::KInt(*i),
+			Constant::Float(f) => ExpKind::KFlt(*f),
+			Constant::String(s) => ExpKind::KStr(s.clone()),
+		})
+	}
+
+	pub fn has_jumps(&self) -> bool {
+		self.t != self.f
+	}
+
+	/// Whether the expression has several values (`hasmultret`).
+	pub fn has_multret(&self) -> bool {
+		matches!(self.k, ExpKind::Call(_) | ExpKind::Vararg(_))
+	}
+
+	/// The register of a value in one.
+	pub fn reg(&self) -> usize {
+		match self.k {
+			ExpKind::NonReloc(reg) | ExpKind::Local(reg) => reg,
+			_ => unreachable!("expression isn't in a register: {:?}", self.k),
+		}
+	}
+
+	/// The pc of the instruction that computes the expression.
+	fn pc(&self) -> usize {
+		match self.k {
+			ExpKind::Jmp(pc) | ExpKind::Reloc(pc) | ExpKind::Call(pc) | ExpKind::Vararg(pc) => pc,
+			_ => unreachable!("expression has no instruction: {:?}", self.k),
+		}
+	}
+
+	/// The value of a numeral without jumps (`tonumeral`).
+	fn numeral(&self) -> Option<Constant> {
+		match self.k {
+			_ if self.has_jumps() => None,
+			ExpKind::KInt(i) => Some(Constant::Integer(i)),
+			ExpKind::KFlt(f) => Some(Constant::Float(f)),
+			_ => None,
+		}
+	}
+
+	/// The integer of an integer constant without jumps (`luaK_isKint`).
+	fn int(&self) -> Option<i64> {
+		match self.k {
+			ExpKind::KInt(i) if !self.has_jumps() => Some(i),
+			_ => None,
+		}
+	}
+
+	/// Whether the expression is an integer that fits in a `C` argument (`isCint`).
+	fn is_cint(&self) -> bool {
+		self.int().is_some_and(|i| (i as u64) <= MAXARG_C as u64)
+	}
+
+	/// Whether the expression is an integer that fits in a signed `C` argument
+	/// (`isSCint`).
+	fn is_scint(&self) -> bool {
+		self.int().is_some_and(fits_c)
+	}
+
+	/// The number as a signed `C` argument, if it's an integer or a float with
+	/// an integer value that fits in one, and whether it's a float
+	/// (`isSCnumber`).
+	fn sc_number(&self) -> Option<(usize, bool)> {
+		let (i, isfloat) = match self.k {
+			ExpKind::KInt(i) => (i, false),
+			ExpKind::KFlt(f) => (float_to_int(f)?, true),
+			_ => return None,
+		};
+		match !self.has_jumps() && fits_c(i) {
+			true => Some((int_to_sc(i), isfloat)),
+			false => None,
+		}
+	}
+}
+
+/// The metamethod event of an arithmetic or bitwise operator, as in `ltm.h`.
+fn event(op: &BinaryOperation) -> usize {
+	use BinaryOperation::*;
+
+	match op {
+		Add => 6,
+		Subtract => 7,
+		Multiply => 8,
+		Modulo => 9,
+		Power => 10,
+		Divide => 11,
+		FloorDivide => 12,
+		BitwiseAnd => 13,
+		BitwiseOr => 14,
+		BitwiseXor => 15,
+		BitwiseLeftShift => 16,
+		BitwiseRightShift => 17,
+		_ => unreachable!("not an arithmetic operator: {op:?}"),
+	}
+}
+
+/// The instruction of an arithmetic or bitwise operator on two registers.
+fn arith_op(op: &BinaryOperation) -> OpCode {
+	use BinaryOperation::*;
+
+	match op {
+		Add => OpCode::Add,
+		Subtract => OpCode::Sub,
+		Multiply => OpCode::Mul,
+		Modulo => OpCode::Mod,
+		Power => OpCode::Pow,
+		Divide => OpCode::Div,
+		FloorDivide => OpCode::IDiv,
+		BitwiseAnd => OpCode::BAnd,
+		BitwiseOr => OpCode::BOr,
+		BitwiseXor => OpCode::BXor,
+		BitwiseLeftShift => OpCode::Shl,
+		BitwiseRightShift => OpCode::Shr,
+		_ => unreachable!("not an arithmetic operator: {op:?}"),
+	}
+}
+
+/// The instruction of an arithmetic or bitwise operator with a constant.
+fn arith_op_k(op: &BinaryOperation) -> OpCode {
+	use BinaryOperation::*;
+
+	match op {
+		Add => OpCode::AddK,
+		Subtract => OpCode::SubK,
+		Multiply => OpCode::MulK,
+		Modulo => OpCode::ModK,
+		Power => OpCode::PowK,
+		Divide => OpCode::DivK,
+		FloorDivide => OpCode::IDivK,
+		BitwiseAnd => OpCode::BAndK,
+		BitwiseOr => OpCode::BOrK,
+		BitwiseXor => OpCode::BXorK,
+		_ => unreachable!("no constant operand for {op:?}"),
+	}
+}
+
+/// Whether `op` is folded when both its operands are numerals (`foldbinop`).
+fn folds(op: &BinaryOperation) -> bool {
+	use BinaryOperation::*;
+
+	matches!(
+		op,
+		Add | Subtract
+			| Multiply
+			| Divide | FloorDivide
+			| Power | Modulo
+			| BitwiseAnd
+			| BitwiseXor
+			| BitwiseOr
+			| BitwiseLeftShift
+			| BitwiseRightShift
+	)
+}
+
+impl FuncState {
+	/// The instruction that computes `e`.
+	fn instruction(&mut self, e: &ExpDesc) -> &mut luna_vm::instruction::Instruction {
+		&mut self.f.code[e.pc()]
+	}
+
+	/// Frees the register of `e`, if it's a temporary (`freeexp`).
+	pub fn free_exp(&mut self, e: &ExpDesc) {
+		if let ExpKind::NonReloc(reg) = e.k {
+			self.free_reg(reg);
+		}
+	}
+
+	/// Frees the registers of two expressions, in the right order (`freeexps`).
+	fn free_exps(&mut self, e1: &ExpDesc, e2: &ExpDesc) {
+		match (&e1.k, &e2.k) {
+			(ExpKind::NonReloc(r1), ExpKind::NonReloc(r2)) => self.free_regs(*r1, *r2),
+			(ExpKind::NonReloc(r1), _) => self.free_reg(*r1),
+			(_, ExpKind::NonReloc(r2)) => self.free_reg(*r2),
+			_ => {}
+		}
+	}
+
+	/// Makes a call or `...` return `nresults` values, or all of them if it's
+	/// `None` (`luaK_setreturns`).
+	pub fn set_returns(&mut self, e: &mut ExpDesc, nresults: Option<usize>) {
+		let c = nresults.map_or(0, |n| n + 1) as u8;
+		let freereg = self.freereg as u8;
+		let inst = self.instruction(e);
+		inst.set_c(c);
+		if let ExpKind::Vararg(_) = e.k {
+			inst.set_a(freereg);
+			self.reserve_regs(1);
+		}
+	}
+
+	/// Makes a call or `...` return all of its values (`luaK_setmultret`).
+	pub fn set_multret(&mut self, e: &mut ExpDesc) {
+		self.set_returns(e, None);
+	}
+
+	/// Makes a call or `...` return a single value (`luaK_setoneret`).
+	pub fn set_one_ret(&mut self, e: &mut ExpDesc) {
+		match e.k {
+			// Calls return one value by default
+			ExpKind::Call(pc) => e.k = ExpKind::NonReloc(self.f.code[pc].a() as usize),
+			ExpKind::Vararg(pc) => {
+				self.f.code[pc].set_c(2);
+				e.k = ExpKind::Reloc(pc);
+			}
+			_ => {}
+		}
+	}
+
+	/// Puts a string in the constant table (`str2K`).
+	fn str_to_k(&mut self, e: &mut ExpDesc) {
+		if let ExpKind::KStr(s) = &e.k {
+			e.k = ExpKind::K(self.string_k(s));
+		}
+	}
+
+	/// Gets the value of a variable, so it's no longer one (`luaK_dischargevars`).
+	pub fn discharge_vars(&mut self, e: &mut ExpDesc) {
+		e.k = match e.k {
+			ExpKind::Local(reg) => ExpKind::NonReloc(reg),
+			ExpKind::Upval(idx) => ExpKind::Reloc(self.code_abc(OpCode::GetUpval, 0, idx, 0)),
+			ExpKind::IndexUp { t, idx } => {
+				ExpKind::Reloc(self.code_abc(OpCode::GetTabUp, 0, t, idx))
+			}
+			ExpKind::IndexI { t, idx } => {
+				self.free_reg(t);
+				ExpKind::Reloc(self.code_abc(OpCode::GetI, 0, t, idx))
+			}
+			ExpKind::IndexStr { t, idx } => {
+				self.free_reg(t);
+				ExpKind::Reloc(self.code_abc(OpCode::GetField, 0, t, idx))
+			}
+			ExpKind::Indexed { t, idx } => {
+				self.free_regs(t, idx);
+				ExpKind::Reloc(self.code_abc(OpCode::GetTable, 0, t, idx))
+			}
+			ExpKind::Vararg(_) | ExpKind::Call(_) => return self.set_one_ret(e),
+			_ => return,
+		};
+	}
+
+	/// Puts the value of `e` in `reg`, unless it's a test (`discharge2reg`).
+	fn discharge_to_reg(&mut self, e: &mut ExpDesc, reg: usize) {
+		self.discharge_vars(e);
+		self.str_to_k(e);
+		match e.k {
+			ExpKind::Nil => self.nil(reg, 1),
+			ExpKind::False => _ = self.code_abc(OpCode::LoadFalse, reg, 0, 0),
+			ExpKind::True => _ = self.code_abc(OpCode::LoadTrue, reg, 0, 0),
+			ExpKind::K(k) => _ = self.code_k(reg, k),
+			ExpKind::KFlt(f) => self.float(reg, f),
+			ExpKind::KInt(i) => self.int(reg, i),
+			ExpKind::Reloc(pc) => self.f.code[pc].set_a(reg as u8),
+			ExpKind::NonReloc(from) => {
+				if reg != from {
+					self.code_abc(OpCode::Move, reg, from, 0);
+				}
+			}
+			_ => {
+				debug_assert!(matches!(e.k, ExpKind::Jmp(_)));
+				return;
+			}
+		}
+		e.k = ExpKind::NonReloc(reg);
+	}
+
+	/// Puts the value of `e` in some register, unless it's a test
+	/// (`discharge2anyreg`).
+	fn discharge_to_any_reg(&mut self, e: &mut ExpDesc) {
+		if !matches!(e.k, ExpKind::NonReloc(_)) {
+			self.reserve_regs(1);
+			self.discharge_to_reg(e, self.freereg - 1);
+		}
+	}
+
+	/// Sets `a` to a boolean, as a jump target (`code_loadbool`).
+	fn code_loadbool(&mut self, a: usize, op: OpCode) -> usize {
+		self.get_label();
+		self.code_abc(op, a, 0, 0)
+	}
+
+	/// Puts the value of `e` in `reg`, including the values of its jumps
+	/// (`exp2reg`).
+	fn exp_to_reg(&mut self, e: &mut ExpDesc, reg: usize) {
+		self.discharge_to_reg(e, reg);
+		if let ExpKind::Jmp(pc) = e.k {
+			self.concat(&mut e.t, Some(pc));
+		}
+		if e.has_jumps() {
+			let mut p_f = None;
+			let mut p_t = None;
+			if self.need_value(e.t) || self.need_value(e.f) {
+				let fj = match e.k {
+					ExpKind::Jmp(_) => None,
+					_ => Some(self.jump()),
+				};
+				p_f = Some(self.code_loadbool(reg, OpCode::LFalseSkip));
+				p_t = Some(self.code_loadbool(reg, OpCode::LoadTrue));
+				self.patch_to_here(fj);
+			}
+			let end = self.get_label();
+			self.patch_list_aux(e.f, end, reg, p_f.unwrap_or(end));
+			self.patch_list_aux(e.t, end, reg, p_t.unwrap_or(end));
+		}
+		e.t = None;
+		e.f = None;
+		e.k = ExpKind::NonReloc(reg);
+	}
+
+	/// Puts the value of `e` in the next free register (`luaK_exp2nextreg`).
+	pub fn exp_to_next_reg(&mut self, e: &mut ExpDesc) {
+		self.discharge_vars(e);
+		self.free_exp(e);
+		self.reserve_regs(1);
+		self.exp_to_reg(e, self.freereg - 1);
+	}
+
+	/// Puts the value of `e` in some register, returning it
+	/// (`luaK_exp2anyreg`).
+	pub fn exp_to_any_reg(&mut self, e: &mut ExpDesc) -> usize {
+		self.discharge_vars(e);
+		if let ExpKind::NonReloc(reg) = e.k {
+			if !e.has_jumps() {
+				return reg;
+			}
+			// A local can't hold the values of the jumps
+			if reg >= self.nvarstack() {
+				self.exp_to_reg(e, reg);
+				return reg;
+			}
+		}
+		self.exp_to_next_reg(e);
+		e.reg()
+	}
+
+	/// Puts the value of `e` in a register, unless it's an upvalue
+	/// (`luaK_exp2anyregup`).
+	pub fn exp_to_any_reg_up(&mut self, e: &mut ExpDesc) {
+		if !matches!(e.k, ExpKind::Upval(_)) || e.has_jumps() {
+			self.exp_to_any_reg(e);
+		}
+	}
+
+	/// Gets the value of `e`, in a register if it has jumps (`luaK_exp2val`).
+	pub fn exp_to_val(&mut self, e: &mut ExpDesc) {
+		match e.has_jumps() {
+			true => _ = self.exp_to_any_reg(e),
+			false => self.discharge_vars(e),
+		}
+	}
+
+	/// Puts a constant in the constant table, if it fits in an `RK` operand
+	/// (`luaK_exp2K`).
+	fn exp_to_k(&mut self, e: &mut ExpDesc) -> bool {
+		if e.has_jumps() {
+			return false;
+		}
+		let k = match &e.k {
+			ExpKind::True => self.bool_k(true),
+			ExpKind::False => self.bool_k(false),
+			ExpKind::Nil => self.nil_k(),
+			ExpKind::KInt(i) => self.int_k(*i),
+			ExpKind::KFlt(f) => self.number_k(*f),
+			ExpKind::KStr(s) => self.string_k(s),
+			ExpKind::K(k) => *k,
+			_ => return false,
+		};
+		if k > MAXINDEXRK {
+			return false;
+		}
+		e.k = ExpKind::K(k);
+		true
+	}
+
+	/// Makes `e` a constant or a register, returning whether it's a constant
+	/// (`exp2RK`).
+	fn exp_to_rk(&mut self, e: &mut ExpDesc) -> bool {
+		match self.exp_to_k(e) {
+			true => true,
+			false => {
+				self.exp_to_any_reg(e);
+				false
+			}
+		}
+	}
+
+	/// The index of a constant or the register of `e`, whichever it is.
+	fn rk(e: &ExpDesc) -> usize {
+		match e.k {
+			ExpKind::K(k) => k,
+			_ => e.reg(),
+		}
+	}
+
+	fn code_abrk(&mut self, op: OpCode, a: usize, b: usize, ec: &mut ExpDesc) {
+		let k = self.exp_to_rk(ec);
+		self.code_abck(op, a, b, Self::rk(ec), k);
+	}
+
+	/// Stores `ex` in the variable `var` (`luaK_storevar`).
+	pub fn store_var(&mut self, var: &ExpDesc, ex: &mut ExpDesc) {
+		match var.k {
+			ExpKind::Local(reg) => {
+				self.free_exp(ex);
+				self.exp_to_reg(ex, reg);
+				return;
+			}
+			ExpKind::Upval(idx) => {
+				let e = self.exp_to_any_reg(ex);
+				self.code_abc(OpCode::SetUpval, e, idx, 0);
+			}
+			ExpKind::IndexUp { t, idx } => self.code_abrk(OpCode::SetTabUp, t, idx, ex),
+			ExpKind::IndexI { t, idx } => self.code_abrk(OpCode::SetI, t, idx, ex),
+			ExpKind::IndexStr { t, idx } => self.code_abrk(OpCode::SetField, t, idx, ex),
+			ExpKind::Indexed { t, idx } => self.code_abrk(OpCode::SetTable, t, idx, ex),
+			_ => unreachable!("not a variable: {:?}", var.k),
+		}
+		self.free_exp(ex);
+	}
+
+	/// Puts `e` and its method `key` in the next two registers, for a method
+	/// call (`luaK_self`).
+	pub fn self_(&mut self, e: &mut ExpDesc, key: &mut ExpDesc) {
+		self.exp_to_any_reg(e);
+		let ereg = e.reg();
+		self.free_exp(e);
+		e.k = ExpKind::NonReloc(self.freereg);
+		// The function and `self`
+		self.reserve_regs(2);
+		self.code_abrk(OpCode::ISelf, e.reg(), ereg, key);
+		self.free_exp(key);
+	}
+
+	/// Flips the test of a jump (`negatecondition`).
+	fn negate_condition(&mut self, e: &ExpDesc) {
+		let control = self.jump_control(e.pc());
+		let inst = &mut self.f.code[control];
+		inst.set_k(!inst.k());
+	}
+
+	/// Adds a test of `e`, and the jump taken if it's `cond` (`jumponcond`).
+	fn jump_on_cond(&mut self, e: &mut ExpDesc, cond: bool) -> usize {
+		if let ExpKind::Reloc(pc) = e.k {
+			let ie = self.f.code[pc];
+			if ie.opcode() == Some(OpCode::Not) {
+				// Test the operand of the `not` instead
+				self.remove_last_instruction();
+				return self.cond_jump(OpCode::Test, ie.b() as usize, 0, 0, !cond);
+			}
+		}
+		self.discharge_to_any_reg(e);
+		self.free_exp(e);
+		self.cond_jump(OpCode::TestSet, NO_REG, e.reg(), 0, cond)
+	}
+
+	/// Goes on when `e` is true, and jumps when it's false (`luaK_goiftrue`).
+	pub fn go_if_true(&mut self, e: &mut ExpDesc) {
+		self.discharge_vars(e);
+		let pc = match e.k {
+			ExpKind::Jmp(pc) => {
+				self.negate_condition(e);
+				Some(pc)
+			}
+			// Always true
+			ExpKind::K(_)
+			| ExpKind::KFlt(_)
+			| ExpKind::KInt(_)
+			| ExpKind::KStr(_)
+			| ExpKind::True => None,
+			_ => Some(self.jump_on_cond(e, false)),
+		};
+		self.concat(&mut e.f, pc);
+		self.patch_to_here(e.t);
+		e.t = None;
+	}
+
+	/// Goes on when `e` is false, and jumps when it's true (`luaK_goiffalse`).
+	pub fn go_if_false(&mut self, e: &mut ExpDesc) {
+		self.discharge_vars(e);
+		let pc = match e.k {
+			ExpKind::Jmp(pc) => Some(pc),
+			// Always false
+			ExpKind::Nil | ExpKind::False => None,
+			_ => Some(self.jump_on_cond(e, true)),
+		};
+		self.concat(&mut e.t, pc);
+		self.patch_to_here(e.f);
+		e.f = None;
+	}
+
+	/// `not e` (`codenot`).
+	fn code_not(&mut self, e: &mut ExpDesc) {
+		match e.k {
+			ExpKind::Nil | ExpKind::False => e.k = ExpKind::True,
+			ExpKind::K(_)
+			| ExpKind::KFlt(_)
+			| ExpKind::KInt(_)
+			| ExpKind::KStr(_)
+			| ExpKind::True => e.k = ExpKind::False,
+			ExpKind::Jmp(_) => self.negate_condition(e),
+			ExpKind::Reloc(_) | ExpKind::NonReloc(_) => {
+				self.discharge_to_any_reg(e);
+				self.free_exp(e);
+				e.k = ExpKind::Reloc(self.code_abc(OpCode::Not, 0, e.reg(), 0));
+			}
+			_ => unreachable!("cannot negate {:?}", e.k),
+		}
+		// The jumps go the other way
+		mem::swap(&mut e.f, &mut e.t);
+		self.remove_values(e.f);
+		self.remove_values(e.t);
+	}
+
+	/// Makes `t` the variable `t[k]` (`luaK_indexed`).
+	pub fn indexed(&mut self, t: &mut ExpDesc, k: &mut ExpDesc) {
+		self.str_to_k(k);
+		let kstr = self.is_kstr(k);
+		if matches!(t.k, ExpKind::Upval(_)) && !kstr {
+			// Only constant strings can index an upvalue
+			self.exp_to_any_reg(t);
+		}
+		t.k = match t.k {
+			ExpKind::Upval(up) => ExpKind::IndexUp {
+				t: up,
+				idx: Self::rk(k),
+			},
+			ExpKind::Local(reg) | ExpKind::NonReloc(reg) if kstr => ExpKind::IndexStr {
+				t: reg,
+				idx: Self::rk(k),
+			},
+			ExpKind::Local(reg) | ExpKind::NonReloc(reg) if k.is_cint() => ExpKind::IndexI {
+				t: reg,
+				idx: k.int().expect("is an integer") as usize,
+			},
+			ExpKind::Local(reg) | ExpKind::NonReloc(reg) => ExpKind::Indexed {
+				t: reg,
+				idx: self.exp_to_any_reg(k),
+			},
+			_ => unreachable!("cannot index {:?}", t.k),
+		};
+	}
+
+	/// Whether `e` is a short string constant that fits in a `B` argument (`isKstr`).
+	fn is_kstr(&self, e: &ExpDesc) -> bool {
+		match e.k {
+			ExpKind::K(k) if !e.has_jumps() && k <= MAXARG_B as usize => matches!(
+				&self.f.constants[k],
+				proto::Constant::String(s) if s.len() <= MAXSHORTLEN
+			),
+			_ => false,
+		}
+	}
+
+	/// Folds `e1 op e2` into `e1`, if both are numerals (`constfolding`).
+	fn const_folding(&mut self, op: &BinaryOperation, e1: &mut ExpDesc, e2: &ExpDesc) -> bool {
+		let (Some(v1), Some(v2)) = (e1.numeral(), e2.numeral()) else {
+			return false;
+		};
+		let folded = fold_binary(op, &v1, &v2);
+		self.set_folded(e1, folded)
+	}
+
+	/// Makes `e` the result of folding, if there's one. NaN and floating-point
+	/// zeroes never are.
+	fn set_folded(&mut self, e: &mut ExpDesc, folded: Option<Constant>) -> bool {
+		e.k = match folded {
+			Some(Constant::Integer(i)) => ExpKind::KInt(i),
+			Some(Constant::Float(f)) => ExpKind::KFlt(f),
+			_ => return false,
+		};
+		true
+	}
+
+	/// Applies the unary operator `op` to `e` (`luaK_prefix`).
+	pub fn prefix(&mut self, op: &UnaryOperation, e: &mut ExpDesc, line: usize) {
+		self.discharge_vars(e);
+		let op = match op {
+			UnaryOperation::Negate | UnaryOperation::BitwiseNot => {
+				if let Some(v) = e.numeral() {
+					if self.set_folded(e, fold_unary(op, &v)) {
+						return;
+					}
+				}
+				match op {
+					UnaryOperation::Negate => OpCode::UnM,
+					_ => OpCode::BNot,
+				}
+			}
+			UnaryOperation::Length => OpCode::Len,
+			UnaryOperation::Not => return self.code_not(e),
+		};
+		let r = self.exp_to_any_reg(e);
+		self.free_exp(e);
+		e.k = ExpKind::Reloc(self.code_abc(op, 0, r, 0));
+		self.fix_line(line);
+	}
+
+	/// Prepares the first operand of `op`, before the second one is compiled
+	/// (`luaK_infix`).
+	pub fn infix(&mut self, op: &BinaryOperation, v: &mut ExpDesc) {
+		use BinaryOperation::*;
+
+		self.discharge_vars(v);
+		match op {
+			And => self.go_if_true(v),
+			Or => self.go_if_false(v),
+			Concat => self.exp_to_next_reg(v),
+			// Numerals may be folded, or be operands of their own
+			IsEqual | IsNotEqual => {
+				if v.numeral().is_none() {
+					self.exp_to_rk(v);
+				}
+			}
+			LessThan | LessEqual | GreaterThan | GreaterEqual => {
+				if v.sc_number().is_none() {
+					self.exp_to_any_reg(v);
+				}
+			}
+			_ => {
+				if v.numeral().is_none() {
+					self.exp_to_any_reg(v);
+				}
+			}
+		}
+	}
+
+	/// Finishes `e1 op e2`, leaving the result in `e1` (`luaK_posfix`).
+	pub fn posfix(
+		&mut self, op: &BinaryOperation, e1: &mut ExpDesc, e2: &mut ExpDesc, line: usize,
+	) {
+		use BinaryOperation::*;
+
+		self.discharge_vars(e2);
+		if folds(op) && self.const_folding(op, e1, e2) {
+			return;
+		}
+		match op {
+			And => {
+				debug_assert!(e1.t.is_none(), "closed by go_if_true");
+				self.concat(&mut e2.f, e1.f);
+				*e1 = e2.clone();
+			}
+			Or => {
+				debug_assert!(e1.f.is_none(), "closed by go_if_false");
+				self.concat(&mut e2.t, e1.t);
+				*e1 = e2.clone();
+			}
+			Concat => {
+				self.exp_to_next_reg(e2);
+				self.code_concat(e1, e2, line);
+			}
+			Add | Multiply => self.code_commutative(op, e1, e2, line),
+			Subtract => {
+				// `x - i` is `x + -i`
+				if !self.finish_binexp_neg(e1, e2, OpCode::AddI, line, op) {
+					self.code_arith(op, e1, e2, false, line);
+				}
+			}
+			Divide | FloorDivide | Modulo | Power => self.code_arith(op, e1, e2, false, line),
+			BitwiseAnd | BitwiseOr | BitwiseXor => self.code_bitwise(op, e1, e2, line),
+			BitwiseLeftShift => {
+				if e1.is_scint() {
+					// `i << x`
+					mem::swap(e1, e2);
+					self.code_bini(OpCode::ShlI, e1, e2, true, line, op);
+				} else if !self.finish_binexp_neg(e1, e2, OpCode::ShrI, line, op) {
+					// `x << i` is `x >> -i`
+					self.code_binexpval(op, e1, e2, line);
+				}
+			}
+			BitwiseRightShift => match e2.is_scint() {
+				true => self.code_bini(OpCode::ShrI, e1, e2, false, line, op),
+				false => self.code_binexpval(op, e1, e2, line),
+			},
+			IsEqual | IsNotEqual => self.code_eq(op, e1, e2),
+			// `a > b` is `b < a`, and `a >= b` is `b <= a`
+			GreaterThan => {
+				mem::swap(e1, e2);
+				self.code_order(&LessThan, e1, e2);
+			}
+			GreaterEqual => {
+				mem::swap(e1, e2);
+				self.code_order(&LessEqual, e1, e2);
+			}
+			LessThan | LessEqual => self.code_order(op, e1, e2),
+		}
+	}
+
+	/// Adds `e1 .. e2`, merging it with the concatenation `e2` if it is one
+	/// (`codeconcat`).
+	fn code_concat(&mut self, e1: &ExpDesc, e2: &ExpDesc, line: usize) {
+		let r1 = e1.reg();
+		if let Some(ie2) = self.previous_instruction() {
+			if ie2.opcode() == Some(OpCode::Concat) {
+				let n = ie2.b();
+				debug_assert_eq!(r1 + 1, ie2.a() as usize);
+				ie2.set_a(r1 as u8);
+				ie2.set_b(n + 1);
+				self.free_exp(e2);
+				return;
+			}
+		}
+		self.code_abc(OpCode::Concat, r1, 2, 0);
+		self.free_exp(e2);
+		self.fix_line(line);
+	}
+
+	/// Adds an operation on `e1` and the argument `v2`, followed by the
+	/// instruction that calls its metamethod if it fails (`finishbinexpval`).
+	#[allow(clippy::too_many_arguments)]
+	fn finish_binexpval(
+		&mut self, e1: &mut ExpDesc, e2: &ExpDesc, op: OpCode, v2: usize, flip: bool, line: usize,
+		mmop: OpCode, event: usize,
+	) {
+		let v1 = self.exp_to_any_reg(e1);
+		let pc = self.code_abc(op, 0, v1, v2);
+		self.free_exps(e1, e2);
+		e1.k = ExpKind::Reloc(pc);
+		self.fix_line(line);
+		self.code_abck(mmop, v1, v2, event, flip);
+		self.fix_line(line);
+	}
+
+	/// An operation on two registers (`codebinexpval`).
+	fn code_binexpval(
+		&mut self, op: &BinaryOperation, e1: &mut ExpDesc, e2: &mut ExpDesc, line: usize,
+	) {
+		let v2 = self.exp_to_any_reg(e2);
+		self.finish_binexpval(
+			e1,
+			e2,
+			arith_op(op),
+			v2,
+			false,
+			line,
+			OpCode::MMBin,
+			event(op),
+		);
+	}
+
+	/// An operation with an immediate integer operand (`codebini`).
+	fn code_bini(
+		&mut self, opc: OpCode, e1: &mut ExpDesc, e2: &ExpDesc, flip: bool, line: usize,
+		op: &BinaryOperation,
+	) {
+		let v2 = int_to_sc(e2.int().expect("immediate operands are integers"));
+		self.finish_binexpval(e1, e2, opc, v2, flip, line, OpCode::MMBinI, event(op));
+	}
+
+	/// An operation with a constant operand (`codebinK`).
+	fn code_bink(
+		&mut self, op: &BinaryOperation, e1: &mut ExpDesc, e2: &ExpDesc, flip: bool, line: usize,
+	) {
+		let v2 = Self::rk(e2);
+		self.finish_binexpval(
+			e1,
+			e2,
+			arith_op_k(op),
+			v2,
+			flip,
+			line,
+			OpCode::MMBinK,
+			event(op),
+		);
+	}
+
+	/// An operation with the negation of an immediate integer, like `x - 1` as
+	/// `x + -1`, if `e2` is one (`finishbinexpneg`).
+	fn finish_binexp_neg(
+		&mut self, e1: &mut ExpDesc, e2: &ExpDesc, opc: OpCode, line: usize, op: &BinaryOperation,
+	) -> bool {
+		let Some(i2) = e2.int() else {
+			return false;
+		};
+		if !(fits_c(i2) && fits_c(i2.wrapping_neg())) {
+			return false;
+		}
+		self.finish_binexpval(
+			e1,
+			e2,
+			opc,
+			int_to_sc(-i2),
+			false,
+			line,
+			OpCode::MMBinI,
+			event(op),
+		);
+		// The metamethod gets the operand as it was written
+		let pc = self.pc() - 1;
+		self.f.code[pc].set_b(int_to_sc(i2) as u8);
+		true
+	}
+
+	/// An operation on two registers, with the operands back in their original
+	/// order (`codebinNoK`).
+	fn code_bin_nok(
+		&mut self, op: &BinaryOperation, e1: &mut ExpDesc, e2: &mut ExpDesc, flip: bool,
+		line: usize,
+	) {
+		if flip {
+			mem::swap(e1, e2);
+		}
+		self.code_binexpval(op, e1, e2, line);
+	}
+
+	/// An arithmetic operation, with a constant operand if `e2` is a numeral
+	/// (`codearith`).
+	fn code_arith(
+		&mut self, op: &BinaryOperation, e1: &mut ExpDesc, e2: &mut ExpDesc, flip: bool,
+		line: usize,
+	) {
+		if e2.numeral().is_some() && self.exp_to_k(e2) {
+			self.code_bink(op, e1, e2, flip, line);
+		} else {
+			self.code_bin_nok(op, e1, e2, flip, line);
+		}
+	}
+
+	/// `+` or `*`, whose numeral operand can go either side (`codecommutative`).
+	fn code_commutative(
+		&mut self, op: &BinaryOperation, e1: &mut ExpDesc, e2: &mut ExpDesc, line: usize,
+	) {
+		let flip = e1.numeral().is_some();
+		if flip {
+			mem::swap(e1, e2);
+		}
+		if *op == BinaryOperation::Add && e2.is_scint() {
+			self.code_bini(OpCode::AddI, e1, e2, flip, line, op);
+		} else {
+			self.code_arith(op, e1, e2, flip, line);
+		}
+	}
+
+	/// `&`, `|` or `~`, whose integer operand can go either side
+	/// (`codebitwise`).
+	fn code_bitwise(
+		&mut self, op: &BinaryOperation, e1: &mut ExpDesc, e2: &mut ExpDesc, line: usize,
+	) {
+		let flip = matches!(e1.k, ExpKind::KInt(_));
+		if flip {
+			mem::swap(e1, e2);
+		}
+		if matches!(e2.k, ExpKind::KInt(_)) && self.exp_to_k(e2) {
+			self.code_bink(op, e1, e2, flip, line);
+		} else {
+			self.code_bin_nok(op, e1, e2, flip, line);
+		}
+	}
+
+	/// `<` or `<=`, with an immediate operand if either is a small number
+	/// (`codeorder`).
+	fn code_order(&mut self, op: &BinaryOperation, e1: &mut ExpDesc, e2: &mut ExpDesc) {
+		let less = *op == BinaryOperation::LessThan;
+		let (r1, r2, isfloat, opc) = if let Some((im, isfloat)) = e2.sc_number() {
+			let opc = if less { OpCode::LtI } else { OpCode::LeI };
+			(self.exp_to_any_reg(e1), im, isfloat, opc)
+		} else if let Some((im, isfloat)) = e1.sc_number() {
+			// `i < x` is `x > i`, and `i <= x` is `x >= i`
+			let opc = if less { OpCode::GtI } else { OpCode::GeI };
+			(self.exp_to_any_reg(e2), im, isfloat, opc)
+		} else {
+			let opc = if less { OpCode::Lt } else { OpCode::Le };
+			let r1 = self.exp_to_any_reg(e1);
+			(r1, self.exp_to_any_reg(e2), false, opc)
+		};
+		self.free_exps(e1, e2);
+		e1.k = ExpKind::Jmp(self.cond_jump(opc, r1, r2, isfloat as usize, true));
+	}
+
+	/// `==` or `~=`, with a constant or immediate second operand if either is
+	/// one (`codeeq`).
+	fn code_eq(&mut self, op: &BinaryOperation, e1: &mut ExpDesc, e2: &mut ExpDesc) {
+		if !matches!(e1.k, ExpKind::NonReloc(_)) {
+			// The first operand is a constant, so the second one is in a register
+			mem::swap(e1, e2);
+		}
+		let r1 = self.exp_to_any_reg(e1);
+		let (r2, isfloat, opc) = if let Some((im, isfloat)) = e2.sc_number() {
+			(im, isfloat, OpCode::EqI)
+		} else if self.exp_to_rk(e2) {
+			(Self::rk(e2), false, OpCode::EqK)
+		} else {
+			(self.exp_to_any_reg(e2), false, OpCode::Eq)
+		};
+		self.free_exps(e1, e2);
+		let eq = *op == BinaryOperation::IsEqual;
+		e1.k = ExpKind::Jmp(self.cond_jump(opc, r1, r2, isfloat as usize, eq));
+	}
+}
@@ -0,0 +1,328 @@
+//! ## Expressions
+//!
+//! Compiles expressions into [ExpDesc]s, like the expression half of
+//! `lparser.c`. Whatever code an expression needs is added as it's compiled,
+//! but its value is left wherever it is until its use calls for a register.
+
+use luna_ast::{
+	affix::{Affix, Call, Index, Prefix, Suffix},
+	expression::{Expression, Value},
+	function::{Arguments, FunctionCall},
+	span::Spanned,
+	table::{Field, TableConstructor},
+	terminal::{Name, Numeral},
+	variable::Variable,
+};
+use luna_vm::{instruction::Instruction, OpCode};
+
+use super::{
+	expdesc::{ExpDesc, ExpKind},
+	Compiler,
+};
+use crate::{
+	error::ErrorKind,
+	resolve::{Binding, Env, LocalId},
+};
+
+/// The number of list items stored in a table at once (`LFIELDS_PER_FLUSH`).
+const LFIELDS_PER_FLUSH: usize = 50;
+
+/// The list items of a table constructor being compiled (`ConsControl`).
+struct ConsControl {
+	/// The last list item read
+	v: ExpDesc,
+	/// The register of the table
+	t: usize,
+	/// The number of record items
+	nh: usize,
+	/// The number of list items already stored
+	na: usize,
+	/// The number of list items waiting to be stored
+	tostore: usize,
+}
+
+impl Compiler<'_> {
+	/// (`expr`)
+	pub(super) fn expr(&mut self, ex: &Expression) -> ExpDesc {
+		match ex {
+			Expression::Value(val) => self.simpleexp(val),
+			Expression::BinaryExpression(ex) => {
+				let mut v1 = self.expr(&ex.left);
+				// The operator follows its left operand
+				let line = self.line(ex.left.span().end);
+				self.fs().infix(&ex.op, &mut v1);
+				let mut v2 = self.expr(&ex.right);
+				self.fs().posfix(&ex.op, &mut v1, &mut v2, line);
+				v1
+			}
+			Expression::UnaryExpression(ex) => {
+				let line = self.line(ex.span.start);
+				let mut v = self.expr(&ex.ex);
+				self.fs().prefix(&ex.op, &mut v, line);
+				v
+			}
+			// Left by a recovering parse, and never run
+			Expression::Error(_) => ExpDesc::new(ExpKind::Nil),
+		}
+	}
+
+	fn simpleexp(&mut self, val: &Value) -> ExpDesc {
+		self.at(val.span());
+		match val {
+			Value::Nil(_) => ExpDesc::new(ExpKind::Nil),
+			Value::False(_) => ExpDesc::new(ExpKind::False),
+			Value::True(_) => ExpDesc::new(ExpKind::True),
+			Value::Numeral(Numeral::Integer(i), _) => ExpDesc::new(ExpKind::KInt(*i)),
+			Value::Numeral(Numeral::Float(f), _) => ExpDesc::new(ExpKind::KFlt(*f)),
+			Value::LiteralString(s) => ExpDesc::new(ExpKind::KStr(s.value.clone())),
+			Value::VarArgs(_) => {
+				let pc = self.fs().code_abc(OpCode::VarArg, 0, 0, 1);
+				ExpDesc::new(ExpKind::Vararg(pc))
+			}
+			Value::AnonFunctionDefinition(def) => {
+				let line = self.line(def.span.start);
+				self.body(&def.fbody, line)
+			}
+			Value::Variable(var) => self.variable(var),
+			Value::FunctionCall(call) => self.funccall(call),
+			Value::ParenExpression(ex, _) => {
+				// Only the first value of a call or `...`
+				let mut v = self.expr(ex);
+				self.fs().discharge_vars(&mut v);
+				v
+			}
+			Value::TableConstructor(table) => self.constructor(table),
+		}
+	}
+
+	pub(super) fn variable(&mut self, var: &Variable) -> ExpDesc {
+		match var {
+			Variable::Name(name) => self.singlevar(name),
+			Variable::Affixed(affix) => self.suffixedexp(affix),
+		}
+	}
+
+	/// The value of the local `id`, which is known if it's a compile-time
+	/// constant (`const2exp`).
+	fn local_exp(&self, id: LocalId) -> ExpDesc {
+		let local = self.res.local(id);
+		match (&local.constant, local.register) {
+			(Some(c), _) => ExpDesc::constant(c),
+			(None, Some(reg)) => ExpDesc::new(ExpKind::Local(reg)),
+			(None, None) => unreachable!("{} has neither a value nor a register", local.name),
+		}
+	}
+
+	/// A reference to the variable `name` (`singlevar`). A name that wasn't
+	/// resolved fails the compilation, and is a global meanwhile.
+	pub(super) fn singlevar(&mut self, name: &Name) -> ExpDesc {
+		let binding = self.res.binding(name).unwrap_or_else(|| {
+			self.fs().fail(ErrorKind::Unresolved(name.value.clone()));
+			Binding::Global(Env::Upvalue(0))
+		});
+		match binding {
+			Binding::Local(id) => self.local_exp(id),
+			Binding::Upvalue(idx) => ExpDesc::new(ExpKind::Upval(idx)),
+			Binding::Global(env) => {
+				// `_ENV.name`
+				let mut var = match env {
+					Env::Local(id) => self.local_exp(id),
+					Env::Upvalue(idx) => ExpDesc::new(ExpKind::Upval(idx)),
+				};
+				let fs = self.fs();
+				fs.exp_to_any_reg_up(&mut var);
+				let mut key = ExpDesc::new(ExpKind::KStr(name.value.as_bytes().to_vec()));
+				fs.indexed(&mut var, &mut key);
+				var
+			}
+		}
+	}
+
+	/// Indexes `v` with the field `name` (`fieldsel`).
+	pub(super) fn fieldsel(&mut self, v: &mut ExpDesc, name: &Name) {
+		let fs = self.fs();
+		fs.exp_to_any_reg_up(v);
+		let mut key = ExpDesc::new(ExpKind::KStr(name.value.as_bytes().to_vec()));
+		fs.indexed(v, &mut key);
+	}
+
+	/// A prefix followed by indexes and calls (`suffixedexp`).
+	fn suffixedexp(&mut self, affix: &Affix) -> ExpDesc {
+		// Calls are on the line the expression starts
+		let line = self.line(affix.span.start);
+		let mut v = match &affix.pfix {
+			Prefix::Name(name) => self.singlevar(name),
+			Prefix::ParenExpression(ex, _) => {
+				let mut v = self.expr(ex);
+				self.fs().discharge_vars(&mut v);
+				v
+			}
+		};
+		for suffix in &affix.suflist {
+			match suffix {
+				Suffix::Index(Index::Member(name)) => self.fieldsel(&mut v, name),
+				Suffix::Index(Index::Expression(ex, _)) => {
+					self.fs().exp_to_any_reg_up(&mut v);
+					let mut key = self.expr(ex);
+					let fs = self.fs();
+					fs.exp_to_val(&mut key);
+					fs.indexed(&mut v, &mut key);
+				}
+				Suffix::Call(call) => self.call(&mut v, call, line),
+			}
+		}
+		v
+	}
+
+	pub(super) fn funccall(&mut self, call: &FunctionCall) -> ExpDesc {
+		let line = self.line(call.affix.span.start);
+		let mut v = self.suffixedexp(&call.affix);
+		self.call(&mut v, &call.call, line);
+		v
+	}
+
+	/// Calls `f`, or its method, leaving the call in `f`.
+	fn call(&mut self, f: &mut ExpDesc, call: &Call, line: usize) {
+		let fs = self.fs();
+		match &call.oname {
+			Some(name) => {
+				let mut key = ExpDesc::new(ExpKind::KStr(name.value.as_bytes().to_vec()));
+				fs.self_(f, &mut key);
+			}
+			None => fs.exp_to_next_reg(f),
+		}
+		self.funcargs(f, &call.argu, line);
+	}
+
+	/// Calls the function in the register of `f` with `argu`, which go in the
+	/// registers after it (`funcargs`).
+	fn funcargs(&mut self, f: &mut ExpDesc, argu: &Arguments, line: usize) {
+		let mut args = match argu {
+			Arguments::ClosedExpressionList(elist, _) => {
+				let mut args = match elist {
+					Some(elist) => self.explist(elist),
+					None => ExpDesc::new(ExpKind::Void),
+				};
+				if args.has_multret() {
+					self.fs().set_multret(&mut args);
+				}
+				args
+			}
+			Arguments::TableConstructor(table) => self.constructor(table),
+			Arguments::LiteralString(s) => ExpDesc::new(ExpKind::KStr(s.value.clone())),
+		};
+
+		let fs = self.fs();
+		let base = f.reg();
+		let nparams = match args.has_multret() {
+			true => None,
+			false => {
+				if args.k != ExpKind::Void {
+					fs.exp_to_next_reg(&mut args);
+				}
+				Some(fs.freereg - (base + 1))
+			}
+		};
+		let b = nparams.map_or(0, |n| n + 1);
+		*f = ExpDesc::new(ExpKind::Call(fs.code_abc(OpCode::Call, base, b, 2)));
+		fs.fix_line(line);
+		// The call leaves one result in the base register
+		fs.freereg = base + 1;
+	}
+
+	/// (`constructor`)
+	fn constructor(&mut self, table: &TableConstructor) -> ExpDesc {
+		let fs = self.fs();
+		let pc = fs.code_abc(OpCode::NewTable, 0, 0, 0);
+		// Room for the `EXTRAARG` with the size of the array part
+		fs.code(Instruction(0));
+		let t = ExpDesc::new(ExpKind::NonReloc(fs.freereg));
+		let mut cc = ConsControl {
+			v: ExpDesc::new(ExpKind::Void),
+			t: fs.freereg,
+			nh: 0,
+			na: 0,
+			tostore: 0,
+		};
+		fs.reserve_regs(1);
+
+		for field in table.oflist.iter().flatten() {
+			self.close_list_field(&mut cc);
+			match field {
+				Field::Expression(ex) => {
+					cc.v = self.expr(ex);
+					cc.tostore += 1;
+				}
+				Field::NameField(field) => {
+					let key = ExpDesc::new(ExpKind::KStr(field.tabname.value.as_bytes().to_vec()));
+					self.recfield(&mut cc, &t, |_| key, &field.val);
+				}
+				Field::BracketField(field) => {
+					let key = |this: &mut Self| {
+						let mut key = this.expr(&field.tabexp);
+						this.fs().exp_to_val(&mut key);
+						key
+					};
+					self.recfield(&mut cc, &t, key, &field.val);
+				}
+			}
+		}
+		self.last_list_field(&mut cc);
+		self.fs().set_table_size(pc, cc.t, cc.na, cc.nh);
+		t
+	}
+
+	/// Stores `val` in the field of table `t` that `key` compiles to
+	/// (`recfield`).
+	fn recfield(
+		&mut self, cc: &mut ConsControl, t: &ExpDesc, key: impl FnOnce(&mut Self) -> ExpDesc,
+		val: &Expression,
+	) {
+		let reg = self.fs().freereg;
+		let mut key = key(self);
+		cc.nh += 1;
+		let mut tab = t.clone();
+		self.fs().indexed(&mut tab, &mut key);
+		let mut val = self.expr(val);
+		let fs = self.fs();
+		fs.store_var(&tab, &mut val);
+		fs.freereg = reg;
+	}
+
+	/// Puts the last list item in a register, storing the items read so far
+	/// if there are enough of them (`closelistfield`).
+	fn close_list_field(&mut self, cc: &mut ConsControl) {
+		if cc.v.k == ExpKind::Void {
+			return;
+		}
+		let fs = self.fs();
+		fs.exp_to_next_reg(&mut cc.v);
+		cc.v = ExpDesc::new(ExpKind::Void);
+		if cc.tostore == LFIELDS_PER_FLUSH {
+			fs.set_list(cc.t, cc.na, Some(cc.tostore));
+			cc.na += cc.tostore;
+			cc.tostore = 0;
+		}
+	}
+
+	/// Stores the list items left, with every value of a call or `...` at the
+	/// end (`lastlistfield`).
+	fn last_list_field(&mut self, cc: &mut ConsControl) {
+		if cc.tostore == 0 {
+			return;
+		}
+		let fs = self.fs();
+		if cc.v.has_multret() {
+			fs.set_multret(&mut cc.v);
+			fs.set_list(cc.t, cc.na, None);
+			// The number of values of the last item isn't known
+			cc.na += cc.tostore - 1;
+		} else {
+			if cc.v.k != ExpKind::Void {
+				fs.exp_to_next_reg(&mut cc.v);
+			}
+			fs.set_list(cc.t, cc.na, Some(cc.tostore));
+			cc.na += cc.tostore;
+		}
+	}
+}
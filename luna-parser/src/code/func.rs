@@ -0,0 +1,558 @@
+//! ## Functions Being Compiled
+//!
+//! Adding instructions, constants and registers to a [Proto], and patching
+//! lists of jumps, like the lower half of `lcode.c`.
+
+use std::collections::HashMap;
+
+use luna_ast::span::Span;
+use luna_vm::{
+	instruction::{
+		Instruction, MAXARG_AX, MAXARG_BX, MAXARG_C, MAXARG_SJ, OFFSET_SBX, OFFSET_SC, OFFSET_SJ,
+	},
+	proto::{Constant, LineWriter, Proto},
+	OpCode,
+};
+
+use super::block::{ActVar, BlockCnt, LabelDesc};
+use crate::error::{Error, ErrorKind};
+
+/// The largest number of registers a function can use (`MAXREGS`).
+const MAXREGS: usize = 255;
+
+/// An invalid register, for tests that don't set one (`NO_REG`).
+pub(super) const NO_REG: usize = 255;
+
+/// The largest constant index an `RK` operand can hold (`MAXINDEXRK`).
+pub(super) const MAXINDEXRK: usize = 255;
+
+/// A list of jumps to patch, by the pc of its first jump. Each jump holds the
+/// offset of the next one, and the last one holds the offset -1 (`NO_JUMP`).
+pub(super) type JumpList = Option<usize>;
+
+/// The key of a constant, to reuse its slot in the constant table.
+#[derive(PartialEq, Eq, Hash)]
+enum ConstantKey {
+	Nil,
+	Boolean(bool),
+	Integer(i64),
+	/// A float, by its bits, so integral floats don't collide with integers
+	Float(u64),
+	String(Vec<u8>),
+}
+
+/// A function being compiled (`FuncState`).
+pub(super) struct FuncState {
+	pub f: Proto,
+	lines: LineWriter,
+	/// The line of the instructions being added
+	pub line: usize,
+	/// Where errors are reported
+	pub span: Span,
+	/// The first error found in the function
+	pub error: Option<Error<Span>>,
+	/// The index of each constant in [Proto::constants]
+	cache: HashMap<ConstantKey, usize>,
+	/// The pc of the last jump target (`lasttarget`)
+	lasttarget: usize,
+	/// The first free register
+	pub freereg: usize,
+	/// The active locals, innermost last
+	pub actvar: Vec<ActVar>,
+	/// The blocks being compiled, innermost last
+	pub blocks: Vec<BlockCnt>,
+	/// The visible labels
+	pub labels: Vec<LabelDesc>,
+	/// The gotos that haven't found their label yet
+	pub gotos: Vec<LabelDesc>,
+	/// Whether returning has to close upvalues or to-be-closed variables
+	pub needclose: bool,
+}
+
+impl FuncState {
+	pub fn new(linedefined: usize, source: &str) -> Self {
+		let f = Proto {
+			// Registers 0 and 1 are always valid
+			maxstacksize: 2,
+			source: Some(source.to_owned()),
+			linedefined,
+			..Proto::default()
+		};
+		Self {
+			f,
+			lines: LineWriter::new(linedefined),
+			line: linedefined,
+			span: Span::default(),
+			error: None,
+			cache: HashMap::new(),
+			lasttarget: 0,
+			freereg: 0,
+			actvar: Vec::new(),
+			blocks: Vec::new(),
+			labels: Vec::new(),
+			gotos: Vec::new(),
+			needclose: false,
+		}
+	}
+
+	/// Records an error at the current span, unless there's one already.
+	pub fn fail(&mut self, kind: ErrorKind) {
+		if self.error.is_none() {
+			self.error = Some(Error::new(self.span, kind));
+		}
+	}
+
+	/// Fails with [ErrorKind::TooMany] when `v` is over `limit` (`checklimit`).
+	pub fn check_limit(&mut self, v: usize, limit: usize, what: &'static str) {
+		if v > limit {
+			let line = self.f.linedefined;
+			self.fail(ErrorKind::TooMany { what, limit, line });
+		}
+	}
+
+	/// The index of the next instruction.
+	pub fn pc(&self) -> usize {
+		self.f.code.len()
+	}
+
+	pub fn code(&mut self, inst: Instruction) -> usize {
+		self.lines.push(&mut self.f, inst, self.line)
+	}
+
+	pub fn code_abck(&mut self, op: OpCode, a: usize, b: usize, c: usize, k: bool) -> usize {
+		self.code(Instruction::create_abc(op, a as u8, b as u8, c as u8, k))
+	}
+
+	pub fn code_abc(&mut self, op: OpCode, a: usize, b: usize, c: usize) -> usize {
+		self.code_abck(op, a, b, c, false)
+	}
+
+	pub fn code_abx(&mut self, op: OpCode, a: usize, bx: usize) -> usize {
+		self.code(Instruction::create_abx(op, a as u8, bx as u32))
+	}
+
+	fn code_asbx(&mut self, op: OpCode, a: usize, sbx: i64) -> usize {
+		self.code(Instruction::create_asbx(op, a as u8, sbx as i32))
+	}
+
+	fn code_extraarg(&mut self, a: usize) -> usize {
+		self.code(Instruction::create_ax(OpCode::ExtraArg, a as u32))
+	}
+
+	/// Loads constant `k` into `reg` (`luaK_codek`).
+	pub fn code_k(&mut self, reg: usize, k: usize) -> usize {
+		if k <= MAXARG_BX as usize {
+			return self.code_abx(OpCode::LoadK, reg, k);
+		}
+		let pc = self.code_abx(OpCode::LoadKX, reg, 0);
+		self.code_extraarg(k);
+		pc
+	}
+
+	/// Changes the line of the last instruction (`luaK_fixline`).
+	pub fn fix_line(&mut self, line: usize) {
+		self.lines.fix_line(&mut self.f, line);
+	}
+
+	pub fn remove_last_instruction(&mut self) {
+		self.lines.pop(&mut self.f);
+	}
+
+	/// The last instruction, unless something jumps past it
+	/// (`previousinstruction`).
+	pub fn previous_instruction(&mut self) -> Option<&mut Instruction> {
+		match self.pc() > self.lasttarget {
+			true => self.f.code.last_mut(),
+			false => None,
+		}
+	}
+
+	/// Sets `n` registers from `from` to nil, extending the previous
+	/// `LOADNIL` when it sets the registers next to them (`luaK_nil`).
+	pub fn nil(&mut self, from: usize, n: usize) {
+		let mut from = from;
+		let mut last = from + n - 1;
+		if let Some(previous) = self.previous_instruction() {
+			if previous.opcode() == Some(OpCode::LoadNil) {
+				let pfrom = previous.a() as usize;
+				let plast = pfrom + previous.b() as usize;
+				if (pfrom <= from && from <= plast + 1) || (from <= pfrom && pfrom <= last + 1) {
+					from = from.min(pfrom);
+					last = last.max(plast);
+					previous.set_a(from as u8);
+					previous.set_b((last - from) as u8);
+					return;
+				}
+			}
+		}
+		self.code_abc(OpCode::LoadNil, from, n - 1, 0);
+	}
+
+	/// Loads the integer `i` into `reg` (`luaK_int`).
+	pub fn int(&mut self, reg: usize, i: i64) {
+		match fits_bx(i) {
+			true => self.code_asbx(OpCode::LoadI, reg, i),
+			false => {
+				let k = self.int_k(i);
+				self.code_k(reg, k)
+			}
+		};
+	}
+
+	/// Loads the float `f` into `reg` (`luaK_float`).
+	pub fn float(&mut self, reg: usize, f: f64) {
+		match float_to_int(f).filter(|&i| fits_bx(i)) {
+			Some(i) => self.code_asbx(OpCode::LoadF, reg, i),
+			None => {
+				let k = self.number_k(f);
+				self.code_k(reg, k)
+			}
+		};
+	}
+
+	/// Adds the instruction to return `nret` values from `first`, or all the
+	/// values from `first` up to the top if `nret` is `None` (`luaK_ret`).
+	pub fn ret(&mut self, first: usize, nret: Option<usize>) {
+		let op = match nret {
+			Some(0) => OpCode::Return0,
+			Some(1) => OpCode::Return1,
+			_ => OpCode::Return,
+		};
+		self.code_abc(op, first, nret.map_or(0, |n| n + 1), 0);
+	}
+
+	/// Sets list items from the `tostore` registers after `base` into the
+	/// table at `base`, after the `nelems` items already in it. All the values
+	/// up to the top are stored if `tostore` is `None` (`luaK_setlist`).
+	pub fn set_list(&mut self, base: usize, nelems: usize, tostore: Option<usize>) {
+		let tostore = tostore.unwrap_or(0);
+		if nelems <= MAXARG_C as usize {
+			self.code_abc(OpCode::SetList, base, tostore, nelems);
+		} else {
+			let extra = nelems / (MAXARG_C as usize + 1);
+			let nelems = nelems % (MAXARG_C as usize + 1);
+			self.code_abck(OpCode::SetList, base, tostore, nelems, true);
+			self.code_extraarg(extra);
+		}
+		self.freereg = base + 1;
+	}
+
+	/// Sets the sizes in the `NEWTABLE` at `pc`, and its `EXTRAARG`
+	/// (`luaK_settablesize`).
+	pub fn set_table_size(&mut self, pc: usize, ra: usize, asize: usize, hsize: usize) {
+		let rb = match hsize {
+			0 => 0,
+			_ => ceil_log2(hsize) + 1,
+		};
+		let extra = asize / (MAXARG_C as usize + 1);
+		let rc = asize % (MAXARG_C as usize + 1);
+		self.f.code[pc] =
+			Instruction::create_abc(OpCode::NewTable, ra as u8, rb as u8, rc as u8, extra > 0);
+		self.f.code[pc + 1] =
+			Instruction::create_ax(OpCode::ExtraArg, extra.min(MAXARG_AX as usize) as u32);
+	}
+
+	// Registers
+
+	/// The number of registers the active locals take (`luaY_nvarstack`).
+	pub fn nvarstack(&self) -> usize {
+		self.reglevel(self.actvar.len())
+	}
+
+	/// The number of registers the first `nvar` active locals take (`reglevel`).
+	pub fn reglevel(&self, nvar: usize) -> usize {
+		self.actvar[..nvar]
+			.iter()
+			.rev()
+			.find_map(|var| var.ridx)
+			.map_or(0, |ridx| ridx + 1)
+	}
+
+	/// Makes sure there are `n` registers past the free ones (`luaK_checkstack`).
+	pub fn check_stack(&mut self, n: usize) {
+		let newstack = self.freereg + n;
+		if newstack > self.f.maxstacksize as usize {
+			match newstack >= MAXREGS {
+				true => self.fail(ErrorKind::TooManyRegisters),
+				false => self.f.maxstacksize = newstack as u8,
+			}
+		}
+	}
+
+	pub fn reserve_regs(&mut self, n: usize) {
+		self.check_stack(n);
+		self.freereg += n;
+	}
+
+	/// Frees `reg`, if it's a temporary. Temporaries are freed in the opposite
+	/// order they were reserved (`freereg`).
+	pub fn free_reg(&mut self, reg: usize) {
+		if reg >= self.nvarstack() {
+			self.freereg -= 1;
+			debug_assert_eq!(reg, self.freereg, "registers are freed in order");
+		}
+	}
+
+	/// Frees two registers, in the right order (`freeregs`).
+	pub fn free_regs(&mut self, r1: usize, r2: usize) {
+		self.free_reg(r1.max(r2));
+		self.free_reg(r1.min(r2));
+	}
+
+	// Constants
+
+	/// The index of `value` in the constant table, adding it if it's new (`addk`).
+	fn add_k(&mut self, key: ConstantKey, value: Constant) -> usize {
+		if let Some(&k) = self.cache.get(&key) {
+			return k;
+		}
+		let k = self.f.constants.len();
+		self.f.constants.push(value);
+		self.cache.insert(key, k);
+		k
+	}
+
+	pub fn string_k(&mut self, s: &[u8]) -> usize {
+		self.add_k(
+			ConstantKey::String(s.to_vec()),
+			Constant::String(s.to_vec()),
+		)
+	}
+
+	pub fn int_k(&mut self, i: i64) -> usize {
+		self.add_k(ConstantKey::Integer(i), Constant::Integer(i))
+	}
+
+	pub fn number_k(&mut self, f: f64) -> usize {
+		self.add_k(ConstantKey::Float(f.to_bits()), Constant::Float(f))
+	}
+
+	pub fn bool_k(&mut self, b: bool) -> usize {
+		self.add_k(ConstantKey::Boolean(b), Constant::Boolean(b))
+	}
+
+	pub fn nil_k(&mut self) -> usize {
+		self.add_k(ConstantKey::Nil, Constant::Nil)
+	}
+
+	// Jumps
+
+	/// Marks the next instruction as a jump target, returning its pc
+	/// (`luaK_getlabel`).
+	pub fn get_label(&mut self) -> usize {
+		self.lasttarget = self.pc();
+		self.pc()
+	}
+
+	/// Adds a jump to be patched later (`luaK_jump`).
+	pub fn jump(&mut self) -> usize {
+		self.code(Instruction::create_sj(OpCode::Jmp, -1))
+	}
+
+	/// Adds a test, and the jump taken when it fails (`condjump`).
+	pub fn cond_jump(&mut self, op: OpCode, a: usize, b: usize, c: usize, k: bool) -> usize {
+		self.code_abck(op, a, b, c, k);
+		self.jump()
+	}
+
+	/// Jumps back to `target` (`luaK_jumpto`).
+	pub fn jump_to(&mut self, target: usize) {
+		let jump = self.jump();
+		self.patch_list(Some(jump), target);
+	}
+
+	/// The destination of the jump at `pc`, which is the next one in its list
+	/// (`getjump`).
+	fn get_jump(&self, pc: usize) -> JumpList {
+		match self.f.code[pc].sj() {
+			-1 => None,
+			offset => Some((pc as isize + 1 + offset as isize) as usize),
+		}
+	}
+
+	/// Points the jump at `pc` to `dest` (`fixjump`).
+	pub fn fix_jump(&mut self, pc: usize, dest: usize) {
+		let offset = dest as i64 - (pc as i64 + 1);
+		if !(-(OFFSET_SJ as i64)..=(MAXARG_SJ as i64 - OFFSET_SJ as i64)).contains(&offset) {
+			self.fail(ErrorKind::ControlStructureTooLong);
+			return;
+		}
+		self.f.code[pc].set_sj(offset as i32);
+	}
+
+	/// Appends the list `l2` to `l1` (`luaK_concat`).
+	pub fn concat(&mut self, l1: &mut JumpList, l2: JumpList) {
+		let Some(l2) = l2 else {
+			return;
+		};
+		let Some(mut list) = *l1 else {
+			*l1 = Some(l2);
+			return;
+		};
+		while let Some(next) = self.get_jump(list) {
+			list = next;
+		}
+		self.fix_jump(list, l2);
+	}
+
+	/// The test that controls the jump at `pc`, or the jump itself if it's
+	/// unconditional (`getjumpcontrol`).
+	pub fn jump_control(&self, pc: usize) -> usize {
+		let is_test = |inst: Instruction| inst.opcode().is_some_and(|op| op.mode().is_test());
+		match pc >= 1 && is_test(self.f.code[pc - 1]) {
+			true => pc - 1,
+			false => pc,
+		}
+	}
+
+	/// Makes the `TESTSET` controlling the jump at `node` set `reg`, or makes
+	/// it a `TEST` if there's no register to set. Returns whether there was a
+	/// `TESTSET` (`patchtestreg`).
+	fn patch_test_reg(&mut self, node: usize, reg: usize) -> bool {
+		let control = self.jump_control(node);
+		let inst = &mut self.f.code[control];
+		if inst.opcode() != Some(OpCode::TestSet) {
+			return false;
+		}
+		if reg != NO_REG && reg != inst.b() as usize {
+			inst.set_a(reg as u8);
+		} else {
+			*inst = Instruction::create_abc(OpCode::Test, inst.b(), 0, 0, inst.k());
+		}
+		true
+	}
+
+	/// Keeps the tests of `list` from setting registers (`removevalues`).
+	pub fn remove_values(&mut self, mut list: JumpList) {
+		while let Some(pc) = list {
+			self.patch_test_reg(pc, NO_REG);
+			list = self.get_jump(pc);
+		}
+	}
+
+	/// Whether any jump of `list` doesn't produce a value (`need_value`).
+	pub fn need_value(&self, mut list: JumpList) -> bool {
+		while let Some(pc) = list {
+			let control = self.f.code[self.jump_control(pc)];
+			if control.opcode() != Some(OpCode::TestSet) {
+				return true;
+			}
+			list = self.get_jump(pc);
+		}
+		false
+	}
+
+	/// Points the jumps of `list` whose tests set `reg` to `vtarget`, and the
+	/// rest to `dtarget` (`patchlistaux`).
+	pub fn patch_list_aux(
+		&mut self, mut list: JumpList, vtarget: usize, reg: usize, dtarget: usize,
+	) {
+		while let Some(pc) = list {
+			let next = self.get_jump(pc);
+			match self.patch_test_reg(pc, reg) {
+				true => self.fix_jump(pc, vtarget),
+				false => self.fix_jump(pc, dtarget),
+			}
+			list = next;
+		}
+	}
+
+	/// Points every jump of `list` to `target` (`luaK_patchlist`).
+	pub fn patch_list(&mut self, list: JumpList, target: usize) {
+		self.patch_list_aux(list, target, NO_REG, target);
+	}
+
+	/// Points every jump of `list` to the next instruction (`luaK_patchtohere`).
+	pub fn patch_to_here(&mut self, list: JumpList) {
+		let here = self.get_label();
+		self.patch_list(list, here);
+	}
+
+	/// Sets the offset of the `FORPREP`, `FORLOOP` or their generic versions at
+	/// `pc`, which jumps to `dest` (`fixforjump`).
+	pub fn fix_for_jump(&mut self, pc: usize, dest: usize, back: bool) {
+		let offset = match back {
+			true => (pc + 1).checked_sub(dest),
+			false => dest.checked_sub(pc + 1),
+		};
+		match offset.filter(|&offset| offset <= MAXARG_BX as usize) {
+			Some(offset) => self.f.code[pc].set_bx(offset as u32),
+			None => self.fail(ErrorKind::ControlStructureTooLong),
+		}
+	}
+
+	/// Finishes the code of the function (`luaK_finish`). Returns close
+	/// upvalues if the function needs them to, and fix up the stack of vararg
+	/// functions. Jumps to jumps go straight to their final target.
+	pub fn finish(&mut self) {
+		for pc in 0..self.pc() {
+			let mut inst = self.f.code[pc];
+			match inst.opcode() {
+				Some(
+					op @ (OpCode::Return0 | OpCode::Return1 | OpCode::Return | OpCode::TailCall),
+				) => {
+					if matches!(op, OpCode::Return0 | OpCode::Return1) {
+						if !(self.needclose || self.f.is_vararg) {
+							continue;
+						}
+						// Only `RETURN` does the extra work
+						inst.set_opcode(OpCode::Return);
+					}
+					if self.needclose {
+						inst.set_k(true);
+					}
+					if self.f.is_vararg {
+						inst.set_c(self.f.numparams + 1);
+					}
+					self.f.code[pc] = inst;
+				}
+				Some(OpCode::Jmp) => {
+					let target = final_target(&self.f.code, pc);
+					self.fix_jump(pc, target);
+				}
+				_ => {}
+			}
+		}
+	}
+}
+
+/// Where the jump at `pc` ends up, following up to 100 jumps (`finaltarget`).
+fn final_target(code: &[Instruction], mut pc: usize) -> usize {
+	for _ in 0..100 {
+		let inst = code[pc];
+		if inst.opcode() != Some(OpCode::Jmp) {
+			break;
+		}
+		pc = (pc as isize + inst.sj() as isize + 1) as usize;
+	}
+	pc
+}
+
+/// Whether `i` fits in a signed `Bx` argument (`fitsBx`).
+fn fits_bx(i: i64) -> bool {
+	(-(OFFSET_SBX as i64)..=(MAXARG_BX as i64 - OFFSET_SBX as i64)).contains(&i)
+}
+
+/// Whether `i` fits in a signed `C` argument (`fitsC`).
+pub(super) fn fits_c(i: i64) -> bool {
+	(i as u64).wrapping_add(OFFSET_SC as u64) <= MAXARG_C as u64
+}
+
+/// `i` as a signed `C` argument (`int2sC`).
+pub(super) fn int_to_sc(i: i64) -> usize {
+	(i + OFFSET_SC as i64) as usize
+}
+
+/// The integer `f` is equal to, if any (`luaV_flttointns` with `F2Ieq`).
+pub(super) fn float_to_int(f: f64) -> Option<i64> {
+	// -2^63 is exact, and 2^63 is the first float past the largest integer
+	match f.floor() == f && (-9223372036854775808.0..9223372036854775808.0).contains(&f) {
+		true => Some(f as i64),
+		false => None,
+	}
+}
+
+/// `ceil(log2(x))`, for a positive `x` (`luaO_ceillog2`).
+fn ceil_log2(x: usize) -> usize {
+	(usize::BITS - (x - 1).leading_zeros()) as usize
+}
@@ -0,0 +1,523 @@
+//! ## Statements
+//!
+//! Compiles statements and blocks, like the statement half of `lparser.c`.
+//! Registers of temporaries are freed after every statement.
+
+use luna_ast::{
+	attribute::AttributeNameList,
+	expression::ExpressionList,
+	span::Spanned,
+	statement::{
+		Assignment, ForExpression, ForList, IfBlock, IfTree, Label, LocalDefinitionWithAttribute,
+		LocalFunctionDefinition, NamedFunctionDefinition, RepeatUntil, Statement, While,
+	},
+	terminal::Name,
+	Block, ReturnStatement,
+};
+use luna_vm::OpCode;
+
+use super::{
+	expdesc::{ExpDesc, ExpKind},
+	func::JumpList,
+	Compiler,
+};
+use crate::resolve::LocalKind;
+
+impl Compiler<'_> {
+	/// Compiles the statements of `bl` in the block that's open (`statlist`).
+	/// In the body of a `repeat`, the condition still follows the last
+	/// statement.
+	pub(super) fn statlist(&mut self, bl: &Block, until: bool) {
+		self.statements(&bl.stlist, bl.oret.as_ref(), until);
+	}
+
+	fn statements(&mut self, stlist: &[Statement], oret: Option<&ReturnStatement>, until: bool) {
+		for (i, stat) in stlist.iter().enumerate() {
+			match stat {
+				Statement::Label(label) => {
+					// Only no-op statements can follow a label at the end of its block
+					let last = !until
+						&& oret.is_none() && stlist[i + 1..]
+						.iter()
+						.all(|stat| matches!(stat, Statement::End(_) | Statement::Label(_)));
+					self.labelstat(label, last);
+				}
+				stat => self.statement(stat),
+			}
+			let fs = self.fs();
+			fs.freereg = fs.nvarstack();
+		}
+		if let Some(ret) = oret {
+			self.retstat(ret);
+			let fs = self.fs();
+			fs.freereg = fs.nvarstack();
+		}
+	}
+
+	fn statement(&mut self, stat: &Statement) {
+		let span = stat.span();
+		self.fs().span = span;
+		self.at(span);
+		match stat {
+			Statement::End(_) | Statement::Error(_) => {}
+			Statement::Assignment(assign) => self.assignment(assign),
+			Statement::FunctionCall(call) => {
+				let e = self.funccall(call);
+				// The results are discarded
+				let ExpKind::Call(pc) = e.k else {
+					unreachable!("calls compile to a call")
+				};
+				self.fs().f.code[pc].set_c(1);
+			}
+			Statement::Label(label) => self.labelstat(label, false),
+			Statement::Break(_) => {
+				let fs = self.fs();
+				let pc = fs.jump();
+				fs.new_goto("break", Some(pc));
+			}
+			Statement::Goto(name, _) => self.gotostat(name),
+			Statement::Do(bl, _) => self.block(bl),
+			Statement::While(w) => self.whilestat(w),
+			Statement::RepeatUntil(r) => self.repeatstat(r),
+			Statement::IfTree(tree) => self.ifstat(tree),
+			Statement::ForExpression(f) => self.fornum(f),
+			Statement::ForList(f) => self.forlist(f),
+			Statement::FunctionDefinition(def) => self.funcstat(def),
+			Statement::LocalFunctionDefinition(def) => self.localfunc(def),
+			Statement::LocalDefinitionWithAttribute(def) => self.localstat(def),
+		}
+	}
+
+	fn block(&mut self, bl: &Block) {
+		self.fs().enter_block(false);
+		self.statlist(bl, false);
+		self.fs().leave_block();
+	}
+
+	/// Adjusts the `nexps` values of an expression list ending in `e` to
+	/// `nvars` values, which end up in the next registers (`adjust_assign`).
+	fn adjust_assign(&mut self, nvars: usize, nexps: usize, e: &mut ExpDesc) {
+		let fs = self.fs();
+		let needed = nvars as isize - nexps as isize;
+		if e.has_multret() {
+			// The last expression provides the difference
+			let extra = (needed + 1).max(0) as usize;
+			fs.set_returns(e, Some(extra));
+		} else {
+			if e.k != ExpKind::Void {
+				fs.exp_to_next_reg(e);
+			}
+			if needed > 0 {
+				let freereg = fs.freereg;
+				fs.nil(freereg, needed as usize);
+			}
+		}
+		match needed > 0 {
+			true => fs.reserve_regs(needed as usize),
+			// Drop the extra values
+			false => fs.freereg = (fs.freereg as isize + needed) as usize,
+		}
+	}
+
+	/// The condition of a loop, returning the jumps taken when it's false
+	/// (`cond`).
+	fn cond(&mut self, ex: &luna_ast::expression::Expression) -> JumpList {
+		let mut v = self.expr(ex);
+		// `nil` is false as far as jumps go
+		if v.k == ExpKind::Nil {
+			v.k = ExpKind::False;
+		}
+		self.fs().go_if_true(&mut v);
+		v.f
+	}
+
+	fn assignment(&mut self, assign: &Assignment) {
+		let mut targets: Vec<ExpDesc> = Vec::new();
+		for var in &assign.vlist {
+			let v = self.variable(var);
+			if !matches!(
+				v.k,
+				ExpKind::Indexed { .. }
+					| ExpKind::IndexUp { .. }
+					| ExpKind::IndexI { .. }
+					| ExpKind::IndexStr { .. }
+			) {
+				self.check_conflict(&mut targets, &v);
+			}
+			targets.push(v);
+		}
+
+		let (nvars, nexps) = (targets.len(), assign.elist.len());
+		let mut e = self.explist(&assign.elist);
+		let mut targets = targets.iter().rev();
+		let last = targets.next().expect("assignments have a target");
+		let fs = self.fs();
+		if nexps == nvars {
+			fs.set_one_ret(&mut e);
+			fs.store_var(last, &mut e);
+		} else {
+			self.adjust_assign(nvars, nexps, &mut e);
+			let fs = self.fs();
+			let mut e = ExpDesc::new(ExpKind::NonReloc(fs.freereg - 1));
+			fs.store_var(last, &mut e);
+		}
+		// The values are stored from the last one
+		for v in targets {
+			let fs = self.fs();
+			let mut e = ExpDesc::new(ExpKind::NonReloc(fs.freereg - 1));
+			fs.store_var(v, &mut e);
+		}
+	}
+
+	/// Keeps the tables and keys of earlier targets of an assignment from
+	/// changing before they're assigned to, when `v` is the variable they're
+	/// in. Its value is copied to a register, which they use instead
+	/// (`check_conflict`).
+	fn check_conflict(&mut self, targets: &mut [ExpDesc], v: &ExpDesc) {
+		let fs = self.fs();
+		let extra = fs.freereg;
+		let mut conflict = false;
+		for lh in targets.iter_mut() {
+			lh.k = match (&lh.k, &v.k) {
+				(ExpKind::IndexUp { t, idx }, ExpKind::Upval(up)) if t == up => {
+					conflict = true;
+					ExpKind::IndexStr {
+						t: extra,
+						idx: *idx,
+					}
+				}
+				(&ExpKind::Indexed { mut t, mut idx }, &ExpKind::Local(reg)) => {
+					if t == reg {
+						conflict = true;
+						t = extra;
+					}
+					if idx == reg {
+						conflict = true;
+						idx = extra;
+					}
+					ExpKind::Indexed { t, idx }
+				}
+				(ExpKind::IndexI { t, idx }, ExpKind::Local(reg)) if t == reg => {
+					conflict = true;
+					ExpKind::IndexI {
+						t: extra,
+						idx: *idx,
+					}
+				}
+				(ExpKind::IndexStr { t, idx }, ExpKind::Local(reg)) if t == reg => {
+					conflict = true;
+					ExpKind::IndexStr {
+						t: extra,
+						idx: *idx,
+					}
+				}
+				_ => continue,
+			};
+		}
+		if conflict {
+			match v.k {
+				ExpKind::Local(reg) => fs.code_abc(OpCode::Move, extra, reg, 0),
+				ExpKind::Upval(up) => fs.code_abc(OpCode::GetUpval, extra, up, 0),
+				_ => unreachable!("only variables conflict"),
+			};
+			fs.reserve_regs(1);
+		}
+	}
+
+	fn labelstat(&mut self, label: &Label, last: bool) {
+		self.fs().create_label(&label.0.value, last);
+	}
+
+	fn gotostat(&mut self, name: &Name) {
+		let fs = self.fs();
+		match fs.find_label(&name.value).map(|lb| (lb.nactvar, lb.pc)) {
+			// A forward jump, patched when the label is found
+			None => {
+				let pc = fs.jump();
+				fs.new_goto(&name.value, Some(pc));
+			}
+			Some((nactvar, pc)) => {
+				// Jumping back out of the scope of a local closes it
+				let lblevel = fs.reglevel(nactvar);
+				if fs.nvarstack() > lblevel {
+					fs.code_abc(OpCode::Close, lblevel, 0, 0);
+				}
+				let jump = fs.jump();
+				fs.patch_list(Some(jump), pc.expect("labels have a position"));
+			}
+		}
+	}
+
+	fn whilestat(&mut self, w: &While) {
+		let whileinit = self.fs().get_label();
+		let condexit = self.cond(&w.cond);
+		self.fs().enter_block(true);
+		self.block(&w.bl);
+		let line = self.line(w.span.end.saturating_sub(1));
+		let fs = self.fs();
+		fs.line = line;
+		fs.jump_to(whileinit);
+		fs.leave_block();
+		fs.patch_to_here(condexit);
+	}
+
+	fn repeatstat(&mut self, r: &RepeatUntil) {
+		let fs = self.fs();
+		let repeat_init = fs.get_label();
+		// The loop block, and the scope block the condition can see
+		fs.enter_block(true);
+		fs.enter_block(false);
+		self.statlist(&r.bl, true);
+		self.at(r.cond.span());
+		let mut condexit = self.cond(&r.cond);
+		let fs = self.fs();
+		let bl2 = *fs.blocks.last().expect("block was entered");
+		fs.leave_block();
+		if bl2.upval {
+			// Repeating closes the upvalues of the body
+			let exit = fs.jump();
+			fs.patch_to_here(condexit);
+			let level = fs.reglevel(bl2.nactvar);
+			fs.code_abc(OpCode::Close, level, 0, 0);
+			condexit = Some(fs.jump());
+			fs.patch_to_here(Some(exit));
+		}
+		fs.patch_list(condexit, repeat_init);
+		fs.leave_block();
+	}
+
+	fn ifstat(&mut self, tree: &IfTree) {
+		let mut escapelist = None;
+		let nbranches = 1 + tree.elseifs.len();
+		for (i, ifb) in std::iter::once(&tree.initial)
+			.chain(&tree.elseifs)
+			.enumerate()
+		{
+			let more = i + 1 < nbranches || tree.otherwise.is_some();
+			self.test_then_block(ifb, more, &mut escapelist);
+		}
+		if let Some(bl) = &tree.otherwise {
+			self.block(bl);
+		}
+		self.fs().patch_to_here(escapelist);
+	}
+
+	/// Compiles a branch of an `if`, which jumps to the end of the `if` in
+	/// `escapelist` when `more` branches follow (`test_then_block`).
+	fn test_then_block(&mut self, ifb: &IfBlock, more: bool, escapelist: &mut JumpList) {
+		let mut v = self.expr(&ifb.cond);
+		let stlist = &ifb.bl.stlist;
+		let (jf, rest) = match stlist.first() {
+			// `if cond then break` jumps out of the loop on the condition itself
+			Some(Statement::Break(_)) => {
+				let fs = self.fs();
+				fs.go_if_false(&mut v);
+				fs.enter_block(false);
+				fs.new_goto("break", v.t);
+				let rest = &stlist[1..];
+				let skip = rest
+					.iter()
+					.take_while(|stat| matches!(stat, Statement::End(_)))
+					.count();
+				let rest = &rest[skip..];
+				if rest.is_empty() && ifb.bl.oret.is_none() {
+					// The jump is the whole block
+					fs.leave_block();
+					return;
+				}
+				(Some(fs.jump()), rest)
+			}
+			_ => {
+				let fs = self.fs();
+				fs.go_if_true(&mut v);
+				fs.enter_block(false);
+				(v.f, &stlist[..])
+			}
+		};
+		self.statements(rest, ifb.bl.oret.as_ref(), false);
+		let fs = self.fs();
+		fs.leave_block();
+		if more {
+			let jump = fs.jump();
+			fs.concat(escapelist, Some(jump));
+		}
+		fs.patch_to_here(jf);
+	}
+
+	/// Compiles `exp` into the next register (`exp1`).
+	fn exp1(&mut self, ex: &luna_ast::expression::Expression) {
+		let mut e = self.expr(ex);
+		self.fs().exp_to_next_reg(&mut e);
+	}
+
+	fn fornum(&mut self, f: &ForExpression) {
+		let line = self.line(f.span.start);
+		self.fs().enter_block(true);
+		let base = self.fs().freereg;
+		self.exp1(f.range.start());
+		self.exp1(f.range.end());
+		match &f.step {
+			Some(step) => self.exp1(step),
+			None => {
+				let fs = self.fs();
+				let reg = fs.freereg;
+				fs.int(reg, 1);
+				fs.reserve_regs(1);
+			}
+		}
+		let fs = self.fs();
+		for _ in 0..3 {
+			fs.add_local("(for state)", false, false);
+		}
+		self.forbody(base, line, std::slice::from_ref(&f.name), false, &f.bl);
+		self.fs().leave_block();
+	}
+
+	fn forlist(&mut self, f: &ForList) {
+		self.fs().enter_block(true);
+		let base = self.fs().freereg;
+		let line = f.elist.first().map_or(f.span.start, |ex| ex.span().start);
+		let line = self.line(line);
+		let mut e = self.explist(&f.elist);
+		self.adjust_assign(4, f.elist.len(), &mut e);
+		let fs = self.fs();
+		for _ in 0..4 {
+			fs.add_local("(for state)", false, false);
+		}
+		// The last control variable is closed when the loop ends
+		fs.mark_to_be_closed();
+		// Room to call the generator
+		fs.check_stack(3);
+		self.forbody(base, line, &f.nlist, true, &f.bl);
+		self.fs().leave_block();
+	}
+
+	/// Compiles the body of a loop over `names`, whose state starts at `base`
+	/// (`forbody`).
+	fn forbody(&mut self, base: usize, line: usize, names: &[Name], isgen: bool, bl: &Block) {
+		let (forprep, forloop) = match isgen {
+			true => (OpCode::TForPrep, OpCode::TForLoop),
+			false => (OpCode::ForPrep, OpCode::ForLoop),
+		};
+		let prep = self.fs().code_abx(forprep, base, 0);
+		self.fs().enter_block(false);
+		for name in names {
+			self.declare(name);
+		}
+		self.fs().reserve_regs(names.len());
+		self.block(bl);
+		let fs = self.fs();
+		fs.leave_block();
+		let label = fs.get_label();
+		fs.fix_for_jump(prep, label, false);
+		fs.line = line;
+		if isgen {
+			fs.code_abc(OpCode::TForCall, base, 0, names.len());
+			fs.fix_line(line);
+		}
+		let endfor = fs.code_abx(forloop, base, 0);
+		fs.fix_for_jump(endfor, prep + 1, true);
+		fs.fix_line(line);
+	}
+
+	fn funcstat(&mut self, def: &NamedFunctionDefinition) {
+		let line = self.line(def.span.start);
+		let fname = &def.fname;
+		let mut v = self.singlevar(&fname.nlist[0]);
+		for name in fname.nlist[1..].iter().chain(&fname.objname) {
+			self.fieldsel(&mut v, name);
+		}
+		let mut b = self.body(&def.fbody, line);
+		let fs = self.fs();
+		fs.store_var(&v, &mut b);
+		// The definition happens on the first line
+		fs.fix_line(line);
+	}
+
+	fn localfunc(&mut self, def: &LocalFunctionDefinition) {
+		// The function can see itself
+		self.declare(&def.name);
+		let fvar = self.fs().actvar.len() - 1;
+		let line = self.line(def.fbody.span.start);
+		self.body(&def.fbody, line);
+		// The local is only in the debug information once it has a value
+		let fs = self.fs();
+		if let Some(pidx) = fs.actvar[fvar].pidx {
+			fs.f.locvars[pidx].startpc = fs.pc();
+		}
+	}
+
+	fn localstat(&mut self, def: &LocalDefinitionWithAttribute) {
+		let atlist = &def.atlist;
+		let nvars = atlist.len();
+		let (nexps, mut e) = match &def.oelist {
+			Some(elist) => (elist.len(), self.explist(elist)),
+			None => (0, ExpDesc::new(ExpKind::Void)),
+		};
+		let nactvar = self.fs().actvar.len();
+		// The value of a compile-time constant is already known, and needs no
+		// register
+		if self.local(&atlist[nvars - 1].name).0 != LocalKind::CompileTimeConstant {
+			self.adjust_assign(nvars, nexps, &mut e);
+		}
+		for attname in atlist {
+			self.declare(&attname.name);
+		}
+		self.check_to_close(atlist, nactvar);
+	}
+
+	/// Marks the `<close>` local of `atlist`, if there's one, to be closed
+	/// (`checktoclose`). The locals come after the first `nactvar`.
+	fn check_to_close(&mut self, atlist: &AttributeNameList, nactvar: usize) {
+		let toclose = atlist
+			.iter()
+			.position(|attname| self.local(&attname.name).0 == LocalKind::ToClose);
+		if let Some(i) = toclose {
+			let fs = self.fs();
+			fs.mark_to_be_closed();
+			let level = fs.reglevel(nactvar + i);
+			fs.code_abc(OpCode::Tbc, level, 0, 0);
+		}
+	}
+
+	fn retstat(&mut self, ret: &ReturnStatement) {
+		self.fs().span = ret.span;
+		self.at(ret.span);
+		let mut first = self.fs().nvarstack();
+		let nret = match &ret.oelist {
+			Some(elist) if !elist.is_empty() => {
+				let mut e = self.explist(elist);
+				let fs = self.fs();
+				if e.has_multret() {
+					fs.set_multret(&mut e);
+					let insidetbc = fs.blocks.last().is_some_and(|bl| bl.insidetbc);
+					if let (ExpKind::Call(pc), 1, false) = (&e.k, elist.len(), insidetbc) {
+						fs.f.code[*pc].set_opcode(OpCode::TailCall);
+					}
+					None
+				} else if elist.len() == 1 {
+					// The value can be returned from wherever it is
+					first = fs.exp_to_any_reg(&mut e);
+					Some(1)
+				} else {
+					fs.exp_to_next_reg(&mut e);
+					Some(elist.len())
+				}
+			}
+			_ => Some(0),
+		};
+		self.fs().ret(first, nret);
+	}
+
+	/// Compiles `elist`, leaving every value but the last in the next
+	/// registers, and returning the last one (`explist`).
+	pub(super) fn explist(&mut self, elist: &ExpressionList) -> ExpDesc {
+		let Some((last, init)) = elist.split_last() else {
+			return ExpDesc::new(ExpKind::Void);
+		};
+		for ex in init {
+			self.exp1(ex);
+		}
+		self.expr(last)
+	}
+}
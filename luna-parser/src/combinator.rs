@@ -1,115 +1,310 @@
-use std::ops::{Range, RangeFrom};
-
 use nom::{
-	bytes::complete::tag,
-	character::complete::{char as tchar, multispace0},
-	multi::separated_list1,
-	sequence::{delimited, separated_pair},
-	AsChar, Compare, IResult, InputIter, InputLength, InputTake, InputTakeAtPosition, Slice,
+	combinator::cut,
+	error::{ErrorKind, ParseError},
+	sequence::{pair, preceded, separated_pair, terminated},
+	Err, Parser,
+};
+
+use luna_ast::span::Span;
+
+use crate::{
+	dialect::Feature,
+	error::{self, Error, Expected},
+	lex::Token,
+	lex::TokenKind,
+	IRes, In,
 };
 
-use crate::terminal::string::{EQUALS, LBRACE, LBRACKET, LPAREN, RBRACE, RBRACKET, RPAREN};
+/// The span covered by the tokens consumed between `input` and `rest`.
+///
+/// If nothing was consumed, this is an empty span at the start of `input`.
+pub fn consumed(input: In, rest: In) -> Span {
+	let used = &input[..input.len() - rest.len()];
+	match (used.first(), used.last()) {
+		(Some(first), Some(last)) => first.span.to(last.span),
+		_ => Span::empty(input.first().map_or(0, |tok| tok.span.start)),
+	}
+}
 
-pub fn negate<I, F>(func: F) -> impl Fn(&I) -> bool
+/// Pairs the output of `parser` with the span of the tokens it consumed.
+#[inline(always)]
+pub fn spanned<'a, F, O>(mut parser: F) -> impl FnMut(In<'a>) -> IRes<'a, (O, Span)>
 where
-	I: ?Sized,
-	F: Fn(&I) -> bool,
+	F: Parser<In<'a>, O, Error<In<'a>>>,
 {
-	move |input: &I| !func(input)
+	move |input: In<'a>| {
+		let (rest, out) = parser.parse(input)?;
+		Ok((rest, (out, consumed(input, rest))))
+	}
 }
 
-/// Abbreivated list combinator for the list grammar rules.
+/// Matches a single token of the given `kind`.
+///
+/// Only useful for tokens that don't carry any data (keywords and symbols).
 #[inline(always)]
-pub fn list<F, G, I, O1, O2>(sep: F, parser: G) -> impl FnMut(I) -> IResult<I, Vec<O2>>
+pub fn token<'a>(kind: TokenKind) -> impl FnMut(In<'a>) -> IRes<'a, &'a Token> {
+	move |input: In<'a>| match input.split_first() {
+		Some((tok, rest)) if tok.kind == kind => Ok((rest, tok)),
+		_ => Err(Err::Error(Error::expected(
+			input,
+			Expected::Token(kind.clone()),
+		))),
+	}
+}
+
+/// Matches the `kind` token that closes the construct started by `opener`
+/// (`check_match`). Missing it is a hard error, unless recovering from errors.
+#[inline(always)]
+pub fn closing<'a>(opener: &'a Token, kind: TokenKind) -> impl FnMut(In<'a>) -> IRes<'a, ()> {
+	let mut closer = token(kind);
+	move |input: In<'a>| match closer(input) {
+		Ok((rest, _)) => Ok((rest, ())),
+		Err(Err::Error(e)) => {
+			input.state.recover(Error {
+				kind: error::ErrorKind::Unclosed {
+					opener: opener.kind.clone(),
+					line: opener.line,
+				},
+				..e
+			})?;
+			// Carry on as if it were there
+			Ok((input, ()))
+		}
+		Err(e) => Err(e),
+	}
+}
+
+/// Matches a `kind` token, then commits to `parser` like Lua does after a keyword.
+#[inline(always)]
+pub fn keyword<'a, F, O>(
+	kind: TokenKind, parser: F,
+) -> impl FnMut(In<'a>) -> IRes<'a, (&'a Token, O)>
 where
-	F: FnMut(I) -> IResult<I, O1>,
-	G: FnMut(I) -> IResult<I, O2>,
-	I: Clone + InputLength + InputIter + InputTake + Slice<Range<usize>> + Slice<RangeFrom<usize>>,
-	<I as InputIter>::Item: AsChar,
+	F: Parser<In<'a>, O, Error<In<'a>>>,
 {
-	separated_list1(sep, parser)
+	pair(token(kind), cut(parser))
 }
 
-/// Strips the whitespace on both sides of `parser`.
+/// Matches `parser`, which is syntax for `feature` (see [State::require]).
 ///
-/// There may or may not be whitespace.
+/// [State::require]: crate::input::State::require
+#[inline(always)]
+pub fn requires<'a, F, O>(feature: Feature, mut parser: F) -> impl FnMut(In<'a>) -> IRes<'a, O>
+where
+	F: Parser<In<'a>, O, Error<In<'a>>>,
+{
+	move |input: In<'a>| {
+		let (rest, out) = parser.parse(input)?;
+		input.state.require(input, feature)?;
+		Ok((rest, out))
+	}
+}
+
+/// Reports a failure of `parser` on its first token as an unexpected symbol,
+/// unless it's because of the nesting limit or the dialect.
 #[inline(always)]
-pub fn ws0<F, I, O>(parser: F) -> impl FnMut(I) -> IResult<I, O>
+pub fn unexpected<'a, F, O>(mut parser: F) -> impl FnMut(In<'a>) -> IRes<'a, O>
 where
-	I: InputIter + InputTakeAtPosition,
-	<I as InputTakeAtPosition>::Item: AsChar + Clone,
-	F: FnMut(I) -> IResult<I, O>,
+	F: Parser<In<'a>, O, Error<In<'a>>>,
 {
-	delimited(multispace0, parser, multispace0)
+	move |input: In<'a>| {
+		parser.parse(input).map_err(|e| {
+			e.map(|mut e| {
+				let specific = matches!(
+					e.kind,
+					error::ErrorKind::TooManySyntaxLevels | error::ErrorKind::Unsupported(_)
+				);
+				if e.input.len() == input.len() && !specific {
+					e.kind = error::ErrorKind::UnexpectedSymbol;
+				}
+				e
+			})
+		})
+	}
 }
 
-/// Strips the whitespace on both sides of an input character `ch`.
+/// Like `opt`, but only skips `parser` when the next token is `kind`. Any
+/// other token has to start a `parser`.
+#[inline(always)]
+pub fn opt_unless<'a, F, O>(
+	kind: TokenKind, mut parser: F,
+) -> impl FnMut(In<'a>) -> IRes<'a, Option<O>>
+where
+	F: Parser<In<'a>, O, Error<In<'a>>>,
+{
+	move |input: In<'a>| match input.first() {
+		Some(tok) if tok.kind == kind => Ok((input, None)),
+		_ => parser.parse(input).map(|(rest, out)| (rest, Some(out))),
+	}
+}
+
+/// Skips the tokens from `at` up to the start of the next statement or the
+/// end of the block, to recover from an error at `at` in a statement
+/// starting at `input`. At least one token is skipped.
+///
+/// Besides `;` and keywords, a name is taken to start a statement if it's at
+/// the start of a line, or if it follows the end of an expression and goes on
+/// like a variable or call does, as `y` does in `x = = 1 y = 2`. Lua has no
+/// expression where a name follows another expression.
+pub fn synchronize<'a>(input: In<'a>, at: In<'a>) -> In<'a> {
+	let mut at = match at.len() == input.len() {
+		true => at.skip(1),
+		false => at,
+	};
+	let mut line = input.first().map_or(0, |tok| tok.line);
+
+	while let Some(tok) = at.first() {
+		use TokenKind::*;
+		let i = input.len() - at.len();
+		let ends_exp = i.checked_sub(1).is_some_and(|prev| {
+			matches!(
+				input[prev].kind,
+				Name(_)
+					| Numeral(_) | String(_)
+					| Nil | True | False
+					| TripleDot | End
+					| RParen | RBracket
+					| RBrace
+			)
+		});
+		let goes_on = at.get(1).is_some_and(|next| {
+			matches!(
+				next.kind,
+				Equals | Comma | Dot | Colon | LParen | LBracket | LBrace | String(_)
+			)
+		});
+		match tok.kind {
+			Semicolon | Local | Function | If | While | For | Do | Repeat | Return | Goto
+			| Break | DoubleColon | End | Else | ElseIf | Until | Eof => break,
+			Name(_) if tok.line > line || ends_exp && goes_on => break,
+			_ => {
+				line = tok.line;
+				at = at.skip(1);
+			}
+		}
+	}
+	at
+}
+
+/// Runs `parser` one syntax level deeper into `input` (`enterlevel`).
+///
+/// The stack is grown first if it's running low, so that deeply nested input
+/// hits the depth limit of the parse rather than overflowing the stack.
+pub fn nested<'a, O>(input: In<'a>, parser: impl FnOnce() -> IRes<'a, O>) -> IRes<'a, O> {
+	let _level = input.state.enter_level(input)?;
+	stacker::maybe_grow(64 * 1024, 1024 * 1024, parser)
+}
+
+/// Matches the tokens that end a block (`block_follow`), without consuming them.
+pub fn block_follow(input: In) -> IRes<()> {
+	match input.first().map(|tok| &tok.kind) {
+		Some(
+			TokenKind::Else
+			| TokenKind::ElseIf
+			| TokenKind::End
+			| TokenKind::Until
+			| TokenKind::Return
+			| TokenKind::Eof,
+		) => Ok((input, ())),
+		_ => Err(Err::Error(Error::from_error_kind(input, ErrorKind::Tag))),
+	}
+}
+
+/// Matches a single token, transforming it with `func` if it's accepted.
+#[inline(always)]
+pub fn satisfy_map<'a, F, O>(expected: Expected, mut func: F) -> impl FnMut(In<'a>) -> IRes<'a, O>
+where
+	F: FnMut(&'a Token) -> Option<O>,
+{
+	move |input: In<'a>| match input
+		.split_first()
+		.and_then(|(tok, rest)| Some((rest, func(tok)?)))
+	{
+		Some(res) => Ok(res),
+		None => Err(Err::Error(Error::expected(input, expected.clone()))),
+	}
+}
+
+/// Abbreivated list combinator for the list grammar rules.
+///
+/// Another element must follow each separator.
 #[inline(always)]
-pub fn wschar<I>(ch: char) -> impl FnMut(I) -> IResult<I, char>
+pub fn list<'a, F, G, O1, O2>(sep: F, mut parser: G) -> impl FnMut(In<'a>) -> IRes<'a, Vec<O2>>
 where
-	I: InputIter + InputTakeAtPosition + Slice<RangeFrom<usize>> + InputIter,
-	<I as InputIter>::Item: AsChar,
-	<I as InputTakeAtPosition>::Item: AsChar + Clone,
+	F: FnMut(In<'a>) -> IRes<'a, O1>,
+	G: FnMut(In<'a>) -> IRes<'a, O2>,
 {
-	ws0(tchar(ch))
+	let mut sep = sep;
+	move |input: In<'a>| {
+		let (mut input, first) = parser(input)?;
+		let mut out = vec![first];
+		loop {
+			match sep(input) {
+				Ok((rest, _)) => {
+					let (rest, next) = cut(&mut parser)(rest)?;
+					out.push(next);
+					input = rest;
+				}
+				Err(Err::Error(_)) => return Ok((input, out)),
+				Err(e) => return Err(e),
+			}
+		}
+	}
 }
 
-/// Strips the whitespace on both sides of `pattern`
+/// Matches `parser` after an `open` token, followed by the `close` token that
+/// matches it. Everything after `open` is committed to.
 #[inline(always)]
-pub fn wstag<T, I>(pattern: T) -> impl FnMut(I) -> IResult<I, I>
+pub fn enclosed<'a, F, O>(
+	open: TokenKind, mut parser: F, close: TokenKind,
+) -> impl FnMut(In<'a>) -> IRes<'a, O>
 where
-	T: InputLength + Clone,
-	I: InputIter + InputTakeAtPosition + InputTake + Compare<T>,
-	<I as InputTakeAtPosition>::Item: AsChar + Clone,
+	F: Parser<In<'a>, O, Error<In<'a>>>,
 {
-	ws0(tag(pattern))
+	move |input: In<'a>| {
+		let (rest, opener) = token(open.clone())(input)?;
+		let (rest, out) = cut(|i| parser.parse(i))(rest)?;
+		let (rest, _) = closing(opener, close.clone())(rest)?;
+		Ok((rest, out))
+	}
 }
 
 /// Matches an object from `parser` encased in parenthesis.
 #[inline(always)]
-pub fn paren<F, I, O>(parser: F) -> impl FnMut(I) -> IResult<I, O>
+pub fn paren<'a, F, O>(parser: F) -> impl FnMut(In<'a>) -> IRes<'a, O>
 where
-	I: InputIter + InputTakeAtPosition + Slice<RangeFrom<usize>>,
-	<I as InputIter>::Item: AsChar,
-	<I as InputTakeAtPosition>::Item: AsChar + Clone,
-	F: FnMut(I) -> IResult<I, O>,
+	F: Parser<In<'a>, O, Error<In<'a>>>,
 {
-	delimited(wschar(LPAREN), parser, wschar(RPAREN))
+	enclosed(TokenKind::LParen, parser, TokenKind::RParen)
 }
 
 /// Matches an object from `parser` encased in brackets.
 #[inline(always)]
-pub fn bracket<F, I, O>(parser: F) -> impl FnMut(I) -> IResult<I, O>
+pub fn bracket<'a, F, O>(parser: F) -> impl FnMut(In<'a>) -> IRes<'a, O>
 where
-	I: InputIter + InputTakeAtPosition + Slice<RangeFrom<usize>>,
-	<I as InputIter>::Item: AsChar,
-	<I as InputTakeAtPosition>::Item: AsChar + Clone,
-	F: FnMut(I) -> IResult<I, O>,
+	F: Parser<In<'a>, O, Error<In<'a>>>,
 {
-	delimited(wschar(LBRACKET), parser, wschar(RBRACKET))
+	preceded(
+		token(TokenKind::LBracket),
+		cut(terminated(parser, token(TokenKind::RBracket))),
+	)
 }
 
 /// Matches an object from `parser` encased in braces.
 #[inline(always)]
-pub fn braces<F, I, O>(parser: F) -> impl FnMut(I) -> IResult<I, O>
+pub fn braces<'a, F, O>(parser: F) -> impl FnMut(In<'a>) -> IRes<'a, O>
 where
-	I: InputIter + InputTakeAtPosition + Slice<RangeFrom<usize>>,
-	<I as InputIter>::Item: AsChar,
-	<I as InputTakeAtPosition>::Item: AsChar + Clone,
-	F: FnMut(I) -> IResult<I, O>,
+	F: Parser<In<'a>, O, Error<In<'a>>>,
 {
-	delimited(wschar(LBRACE), parser, wschar(RBRACE))
+	enclosed(TokenKind::LBrace, parser, TokenKind::RBrace)
 }
 
 /// Matches objects from the `first` and `second` parsers, which are separated by an assignment (`=`) operator.
 #[inline(always)]
-pub fn assign<F, G, I, O1, O2>(first: F, second: G) -> impl FnMut(I) -> IResult<I, (O1, O2)>
+pub fn assign<'a, F, G, O1, O2>(first: F, second: G) -> impl FnMut(In<'a>) -> IRes<'a, (O1, O2)>
 where
-	I: InputIter + InputTakeAtPosition + Slice<RangeFrom<usize>>,
-	<I as InputIter>::Item: AsChar,
-	<I as InputTakeAtPosition>::Item: AsChar + Clone,
-	F: FnMut(I) -> IResult<I, O1>,
-	G: FnMut(I) -> IResult<I, O2>,
+	F: FnMut(In<'a>) -> IRes<'a, O1>,
+	G: FnMut(In<'a>) -> IRes<'a, O2>,
 {
-	separated_pair(first, wschar(EQUALS), second)
+	separated_pair(first, token(TokenKind::Equals), second)
 }
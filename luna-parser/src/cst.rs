@@ -0,0 +1,404 @@
+//! # Concrete Syntax Trees
+//!
+//! A [Cst] keeps every byte of the source it was parsed from: each token
+//! holds its own text along with the whitespace and comments around it (its
+//! [Trivia]), so tools can edit a file and write it back without disturbing
+//! anything they didn't touch. Tokens are grouped into [SyntaxNode]s that
+//! follow the nodes of the syntax tree.
+//!
+//! Trivia on the same line after a token trails it. Everything else,
+//! starting with the first line break, leads the next token; trivia at the
+//! end of the source leads the [TokenKind::Eof] token.
+
+use luna_ast::{
+	affix::{Affix, Call},
+	attribute::AttributeName,
+	comment::{Comment, CommentKind, Comments},
+	expression::{AnonFunctionDefinition, BinaryExpression, UnaryExpression},
+	function::{Arguments, FunctionBody, FunctionCall, FunctionName},
+	span::{LineIndex, Span, Spanned},
+	statement::{
+		Assignment, ForExpression, ForList, IfBlock, IfTree, LocalDefinitionWithAttribute,
+		LocalFunctionDefinition, NamedFunctionDefinition, RepeatUntil, While,
+	},
+	table::{BracketField, NameField, TableConstructor},
+	visit::{self, Visit},
+	Block, Chunk, ReturnStatement,
+};
+
+use crate::{
+	error::Error,
+	error_chunk,
+	lex::{Lexer, Token, TokenKind},
+	parse_tokens, whole_chunk, Parse, ParseOptions,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TriviaKind {
+	Whitespace,
+	Comment(CommentKind),
+	/// Source the lexer skipped after an error, when recovering from errors
+	Skipped,
+}
+
+/// Source between tokens, which doesn't change what the program means.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Trivia {
+	pub kind: TriviaKind,
+	pub text: Vec<u8>,
+	/// Where the trivia was in the parsed source
+	pub span: Span,
+}
+
+/// A token, with the trivia around it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SyntaxToken {
+	pub kind: TokenKind,
+	/// The source of the token. Edits should keep it in line with `kind`.
+	pub text: Vec<u8>,
+	/// Where the token was in the parsed source
+	pub span: Span,
+	pub leading: Vec<Trivia>,
+	pub trailing: Vec<Trivia>,
+}
+
+impl SyntaxToken {
+	fn write(&self, out: &mut Vec<u8>) {
+		for trivia in &self.leading {
+			out.extend_from_slice(&trivia.text);
+		}
+		out.extend_from_slice(&self.text);
+		for trivia in &self.trailing {
+			out.extend_from_slice(&trivia.text);
+		}
+	}
+}
+
+/// The syntax tree nodes that group tokens, named after their types in
+/// [luna_ast].
+///
+/// Statements and expressions that are only a keyword or a single value
+/// don't have nodes of their own; their tokens belong to the node around them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SyntaxKind {
+	Chunk,
+	Block,
+	ReturnStatement,
+	Assignment,
+	While,
+	RepeatUntil,
+	IfTree,
+	IfBlock,
+	ForExpression,
+	ForList,
+	NamedFunctionDefinition,
+	FunctionName,
+	LocalFunctionDefinition,
+	LocalDefinitionWithAttribute,
+	AttributeName,
+	BinaryExpression,
+	UnaryExpression,
+	AnonFunctionDefinition,
+	FunctionBody,
+	FunctionCall,
+	Call,
+	Arguments,
+	Affix,
+	TableConstructor,
+	BracketField,
+	NameField,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SyntaxElement {
+	Node(SyntaxNode),
+	Token(SyntaxToken),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SyntaxNode {
+	pub kind: SyntaxKind,
+	/// Nodes and tokens, in source order
+	pub children: Vec<SyntaxElement>,
+}
+
+impl SyntaxNode {
+	fn new(kind: SyntaxKind) -> Self {
+		Self {
+			kind,
+			children: Vec::new(),
+		}
+	}
+
+	/// The tokens of this node and its descendants, in source order.
+	pub fn tokens(&self) -> Vec<&SyntaxToken> {
+		let mut tokens = Vec::new();
+		self.collect_tokens(&mut tokens);
+		tokens
+	}
+
+	fn collect_tokens<'a>(&'a self, tokens: &mut Vec<&'a SyntaxToken>) {
+		for child in &self.children {
+			match child {
+				SyntaxElement::Node(node) => node.collect_tokens(tokens),
+				SyntaxElement::Token(token) => tokens.push(token),
+			}
+		}
+	}
+
+	/// The source of this node, including the trivia of its tokens.
+	pub fn text(&self) -> Vec<u8> {
+		let mut out = Vec::new();
+		for token in self.tokens() {
+			token.write(&mut out);
+		}
+		out
+	}
+}
+
+/// A lossless syntax tree. See the [module documentation](self).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cst {
+	root: SyntaxNode,
+}
+
+impl Cst {
+	/// The [SyntaxKind::Chunk] node, which ends with the [TokenKind::Eof] token.
+	pub fn root(&self) -> &SyntaxNode {
+		&self.root
+	}
+
+	pub fn root_mut(&mut self) -> &mut SyntaxNode {
+		&mut self.root
+	}
+
+	/// The source of the tree, which is exactly the parsed source if it
+	/// hasn't been edited.
+	pub fn to_bytes(&self) -> Vec<u8> {
+		self.root.text()
+	}
+
+	/// Derives the syntax tree from the tokens of this tree, like
+	/// [parse_chunk](crate::parse_chunk) would from [Cst::to_bytes].
+	pub fn to_ast(&self, options: &ParseOptions) -> Result<Parse<Chunk>, Error<Span>> {
+		let src = self.to_bytes();
+		let lines = LineIndex::new(&src);
+		let mut tokens = Vec::new();
+		let mut comments = Comments::default();
+		let mut pending = Vec::new();
+
+		let mut pos = 0;
+		let mut prev = 0;
+		for token in self.root.tokens() {
+			for trivia in &token.leading {
+				if let TriviaKind::Comment(_) = trivia.kind {
+					pending.push((comment(trivia, pos), prev, false));
+				}
+				pos += trivia.text.len();
+			}
+
+			let span = Span::new(pos, pos + token.text.len());
+			for (comment, prev, trailing) in pending.drain(..) {
+				comments.push(comment, prev, span.start, trailing);
+			}
+			let mut kind = token.kind.clone();
+			match &mut kind {
+				TokenKind::Name(name) => name.span = span,
+				TokenKind::String(string) => string.span = span,
+				_ => {}
+			}
+			tokens.push(Token {
+				kind,
+				span,
+				line: lines.line(span.start),
+			});
+			pos = span.end;
+			prev = span.end;
+
+			for trivia in &token.trailing {
+				if let TriviaKind::Comment(_) = trivia.kind {
+					pending.push((comment(trivia, pos), prev, true));
+				}
+				pos += trivia.text.len();
+			}
+		}
+
+		let (tree, errors) = parse_tokens(&tokens, options.state(), whole_chunk, error_chunk)?;
+		Ok(Parse {
+			tree,
+			comments: options.kept_comments(comments),
+			errors,
+		})
+	}
+}
+
+/// The comment in `trivia`, which now starts at `pos`.
+fn comment(trivia: &Trivia, pos: usize) -> Comment {
+	// The lexer knows how the text of a long comment is read
+	let mut lexer = Lexer::new(&trivia.text).keep_comments();
+	lexer.by_ref().for_each(drop);
+	let mut comment = lexer.take_comments().iter().next().cloned().unwrap();
+	comment.span = Span::new(pos, pos + trivia.text.len());
+	comment
+}
+
+/// Builds a [Cst] from the tokens and comments of `src`, grouping them by the
+/// nodes of `chunk`, which was parsed from them.
+pub(crate) fn build(src: &[u8], tokens: &[Token], comments: &Comments, chunk: &Chunk) -> Cst {
+	let mut comments = comments.iter().peekable();
+	let mut syntax_tokens = Vec::with_capacity(tokens.len());
+	let mut pos = 0;
+	for token in tokens {
+		// Split the trivia since the last token at its first line break
+		let mut trivia = Vec::new();
+		while pos < token.span.start {
+			let start = pos;
+			let kind = match comments.next_if(|c| c.span.start == pos) {
+				Some(comment) => {
+					pos = comment.span.end;
+					TriviaKind::Comment(comment.kind)
+				}
+				None => {
+					let space = is_whitespace(src[pos]);
+					let comment_at = comments.peek().map_or(token.span.start, |c| c.span.start);
+					let end = token.span.start.min(comment_at);
+					while pos < end && is_whitespace(src[pos]) == space {
+						pos += 1;
+					}
+					match space {
+						true => TriviaKind::Whitespace,
+						false => TriviaKind::Skipped,
+					}
+				}
+			};
+			trivia.push(Trivia {
+				kind,
+				text: src[start..pos].to_vec(),
+				span: Span::new(start, pos),
+			});
+		}
+
+		let leading = match syntax_tokens.last_mut() {
+			Some(SyntaxToken { trailing, .. }) => split_trailing(trivia, trailing),
+			None => trivia,
+		};
+		syntax_tokens.push(SyntaxToken {
+			kind: token.kind.clone(),
+			text: src[token.span.start..token.span.end].to_vec(),
+			span: token.span,
+			leading,
+			trailing: Vec::new(),
+		});
+		pos = token.span.end;
+	}
+
+	let mut builder = Builder {
+		tokens: syntax_tokens.into_iter().peekable(),
+		stack: vec![SyntaxNode::new(SyntaxKind::Chunk)],
+	};
+	builder.visit_block(&chunk.0);
+	builder.take_until(usize::MAX);
+	Cst {
+		root: builder.stack.pop().unwrap(),
+	}
+}
+
+/// Whether the lexer skips `c` between tokens (`lisspace`).
+fn is_whitespace(c: u8) -> bool {
+	matches!(c, b' ' | b'\t' | b'\n' | b'\r' | b'\x0B' | b'\x0C')
+}
+
+/// Moves the trivia before the first line break into `trailing`, returning
+/// the rest.
+fn split_trailing(trivia: Vec<Trivia>, trailing: &mut Vec<Trivia>) -> Vec<Trivia> {
+	let mut trivia = trivia.into_iter();
+	let mut leading = Vec::new();
+	for mut piece in trivia.by_ref() {
+		let newline = piece.text.iter().position(|c| matches!(c, b'\n' | b'\r'));
+		match newline {
+			// A comment spanning lines still starts on this one
+			Some(i) if piece.kind == TriviaKind::Whitespace => {
+				let rest = Trivia {
+					kind: piece.kind,
+					text: piece.text.split_off(i),
+					span: Span::new(piece.span.start + i, piece.span.end),
+				};
+				piece.span.end = rest.span.start;
+				if !piece.text.is_empty() {
+					trailing.push(piece);
+				}
+				leading.push(rest);
+				break;
+			}
+			_ => trailing.push(piece),
+		}
+	}
+	leading.extend(trivia);
+	leading
+}
+
+/// Groups tokens into nodes while visiting the syntax tree in source order.
+struct Builder {
+	tokens: std::iter::Peekable<std::vec::IntoIter<SyntaxToken>>,
+	/// The nodes being built, innermost last
+	stack: Vec<SyntaxNode>,
+}
+
+impl Builder {
+	/// Adds the tokens that start before `pos` to the innermost node.
+	fn take_until(&mut self, pos: usize) {
+		let node = self.stack.last_mut().unwrap();
+		while let Some(token) = self.tokens.next_if(|tok| tok.span.start < pos) {
+			node.children.push(SyntaxElement::Token(token));
+		}
+	}
+
+	fn node(&mut self, kind: SyntaxKind, span: Span, walk: impl FnOnce(&mut Self)) {
+		self.take_until(span.start);
+		self.stack.push(SyntaxNode::new(kind));
+		walk(self);
+		self.take_until(span.end);
+		let node = self.stack.pop().unwrap();
+		let parent = self.stack.last_mut().unwrap();
+		parent.children.push(SyntaxElement::Node(node));
+	}
+}
+
+/// Visits each listed type as a node of the same [SyntaxKind].
+macro_rules! nodes {
+	($($visit:ident, $walk:ident: $t:ident;)*) => {
+		$(fn $visit(&mut self, node: &$t) {
+			self.node(SyntaxKind::$t, node.span(), |b| visit::$walk(b, node));
+		})*
+	};
+}
+
+impl Visit for Builder {
+	nodes! {
+		visit_block, walk_block: Block;
+		visit_return_statement, walk_return_statement: ReturnStatement;
+		visit_assignment, walk_assignment: Assignment;
+		visit_while, walk_while: While;
+		visit_repeat_until, walk_repeat_until: RepeatUntil;
+		visit_if_tree, walk_if_tree: IfTree;
+		visit_if_block, walk_if_block: IfBlock;
+		visit_for_expression, walk_for_expression: ForExpression;
+		visit_for_list, walk_for_list: ForList;
+		visit_named_function_definition, walk_named_function_definition: NamedFunctionDefinition;
+		visit_function_name, walk_function_name: FunctionName;
+		visit_local_function_definition, walk_local_function_definition: LocalFunctionDefinition;
+		visit_local_definition_with_attribute, walk_local_definition_with_attribute: LocalDefinitionWithAttribute;
+		visit_attribute_name, walk_attribute_name: AttributeName;
+		visit_binary_expression, walk_binary_expression: BinaryExpression;
+		visit_unary_expression, walk_unary_expression: UnaryExpression;
+		visit_anon_function_definition, walk_anon_function_definition: AnonFunctionDefinition;
+		visit_function_body, walk_function_body: FunctionBody;
+		visit_function_call, walk_function_call: FunctionCall;
+		visit_call, walk_call: Call;
+		visit_arguments, walk_arguments: Arguments;
+		visit_affix, walk_affix: Affix;
+		visit_table_constructor, walk_table_constructor: TableConstructor;
+		visit_bracket_field, walk_bracket_field: BracketField;
+		visit_name_field, walk_name_field: NameField;
+	}
+}
@@ -0,0 +1,69 @@
+//! # Dialects
+//!
+//! Each version of Lua added syntax to the one before it. The parser accepts
+//! the syntax of the [Dialect] it's given, and rejects any [Feature] that came
+//! later with an error naming the version it needs.
+
+use std::fmt::{self, Display};
+
+/// A version of Lua, whose syntax the parser accepts.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Dialect {
+	Lua51,
+	Lua52,
+	Lua53,
+	#[default]
+	Lua54,
+}
+
+impl Display for Dialect {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			Self::Lua51 => "Lua 5.1",
+			Self::Lua52 => "Lua 5.2",
+			Self::Lua53 => "Lua 5.3",
+			Self::Lua54 => "Lua 5.4",
+		})
+	}
+}
+
+/// Syntax that only some versions of Lua accept.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Feature {
+	/// A `;` that doesn't follow a statement
+	EmptyStatement,
+	Goto,
+	Labels,
+	/// `//`
+	IntegerDivision,
+	/// `&`, `|`, `~`, `<<` and `>>`
+	BitwiseOperators,
+	/// `<const>` and `<close>`
+	Attributes,
+}
+
+impl Feature {
+	/// The first version of Lua with this feature.
+	pub fn since(self) -> Dialect {
+		match self {
+			Self::EmptyStatement | Self::Goto | Self::Labels => Dialect::Lua52,
+			Self::IntegerDivision | Self::BitwiseOperators => Dialect::Lua53,
+			Self::Attributes => Dialect::Lua54,
+		}
+	}
+}
+
+impl Display for Feature {
+	/// The requirement for this feature, like `goto requires Lua 5.2`.
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let what = match self {
+			Self::EmptyStatement => "empty statements require",
+			Self::Goto => "goto requires",
+			Self::Labels => "labels require",
+			Self::IntegerDivision => "integer division requires",
+			Self::BitwiseOperators => "bitwise operators require",
+			Self::Attributes => "attributes require",
+		};
+		write!(f, "{what} {}", self.since())
+	}
+}
@@ -1,31 +1,446 @@
-use std::fmt::Debug;
+//! # Diagnostics
+//!
+//! Errors from both the lexer and the parser. Their messages follow PUC Lua's
+//! (`'end' expected near <eof>`), and [Error::render] shows where in the
+//! source they happened.
 
+use std::{
+	fmt::{self, Debug, Display, Write},
+	io,
+};
+
+use luna_ast::span::{LineIndex, Span};
 use nom::error;
 
+use crate::{dialect::Feature, input::Input, lex::TokenKind};
+
+/// Something the parser would have accepted where it failed.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expected {
+	/// A keyword or symbol
+	Token(TokenKind),
+	Name,
+	Numeral,
+	String,
+}
+
+impl Display for Expected {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			// Fixed tokens are quoted, like `luaX_token2str`
+			Self::Token(TokenKind::Eof) => f.write_str("<eof>"),
+			Self::Token(kind) => write!(f, "'{kind}'"),
+			Self::Name => f.write_str("<name>"),
+			Self::Numeral => f.write_str("<number>"),
+			Self::String => f.write_str("<string>"),
+		}
+	}
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct Error<I> {
+	/// Where the error happened
 	pub input: I,
 	pub kind: ErrorKind,
+	/// What could have appeared at `input` instead, in the order the parser tried them
+	pub expected: Vec<Expected>,
 }
 
-impl<I> error::ParseError<I> for Error<I> {
-	fn from_error_kind(input: I, kind: error::ErrorKind) -> Self {
-		let kind = match kind {
-			error::ErrorKind::Eof => ErrorKind::Eof,
-			_ => ErrorKind::NotEnoughData,
+impl<I> Error<I> {
+	pub fn new(input: I, kind: ErrorKind) -> Self {
+		Self {
+			input,
+			kind,
+			expected: Vec::new(),
+		}
+	}
+
+	/// Converts the location of this error with `f`.
+	pub fn map_input<J>(self, f: impl FnOnce(I) -> J) -> Error<J> {
+		Error {
+			input: f(self.input),
+			kind: self.kind,
+			expected: self.expected,
+		}
+	}
+}
+
+impl<'a> Error<Input<'a>> {
+	/// `what` was expected at the start of `input`.
+	pub fn expected(input: Input<'a>, what: Expected) -> Self {
+		Self {
+			input,
+			kind: ErrorKind::Expected,
+			expected: vec![what],
+		}
+	}
+}
+
+impl<'a> error::ParseError<Input<'a>> for Error<Input<'a>> {
+	fn from_error_kind(input: Input<'a>, _kind: error::ErrorKind) -> Self {
+		Self::new(input, ErrorKind::SyntaxError)
+	}
+
+	fn append(_input: Input<'a>, _kind: error::ErrorKind, other: Self) -> Self {
+		other
+	}
+
+	/// Keeps the error that got furthest into the input, merging their
+	/// expectations if both stopped at the same token.
+	fn or(mut self, other: Self) -> Self {
+		match self.input.len().cmp(&other.input.len()) {
+			std::cmp::Ordering::Less => self,
+			std::cmp::Ordering::Greater => other,
+			std::cmp::Ordering::Equal => {
+				for what in other.expected {
+					if !self.expected.contains(&what) {
+						self.expected.push(what);
+					}
+				}
+				// A specific complaint beats a list of expectations
+				if self.kind == ErrorKind::Expected {
+					self.kind = other.kind;
+				}
+				self
+			}
+		}
+	}
+}
+
+impl Error<Span> {
+	/// The error message, without its location (like `'=' expected near 'x'`).
+	pub fn message(&self, src: &[u8]) -> String {
+		let lines = LineIndex::new(src);
+		let mut msg = match &self.kind {
+			ErrorKind::Expected => {
+				let mut list = String::new();
+				for (i, what) in self.expected.iter().enumerate() {
+					if i > 0 {
+						list.push_str(" or ");
+					}
+					write!(list, "{what}").unwrap();
+				}
+				format!("{list} expected")
+			}
+			ErrorKind::Unclosed { opener, line } => {
+				let what = self.expected.first().cloned();
+				let what = what.unwrap_or(Expected::Token(TokenKind::End));
+				if *line == lines.line(self.input.start) {
+					format!("{what} expected")
+				} else {
+					format!("{what} expected (to close '{opener}' at line {line})")
+				}
+			}
+			// These name the line of the offending statement, like `undefgoto`
+			ErrorKind::BreakOutsideLoop => {
+				format!(
+					"break outside a loop at line {}",
+					lines.line(self.input.start)
+				)
+			}
+			ErrorKind::UndefinedGoto(label) => format!(
+				"no visible label '{label}' for <goto> at line {}",
+				lines.line(self.input.start)
+			),
+			ErrorKind::JumpIntoScope { label, local } => format!(
+				"<goto {label}> at line {} jumps into the scope of local '{local}'",
+				lines.line(self.input.start)
+			),
+			ErrorKind::DuplicateLabel { label, previous } => format!(
+				"label '{label}' already defined on line {}",
+				lines.line(previous.start)
+			),
+			kind => kind.to_string(),
 		};
 
-		Self { input, kind }
+		// Like `enterlevel`, which doesn't point at a token either
+		if !self.kind.is_semantic() && !self.kind.is_limit() {
+			write!(msg, " near {}", self.near(src)).unwrap();
+		}
+		msg
 	}
 
-	fn append(input: I, kind: error::ErrorKind, _other: Self) -> Self {
-		// TODO!: more errors?
-		Self::from_error_kind(input, kind)
+	/// The source text the error points at, quoted, or `<eof>` at the end.
+	fn near(&self, src: &[u8]) -> String {
+		match src.get(self.input.start..self.input.end) {
+			Some(text) if !text.is_empty() => format!("'{}'", String::from_utf8_lossy(text)),
+			_ => "<eof>".to_owned(),
+		}
+	}
+
+	/// The full diagnostic, as the `chunk:line: message` Lua would give,
+	/// followed by the offending line with a caret under the error.
+	///
+	/// Long lines are clipped to [EXCERPT_RADIUS] bytes on each side of the
+	/// error, with `...` where they were cut.
+	pub fn render(&self, chunkname: &str, src: &[u8]) -> String {
+		let lines = LineIndex::new(src);
+		let at = self.input.start.min(src.len());
+		let line = lines.line(at);
+		let line_start = lines.line_start(line).unwrap_or(0);
+		let line_end = src[line_start..]
+			.iter()
+			.position(|&c| c == b'\n' || c == b'\r')
+			.map_or(src.len(), |i| line_start + i);
+
+		let start = char_start(src, at.saturating_sub(EXCERPT_RADIUS).max(line_start));
+		let end = char_start(src, (at + EXCERPT_RADIUS).min(line_end));
+		let before = if start > line_start { "..." } else { "" };
+		let after = if end < line_end { "..." } else { "" };
+		let text = String::from_utf8_lossy(&src[start..end]);
+
+		let width = line.to_string().len();
+		let carets = self.input.len().min(end.saturating_sub(at)).max(1);
+		format!(
+			"{chunkname}:{line}: {msg}\n{:width$} |\n{line} | {before}{text}{after}\n{:width$} | {:col$}{}",
+			"",
+			"",
+			"",
+			"^".repeat(carets),
+			msg = self.message(src),
+			col = before.len() + at - start,
+		)
+	}
+}
+
+/// How many bytes of the offending line [Error::render] shows on each side of
+/// the error, at most.
+pub const EXCERPT_RADIUS: usize = 40;
+
+/// `pos`, moved back to the start of the UTF-8 character it falls in.
+fn char_start(src: &[u8], mut pos: usize) -> usize {
+	while pos > 0 && src.get(pos).is_some_and(|&c| c & 0xC0 == 0x80) {
+		pos -= 1;
+	}
+	pos
+}
+
+impl Display for Error<Span> {
+	/// The error kind and location, for when the source isn't at hand.
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"{} at {}..{}",
+			self.kind, self.input.start, self.input.end
+		)
+	}
+}
+
+impl std::error::Error for Error<Span> {}
+
+/// Why a chunk couldn't be loaded from a reader.
+#[derive(Debug)]
+pub enum LoadError {
+	/// Reading the source failed
+	Io(io::Error),
+	/// The source was read, but isn't valid Lua. It's kept to render the error.
+	Syntax { error: Error<Span>, src: Vec<u8> },
+	/// The file holds a precompiled chunk, which can't be loaded as source
+	BinaryChunk,
+}
+
+impl LoadError {
+	/// The full diagnostic, like [Error::render] for syntax errors.
+	pub fn render(&self, chunkname: &str) -> String {
+		match self {
+			Self::Io(e) => format!("cannot read {chunkname}: {e}"),
+			Self::Syntax { error, src } => error.render(chunkname, src),
+			Self::BinaryChunk => format!("{chunkname}: {self}"),
+		}
+	}
+}
+
+impl Display for LoadError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Io(e) => write!(f, "cannot read: {e}"),
+			Self::Syntax { error, src } => f.write_str(&error.message(src)),
+			Self::BinaryChunk => f.write_str("attempt to load a binary chunk"),
+		}
+	}
+}
+
+impl std::error::Error for LoadError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			Self::Io(e) => Some(e),
+			Self::Syntax { error, .. } => Some(error),
+			Self::BinaryChunk => None,
+		}
+	}
+}
+
+impl From<io::Error> for LoadError {
+	fn from(e: io::Error) -> Self {
+		Self::Io(e)
 	}
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum ErrorKind {
-	Eof,
-	NotEnoughData,
+	/// The lexer found a character that can't start any token, or the parser
+	/// found a token that can't start an expression or statement.
+	UnexpectedSymbol,
+	/// A string literal was not closed before the end of its line.
+	UnfinishedString,
+	/// A long string was not closed; it was opened on `line`.
+	UnfinishedLongString {
+		line: usize,
+	},
+	/// A long comment was not closed; it was opened on `line`.
+	UnfinishedLongComment {
+		line: usize,
+	},
+	/// A `[` followed by `=` signs didn't open a long bracket.
+	InvalidLongStringDelimiter,
+	/// A backslash in a string wasn't followed by a valid escape sequence.
+	InvalidEscape,
+	/// A `\ddd` escape is past 255.
+	DecimalEscapeTooLarge,
+	/// A `\x` or `\u{XXX}` escape is missing a hexadecimal digit.
+	HexDigitExpected,
+	/// A `\u{XXX}` escape is missing this brace.
+	MissingBrace(char),
+	/// A `\u{XXX}` escape is past `7FFFFFFF`.
+	Utf8ValueTooLarge,
+	MalformedNumber,
+	/// One of the tokens in [Error::expected] was required.
+	Expected,
+	/// The closing token in [Error::expected] for the `opener` on `line` is missing.
+	Unclosed {
+		opener: TokenKind,
+		line: usize,
+	},
+	/// A statement or assignment target isn't valid Lua.
+	SyntaxError,
+	/// A method call without its arguments.
+	FunctionArgumentsExpected,
+	/// A `break` that isn't inside a loop.
+	BreakOutsideLoop,
+	/// A `goto` with no visible label of that name.
+	UndefinedGoto(String),
+	/// A `goto` that jumps forward into the scope of a `local`.
+	JumpIntoScope {
+		label: String,
+		local: String,
+	},
+	/// A label with the same name as the visible one at `previous`.
+	DuplicateLabel {
+		label: String,
+		previous: Span,
+	},
+	/// `...` used in a function that doesn't take it.
+	VarargOutsideVararg,
+	/// An attribute other than `const` or `close`.
+	UnknownAttribute(String),
+	/// More than one `<close>` variable in a single `local` statement.
+	MultipleToBeClosed,
+	/// An assignment to a `<const>` or `<close>` variable.
+	AssignToConst(String),
+	/// Statements or expressions are nested too deeply.
+	TooManySyntaxLevels,
+	/// A function needs more than 255 registers at once.
+	TooManyRegisters,
+	/// A function has more than `limit` of `what`. `line` is where the function
+	/// was defined, or 0 for the main function (`errorlimit`).
+	TooMany {
+		what: &'static str,
+		limit: usize,
+		line: usize,
+	},
+	/// A jump is too far for its instruction.
+	ControlStructureTooLong,
+	/// A name the compiler found no binding for in the scope resolution.
+	Unresolved(String),
+	/// Syntax from a later version of Lua than the one being parsed.
+	Unsupported(Feature),
+}
+
+impl ErrorKind {
+	/// Whether this error was found by [validate](crate::validate), and not
+	/// at a particular token. Lua leaves the `near` part out of these messages.
+	pub fn is_semantic(&self) -> bool {
+		matches!(
+			self,
+			Self::BreakOutsideLoop
+				| Self::UndefinedGoto(_)
+				| Self::JumpIntoScope { .. }
+				| Self::DuplicateLabel { .. }
+				| Self::UnknownAttribute(_)
+				| Self::MultipleToBeClosed
+				| Self::AssignToConst(_)
+		)
+	}
+
+	/// Whether this error is about a limit of the compiler, and not at a
+	/// particular token.
+	pub fn is_limit(&self) -> bool {
+		matches!(
+			self,
+			Self::TooManySyntaxLevels
+				| Self::TooManyRegisters
+				| Self::TooMany { .. }
+				| Self::ControlStructureTooLong
+		)
+	}
+}
+
+impl Display for ErrorKind {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::UnexpectedSymbol => f.write_str("unexpected symbol"),
+			Self::UnfinishedString => f.write_str("unfinished string"),
+			Self::UnfinishedLongString { line } => {
+				write!(f, "unfinished long string (starting at line {line})")
+			}
+			Self::UnfinishedLongComment { line } => {
+				write!(f, "unfinished long comment (starting at line {line})")
+			}
+			Self::InvalidLongStringDelimiter => f.write_str("invalid long string delimiter"),
+			Self::InvalidEscape => f.write_str("invalid escape sequence"),
+			Self::DecimalEscapeTooLarge => f.write_str("decimal escape too large"),
+			Self::HexDigitExpected => f.write_str("hexadecimal digit expected"),
+			Self::MissingBrace(brace) => write!(f, "missing '{brace}'"),
+			Self::Utf8ValueTooLarge => f.write_str("UTF-8 value too large"),
+			Self::MalformedNumber => f.write_str("malformed number"),
+			Self::Expected => f.write_str("token expected"),
+			Self::Unclosed { opener, line } => {
+				write!(f, "unclosed '{opener}' at line {line}")
+			}
+			Self::SyntaxError => f.write_str("syntax error"),
+			Self::FunctionArgumentsExpected => f.write_str("function arguments expected"),
+			Self::BreakOutsideLoop => f.write_str("break outside a loop"),
+			Self::UndefinedGoto(label) => write!(f, "no visible label '{label}' for <goto>"),
+			Self::JumpIntoScope { label, local } => {
+				write!(f, "<goto {label}> jumps into the scope of local '{local}'")
+			}
+			Self::DuplicateLabel { label, .. } => write!(f, "label '{label}' already defined"),
+			Self::VarargOutsideVararg => f.write_str("cannot use '...' outside a vararg function"),
+			Self::UnknownAttribute(name) => write!(f, "unknown attribute '{name}'"),
+			Self::MultipleToBeClosed => {
+				f.write_str("multiple to-be-closed variables in local list")
+			}
+			Self::AssignToConst(name) => write!(f, "attempt to assign to const variable '{name}'"),
+			Self::TooManySyntaxLevels => f.write_str("chunk has too many syntax levels"),
+			Self::TooManyRegisters => {
+				f.write_str("function or expression needs too many registers")
+			}
+			Self::TooMany {
+				what,
+				limit,
+				line: 0,
+			} => {
+				write!(f, "too many {what} (limit is {limit}) in main function")
+			}
+			Self::TooMany { what, limit, line } => {
+				write!(
+					f,
+					"too many {what} (limit is {limit}) in function at line {line}"
+				)
+			}
+			Self::ControlStructureTooLong => f.write_str("control structure too long"),
+			Self::Unresolved(name) => write!(f, "unresolved name '{name}'"),
+			Self::Unsupported(feature) => write!(f, "{feature}"),
+		}
+	}
 }
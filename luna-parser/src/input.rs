@@ -0,0 +1,196 @@
+//! # Parser Input
+//!
+//! The grammar parsers run over a slice of [Token]s, along with [State]
+//! shared by the whole parse.
+
+use std::{
+	cell::{Cell, RefCell},
+	fmt::{self, Debug},
+	ops::Deref,
+};
+
+use luna_ast::span::Span;
+use nom::{Err, InputLength};
+
+use crate::{
+	dialect::{Dialect, Feature},
+	error::{Error, ErrorKind},
+	lex::Token,
+};
+
+/// The remaining tokens of a parse.
+#[derive(Clone, Copy)]
+pub struct Input<'a> {
+	tokens: &'a [Token],
+	pub(crate) state: &'a State,
+}
+
+impl<'a> Input<'a> {
+	pub fn new(tokens: &'a [Token], state: &'a State) -> Self {
+		Self { tokens, state }
+	}
+
+	pub fn tokens(&self) -> &'a [Token] {
+		self.tokens
+	}
+
+	/// The next token, and the input after it.
+	pub fn split_first(self) -> Option<(&'a Token, Self)> {
+		let (tok, tokens) = self.tokens.split_first()?;
+		Some((tok, Self { tokens, ..self }))
+	}
+
+	/// The input after the next `n` tokens.
+	pub fn skip(self, n: usize) -> Self {
+		Self {
+			tokens: &self.tokens[n.min(self.tokens.len())..],
+			..self
+		}
+	}
+
+	/// The span of the next token, which errors at this input point to.
+	pub fn span(&self) -> Span {
+		self.tokens.first().map_or(Span::default(), |tok| tok.span)
+	}
+}
+
+impl Deref for Input<'_> {
+	type Target = [Token];
+
+	fn deref(&self) -> &Self::Target {
+		self.tokens
+	}
+}
+
+impl InputLength for Input<'_> {
+	fn input_len(&self) -> usize {
+		self.tokens.len()
+	}
+}
+
+impl Debug for Input<'_> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_list()
+			.entries(self.tokens.iter().map(|tok| &tok.kind))
+			.finish()
+	}
+}
+
+/// How deeply statements and expressions can nest by default (`LUAI_MAXCCALLS`).
+pub const MAX_DEPTH: usize = 200;
+
+/// Settings and results shared by every parser of a chunk.
+#[derive(Debug)]
+pub struct State {
+	/// The errors recovered from so far, if the parse recovers from them
+	recovered: Option<RefCell<Vec<Error<Span>>>>,
+	/// How many syntax levels the parse is in
+	depth: Cell<usize>,
+	max_depth: usize,
+	dialect: Dialect,
+}
+
+impl Default for State {
+	fn default() -> Self {
+		Self {
+			recovered: None,
+			depth: Cell::new(0),
+			max_depth: MAX_DEPTH,
+			dialect: Dialect::default(),
+		}
+	}
+}
+
+impl State {
+	/// A state for parses that recover from errors, collecting them.
+	pub fn recovering() -> Self {
+		Self {
+			recovered: Some(RefCell::default()),
+			..Self::default()
+		}
+	}
+
+	/// Limits how deeply statements and expressions can nest.
+	pub fn with_max_depth(self, max_depth: usize) -> Self {
+		Self { max_depth, ..self }
+	}
+
+	/// Accepts the syntax of `dialect`.
+	pub fn with_dialect(self, dialect: Dialect) -> Self {
+		Self { dialect, ..self }
+	}
+
+	pub fn dialect(&self) -> Dialect {
+		self.dialect
+	}
+
+	/// Checks that the dialect of the parse has `feature`, which starts at
+	/// `input`. If not, the error is recovered from like any other.
+	pub fn require<'a>(
+		&self, input: Input<'a>, feature: Feature,
+	) -> Result<(), Err<Error<Input<'a>>>> {
+		if self.dialect >= feature.since() {
+			return Ok(());
+		}
+		self.recover(Error::new(input, ErrorKind::Unsupported(feature)))
+	}
+
+	/// Enters a nested statement or expression at `input` (`enterlevel`),
+	/// until the returned guard is dropped.
+	///
+	/// Too much nesting is a hard error, so hostile input can't overflow the stack.
+	pub fn enter_level<'a>(&'a self, input: Input<'a>) -> Result<Level<'a>, Err<Error<Input<'a>>>> {
+		if self.depth.get() >= self.max_depth {
+			return Err(Err::Failure(Error::new(
+				input,
+				ErrorKind::TooManySyntaxLevels,
+			)));
+		}
+		self.depth.set(self.depth.get() + 1);
+		Ok(Level(self))
+	}
+
+	pub fn is_recovering(&self) -> bool {
+		self.recovered.is_some()
+	}
+
+	/// Records `error` so the parse can carry on past it. When not recovering,
+	/// the error is handed back as a failure instead.
+	///
+	/// An error at the same place as one that was already recorded is
+	/// dropped, since it's usually a consequence of the first.
+	pub fn recover<'a>(&self, error: Error<Input<'a>>) -> Result<(), Err<Error<Input<'a>>>> {
+		if !self.is_recovering() {
+			return Err(Err::Failure(error));
+		}
+
+		self.record(error.map_input(|input| input.span()));
+		Ok(())
+	}
+
+	/// Records an error that has already been located, like one from the lexer.
+	pub fn record(&self, error: Error<Span>) {
+		if let Some(recovered) = &self.recovered {
+			let mut recovered = recovered.borrow_mut();
+			if !recovered.iter().any(|e| e.input.start == error.input.start) {
+				recovered.push(error);
+			}
+		}
+	}
+
+	/// The recorded errors, in source order.
+	pub fn into_errors(self) -> Vec<Error<Span>> {
+		let mut errors = self.recovered.map(RefCell::into_inner).unwrap_or_default();
+		errors.sort_by_key(|e| e.input.start);
+		errors
+	}
+}
+
+/// A syntax level entered with [State::enter_level], left when dropped (`leavelevel`).
+pub struct Level<'a>(&'a State);
+
+impl Drop for Level<'_> {
+	fn drop(&mut self) {
+		self.0.depth.set(self.0.depth.get() - 1);
+	}
+}
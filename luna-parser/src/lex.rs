@@ -0,0 +1,850 @@
+//! # Lexical Analysis
+//!
+//! Splits Lua source into a stream of [Token]s for the grammar parsers.
+//! The structure of the lexer follows `llex.c`.
+
+use std::fmt::{self, Display};
+
+use luna_ast::{
+	comment::{Comment, CommentKind, Comments},
+	span::Span,
+	terminal::{LiteralString, Name, Numeral},
+};
+
+use crate::{
+	dialect::Dialect,
+	error::{Error, ErrorKind},
+	terminal::{keyword::*, numeral::str2num, string::*},
+};
+
+/// The category of a [Token], along with any semantic data it carries.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TokenKind {
+	And,
+	Break,
+	Do,
+	Else,
+	ElseIf,
+	End,
+	False,
+	For,
+	Function,
+	Goto,
+	If,
+	In,
+	Local,
+	Nil,
+	Not,
+	Or,
+	Repeat,
+	Return,
+	Then,
+	True,
+	Until,
+	While,
+	Plus,
+	Minus,
+	Star,
+	Slash,
+	DoubleSlash,
+	Percent,
+	Caret,
+	Pound,
+	Amph,
+	Tilde,
+	Pipe,
+	LShift,
+	RShift,
+	IsEqual,
+	NotEqual,
+	LessEqual,
+	GreaterEqual,
+	Less,
+	Greater,
+	Equals,
+	LParen,
+	RParen,
+	LBrace,
+	RBrace,
+	LBracket,
+	RBracket,
+	DoubleColon,
+	Semicolon,
+	Colon,
+	Comma,
+	Dot,
+	DoubleDot,
+	TripleDot,
+	Numeral(Numeral),
+	String(LiteralString),
+	Name(Name),
+	/// The end of the stream. Always the last token the lexer produces.
+	Eof,
+	/// Stands in for input the lexer couldn't read, when recovering from errors.
+	Error,
+}
+
+impl TokenKind {
+	/// Looks up the keyword spelled by `word`, if there is one.
+	pub fn keyword(word: &str) -> Option<Self> {
+		use TokenKind::*;
+
+		let kind = match word {
+			KAND => And,
+			KBREAK => Break,
+			KDO => Do,
+			KELSE => Else,
+			KELSEIF => ElseIf,
+			KEND => End,
+			KFALSE => False,
+			KFOR => For,
+			KFUNCTION => Function,
+			KGOTO => Goto,
+			KIF => If,
+			KIN => In,
+			KLOCAL => Local,
+			KNIL => Nil,
+			KNOT => Not,
+			KOR => Or,
+			KREPEAT => Repeat,
+			KRETURN => Return,
+			KTHEN => Then,
+			KTRUE => True,
+			KUNTIL => Until,
+			KWHILE => While,
+			_ => return None,
+		};
+
+		Some(kind)
+	}
+
+	/// The fixed spelling of this token, or `None` for tokens that carry data.
+	pub fn as_str(&self) -> Option<&'static str> {
+		use TokenKind::*;
+
+		let s = match self {
+			And => KAND,
+			Break => KBREAK,
+			Do => KDO,
+			Else => KELSE,
+			ElseIf => KELSEIF,
+			End => KEND,
+			False => KFALSE,
+			For => KFOR,
+			Function => KFUNCTION,
+			Goto => KGOTO,
+			If => KIF,
+			In => KIN,
+			Local => KLOCAL,
+			Nil => KNIL,
+			Not => KNOT,
+			Or => KOR,
+			Repeat => KREPEAT,
+			Return => KRETURN,
+			Then => KTHEN,
+			True => KTRUE,
+			Until => KUNTIL,
+			While => KWHILE,
+			Plus => "+",
+			Minus => "-",
+			Star => "*",
+			Slash => "/",
+			DoubleSlash => DOUBLESLASH,
+			Percent => "%",
+			Caret => "^",
+			Pound => "#",
+			Amph => "&",
+			Tilde => "~",
+			Pipe => "|",
+			LShift => LSHIFT,
+			RShift => RSHIFT,
+			IsEqual => ISEQUAL,
+			NotEqual => NOTEQUAL,
+			LessEqual => LESSEQUAL,
+			GreaterEqual => GREATEREQUAL,
+			Less => "<",
+			Greater => ">",
+			Equals => "=",
+			LParen => "(",
+			RParen => ")",
+			LBrace => "{",
+			RBrace => "}",
+			LBracket => "[",
+			RBracket => "]",
+			DoubleColon => DOUBLECOLON,
+			Semicolon => ";",
+			Colon => ":",
+			Comma => ",",
+			Dot => ".",
+			DoubleDot => DOUBLEDOT,
+			TripleDot => TRIPLEDOT,
+			Numeral(_) | String(_) | Name(_) | Eof | Error => return None,
+		};
+
+		Some(s)
+	}
+}
+
+impl Display for TokenKind {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Numeral(_) => f.write_str("<number>"),
+			Self::String(_) => f.write_str("<string>"),
+			Self::Name(_) => f.write_str("<name>"),
+			Self::Eof => f.write_str("<eof>"),
+			Self::Error => f.write_str("<error>"),
+			kind => f.write_str(kind.as_str().unwrap()),
+		}
+	}
+}
+
+/// A single lexical unit of Lua source.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Token {
+	pub kind: TokenKind,
+	pub span: Span,
+	/// The line the token starts on, beginning at 1
+	pub line: usize,
+}
+
+/// Whitespace as defined by `lisspace`, not counting line breaks.
+fn is_space(c: u8) -> bool {
+	matches!(c, b' ' | b'\t' | b'\x0B' | b'\x0C')
+}
+
+/// Appends the UTF-8 encoding of `x` to `buf` (`luaO_utf8esc`).
+///
+/// Unlike [char], this accepts surrogates and values up to 2^31, using the
+/// original 6-byte form of UTF-8.
+fn utf8_esc(buf: &mut Vec<u8>, mut x: u32) {
+	if x < 0x80 {
+		buf.push(x as u8);
+		return;
+	}
+
+	let mut tail = Vec::new();
+	// Maximum that fits in the first byte
+	let mut mfb = 0x3F;
+	while x > mfb {
+		tail.push(0x80 | (x & 0x3F) as u8);
+		x >>= 6;
+		mfb >>= 1;
+	}
+	buf.push(((!mfb << 1) | x) as u8);
+	buf.extend(tail.iter().rev());
+}
+
+/// Produces [Token]s from a buffer of Lua source.
+pub struct Lexer<'a> {
+	src: &'a [u8],
+	pos: usize,
+	line: usize,
+	done: bool,
+	/// Collected comments, if they're being kept
+	comments: Option<Comments>,
+	/// Comments waiting for the token after them
+	pending: Vec<(Comment, usize, bool)>,
+	/// The end of the last token produced
+	prev_end: usize,
+	/// Whether a line break was skipped since the last token
+	newline: bool,
+	/// Errors recovered from, if the lexer recovers from them
+	errors: Option<Vec<Error<Span>>>,
+	/// Whether names may contain non-ASCII letters
+	unicode_names: bool,
+	/// Decides which words are keywords and which escapes strings accept
+	dialect: Dialect,
+}
+
+impl<'a> Lexer<'a> {
+	pub fn new(src: &'a [u8]) -> Self {
+		Self {
+			src,
+			pos: 0,
+			line: 1,
+			done: false,
+			comments: None,
+			pending: Vec::new(),
+			prev_end: 0,
+			newline: true,
+			errors: None,
+			unicode_names: false,
+			dialect: Dialect::default(),
+		}
+	}
+
+	/// Carries on past errors, producing [TokenKind::Error] tokens in their place.
+	pub fn recovering(mut self) -> Self {
+		self.errors = Some(Vec::new());
+		self
+	}
+
+	/// Takes the errors recovered from so far.
+	pub fn take_errors(&mut self) -> Vec<Error<Span>> {
+		self.errors.as_mut().map(std::mem::take).unwrap_or_default()
+	}
+
+	/// Moves past the rest of the input that caused `error`, so lexing can resume.
+	fn resync(&mut self, error: &Error<Span>) {
+		self.pos = self.pos.max(error.input.end).max(error.input.start + 1);
+		if matches!(
+			error.kind,
+			ErrorKind::InvalidEscape
+				| ErrorKind::DecimalEscapeTooLarge
+				| ErrorKind::HexDigitExpected
+				| ErrorKind::MissingBrace(_)
+				| ErrorKind::Utf8ValueTooLarge
+		) {
+			// Skip the rest of the string
+			let delim = self.src[error.input.start];
+			while let Some(c) = self.current() {
+				if c == b'\n' || c == b'\r' {
+					break;
+				}
+				self.pos += 1;
+				if c == delim {
+					break;
+				}
+			}
+		}
+	}
+
+	/// Keeps the comments in the source instead of discarding them.
+	pub fn keep_comments(mut self) -> Self {
+		self.comments = Some(Comments::default());
+		self
+	}
+
+	/// Starts lexing after the first `pos` bytes of the source, such as the
+	/// header of a file. Spans still count from the start of the source.
+	pub fn starting_at(mut self, pos: usize) -> Self {
+		self.pos = pos;
+		self
+	}
+
+	/// Accepts UTF-8 letters in names, like Lua built with `LUAI_UCID`.
+	///
+	/// Lua only accepts ASCII letters, digits and underscores otherwise.
+	pub fn unicode_names(mut self) -> Self {
+		self.unicode_names = true;
+		self
+	}
+
+	/// Lexes the source as `dialect`, instead of Lua 5.4.
+	///
+	/// Older dialects have fewer keywords (`goto` is a name in Lua 5.1) and
+	/// fewer escape sequences in strings.
+	pub fn dialect(mut self, dialect: Dialect) -> Self {
+		self.dialect = dialect;
+		self
+	}
+
+	/// Takes the comments collected so far.
+	pub fn take_comments(&mut self) -> Comments {
+		self.comments.take().unwrap_or_default()
+	}
+
+	fn current(&self) -> Option<u8> {
+		self.src.get(self.pos).copied()
+	}
+
+	fn peek(&self, n: usize) -> Option<u8> {
+		self.src.get(self.pos + n).copied()
+	}
+
+	/// Consumes the current byte if it is `ch`.
+	fn check_next(&mut self, ch: u8) -> bool {
+		if self.current() == Some(ch) {
+			self.pos += 1;
+			true
+		} else {
+			false
+		}
+	}
+
+	fn error(&self, start: usize, kind: ErrorKind) -> Error<Span> {
+		Error::new(Span::new(start, self.pos), kind)
+	}
+
+	/// An invalid escape in the string starting at `start`, pointing up to
+	/// and including the offending character (`esccheck`).
+	fn escape_error(&self, start: usize, kind: ErrorKind) -> Error<Span> {
+		let end = (self.pos + 1).min(self.src.len());
+		Error::new(Span::new(start, end), kind)
+	}
+
+	/// Skips a newline sequence (`\n`, `\r`, `\n\r` or `\r\n`).
+	fn inclinenumber(&mut self) {
+		let old = self.current();
+		self.pos += 1;
+		if matches!(self.current(), Some(b'\n' | b'\r')) && self.current() != old {
+			self.pos += 1;
+		}
+		self.line += 1;
+	}
+
+	/// The length of the character at the current position if it can continue
+	/// a name (`lislalnum`), or start one (`lislalpha`) if `first`.
+	fn name_char_len(&self, first: bool) -> Option<usize> {
+		let c = self.current()?;
+		if c.is_ascii() {
+			let ok = c.is_ascii_alphabetic() || c == b'_' || (!first && c.is_ascii_digit());
+			return ok.then_some(1);
+		}
+		if !self.unicode_names {
+			return None;
+		}
+
+		let len = match c {
+			0xC0..=0xDF => 2,
+			0xE0..=0xEF => 3,
+			0xF0..=0xF7 => 4,
+			_ => return None,
+		};
+		let ch = std::str::from_utf8(self.src.get(self.pos..self.pos + len)?)
+			.ok()?
+			.chars()
+			.next()?;
+		let ok = ch.is_alphabetic() || (!first && ch.is_alphanumeric());
+		ok.then_some(len)
+	}
+
+	fn read_name(&mut self) -> TokenKind {
+		let start = self.pos;
+		while let Some(len) = self.name_char_len(self.pos == start) {
+			self.pos += len;
+		}
+
+		// SAFETY: Only whole ASCII or UTF-8 characters were consumed.
+		let word = std::str::from_utf8(&self.src[start..self.pos]).unwrap();
+		let keyword = TokenKind::keyword(word).filter(|_| keyword_since(word) <= self.dialect);
+		keyword.unwrap_or_else(|| {
+			TokenKind::Name(Name {
+				value: word.to_owned(),
+				span: Span::new(start, self.pos),
+			})
+		})
+	}
+
+	/// Reads a numeral (`read_numeral`).
+	///
+	/// This is deliberately lenient and lets [str2num] reject malformed input.
+	fn read_numeral(&mut self) -> Result<TokenKind, Error<Span>> {
+		let start = self.pos;
+		let mut expo: &[u8] = b"eE";
+		let mut point = true;
+		if self.current() == Some(b'0') && matches!(self.peek(1), Some(b'x' | b'X')) {
+			self.pos += 2;
+			expo = b"pP";
+			// Lua 5.1 only has hexadecimal integers
+			if self.dialect == Dialect::Lua51 {
+				expo = b"";
+				point = false;
+			}
+		}
+
+		loop {
+			match self.current() {
+				Some(c) if expo.contains(&c) => {
+					self.pos += 1;
+					// Optional exponent sign
+					if matches!(self.current(), Some(b'-' | b'+')) {
+						self.pos += 1;
+					}
+				}
+				Some(c) if c.is_ascii_hexdigit() || (point && c == b'.') => self.pos += 1,
+				_ => break,
+			}
+		}
+
+		// A numeral touching a letter is an error
+		if matches!(self.current(), Some(c) if c.is_ascii_alphabetic() || c == b'_') {
+			self.pos += 1;
+		}
+
+		str2num(&self.src[start..self.pos])
+			.map(TokenKind::Numeral)
+			.ok_or_else(|| self.error(start, ErrorKind::MalformedNumber))
+	}
+
+	/// Reads the `=` signs of a long bracket starting at the current `[` or `]`.
+	///
+	/// Returns the level plus 2 for a well-formed bracket, 1 for a lone
+	/// bracket, and 0 for a bracket with `=` signs but no second bracket.
+	fn skip_sep(&mut self) -> usize {
+		let s = self.current();
+		self.pos += 1;
+		let mut count = 0;
+		while self.check_next(b'=') {
+			count += 1;
+		}
+
+		if self.current() == s {
+			count + 2
+		} else if count == 0 {
+			1
+		} else {
+			0
+		}
+	}
+
+	/// Reads the contents of a long string or comment, after its opening `[=*`.
+	fn read_long_string(&mut self, sep: usize, comment: bool) -> Result<Vec<u8>, Error<Span>> {
+		let line = self.line;
+		let mut content = Vec::new();
+		// Skip the second `[`
+		self.pos += 1;
+		// A newline right after the opening bracket is not part of the string
+		if matches!(self.current(), Some(b'\n' | b'\r')) {
+			self.inclinenumber();
+		}
+
+		loop {
+			match self.current() {
+				None => {
+					let kind = match comment {
+						true => ErrorKind::UnfinishedLongComment { line },
+						false => ErrorKind::UnfinishedLongString { line },
+					};
+					return Err(Error::new(Span::empty(self.pos), kind));
+				}
+				Some(b']') => {
+					let bracket = self.pos;
+					if self.skip_sep() == sep {
+						// Skip the second `]`
+						self.pos += 1;
+						return Ok(content);
+					}
+					// Keep the `]` and any `=`; a second `]` is scanned again
+					content.extend_from_slice(&self.src[bracket..self.pos]);
+				}
+				Some(b'\n' | b'\r') => {
+					content.push(b'\n');
+					self.inclinenumber();
+				}
+				Some(c) => {
+					content.push(c);
+					self.pos += 1;
+				}
+			}
+		}
+	}
+
+	/// Reads a line or long comment starting at the current `--`.
+	fn read_comment(&mut self) -> Result<(), Error<Span>> {
+		let start = self.pos;
+		self.pos += 2;
+
+		let mut kind = CommentKind::Line;
+		let mut text = None;
+		if self.current() == Some(b'[') {
+			let bracket = self.pos;
+			match self.skip_sep() {
+				sep @ 2.. => {
+					kind = CommentKind::Long(sep - 2);
+					text = Some(self.read_long_string(sep, true)?);
+				}
+				// Not a long bracket, so this is a line comment after all
+				_ => self.pos = bracket,
+			}
+		}
+		let text = match text {
+			Some(text) => text,
+			None => {
+				while !matches!(self.current(), None | Some(b'\n' | b'\r')) {
+					self.pos += 1;
+				}
+				self.src[start + 2..self.pos].to_vec()
+			}
+		};
+
+		if self.comments.is_some() {
+			let comment = Comment {
+				kind,
+				text,
+				span: Span::new(start, self.pos),
+			};
+			self.pending.push((comment, self.prev_end, !self.newline));
+		}
+		Ok(())
+	}
+
+	/// Reads a hexadecimal digit of a `\x` or `\u` escape.
+	fn read_hex_digit(&mut self, start: usize) -> Result<u32, Error<Span>> {
+		match self.current().and_then(|c| (c as char).to_digit(16)) {
+			Some(d) => {
+				self.pos += 1;
+				Ok(d)
+			}
+			None => Err(self.escape_error(start, ErrorKind::HexDigitExpected)),
+		}
+	}
+
+	/// Reads the `{XXX}` of a `\u{XXX}` escape, returning the code point.
+	fn read_utf8_esc(&mut self, start: usize) -> Result<u32, Error<Span>> {
+		if !self.check_next(b'{') {
+			return Err(self.escape_error(start, ErrorKind::MissingBrace('{')));
+		}
+
+		let mut r = self.read_hex_digit(start)?;
+		while let Some(d) = self.current().and_then(|c| (c as char).to_digit(16)) {
+			if r > 0x7FFF_FFFF >> 4 {
+				return Err(self.escape_error(start, ErrorKind::Utf8ValueTooLarge));
+			}
+			r = (r << 4) + d;
+			self.pos += 1;
+		}
+
+		if !self.check_next(b'}') {
+			return Err(self.escape_error(start, ErrorKind::MissingBrace('}')));
+		}
+		Ok(r)
+	}
+
+	/// Reads a short string delimited by `delim` (`read_string`).
+	fn read_string(&mut self, delim: u8) -> Result<TokenKind, Error<Span>> {
+		let start = self.pos;
+		let mut content = Vec::new();
+		self.pos += 1;
+
+		loop {
+			match self.current() {
+				None => {
+					return Err(Error::new(
+						Span::empty(self.pos),
+						ErrorKind::UnfinishedString,
+					))
+				}
+				Some(b'\n' | b'\r') => return Err(self.error(start, ErrorKind::UnfinishedString)),
+				Some(c) if c == delim => break,
+				Some(b'\\') => self.pos += 1,
+				Some(c) => {
+					content.push(c);
+					self.pos += 1;
+					continue;
+				}
+			}
+
+			// Escape sequences
+			let Some(esc) = self.current() else {
+				// Reported as an unfinished string on the next iteration
+				continue;
+			};
+			let byte = match esc {
+				b'a' => b'\x07',
+				b'b' => b'\x08',
+				b'f' => b'\x0C',
+				b'n' => b'\n',
+				b'r' => b'\r',
+				b't' => b'\t',
+				b'v' => b'\x0B',
+				b'\\' | b'"' | b'\'' => esc,
+				b'\n' | b'\r' => {
+					self.inclinenumber();
+					content.push(b'\n');
+					continue;
+				}
+				b'x' if self.dialect >= Dialect::Lua52 => {
+					self.pos += 1;
+					let hi = self.read_hex_digit(start)?;
+					let lo = self.read_hex_digit(start)?;
+					content.push((hi << 4 | lo) as u8);
+					continue;
+				}
+				b'u' if self.dialect >= Dialect::Lua53 => {
+					self.pos += 1;
+					let r = self.read_utf8_esc(start)?;
+					utf8_esc(&mut content, r);
+					continue;
+				}
+				b'z' if self.dialect >= Dialect::Lua52 => {
+					// Skip the following whitespace, including line breaks
+					self.pos += 1;
+					loop {
+						match self.current() {
+							Some(b'\n' | b'\r') => self.inclinenumber(),
+							Some(c) if is_space(c) => self.pos += 1,
+							_ => break,
+						}
+					}
+					continue;
+				}
+				b'0'..=b'9' => {
+					// Up to three decimal digits
+					let mut r: u32 = 0;
+					for _ in 0..3 {
+						match self.current() {
+							Some(c @ b'0'..=b'9') => {
+								r = 10 * r + (c - b'0') as u32;
+								self.pos += 1;
+							}
+							_ => break,
+						}
+					}
+					if r > u8::MAX as u32 {
+						return Err(self.escape_error(start, ErrorKind::DecimalEscapeTooLarge));
+					}
+					content.push(r as u8);
+					continue;
+				}
+				// Lua 5.1 lets any other character escape itself
+				_ if self.dialect == Dialect::Lua51 => esc,
+				_ => return Err(self.escape_error(start, ErrorKind::InvalidEscape)),
+			};
+			content.push(byte);
+			self.pos += 1;
+		}
+		self.pos += 1;
+
+		Ok(TokenKind::String(LiteralString {
+			value: content,
+			span: Span::new(start, self.pos),
+		}))
+	}
+
+	/// Reads the next token, skipping any whitespace before it.
+	fn lex(&mut self) -> Result<Token, Error<Span>> {
+		use TokenKind::*;
+
+		loop {
+			let start = self.pos;
+			let line = self.line;
+			let Some(ch) = self.current() else {
+				return Ok(Token {
+					kind: Eof,
+					span: Span::empty(start),
+					line,
+				});
+			};
+
+			let kind = match ch {
+				b'\n' | b'\r' => {
+					self.inclinenumber();
+					self.newline = true;
+					continue;
+				}
+				b'-' if self.peek(1) == Some(b'-') => {
+					self.read_comment()?;
+					continue;
+				}
+				c if is_space(c) => {
+					self.pos += 1;
+					continue;
+				}
+				b'"' | b'\'' => self.read_string(ch)?,
+				b'[' => match self.skip_sep() {
+					1 => LBracket,
+					0 => return Err(self.error(start, ErrorKind::InvalidLongStringDelimiter)),
+					sep => String(LiteralString {
+						value: self.read_long_string(sep, false)?,
+						span: Span::new(start, self.pos),
+					}),
+				},
+				b'0'..=b'9' => self.read_numeral()?,
+				b'.' if matches!(self.peek(1), Some(b'0'..=b'9')) => self.read_numeral()?,
+				_ if self.name_char_len(true).is_some() => self.read_name(),
+				_ => {
+					self.pos += 1;
+					match ch {
+						b'+' => Plus,
+						b'-' => Minus,
+						b'*' => Star,
+						b'/' if self.check_next(b'/') => DoubleSlash,
+						b'/' => Slash,
+						b'%' => Percent,
+						b'^' => Caret,
+						b'#' => Pound,
+						b'&' => Amph,
+						b'~' if self.check_next(b'=') => NotEqual,
+						b'~' => Tilde,
+						b'|' => Pipe,
+						b'<' if self.check_next(b'<') => LShift,
+						b'<' if self.check_next(b'=') => LessEqual,
+						b'<' => Less,
+						b'>' if self.check_next(b'>') => RShift,
+						b'>' if self.check_next(b'=') => GreaterEqual,
+						b'>' => Greater,
+						b'=' if self.check_next(b'=') => IsEqual,
+						b'=' => Equals,
+						b'(' => LParen,
+						b')' => RParen,
+						b'{' => LBrace,
+						b'}' => RBrace,
+						b']' => RBracket,
+						b':' if self.check_next(b':') => DoubleColon,
+						b':' => Colon,
+						b';' => Semicolon,
+						b',' => Comma,
+						b'.' if self.peek(0) == Some(b'.') && self.peek(1) == Some(b'.') => {
+							self.pos += 2;
+							TripleDot
+						}
+						b'.' if self.check_next(b'.') => DoubleDot,
+						b'.' => Dot,
+						_ => return Err(self.error(start, ErrorKind::UnexpectedSymbol)),
+					}
+				}
+			};
+
+			return Ok(Token {
+				kind,
+				span: Span::new(start, self.pos),
+				line,
+			});
+		}
+	}
+}
+
+impl Iterator for Lexer<'_> {
+	type Item = Result<Token, Error<Span>>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if self.done {
+			return None;
+		}
+
+		let token = match self.lex() {
+			Err(error) if self.errors.is_some() => {
+				self.resync(&error);
+				let span = error.input;
+				self.errors.get_or_insert_with(Vec::new).push(error);
+				Ok(Token {
+					kind: TokenKind::Error,
+					span,
+					line: self.line,
+				})
+			}
+			token => token,
+		};
+
+		if let Ok(token) = &token {
+			if let Some(comments) = &mut self.comments {
+				for (comment, prev, trailing) in self.pending.drain(..) {
+					comments.push(comment, prev, token.span.start, trailing);
+				}
+			}
+			self.prev_end = token.span.end;
+			self.newline = false;
+		}
+		self.done = matches!(
+			token,
+			Ok(Token {
+				kind: TokenKind::Eof,
+				..
+			}) | Err(_)
+		);
+		Some(token)
+	}
+}
+
+/// Splits `src` into tokens. The returned list always ends with [TokenKind::Eof].
+pub fn tokenize(src: &[u8]) -> Result<Vec<Token>, Error<Span>> {
+	Lexer::new(src).collect()
+}
+
+/// Like [tokenize], but also returns the comments in `src`.
+pub fn tokenize_with_comments(src: &[u8]) -> Result<(Vec<Token>, Comments), Error<Span>> {
+	let mut lexer = Lexer::new(src).keep_comments();
+	let tokens = lexer.by_ref().collect::<Result<_, _>>()?;
+	Ok((tokens, lexer.take_comments()))
+}
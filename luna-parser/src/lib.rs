@@ -1,59 +1,365 @@
 /// The input type for all of the parsers.
-pub(crate) type In<'a> = &'a str;
+pub(crate) type In<'a> = Input<'a>;
 
 /// Abbreviated parser result type
-pub(crate) type IRes<'a, O> = IResult<In<'a>, O>;
+pub(crate) type IRes<'a, O> = IResult<In<'a>, O, Error<In<'a>>>;
 
-use combinator::list;
-use luna_ast::{Block, Chunk, ReturnStatement};
+use std::{
+	fs::File,
+	io::{BufReader, Read},
+	path::Path,
+};
+
+use cst::Cst;
+pub use dialect::{Dialect, Feature};
+use error::{Error, LoadError};
+pub use input::MAX_DEPTH;
+use input::{Input, State};
+use lex::{Token, TokenKind};
+use luna_ast::{
+	comment::Comments,
+	expression::{Expression, ExpressionList},
+	span::Span,
+	statement::Statement,
+	Block, Chunk, ReturnStatement,
+};
 use nom::{
-	combinator::{all_consuming, opt},
+	combinator::{all_consuming, not, opt},
 	multi::many0,
-	sequence::{delimited, pair},
+	sequence::{preceded, terminated},
 	Finish, IResult, Parser,
 };
-use parse::{expression::exp, statement::stat};
-use terminal::{
-	keyword::KRETURN,
-	string::{COMMA, SEMICOLON},
+pub use options::ParseOptions;
+use parse::{
+	expression::{exp, explist},
+	statement::stat,
 };
 
-use combinator::{ws0, wschar, wstag};
+use combinator::{block_follow, consumed, keyword, spanned, synchronize, token, unexpected};
 
+pub mod code;
 mod combinator;
-pub use nom::error;
+pub mod cst;
+mod dialect;
+pub mod error;
+mod input;
+pub mod lex;
+pub mod load;
+mod options;
 mod parse;
+pub mod resolve;
 pub mod terminal;
+pub mod validate;
+pub mod zio;
 
 #[cfg(test)]
 mod test;
 
-pub fn chunk(input: In) -> Result<Chunk, nom::error::Error<In>> {
-	dbg!(input);
+/// The result of a successful parse.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Parse<T> {
+	pub tree: T,
+	/// The comments of the source, if [ParseOptions::keep_comments] was set.
+	pub comments: Comments,
+	/// The errors recovered from, if [ParseOptions::recovering] was set. The
+	/// tree has error nodes where they were found.
+	pub errors: Vec<Error<Span>>,
+}
+
+/// Parses `src` as a chunk: a whole file or string of Lua code.
+///
+/// Unless recovering from errors, the first error is returned instead.
+pub fn parse_chunk(src: &[u8], options: &ParseOptions) -> Result<Parse<Chunk>, Error<Span>> {
+	parse_from(src, 0, options, whole_chunk, error_chunk)
+}
+
+/// Parses `src` as a single expression.
+pub fn parse_expression(
+	src: &[u8], options: &ParseOptions,
+) -> Result<Parse<Expression>, Error<Span>> {
+	parse_from(
+		src,
+		0,
+		options,
+		|input| terminated(exp, end)(input),
+		Expression::Error,
+	)
+}
+
+/// Parses `src` as a single statement.
+pub fn parse_statement(
+	src: &[u8], options: &ParseOptions,
+) -> Result<Parse<Statement>, Error<Span>> {
+	parse_from(
+		src,
+		0,
+		options,
+		|input| terminated(recover_stat, end)(input),
+		Statement::Error,
+	)
+}
+
+/// Parses `src` with the default options, returning just the chunk.
+pub fn chunk(input: &str) -> Result<Chunk, Error<Span>> {
+	chunk_bytes(input.as_bytes())
+}
+
+/// Parses a chunk from raw bytes.
+///
+/// Lua source doesn't have to be UTF-8; string literals can hold any bytes.
+pub fn chunk_bytes(input: &[u8]) -> Result<Chunk, Error<Span>> {
+	parse_chunk(input, &ParseOptions::default()).map(|parse| parse.tree)
+}
+
+/// Parses a chunk read from `reader`, such as a file or a [zio::Zio].
+///
+/// The reader is read to its end before parsing starts, so the whole source
+/// is held in memory: the lexer works on one slice, and the spans in the
+/// syntax tree and errors point back into it. Nothing is parsed while the
+/// reader is still handing out pieces.
+pub fn chunk_from_reader(mut reader: impl Read) -> Result<Chunk, LoadError> {
+	let mut src = Vec::new();
+	reader.read_to_end(&mut src)?;
+	chunk_bytes(&src).map_err(|error| LoadError::Syntax { error, src })
+}
+
+/// Parses the script file at `path`, skipping its header (`luaL_loadfilex`).
+///
+/// See [chunk_from_script] for what's skipped.
+pub fn chunk_from_file(path: impl AsRef<Path>) -> Result<Chunk, LoadError> {
+	chunk_from_script(BufReader::new(File::open(path)?))
+}
+
+/// Parses a script file read from `reader`, like standard input.
+///
+/// As with [chunk_from_reader], the whole file is read before it's parsed.
+/// A UTF-8 byte order mark and a first line starting with `#` are skipped.
+/// Line numbers and spans still count them, so errors point into the file
+/// as it is. Precompiled chunks are detected by their signature, and
+/// rejected.
+pub fn chunk_from_script(mut reader: impl Read) -> Result<Chunk, LoadError> {
+	let mut src = Vec::new();
+	reader.read_to_end(&mut src)?;
+	if load::is_binary(&src) {
+		return Err(LoadError::BinaryChunk);
+	}
+
+	let options = ParseOptions::default();
+	let start = load::header_len(&src);
+	match parse_from(&src, start, &options, whole_chunk, error_chunk) {
+		Ok(parse) => Ok(parse.tree),
+		Err(error) => Err(LoadError::Syntax { error, src }),
+	}
+}
+
+/// Runs `parser` over the tokens of `src` after the first `start` bytes.
+fn parse_from<T, P>(
+	src: &[u8], start: usize, options: &ParseOptions, parser: P, error: impl FnOnce(Span) -> T,
+) -> Result<Parse<T>, Error<Span>>
+where
+	P: for<'a> FnMut(In<'a>) -> IRes<'a, T>,
+{
+	let mut lexer = options.lexer(src).starting_at(start);
+	let tokens = lexer.by_ref().collect::<Result<Vec<_>, _>>()?;
+	let state = options.state();
+	for e in lexer.take_errors() {
+		state.record(e);
+	}
 
-	all_consuming(ws0(block).map(Chunk))
-		.parse(input)
+	let (tree, errors) = parse_tokens(&tokens, state, parser, error)?;
+	Ok(Parse {
+		tree,
+		comments: lexer.take_comments(),
+		errors,
+	})
+}
+
+/// Runs `parser` over `tokens`, returning the tree and the errors recovered from.
+///
+/// When recovering, a parse that fails anyway is replaced with the `error`
+/// node for all of the tokens.
+pub(crate) fn parse_tokens<T, P>(
+	tokens: &[Token], state: State, parser: P, error: impl FnOnce(Span) -> T,
+) -> Result<(T, Vec<Error<Span>>), Error<Span>>
+where
+	P: for<'a> FnMut(In<'a>) -> IRes<'a, T>,
+{
+	let tree = match all_consuming(parser)
+		.parse(Input::new(tokens, &state))
 		.finish()
-		// If there's any remaining input, there's a problem
-		.map(|(_, chunk)| chunk)
-	// Convert nom's error type into our error type
-	// .map_err(|e| error::Error::from_error_kind(e.input, e.code))
+	{
+		Ok((_, tree)) => tree,
+		// Point the error at the first token that couldn't be parsed
+		Err(e) if !state.is_recovering() => return Err(e.map_input(|rest| rest.span())),
+		Err(e) => {
+			// Every statement recovers, so this shouldn't happen. Just in case, give up
+			// on the whole source.
+			state.record(e.map_input(|rest| rest.span()));
+			let span = match (tokens.first(), tokens.last()) {
+				(Some(first), Some(last)) => first.span.to(last.span),
+				_ => Span::default(),
+			};
+			error(span)
+		}
+	};
+	Ok((tree, state.into_errors()))
+}
+
+/// Parses `src` as a chunk, keeping all of its source in a [Cst].
+///
+/// The comments are returned as well if [ParseOptions::keep_comments] was set.
+pub fn parse_cst(src: &[u8], options: &ParseOptions) -> Result<Parse<Cst>, Error<Span>> {
+	let mut lexer = options.lexer(src).keep_comments();
+	let tokens = lexer.by_ref().collect::<Result<Vec<_>, _>>()?;
+	let state = options.state();
+	for e in lexer.take_errors() {
+		state.record(e);
+	}
+
+	let comments = lexer.take_comments();
+	let (chunk, errors) = parse_tokens(&tokens, state, whole_chunk, error_chunk)?;
+	Ok(Parse {
+		tree: cst::build(src, &tokens, &comments, &chunk),
+		comments: options.kept_comments(comments),
+		errors,
+	})
+}
+
+/// Matches the end of the source after a single expression or statement. When
+/// recovering, anything left over is skipped.
+fn end(input: In) -> IRes<()> {
+	match token(TokenKind::Eof)(input) {
+		Ok((rest, _)) => Ok((rest, ())),
+		Err(nom::Err::Error(e)) => {
+			input.state.recover(e)?;
+			Ok((input.skip(input.len()), ()))
+		}
+		Err(e) => Err(e),
+	}
+}
+
+fn whole_chunk(input: In) -> IRes<Chunk> {
+	main_block.map(Chunk).parse(input)
+}
+
+/// A chunk that couldn't be parsed at all.
+fn error_chunk(span: Span) -> Chunk {
+	Chunk(Block {
+		stlist: vec![Statement::Error(span)],
+		oret: None,
+		span,
+	})
+}
+
+/// The block of a whole chunk, which must be followed by the end of the source.
+fn main_block(input: In) -> IRes<Block> {
+	let (mut rest, mut bl) = block(input)?;
+
+	loop {
+		let e = match token(TokenKind::Eof)(rest) {
+			Ok((end, _)) => {
+				bl.span = consumed(input, rest);
+				return Ok((end, bl));
+			}
+			Err(nom::Err::Error(e)) => e,
+			Err(e) => return Err(e),
+		};
+
+		// A stray token that ends a block. Skip it and carry on with the rest.
+		rest.state.recover(e)?;
+		let skipped = synchronize(rest, rest);
+		let mut span = consumed(rest, skipped);
+		if let Some(ret) = bl.oret.take() {
+			span = ret.span.to(span);
+		}
+		bl.stlist.push(Statement::Error(span));
+
+		let (next, more) = block(skipped)?;
+		bl.stlist.extend(more.stlist);
+		bl.oret = more.oret;
+		rest = next;
+	}
 }
 
 pub(crate) fn block(input: In) -> IRes<Block> {
-	dbg!(input);
-	pair(many0(ws0(stat)), opt(ws0(retstat)))
-		.map(|(stlist, oret)| Block { stlist, oret })
-		.parse(input)
+	let (rest, (stlist, closed)) = match input.state.dialect() {
+		Dialect::Lua51 => lua51_statements(input)?,
+		_ => {
+			// Everything up to the end of the block has to be a statement
+			let statement = preceded(not(block_follow), recover_stat);
+			many0(statement)
+				.map(|stlist| (stlist, false))
+				.parse(input)?
+		}
+	};
+	let (rest, oret) = match closed {
+		true => (rest, None),
+		false => opt(retstat)(rest)?,
+	};
+	let span = consumed(input, rest);
+	Ok((rest, Block { stlist, oret, span }))
+}
+
+/// The statements of a Lua 5.1 block, where `;` can only follow a statement
+/// and `break` has to be the last one, like `return`.
+///
+/// Also returns whether the block was closed by a `break`.
+fn lua51_statements(input: In) -> IRes<(Vec<Statement>, bool)> {
+	let mut stlist = Vec::new();
+	let mut rest = input;
+	while block_follow(rest).is_err() {
+		if let Ok((next, tok)) = token(TokenKind::Semicolon)(rest) {
+			if matches!(stlist.last(), None | Some(Statement::End(_))) {
+				rest.state.require(rest, Feature::EmptyStatement)?;
+			}
+			stlist.push(Statement::End(tok.span));
+			rest = next;
+			continue;
+		}
+
+		let (next, stat) = recover_stat(rest)?;
+		let closed = matches!(stat, Statement::Break(_));
+		stlist.push(stat);
+		rest = next;
+		if closed {
+			if let Ok((next, tok)) = token(TokenKind::Semicolon)(rest) {
+				stlist.push(Statement::End(tok.span));
+				rest = next;
+			}
+			return Ok((rest, (stlist, true)));
+		}
+	}
+	Ok((rest, (stlist, false)))
+}
+
+/// Parses a statement. When recovering from errors, a statement that can't be
+/// parsed is skipped up to the start of the next one.
+fn recover_stat(input: In) -> IRes<Statement> {
+	match unexpected(stat)(input) {
+		Err(nom::Err::Error(e) | nom::Err::Failure(e)) => {
+			let at = e.input;
+			input.state.recover(e)?;
+			let rest = synchronize(input, at);
+			Ok((rest, Statement::Error(consumed(input, rest))))
+		}
+		res => res,
+	}
 }
 
 pub(crate) fn retstat(input: In) -> IRes<ReturnStatement> {
-	dbg!(input);
-	delimited(
-		wstag(KRETURN),
-		opt(list(wschar(COMMA), exp)),
-		opt(wschar(SEMICOLON)),
-	)
-	.map(|oelist| ReturnStatement { oelist })
+	spanned(keyword(
+		TokenKind::Return,
+		terminated(retvalues, opt(token(TokenKind::Semicolon))),
+	))
+	.map(|((_, oelist), span)| ReturnStatement { oelist, span })
 	.parse(input)
 }
+
+/// The values of a return statement, which may be left out at the end of a block.
+fn retvalues(input: In) -> IRes<Option<ExpressionList>> {
+	match input.first().map(|tok| &tok.kind) {
+		Some(TokenKind::Semicolon) => Ok((input, None)),
+		_ if block_follow(input).is_ok() => Ok((input, None)),
+		_ => explist.map(Some).parse(input),
+	}
+}
@@ -0,0 +1,55 @@
+//! # Loading Files
+//!
+//! Script files can start with things that aren't Lua: a UTF-8 byte order
+//! mark, and a `#!` line so they can be run directly. Like `luaL_loadfilex`,
+//! these are skipped before the chunk is parsed.
+
+/// The first bytes of a precompiled chunk (`LUA_SIGNATURE`).
+pub const SIGNATURE: &[u8] = b"\x1bLua";
+
+const BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// The length of the header before the Lua source in the file `src`: a byte
+/// order mark, then a first line starting with `#` (`skipcomment`).
+///
+/// The line break after a `#` line isn't part of the header, so the lines of
+/// the chunk keep their numbers.
+pub fn header_len(src: &[u8]) -> usize {
+	let start = bom_len(src);
+	if src.get(start) != Some(&b'#') {
+		return start;
+	}
+
+	src[start..]
+		.iter()
+		.position(|&c| c == b'\n' || c == b'\r')
+		.map_or(src.len(), |end| start + end)
+}
+
+/// The line break ending the `#` line of the header of the file `src`, as it
+/// is. It's empty if there's no such line, or it ends the file.
+pub fn header_break(src: &[u8]) -> &[u8] {
+	let start = header_len(src);
+	if start == bom_len(src) {
+		return &[];
+	}
+	let rest = &src[start..];
+	[&b"\r\n"[..], b"\n", b"\r"]
+		.into_iter()
+		.find(|eol| rest.starts_with(eol))
+		.unwrap_or_default()
+}
+
+/// Whether the file `src` holds a precompiled chunk, after its header.
+pub fn is_binary(src: &[u8]) -> bool {
+	let start = header_len(src) + header_break(src).len();
+	src.get(start) == Some(&SIGNATURE[0])
+}
+
+fn bom_len(src: &[u8]) -> usize {
+	if src.starts_with(BOM) {
+		BOM.len()
+	} else {
+		0
+	}
+}
@@ -0,0 +1,120 @@
+//! # Parser Options
+//!
+//! [ParseOptions] configures a parse started with [parse_chunk] and friends.
+//! The defaults parse Lua 5.4 like `luac` would, stopping at the first error.
+//!
+//! [parse_chunk]: crate::parse_chunk
+
+use luna_ast::{comment::Comments, span::Span};
+
+use crate::{
+	dialect::Dialect,
+	error::Error,
+	input::{State, MAX_DEPTH},
+	lex::Lexer,
+};
+
+#[derive(Clone, Debug)]
+pub struct ParseOptions {
+	chunk_name: String,
+	dialect: Dialect,
+	comments: bool,
+	max_depth: usize,
+	recovering: bool,
+	unicode_names: bool,
+}
+
+impl Default for ParseOptions {
+	fn default() -> Self {
+		Self {
+			chunk_name: "?".to_owned(),
+			dialect: Dialect::default(),
+			comments: false,
+			max_depth: MAX_DEPTH,
+			recovering: false,
+			unicode_names: false,
+		}
+	}
+}
+
+impl ParseOptions {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Names the chunk in rendered errors, like a file name (`?` by default).
+	pub fn chunk_name(mut self, name: impl Into<String>) -> Self {
+		self.chunk_name = name.into();
+		self
+	}
+
+	/// Parses the syntax of `dialect` instead of Lua 5.4.
+	pub fn dialect(mut self, dialect: Dialect) -> Self {
+		self.dialect = dialect;
+		self
+	}
+
+	/// Collects the comments of the source, for tools like formatters.
+	pub fn keep_comments(mut self) -> Self {
+		self.comments = true;
+		self
+	}
+
+	/// Limits how deeply statements and expressions can nest ([MAX_DEPTH] by default).
+	pub fn max_depth(mut self, max_depth: usize) -> Self {
+		self.max_depth = max_depth;
+		self
+	}
+
+	/// Recovers from syntax errors, returning a partial tree along with every
+	/// error instead of stopping at the first.
+	pub fn recovering(mut self) -> Self {
+		self.recovering = true;
+		self
+	}
+
+	/// Accepts UTF-8 letters in names. See [Lexer::unicode_names].
+	pub fn unicode_names(mut self) -> Self {
+		self.unicode_names = true;
+		self
+	}
+
+	/// Renders `error` in `src` as a diagnostic for this chunk. See [Error::render].
+	pub fn render(&self, error: &Error<Span>, src: &[u8]) -> String {
+		error.render(&self.chunk_name, src)
+	}
+
+	/// `comments`, if they're being kept.
+	pub(crate) fn kept_comments(&self, comments: Comments) -> Comments {
+		match self.comments {
+			true => comments,
+			false => Comments::default(),
+		}
+	}
+
+	/// A lexer for `src` with these options.
+	pub(crate) fn lexer<'a>(&self, src: &'a [u8]) -> Lexer<'a> {
+		let mut lexer = Lexer::new(src);
+		if self.comments {
+			lexer = lexer.keep_comments();
+		}
+		if self.recovering {
+			lexer = lexer.recovering();
+		}
+		if self.unicode_names {
+			lexer = lexer.unicode_names();
+		}
+		lexer.dialect(self.dialect)
+	}
+
+	/// The state of a parse with these options.
+	pub(crate) fn state(&self) -> State {
+		let state = match self.recovering {
+			true => State::recovering(),
+			false => State::default(),
+		};
+		state
+			.with_max_depth(self.max_depth)
+			.with_dialect(self.dialect)
+	}
+}
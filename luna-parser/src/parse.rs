@@ -8,4 +8,3 @@ pub mod function;
 pub mod operation;
 pub mod statement;
 pub mod table;
-pub mod variable;
@@ -1,56 +1,119 @@
-use luna_ast::affix::{Affix, Call, Index, Prefix, Suffix};
+use luna_ast::{
+	affix::{Affix, Call, Index, Prefix, Suffix},
+	expression::{Expression, Value},
+	function::{Arguments, FunctionCall},
+	span::{Span, Spanned},
+	variable::Variable,
+};
 use nom::{
 	branch::alt,
-	combinator::opt,
-	multi::many1,
+	combinator::cut,
+	multi::many0,
 	sequence::{pair, preceded},
 	Parser,
 };
 
 use crate::{
-	combinator::{bracket, paren, wschar},
+	combinator::{bracket, paren, spanned, token},
+	error::{Error, ErrorKind},
+	lex::TokenKind,
 	parse::{expression::exp, function::args},
-	terminal::{
-		name,
-		string::{COLON, DOT},
-	},
+	terminal::name,
 	IRes, In,
 };
 
 pub fn prefix(input: In) -> IRes<Prefix> {
-	dbg!(input);
 	alt((
-		paren(exp).map(Prefix::ParenExpression),
+		spanned(paren(exp)).map(|(ex, span)| Prefix::ParenExpression(ex, span)),
 		name.map(Prefix::Name),
 	))
 	.parse(input)
 }
 
 pub fn index(input: In) -> IRes<Index> {
-	dbg!(input);
 	alt((
-		bracket(exp).map(Index::Expression),
-		preceded(wschar(DOT), name).map(Index::Member),
+		spanned(bracket(exp)).map(|(ex, span)| Index::Expression(ex, span)),
+		preceded(token(TokenKind::Dot), cut(name)).map(Index::Member),
 	))
 	.parse(input)
 }
 
+/// The arguments of a method call, which can't be left out (`funcargs`).
+fn method_args(input: In) -> IRes<Arguments> {
+	cut(args)(input).map_err(|e| {
+		e.map(|e| match e.kind {
+			ErrorKind::Expected if e.input.len() == input.len() => {
+				Error::new(e.input, ErrorKind::FunctionArgumentsExpected)
+			}
+			_ => e,
+		})
+	})
+}
+
 pub fn call(input: In) -> IRes<Call> {
-	dbg!(input);
-	pair(opt(preceded(wschar(COLON), name)), args)
-		.map(|(oname, argu)| Call { oname, argu })
-		.parse(input)
+	let method = preceded(token(TokenKind::Colon), cut(name));
+	spanned(alt((
+		pair(method.map(Some), method_args),
+		args.map(|argu| (None, argu)),
+	)))
+	.map(|((oname, argu), span)| Call { oname, argu, span })
+	.parse(input)
 }
 
 pub fn suffix(input: In) -> IRes<Suffix> {
-	dbg!(input);
 	alt((call.map(Suffix::Call), index.map(Suffix::Index))).parse(input)
 }
 
 pub fn affix(input: In) -> IRes<Affix> {
-	dbg!(input);
-	prefix
-		.and(many1(suffix))
-		.map(|(pfix, suflist)| Affix { pfix, suflist })
+	spanned(prefix.and(many0(suffix)))
+		.map(|((pfix, suflist), span)| Affix {
+			pfix,
+			suflist,
+			span,
+		})
+		.parse(input)
+}
+
+/// What a prefix and its suffixes turned out to be.
+pub enum Suffixed {
+	Variable(Variable),
+	FunctionCall(FunctionCall),
+	/// A parenthesized expression without any suffixes
+	ParenExpression(Expression, Span),
+}
+
+impl From<Suffixed> for Value {
+	fn from(value: Suffixed) -> Self {
+		match value {
+			Suffixed::Variable(var) => Value::Variable(var),
+			Suffixed::FunctionCall(call) => Value::FunctionCall(call),
+			Suffixed::ParenExpression(ex, span) => Value::ParenExpression(ex, span),
+		}
+	}
+}
+
+/// Parses a prefix with any number of suffixes (`suffixedexp`), and works
+/// out whether it's a variable or a call from its last suffix.
+pub fn suffixedexp(input: In) -> IRes<Suffixed> {
+	affix
+		.map(|mut affix| match affix.suflist.last() {
+			Some(Suffix::Call(_)) => {
+				let span = affix.span;
+				let Some(Suffix::Call(call)) = affix.suflist.pop() else {
+					unreachable!()
+				};
+				// The affix no longer covers the call
+				affix.span = match affix.suflist.last() {
+					Some(suffix) => affix.pfix.span().to(suffix.span()),
+					None => affix.pfix.span(),
+				};
+				Suffixed::FunctionCall(FunctionCall { affix, call, span })
+			}
+			Some(Suffix::Index(_)) => Suffixed::Variable(Variable::Affixed(affix)),
+			None => match affix.pfix {
+				Prefix::Name(name) => Suffixed::Variable(Variable::Name(name)),
+				Prefix::ParenExpression(ex, span) => Suffixed::ParenExpression(ex, span),
+			},
+		})
 		.parse(input)
 }
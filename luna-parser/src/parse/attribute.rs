@@ -1,30 +1,29 @@
 use luna_ast::attribute::{Attribute, AttributeName};
-use nom::{combinator::opt, sequence::delimited, Parser};
+use nom::{combinator::opt, sequence::terminated, Parser};
 
 use crate::{
-	combinator::{list, wschar},
-	terminal::{
-		name,
-		string::{COMMA, GREATER, LESS},
-	},
+	combinator::{keyword, list, requires, spanned, token},
+	dialect::Feature,
+	lex::TokenKind,
+	terminal::name,
 	IRes, In,
 };
 
 fn attrib_name(input: In) -> IRes<AttributeName> {
-	dbg!(input);
-	name.and(attrib)
-		.map(|(name, attr)| AttributeName { name, attr })
+	spanned(name.and(attrib))
+		.map(|((name, attr), span)| AttributeName { name, attr, span })
 		.parse(input)
 }
 
 pub fn attrib(input: In) -> IRes<Attribute> {
-	dbg!(input);
-	delimited(wschar(LESS), opt(name), wschar(GREATER))
-		.map(Attribute)
-		.parse(input)
+	spanned(opt(requires(
+		Feature::Attributes,
+		keyword(TokenKind::Less, terminated(name, token(TokenKind::Greater))),
+	)))
+	.map(|(attr, span)| Attribute(attr.map(|(_, name)| name), span))
+	.parse(input)
 }
 
 pub fn attnamelist(input: In) -> IRes<Vec<AttributeName>> {
-	dbg!(input);
-	list(wschar(COMMA), attrib_name)(input)
+	list(token(TokenKind::Comma), attrib_name)(input)
 }
@@ -1,80 +1,104 @@
-use luna_ast::expression::{BinaryExpression, Expression, UnaryExpression, Value};
-use nom::{
-	branch::alt,
-	combinator::{self, opt},
-	sequence::pair,
-	Parser,
+use luna_ast::{
+	expression::{BinaryExpression, Expression, UnaryExpression, Value},
+	operation::UNARY_PRIORITY,
+	span::{Span, Spanned},
 };
+use nom::{branch::alt, combinator::cut, Parser};
 
 use crate::{
-	combinator::{list, paren, wschar, wstag},
+	combinator::{list, nested, spanned, token, unexpected},
+	lex::TokenKind,
 	parse::function::functiondef,
-	terminal::{
-		keyword::{KFALSE, KNIL, KTRUE},
-		literal_string, numeral,
-		string::COMMA,
-	},
+	terminal::{literal_string, numeral},
 	IRes, In,
 };
 
 use super::{
-	function::{functioncall, varargs},
+	affix::suffixedexp,
+	function::varargs,
 	operation::{binop, unop},
 	table::tableconstructor,
-	variable::var,
 };
 
-fn binary_op(input: In) -> IRes<BinaryExpression> {
-	dbg!(input);
-	use BinaryExpression::*;
+/// Parses an expression made of operators that bind tighter than `limit`.
+///
+/// This is the precedence climbing loop of `subexpr` in `lparser.c`:
+/// `(unop subexpr | value) {binop subexpr}`
+fn subexpr(input: In, limit: u8) -> IRes<Expression> {
+	nested(input, || {
+		let (mut input, mut left) = match spanned(unop).parse(input) {
+			Ok((rest, (op, opspan))) => {
+				let (rest, ex) = cut(|i| subexpr(i, UNARY_PRIORITY))(rest)?;
+				let span = opspan.to(ex.span());
+				let ex = Box::new(ex);
+				(rest, UnaryExpression { op, ex, span }.into())
+			}
+			Err(nom::Err::Error(_)) => match unexpected(value).parse(input) {
+				Ok((rest, val)) => (rest, val.into()),
+				// Leave a hole in the tree and let the caller deal with the token
+				Err(nom::Err::Error(e)) if input.state.is_recovering() => {
+					input.state.recover(e)?;
+					(input, Expression::Error(Span::empty(input.span().start)))
+				}
+				Err(e) => return Err(e),
+			},
+			Err(e) => return Err(e),
+		};
 
-	value
-		.map(Box::new)
-		.and(opt(pair(binop, exp.map(Box::new))))
-		.map(|(left, oright)| match oright {
-			Some((op, right)) => AsExpression { left, op, right },
-			None => AsValue(left),
-		})
-		.parse(input)
-}
+		// Expand while operators have priorities higher than `limit`. Each
+		// operator makes the tree one level deeper, even when the source isn't
+		let mut levels = Vec::new();
+		loop {
+			let (rest, op) = match binop(input) {
+				Ok(res) => res,
+				Err(nom::Err::Error(_)) => break,
+				Err(e) => return Err(e),
+			};
+
+			let (lprio, rprio) = op.priority();
+			if lprio <= limit {
+				break;
+			}
+
+			levels.push(input.state.enter_level(input)?);
+			let (rest, right) = cut(|i| subexpr(i, rprio))(rest)?;
+			let span = left.span().to(right.span());
+			let (left_ex, right) = (Box::new(left), Box::new(right));
+			left = BinaryExpression {
+				left: left_ex,
+				op,
+				right,
+				span,
+			}
+			.into();
+			input = rest;
+		}
 
-fn unary_op(input: In) -> IRes<UnaryExpression> {
-	dbg!(input);
-	pair(unop, exp.map(Box::new))
-		.map(|(op, ex)| UnaryExpression { op, ex })
-		.parse(input)
+		Ok((input, left))
+	})
 }
 
 pub fn value(input: In) -> IRes<Value> {
-	dbg!(input);
 	use Value::*;
 
 	alt((
-		combinator::value(Nil, wstag(KNIL)),
-		combinator::value(False, wstag(KFALSE)),
-		combinator::value(True, wstag(KTRUE)),
-		numeral.map(Numeral),
+		token(TokenKind::Nil).map(|tok| Nil(tok.span)),
+		token(TokenKind::False).map(|tok| False(tok.span)),
+		token(TokenKind::True).map(|tok| True(tok.span)),
+		spanned(numeral).map(|(num, span)| Numeral(num, span)),
 		literal_string.map(LiteralString),
 		varargs.map(VarArgs),
 		functiondef.map(AnonFunctionDefinition),
-		var.map(Variable),
-		functioncall.map(FunctionCall),
-		paren(exp).map(ParenExpression),
 		tableconstructor.map(TableConstructor),
+		suffixedexp.map(Value::from),
 	))
 	.parse(input)
 }
 
 pub fn exp(input: In) -> IRes<Expression> {
-	dbg!(input);
-	alt((
-		binary_op.map(Expression::BinaryExpression),
-		unary_op.map(Expression::UnaryExpression),
-	))
-	.parse(input)
+	subexpr(input, 0)
 }
 
 pub fn explist(input: In) -> IRes<Vec<Expression>> {
-	dbg!(input);
-	list(wschar(COMMA), exp)(input)
+	list(token(TokenKind::Comma), exp)(input)
 }
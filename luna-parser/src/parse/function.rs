@@ -1,80 +1,70 @@
 //! # Function Structure Parsers
 
 use luna_ast::{
-	affix::Suffix,
 	expression::AnonFunctionDefinition,
-	function::{Arguments, FunctionBody, FunctionCall, FunctionName, ParameterList, VarArgs},
+	function::{Arguments, FunctionBody, FunctionName, ParameterList, VarArgs},
 };
 use nom::{
 	branch::alt,
-	combinator::{opt, value, verify},
+	combinator::{cut, opt},
 	sequence::{pair, preceded, terminated},
 	Parser,
 };
 
 use crate::{
 	block,
-	combinator::{list, paren, wschar, wstag},
-	parse::affix::affix,
-	terminal::{
-		keyword::{KEND, KFUNCTION},
-		literal_string, name, namelist,
-		string::{COLON, COMMA, DOT, TRIPLEDOT},
-	},
+	combinator::{closing, consumed, list, opt_unless, paren, spanned, token},
+	lex::{Token, TokenKind},
+	terminal::{literal_string, name},
 	IRes, In,
 };
 
 use super::{expression::explist, table::tableconstructor};
 
 pub(super) fn varargs(input: In) -> IRes<VarArgs> {
-	dbg!(input);
-	value(VarArgs, wstag(TRIPLEDOT)).parse(input)
+	token(TokenKind::TripleDot)
+		.map(|tok| VarArgs { span: tok.span })
+		.parse(input)
 }
 
 pub fn funcname(input: In) -> IRes<FunctionName> {
-	dbg!(input);
-	list(wschar(DOT), name)
-		.and(opt(preceded(wschar(COLON), name)))
-		.map(|(nlist, objname)| FunctionName { nlist, objname })
-		.parse(input)
+	spanned(
+		list(token(TokenKind::Dot), name).and(opt(preceded(token(TokenKind::Colon), cut(name)))),
+	)
+	.map(|((nlist, objname), span)| FunctionName {
+		nlist,
+		objname,
+		span,
+	})
+	.parse(input)
 }
 
-pub fn funcbody(input: In) -> IRes<FunctionBody> {
-	dbg!(input);
-	terminated(pair(paren(opt(parlist)), block), wstag(KEND))
-		.map(|(oplist, bl)| FunctionBody { oplist, bl })
+/// Parses the parameters and body of a function, up to the `end` that closes
+/// the `function` keyword `opener`.
+pub fn funcbody<'a>(opener: &'a Token) -> impl FnMut(In<'a>) -> IRes<'a, FunctionBody> {
+	move |input: In<'a>| {
+		spanned(pair(
+			paren(opt_unless(TokenKind::RParen, parlist)),
+			terminated(block, closing(opener, TokenKind::End)),
+		))
+		.map(|((oplist, bl), span)| FunctionBody { oplist, bl, span })
 		.parse(input)
+	}
 }
 
 pub fn functiondef(input: In) -> IRes<AnonFunctionDefinition> {
-	dbg!(input);
-	preceded(wstag(KFUNCTION), funcbody)
-		.map(AnonFunctionDefinition)
-		.parse(input)
-}
-
-pub fn functioncall(input: In) -> IRes<FunctionCall> {
-	dbg!(input);
-	verify(affix, |affix| {
-		matches!(affix.suflist.last().unwrap(), Suffix::Call(_))
-	})
-	.map(|mut affix| {
-		if let Suffix::Call(call) = affix.suflist.pop().unwrap() {
-			FunctionCall { affix, call }
-		} else {
-			// SAFETY: `verify` assured us that the popped element here is an `Call`
-			unreachable!()
-		}
-	})
-	.parse(input)
+	let (rest, opener) = token(TokenKind::Function)(input)?;
+	let (rest, fbody) = cut(funcbody(opener))(rest)?;
+	let span = consumed(input, rest);
+	Ok((rest, AnonFunctionDefinition { fbody, span }))
 }
 
 pub fn args(input: In) -> IRes<Arguments> {
-	dbg!(input);
 	use Arguments::*;
 
 	alt((
-		paren(opt(explist)).map(ClosedExpressionList),
+		spanned(paren(opt_unless(TokenKind::RParen, explist)))
+			.map(|(oelist, span)| ClosedExpressionList(oelist, span)),
 		tableconstructor.map(TableConstructor),
 		literal_string.map(LiteralString),
 	))
@@ -82,17 +72,24 @@ pub fn args(input: In) -> IRes<Arguments> {
 }
 
 pub fn parlist(input: In) -> IRes<ParameterList> {
-	dbg!(input);
 	use ParameterList::*;
 
-	alt((
-		namelist
-			.and(opt(preceded(wschar(COMMA), varargs)))
-			.map(|(nlist, vargs)| match vargs {
-				Some(_) => NameListWithVarArgs(nlist),
-				None => NameList(nlist),
-			}),
-		varargs.map(VarArgs),
-	))
-	.parse(input)
+	let names = |input| {
+		let (mut input, first) = name(input)?;
+		let mut nlist = vec![first];
+		loop {
+			let Ok((rest, _)) = token(TokenKind::Comma)(input) else {
+				return Ok((input, NameList(nlist)));
+			};
+			// `...` can only be the last parameter
+			let (rest, param) = cut(alt((name.map(Ok), varargs.map(Err))))(rest)?;
+			match param {
+				Ok(name) => nlist.push(name),
+				Err(vargs) => return Ok((rest, NameListWithVarArgs(nlist, vargs))),
+			}
+			input = rest;
+		}
+	};
+
+	alt((names, varargs.map(VarArgs))).parse(input)
 }
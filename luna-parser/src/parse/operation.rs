@@ -2,48 +2,55 @@ use luna_ast::operation::{BinaryOperation, UnaryOperation};
 use nom::{branch::alt, combinator::value};
 
 use crate::{
-	combinator::{wschar, wstag},
-	terminal::{keyword::*, string::*},
+	combinator::{requires, token},
+	dialect::Feature,
+	lex::TokenKind,
 	IRes, In,
 };
 
 pub fn binop(input: In) -> IRes<BinaryOperation> {
-	dbg!(input);
 	use BinaryOperation::*;
 
 	alt((
-		value(Add, wschar(PLUS)),
-		value(Subtract, wschar(MINUS)),
-		value(Multiply, wschar(STAR)),
-		value(Divide, wschar(SLASH)),
-		value(FloorDivide, wstag(DOUBLESLASH)),
-		value(Power, wschar(CARET)),
-		value(Modulo, wschar(PERCENT)),
-		value(BitwiseAnd, wschar(AMPH)),
-		value(BitwiseXor, wschar(TILDE)),
-		value(BitwiseOr, wschar(PIPE)),
-		value(BitwiseRightShift, wstag(RSHIFT)),
-		value(BitwiseLeftShift, wstag(LSHIFT)),
-		value(Concat, wstag(DOUBLEDOT)),
-		value(LessThan, wschar(LESS)),
-		value(LessEqual, wstag(LESSEQUAL)),
-		value(GreaterThan, wschar(GREATER)),
-		value(GreaterEqual, wstag(GREATEREQUAL)),
-		value(IsEqual, wstag(ISEQUAL)),
-		value(IsNotEqual, wstag(NOTEQUAL)),
-		value(And, wstag(KAND)),
-		value(Or, wstag(KOR)),
+		value(Add, token(TokenKind::Plus)),
+		value(Subtract, token(TokenKind::Minus)),
+		value(Multiply, token(TokenKind::Star)),
+		value(Divide, token(TokenKind::Slash)),
+		requires(
+			Feature::IntegerDivision,
+			value(FloorDivide, token(TokenKind::DoubleSlash)),
+		),
+		value(Power, token(TokenKind::Caret)),
+		value(Modulo, token(TokenKind::Percent)),
+		bitwise(BitwiseAnd, TokenKind::Amph),
+		bitwise(BitwiseXor, TokenKind::Tilde),
+		bitwise(BitwiseOr, TokenKind::Pipe),
+		bitwise(BitwiseRightShift, TokenKind::RShift),
+		bitwise(BitwiseLeftShift, TokenKind::LShift),
+		value(Concat, token(TokenKind::DoubleDot)),
+		value(LessThan, token(TokenKind::Less)),
+		value(LessEqual, token(TokenKind::LessEqual)),
+		value(GreaterThan, token(TokenKind::Greater)),
+		value(GreaterEqual, token(TokenKind::GreaterEqual)),
+		value(IsEqual, token(TokenKind::IsEqual)),
+		value(IsNotEqual, token(TokenKind::NotEqual)),
+		value(And, token(TokenKind::And)),
+		value(Or, token(TokenKind::Or)),
 	))(input)
 }
 
 pub fn unop(input: In) -> IRes<UnaryOperation> {
-	dbg!(input);
 	use UnaryOperation::*;
 
 	alt((
-		value(Not, wschar(MINUS)),
-		value(Negate, wstag(KNOT)),
-		value(Length, wschar(POUND)),
-		value(BitwiseNot, wschar(TILDE)),
+		value(Negate, token(TokenKind::Minus)),
+		value(Not, token(TokenKind::Not)),
+		value(Length, token(TokenKind::Pound)),
+		bitwise(BitwiseNot, TokenKind::Tilde),
 	))(input)
 }
+
+/// A bitwise operator, which needs Lua 5.3.
+fn bitwise<'a, O: Clone>(op: O, kind: TokenKind) -> impl FnMut(In<'a>) -> IRes<'a, O> {
+	requires(Feature::BitwiseOperators, value(op, token(kind)))
+}
@@ -1,177 +1,261 @@
 use luna_ast::{
+	expression::{Expression, ExpressionList},
 	statement::{
 		Assignment, ForExpression, ForList, IfBlock, IfTree, Label, LocalDefinitionWithAttribute,
 		LocalFunctionDefinition, NamedFunctionDefinition, RepeatUntil, Statement, While,
 	},
+	terminal::{Name, NameList},
 	Block,
 };
 use nom::{
 	branch::alt,
-	combinator::{opt, value},
+	combinator::{cut, opt, verify},
 	multi::many0,
-	sequence::{delimited, pair, preceded, separated_pair, terminated, tuple},
-	Parser,
+	sequence::{pair, preceded, separated_pair, terminated, tuple},
+	Err, Parser,
 };
 
 use crate::{
 	block,
-	combinator::{assign, wschar, wstag},
-	terminal::{
-		keyword::{
-			KBREAK, KDO, KELSE, KELSEIF, KEND, KFOR, KFUNCTION, KGOTO, KIF, KIN, KLOCAL, KREPEAT,
-			KTHEN, KUNTIL, KWHILE,
-		},
-		name, namelist,
-		string::{COMMA, DOUBLECOLON, EQUALS, SEMICOLON},
-	},
+	combinator::{closing, consumed, enclosed, keyword, nested, requires, spanned, token},
+	dialect::Feature,
+	error::{Error, ErrorKind},
+	lex::TokenKind,
+	terminal::{keyword::KGOTO, name, namelist},
 	IRes, In,
 };
 
 use super::{
+	affix::{suffixedexp, Suffixed},
 	attribute::attnamelist,
 	expression::{exp, explist},
-	function::{funcbody, funcname, functioncall},
-	variable::varlist,
+	function::{funcbody, funcname},
 };
 
 pub fn label(input: In) -> IRes<Label> {
-	dbg!(input);
-	delimited(wstag(DOUBLECOLON), name, wstag(DOUBLECOLON))
-		.map(Label)
-		.parse(input)
+	spanned(requires(
+		Feature::Labels,
+		keyword(
+			TokenKind::DoubleColon,
+			terminated(name, token(TokenKind::DoubleColon)),
+		),
+	))
+	.map(|((_, name), span)| Label(name, span))
+	.parse(input)
 }
 
-fn if_block(keyword: In) -> impl FnMut(In) -> IRes<IfBlock> + '_ {
+fn if_block(kind: TokenKind) -> impl FnMut(In) -> IRes<IfBlock> {
 	move |input: In| {
-		preceded(wstag(keyword), separated_pair(exp, wstag(KTHEN), block))
-			.map(|(cond, bl)| IfBlock { cond, bl })
-			.parse(input)
+		spanned(keyword(
+			kind.clone(),
+			separated_pair(exp, token(TokenKind::Then), block),
+		))
+		.map(|((_, (cond, bl)), span)| IfBlock { cond, bl, span })
+		.parse(input)
 	}
 }
 
 fn if_tree(input: In) -> IRes<IfTree> {
-	dbg!(input);
-	terminated(
-		tuple((
-			if_block(KIF),
-			many0(if_block(KELSEIF)),
-			opt(preceded(wstag(KELSE), block)),
-		)),
-		wstag(KEND),
-	)
-	.map(|(initial, elseifs, otherwise)| IfTree { initial, elseifs, otherwise })
-	.parse(input)
+	let (rest, initial) = if_block(TokenKind::If)(input)?;
+	let (rest, elseifs) = many0(if_block(TokenKind::ElseIf))(rest)?;
+	let (rest, otherwise) = opt(preceded(token(TokenKind::Else), block))(rest)?;
+	let (rest, _) = closing(&input.tokens()[0], TokenKind::End)(rest)?;
+
+	let span = consumed(input, rest);
+	Ok((
+		rest,
+		IfTree {
+			initial,
+			elseifs,
+			otherwise,
+			span,
+		},
+	))
+}
+
+/// The part of a `for` loop between `for` and `do`.
+enum ForHead {
+	Numeric(Name, (Expression, Expression, Option<Expression>)),
+	Generic(NameList, ExpressionList),
 }
 
-fn for_exp(input: In) -> IRes<ForExpression> {
-	dbg!(input);
-	let parse_exp = tuple((
-		exp,
-		preceded(wschar(COMMA), exp),
-		opt(preceded(wschar(COMMA), exp)),
-	));
-	let lhs = preceded(wstag(KFOR), name);
-	let rhs = pair(
-		terminated(parse_exp, wstag(KDO)),
-		terminated(block, wstag(KEND)),
-	);
-	assign(lhs, rhs)
-		.map(|(name, ((start, stop, step), bl))| ForExpression {
+fn for_stat(input: In) -> IRes<Statement> {
+	let numeric = pair(
+		name,
+		preceded(
+			token(TokenKind::Equals),
+			cut(tuple((
+				exp,
+				preceded(token(TokenKind::Comma), exp),
+				opt(preceded(token(TokenKind::Comma), cut(exp))),
+			))),
+		),
+	)
+	.map(|(name, range)| ForHead::Numeric(name, range));
+	let generic = pair(namelist, preceded(token(TokenKind::In), cut(explist)))
+		.map(|(nlist, elist)| ForHead::Generic(nlist, elist));
+
+	let (rest, (opener, (head, bl))) = keyword(
+		TokenKind::For,
+		pair(
+			alt((numeric, generic)),
+			preceded(token(TokenKind::Do), block),
+		),
+	)(input)?;
+	let (rest, _) = closing(opener, TokenKind::End)(rest)?;
+
+	let span = consumed(input, rest);
+	let stat = match head {
+		ForHead::Numeric(name, (start, stop, step)) => ForExpression {
 			name,
 			range: start..=stop,
 			step,
 			bl,
-		})
-		.parse(input)
-}
-
-fn for_list(input: In) -> IRes<ForList> {
-	dbg!(input);
-	tuple((
-		preceded(wstag(KFOR), namelist),
-		delimited(wstag(KIN), explist, wstag(KDO)),
-		terminated(block, wstag(KEND)),
-	))
-	.map(|(nlist, elist, bl)| ForList { nlist, elist, bl })
-	.parse(input)
+			span,
+		}
+		.into(),
+		ForHead::Generic(nlist, elist) => ForList {
+			nlist,
+			elist,
+			bl,
+			span,
+		}
+		.into(),
+	};
+	Ok((rest, stat))
 }
 
 fn doblk(input: In) -> IRes<Block> {
-	delimited(wstag(KWHILE), block, wstag(KEND)).parse(input)
+	enclosed(TokenKind::Do, block, TokenKind::End).parse(input)
 }
 
 fn whileblk(input: In) -> IRes<While> {
-	dbg!(input);
-	delimited(
-		wstag(KWHILE),
-		separated_pair(exp, wstag(KDO), block),
-		wstag(KEND),
-	)
-	.map(|(cond, bl)| While { cond, bl })
+	spanned(enclosed(
+		TokenKind::While,
+		separated_pair(exp, token(TokenKind::Do), block),
+		TokenKind::End,
+	))
+	.map(|((cond, bl), span)| While { cond, bl, span })
 	.parse(input)
 }
 
 fn repeat_until(input: In) -> IRes<RepeatUntil> {
-	dbg!(input);
-	preceded(wstag(KREPEAT), separated_pair(block, wstag(KUNTIL), exp))
-		.map(|(bl, cond)| RepeatUntil { cond, bl })
-		.parse(input)
+	spanned(pair(
+		enclosed(TokenKind::Repeat, block, TokenKind::Until),
+		cut(exp),
+	))
+	.map(|((bl, cond), span)| RepeatUntil { cond, bl, span })
+	.parse(input)
 }
 
-fn assignment(input: In) -> IRes<Assignment> {
-	dbg!(input);
-	assign(varlist, explist)
-		.map(|(vlist, elist)| Assignment { vlist, elist })
-		.parse(input)
+fn named_functiondef(input: In) -> IRes<NamedFunctionDefinition> {
+	let (rest, opener) = token(TokenKind::Function)(input)?;
+	let (rest, (fname, fbody)) = cut(pair(funcname, funcbody(opener)))(rest)?;
+	let span = consumed(input, rest);
+	Ok((rest, NamedFunctionDefinition { fname, fbody, span }))
 }
 
-fn named_functiondef(input: In) -> IRes<NamedFunctionDefinition> {
-	dbg!(input);
-	pair(preceded(wstag(KFUNCTION), funcname), funcbody)
-		.map(|(fname, fbody)| NamedFunctionDefinition { fname, fbody })
-		.parse(input)
+fn local_func_def<'a>(local: In<'a>) -> impl FnMut(In<'a>) -> IRes<'a, Statement> {
+	move |input: In<'a>| {
+		let (rest, opener) = token(TokenKind::Function)(input)?;
+		let (rest, (name, fbody)) = cut(pair(name, funcbody(opener)))(rest)?;
+		let span = consumed(local, rest);
+		Ok((rest, LocalFunctionDefinition { name, fbody, span }.into()))
+	}
 }
 
-fn local_func_def(input: In) -> IRes<LocalFunctionDefinition> {
-	dbg!(input);
-	preceded(
-		wstag(KLOCAL),
-		pair(preceded(wstag(KFUNCTION), name), funcbody),
-	)
-	.map(|(name, fbody)| LocalFunctionDefinition { name, fbody })
+fn local_def_attr<'a>(local: In<'a>) -> impl FnMut(In<'a>) -> IRes<'a, Statement> {
+	move |input: In<'a>| {
+		let (rest, atlist) = attnamelist(input)?;
+		let (rest, oelist) = opt(keyword(TokenKind::Equals, explist))(rest)?;
+		let oelist = oelist.map(|(_, elist)| elist);
+		let span = consumed(local, rest);
+		Ok((
+			rest,
+			LocalDefinitionWithAttribute {
+				atlist,
+				oelist,
+				span,
+			}
+			.into(),
+		))
+	}
+}
+
+fn local_stat(input: In) -> IRes<Statement> {
+	let (rest, _) = token(TokenKind::Local)(input)?;
+	match rest.first().map(|tok| &tok.kind) {
+		Some(TokenKind::Function) => cut(local_func_def(input))(rest),
+		_ => cut(local_def_attr(input))(rest),
+	}
+}
+
+/// A `goto` in Lua 5.1, where it's a name. It can't be followed by another
+/// name in valid code, so it's reported as needing Lua 5.2.
+fn name_goto(input: In) -> IRes<Statement> {
+	spanned(requires(
+		Feature::Goto,
+		pair(verify(name, |word: &Name| word.value == KGOTO), name),
+	))
+	.map(|((_, label), span)| Statement::Goto(label, span))
 	.parse(input)
 }
 
-fn local_def_attr(input: In) -> IRes<LocalDefinitionWithAttribute> {
-	dbg!(input);
-	preceded(
-		wstag(KLOCAL),
-		pair(attnamelist, opt(preceded(wschar(EQUALS), explist))),
+/// A statement that starts with an expression: an assignment or a call (`exprstat`).
+fn exprstat(input: In) -> IRes<Statement> {
+	let syntax_error = |at| Err::Failure(Error::new(at, ErrorKind::SyntaxError));
+	let (mut rest, first) = suffixedexp(input)?;
+
+	let first = match first {
+		Suffixed::FunctionCall(call) if !starts_assignment(rest) => {
+			return Ok((rest, Statement::FunctionCall(call)));
+		}
+		Suffixed::Variable(var) if starts_assignment(rest) => var,
+		_ => return Err(syntax_error(rest)),
+	};
+
+	let mut vlist = vec![first];
+	while let Ok((next, _)) = token(TokenKind::Comma)(rest) {
+		let (next, var) = cut(suffixedexp)(next)?;
+		match var {
+			Suffixed::Variable(var) => vlist.push(var),
+			_ => return Err(syntax_error(next)),
+		}
+		rest = next;
+	}
+
+	let (rest, (_, elist)) = cut(keyword(TokenKind::Equals, explist))(rest)?;
+	let span = consumed(input, rest);
+	Ok((rest, Assignment { vlist, elist, span }.into()))
+}
+
+/// Whether the next token continues an assignment.
+fn starts_assignment(input: In) -> bool {
+	matches!(
+		input.first().map(|tok| &tok.kind),
+		Some(TokenKind::Equals | TokenKind::Comma)
 	)
-	.map(|(atlist, oelist)| LocalDefinitionWithAttribute { atlist, oelist })
-	.parse(input)
 }
 
 pub fn stat(input: In) -> IRes<Statement> {
-	dbg!(input);
 	use Statement::*;
 
-	alt((
-		value(Statement::End, wschar(SEMICOLON)),
-		assignment.map(Assignment),
-		functioncall.map(FunctionCall),
-		label.map(Label),
-		value(Break, wstag(KBREAK)),
-		preceded(wstag(KGOTO), name).map(Goto),
-		doblk.map(Box::new).map(Do),
-		whileblk.map(While),
-		repeat_until.map(RepeatUntil),
-		if_tree.map(IfTree),
-		for_exp.map(ForExpression),
-		for_list.map(ForList),
-		named_functiondef.map(FunctionDefinition),
-		local_func_def.map(LocalFunctionDefinition),
-		local_def_attr.map(LocalDefinitionWithAttribute),
-	))
-	.parse(input)
+	nested(input, || {
+		alt((
+			token(TokenKind::Semicolon).map(|tok| Statement::End(tok.span)),
+			if_tree.map(IfTree),
+			whileblk.map(While),
+			spanned(doblk).map(|(bl, span)| Do(Box::new(bl), span)),
+			for_stat,
+			repeat_until.map(RepeatUntil),
+			named_functiondef.map(FunctionDefinition),
+			local_stat,
+			label.map(Label),
+			token(TokenKind::Break).map(|tok| Break(tok.span)),
+			spanned(keyword(TokenKind::Goto, name)).map(|((_, name), span)| Goto(name, span)),
+			name_goto,
+			exprstat,
+		))
+		.parse(input)
+	})
 }
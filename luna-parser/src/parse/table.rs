@@ -1,50 +1,52 @@
 use luna_ast::table::{BracketField, Field, FieldList, NameField, TableConstructor};
-use nom::{
-	branch::alt,
-	combinator::{opt, recognize},
-	sequence::terminated,
-	Parser,
-};
+use nom::{branch::alt, combinator::cut, sequence::separated_pair, Parser};
 
 use crate::{
-	combinator::{assign, braces, bracket, list, wschar},
-	terminal::{
-		name,
-		string::{COMMA, SEMICOLON},
-	},
+	combinator::{assign, braces, bracket, opt_unless, spanned, token},
+	lex::{Token, TokenKind},
+	terminal::name,
 	IRes, In,
 };
 
 use super::expression::exp;
 
 pub fn tableconstructor(input: In) -> IRes<TableConstructor> {
-	dbg!(input);
-	braces(opt(fieldlist))
-		.map(|oflist| TableConstructor { oflist })
+	spanned(braces(opt_unless(TokenKind::RBrace, fieldlist)))
+		.map(|(oflist, span)| TableConstructor { oflist, span })
 		.parse(input)
 }
 
 fn bracket_field(input: In) -> IRes<BracketField> {
-	dbg!(input);
-	assign(bracket(exp), exp)
-		.map(|(tabexp, val)| BracketField { tabexp, val })
+	spanned(assign(bracket(exp), cut(exp)))
+		.map(|((tabexp, val), span)| BracketField { tabexp, val, span })
 		.parse(input)
 }
 
 fn name_field(input: In) -> IRes<NameField> {
-	dbg!(input);
-	assign(name, exp)
-		.map(|(tabname, val)| NameField { tabname, val })
+	spanned(separated_pair(name, token(TokenKind::Equals), cut(exp)))
+		.map(|((tabname, val), span)| NameField { tabname, val, span })
 		.parse(input)
 }
 
 fn fieldlist(input: In) -> IRes<FieldList> {
-	dbg!(input);
-	terminated(list(fieldsep, field), opt(fieldsep))(input)
+	let (mut input, first) = field(input)?;
+	let mut flist = vec![first];
+
+	// The last field may be followed by a separator
+	while let Ok((rest, _)) = fieldsep(input) {
+		input = rest;
+		if matches!(rest.first(), Some(tok) if tok.kind == TokenKind::RBrace) {
+			break;
+		}
+		let (rest, field) = cut(field)(rest)?;
+		flist.push(field);
+		input = rest;
+	}
+
+	Ok((input, flist))
 }
 
 pub fn field(input: In) -> IRes<Field> {
-	dbg!(input);
 	alt((
 		bracket_field.map(Field::from),
 		name_field.map(Field::from),
@@ -53,7 +55,8 @@ pub fn field(input: In) -> IRes<Field> {
 	.parse(input)
 }
 
-pub fn fieldsep(input: In) -> IRes<In> {
-	dbg!(input);
-	recognize(wschar(COMMA).or(wschar(SEMICOLON))).parse(input)
+pub fn fieldsep(input: In<'_>) -> IRes<'_, &Token> {
+	token(TokenKind::Comma)
+		.or(token(TokenKind::Semicolon))
+		.parse(input)
 }
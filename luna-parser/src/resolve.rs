@@ -0,0 +1,651 @@
+//! # Scope Resolution
+//!
+//! The compiler has to know what each name refers to: a local in a register,
+//! a local of an enclosing function captured as an upvalue, or a global, which
+//! is a field of whichever `_ENV` is visible. [resolve] works this out for a
+//! whole chunk the way `singlevar` in `lparser.c` does, and returns a
+//! [Resolution] to look it up in, along with the scopes, functions and locals
+//! it found.
+//!
+//! Nodes are looked up by identity rather than by span, so trees that were
+//! built or rewritten, where spans repeat, resolve as well as parsed ones.
+
+use std::{collections::HashMap, marker::PhantomData};
+
+pub use luna_ast::fold::Constant;
+use luna_ast::{
+	affix::{Affix, Call, Index, Prefix, Suffix},
+	attribute::AttributeNameList,
+	expression::{Expression, ExpressionList, Value},
+	fold::fold_expression,
+	function::{Arguments, FunctionBody, ParameterList},
+	span::Span,
+	statement::Statement,
+	table::{Field, TableConstructor},
+	terminal::Name,
+	variable::Variable,
+	Block, Chunk,
+};
+
+/// Resolves every name in `chunk`.
+pub fn resolve(chunk: &Chunk) -> Resolution<'_> {
+	let mut resolver = Resolver::default();
+	resolver.main(&chunk.0);
+	resolver.res
+}
+
+/// The address of `node`, which identifies it in the tree being resolved.
+fn key<T>(node: &T) -> usize {
+	node as *const T as usize
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct LocalId(pub usize);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ScopeId(pub usize);
+
+/// A function of the chunk. The main chunk is always `FunctionId(0)`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FunctionId(pub usize);
+
+/// What kind of variable a local is (`VDKREG`, `RDKCONST`, `RDKTOCLOSE` and `RDKCTC`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LocalKind {
+	Regular,
+	/// A `<const>` local that still needs a register
+	Const,
+	/// A `<close>` local
+	ToClose,
+	/// A `<const>` local initialized with a constant, which never gets a register
+	CompileTimeConstant,
+}
+
+/// A local variable, including parameters and loop variables (`Vardesc`).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Local {
+	pub name: String,
+	pub kind: LocalKind,
+	/// Where the local is declared. An implicit `self` has the span of its method.
+	pub span: Span,
+	pub scope: ScopeId,
+	pub function: FunctionId,
+	/// The register holding the local (`ridx`), unless it's a compile-time constant
+	pub register: Option<usize>,
+	/// The value of a compile-time constant
+	pub constant: Option<Constant>,
+	/// Whether a nested function captures the local as an upvalue
+	pub captured: bool,
+}
+
+/// An upvalue of a function (`Upvaldesc`).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Upvalue {
+	pub name: String,
+	/// Whether the upvalue is a local of the enclosing function, rather than
+	/// one of its upvalues
+	pub instack: bool,
+	/// The register of the local, or the index of the upvalue, in the
+	/// enclosing function
+	pub index: usize,
+	pub kind: LocalKind,
+	/// The local captured, or `None` for the `_ENV` of the main chunk
+	pub local: Option<LocalId>,
+}
+
+/// A block, or the variables of a `for` loop around its body.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Scope {
+	pub parent: Option<ScopeId>,
+	pub function: FunctionId,
+	pub span: Span,
+	/// The locals declared in the scope, in order
+	pub locals: Vec<LocalId>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Function {
+	pub parent: Option<FunctionId>,
+	/// The function body, or the whole chunk
+	pub span: Span,
+	/// The scope of the parameters and the body
+	pub scope: ScopeId,
+	/// The number of named parameters, including an implicit `self`
+	pub params: usize,
+	pub vararg: bool,
+	/// The upvalues, in the order they were first used
+	pub upvalues: Vec<Upvalue>,
+}
+
+/// The `_ENV` a global is found in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Env {
+	Local(LocalId),
+	Upvalue(usize),
+}
+
+/// What a name refers to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Binding {
+	/// A local of the function the name is in. Compile-time constants are
+	/// always referred to this way, since they're never captured.
+	Local(LocalId),
+	/// The upvalue at this index of the function the name is in
+	Upvalue(usize),
+	/// A field of `_ENV`
+	Global(Env),
+}
+
+/// The scopes of a chunk, and the binding of each name in it.
+///
+/// Nodes are looked up by their address, so only the nodes of the chunk that
+/// was resolved have answers. It's borrowed for `'a` to keep it where it is;
+/// a clone of it has none.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Resolution<'a> {
+	locals: Vec<Local>,
+	scopes: Vec<Scope>,
+	functions: Vec<Function>,
+	/// The binding of each name, with the span of the name
+	bindings: HashMap<usize, (Span, Binding)>,
+	/// The scope of each block
+	blocks: HashMap<usize, ScopeId>,
+	/// The function of each body
+	bodies: HashMap<usize, FunctionId>,
+	chunk: PhantomData<&'a Chunk>,
+}
+
+impl Resolution<'_> {
+	/// What `name` refers to, if it's a variable. Declarations are bindings of
+	/// the locals they declare; field and label names have none.
+	pub fn binding(&self, name: &Name) -> Option<Binding> {
+		self.bindings.get(&key(name)).map(|&(_, binding)| binding)
+	}
+
+	/// Every binding, with the span of its name.
+	pub fn bindings(&self) -> impl Iterator<Item = (Span, Binding)> + '_ {
+		self.bindings.values().copied()
+	}
+
+	pub fn local(&self, id: LocalId) -> &Local {
+		&self.locals[id.0]
+	}
+
+	pub fn scope(&self, id: ScopeId) -> &Scope {
+		&self.scopes[id.0]
+	}
+
+	pub fn function(&self, id: FunctionId) -> &Function {
+		&self.functions[id.0]
+	}
+
+	/// All locals, in the order they were declared.
+	pub fn locals(&self) -> &[Local] {
+		&self.locals
+	}
+
+	/// All scopes. Each comes after its parent.
+	pub fn scopes(&self) -> &[Scope] {
+		&self.scopes
+	}
+
+	/// All functions. Each comes after the one it's nested in.
+	pub fn functions(&self) -> &[Function] {
+		&self.functions
+	}
+
+	/// The scope of `block`.
+	pub fn block_scope(&self, block: &Block) -> Option<ScopeId> {
+		self.blocks.get(&key(block)).copied()
+	}
+
+	/// The function defined by `body`.
+	pub fn body_function(&self, body: &FunctionBody) -> Option<FunctionId> {
+		self.bodies.get(&key(body)).copied()
+	}
+}
+
+/// A function being resolved (`FuncState`).
+struct FuncState {
+	id: FunctionId,
+	/// The active locals, innermost last (`actvar`). `None` is the internal
+	/// state of a `for` loop.
+	actvar: Vec<Option<LocalId>>,
+	/// The number of registers the active locals take (`reglevel`)
+	nregs: usize,
+}
+
+#[derive(Default)]
+struct Resolver<'a> {
+	res: Resolution<'a>,
+	/// The function being resolved, after the ones it's nested in
+	funcs: Vec<FuncState>,
+	/// The scope names are declared in, after the ones it's nested in, with
+	/// the number of locals active in its function outside it
+	scopes: Vec<(ScopeId, usize)>,
+}
+
+impl Resolver<'_> {
+	fn fs(&mut self) -> &mut FuncState {
+		self.funcs.last_mut().expect("always inside a function")
+	}
+
+	fn main(&mut self, bl: &Block) {
+		// The main chunk is a vararg function with `_ENV` as its only upvalue
+		let upvalue = Upvalue {
+			name: "_ENV".to_owned(),
+			instack: true,
+			index: 0,
+			kind: LocalKind::Regular,
+			local: None,
+		};
+		self.function(bl.span, None, &[], true, vec![upvalue], bl);
+	}
+
+	/// Resolves a function, returning its id. A method gets an implicit
+	/// `self` parameter, declared at the span of the method's name.
+	fn function(
+		&mut self, span: Span, method: Option<Span>, params: &[Name], vararg: bool,
+		upvalues: Vec<Upvalue>, bl: &Block,
+	) -> FunctionId {
+		let id = FunctionId(self.res.functions.len());
+		let parent = self.funcs.last().map(|fs| fs.id);
+		let scope = ScopeId(self.res.scopes.len());
+		self.res.functions.push(Function {
+			parent,
+			span,
+			scope,
+			params: params.len() + usize::from(method.is_some()),
+			vararg,
+			upvalues,
+		});
+		self.funcs.push(FuncState {
+			id,
+			actvar: Vec::new(),
+			nregs: 0,
+		});
+
+		self.enter_scope(bl.span);
+		self.res.blocks.insert(key(bl), scope);
+		if let Some(span) = method {
+			// There's no name to bind; the method's name is a field
+			self.declare("self", span, LocalKind::Regular, None);
+		}
+		for param in params {
+			self.declare_name(param, LocalKind::Regular, None);
+		}
+		self.statements(bl);
+		self.leave_scope();
+		self.funcs.pop();
+		id
+	}
+
+	fn funcbody(&mut self, fbody: &FunctionBody, method: Option<Span>) {
+		let (params, vararg) = match &fbody.oplist {
+			None => (&[][..], false),
+			Some(ParameterList::NameList(nlist)) => (&nlist[..], false),
+			Some(ParameterList::NameListWithVarArgs(nlist, _)) => (&nlist[..], true),
+			Some(ParameterList::VarArgs(_)) => (&[][..], true),
+		};
+		let id = self.function(fbody.span, method, params, vararg, Vec::new(), &fbody.bl);
+		self.res.bodies.insert(key(fbody), id);
+	}
+
+	fn enter_scope(&mut self, span: Span) {
+		let id = ScopeId(self.res.scopes.len());
+		let function = self.fs().id;
+		self.res.scopes.push(Scope {
+			parent: self.scopes.last().map(|(id, _)| *id),
+			function,
+			span,
+			locals: Vec::new(),
+		});
+		let nactvar = self.fs().actvar.len();
+		self.scopes.push((id, nactvar));
+	}
+
+	fn leave_scope(&mut self) {
+		let (_, nactvar) = self.scopes.pop().expect("scope was entered");
+		let fs = self.funcs.last_mut().expect("always inside a function");
+		fs.actvar.truncate(nactvar);
+		let locals = &self.res.locals;
+		fs.nregs = fs
+			.actvar
+			.iter()
+			.filter(|active| active.map_or(true, |id| locals[id.0].register.is_some()))
+			.count();
+	}
+
+	fn scope(&self) -> ScopeId {
+		self.scopes.last().expect("always inside a scope").0
+	}
+
+	fn block(&mut self, bl: &Block) {
+		self.enter_scope(bl.span);
+		self.res.blocks.insert(key(bl), self.scope());
+		self.statements(bl);
+		self.leave_scope();
+	}
+
+	fn statements(&mut self, bl: &Block) {
+		for stat in &bl.stlist {
+			self.statement(stat);
+		}
+		if let Some(ret) = &bl.oret {
+			self.exps(ret.oelist.iter().flatten());
+		}
+	}
+
+	/// Declares a local in the innermost scope (`new_localvar` and `adjustlocalvars`).
+	fn declare(
+		&mut self, name: &str, span: Span, kind: LocalKind, constant: Option<Constant>,
+	) -> LocalId {
+		let id = LocalId(self.res.locals.len());
+		let scope = self.scope();
+		let fs = self.fs();
+		let function = fs.id;
+		let register = match kind {
+			LocalKind::CompileTimeConstant => None,
+			_ => {
+				fs.nregs += 1;
+				Some(fs.nregs - 1)
+			}
+		};
+		fs.actvar.push(Some(id));
+		self.res.locals.push(Local {
+			name: name.to_owned(),
+			kind,
+			span,
+			scope,
+			function,
+			register,
+			constant,
+			captured: false,
+		});
+		self.res.scopes[scope.0].locals.push(id);
+		id
+	}
+
+	/// Declares the local `name`, which is bound to it.
+	fn declare_name(&mut self, name: &Name, kind: LocalKind, constant: Option<Constant>) {
+		let id = self.declare(&name.value, name.span, kind, constant);
+		self.res
+			.bindings
+			.insert(key(name), (name.span, Binding::Local(id)));
+	}
+
+	/// Reserves the registers of a `for` loop's internal state.
+	fn for_state(&mut self, n: usize) {
+		let fs = self.fs();
+		fs.actvar.extend(std::iter::repeat(None).take(n));
+		fs.nregs += n;
+	}
+
+	fn statement(&mut self, stat: &Statement) {
+		match stat {
+			Statement::End(_)
+			| Statement::Break(_)
+			| Statement::Goto(..)
+			| Statement::Label(_)
+			| Statement::Error(_) => {}
+			Statement::Assignment(assign) => {
+				for var in &assign.vlist {
+					self.variable(var);
+				}
+				self.exps(&assign.elist);
+			}
+			Statement::FunctionCall(call) => {
+				self.affix(&call.affix);
+				self.call(&call.call);
+			}
+			Statement::Do(bl, _) => self.block(bl),
+			Statement::While(w) => {
+				self.exp(&w.cond);
+				self.block(&w.bl);
+			}
+			Statement::RepeatUntil(r) => {
+				self.enter_scope(r.bl.span);
+				self.res.blocks.insert(key(&r.bl), self.scope());
+				self.statements(&r.bl);
+				// The condition can see the body's locals
+				self.exp(&r.cond);
+				self.leave_scope();
+			}
+			Statement::IfTree(tree) => {
+				for ifb in std::iter::once(&tree.initial).chain(&tree.elseifs) {
+					self.exp(&ifb.cond);
+					self.block(&ifb.bl);
+				}
+				if let Some(bl) = &tree.otherwise {
+					self.block(bl);
+				}
+			}
+			Statement::ForExpression(f) => {
+				self.exp(f.range.start());
+				self.exp(f.range.end());
+				self.exps(&f.step);
+				self.enter_scope(f.span);
+				self.for_state(3);
+				self.declare_name(&f.name, LocalKind::Regular, None);
+				self.block(&f.bl);
+				self.leave_scope();
+			}
+			Statement::ForList(f) => {
+				self.exps(&f.elist);
+				self.enter_scope(f.span);
+				self.for_state(4);
+				for name in &f.nlist {
+					self.declare_name(name, LocalKind::Regular, None);
+				}
+				self.block(&f.bl);
+				self.leave_scope();
+			}
+			Statement::FunctionDefinition(def) => {
+				// Only the first name is a variable; the rest are fields
+				self.singlevar(&def.fname.nlist[0]);
+				let method = def.fname.objname.as_ref().map(|name| name.span);
+				self.funcbody(&def.fbody, method);
+			}
+			Statement::LocalFunctionDefinition(def) => {
+				// The function can call itself
+				self.declare_name(&def.name, LocalKind::Regular, None);
+				self.funcbody(&def.fbody, None);
+			}
+			Statement::LocalDefinitionWithAttribute(def) => {
+				// The values can't see the new locals
+				self.exps(def.oelist.iter().flatten());
+				self.locals(&def.atlist, def.oelist.as_ref());
+			}
+		}
+	}
+
+	/// Declares the locals of a `local` statement (`localstat`).
+	fn locals(&mut self, atlist: &AttributeNameList, oelist: Option<&ExpressionList>) {
+		let nexps = oelist.map_or(0, |elist| elist.len());
+		for (i, attname) in atlist.iter().enumerate() {
+			let mut kind = match attname.attr.0.as_ref().map(|n| n.value.as_str()) {
+				Some("const") => LocalKind::Const,
+				Some("close") => LocalKind::ToClose,
+				// Anything else is an error for `validate`
+				_ => LocalKind::Regular,
+			};
+			// Only the last local can be a compile-time constant, and only when
+			// it gets a value of its own
+			let mut constant = None;
+			if kind == LocalKind::Const && i + 1 == atlist.len() && nexps == atlist.len() {
+				constant = oelist
+					.and_then(|elist| elist.last())
+					.and_then(|ex| self.constant(ex));
+				if constant.is_some() {
+					kind = LocalKind::CompileTimeConstant;
+				}
+			}
+			self.declare_name(&attname.name, kind, constant);
+		}
+	}
+
+	/// The value of `ex`, if it's known while compiling (`luaK_exp2const`).
+	fn constant(&self, ex: &Expression) -> Option<Constant> {
+		let Expression::Value(val) = ex else {
+			// Operations on literals are folded first (`constfolding`)
+			let mut ex = ex.clone();
+			fold_expression(&mut ex);
+			return Constant::of(&ex);
+		};
+		match &**val {
+			Value::ParenExpression(ex, _) => self.constant(ex),
+			Value::Variable(Variable::Name(name)) => match self.res.binding(name)? {
+				Binding::Local(id) => self.res.local(id).constant.clone(),
+				_ => None,
+			},
+			_ => Constant::of(ex),
+		}
+	}
+
+	/// Resolves a reference to `name` (`singlevar`).
+	fn singlevar(&mut self, name: &Name) {
+		let level = self.funcs.len() - 1;
+		let binding = match self.find(level, &name.value) {
+			Some(binding) => binding,
+			None => {
+				// A global, in whichever `_ENV` is visible
+				let env = match self.find(level, "_ENV") {
+					Some(Binding::Local(id)) => Env::Local(id),
+					Some(Binding::Upvalue(index)) => Env::Upvalue(index),
+					_ => unreachable!("the main chunk always has _ENV"),
+				};
+				Binding::Global(env)
+			}
+		};
+		self.res.bindings.insert(key(name), (name.span, binding));
+	}
+
+	/// Finds `name` in the function at `level` or the ones it's nested in,
+	/// capturing it as an upvalue on the way (`singlevaraux`).
+	fn find(&mut self, level: usize, name: &str) -> Option<Binding> {
+		let fs = &self.funcs[level];
+		let mut active = fs.actvar.iter().rev().flatten();
+		if let Some(&id) = active.find(|id| self.res.locals[id.0].name == name) {
+			return Some(Binding::Local(id));
+		}
+
+		let function = &self.res.functions[fs.id.0];
+		if let Some(index) = function.upvalues.iter().position(|up| up.name == name) {
+			return Some(Binding::Upvalue(index));
+		}
+		if level == 0 {
+			return None;
+		}
+
+		let upvalue = match self.find(level - 1, name)? {
+			Binding::Local(id) => {
+				let local = &mut self.res.locals[id.0];
+				if local.kind == LocalKind::CompileTimeConstant {
+					return Some(Binding::Local(id));
+				}
+				local.captured = true;
+				Upvalue {
+					name: name.to_owned(),
+					instack: true,
+					index: local.register.expect("only constants have no register"),
+					kind: local.kind,
+					local: Some(id),
+				}
+			}
+			Binding::Upvalue(index) => {
+				let parent = &self.res.functions[self.funcs[level - 1].id.0];
+				let up = &parent.upvalues[index];
+				Upvalue {
+					name: name.to_owned(),
+					instack: false,
+					index,
+					kind: up.kind,
+					local: up.local,
+				}
+			}
+			Binding::Global(_) => unreachable!("find doesn't return globals"),
+		};
+		let upvalues = &mut self.res.functions[self.funcs[level].id.0].upvalues;
+		upvalues.push(upvalue);
+		Some(Binding::Upvalue(upvalues.len() - 1))
+	}
+
+	fn variable(&mut self, var: &Variable) {
+		match var {
+			Variable::Name(name) => self.singlevar(name),
+			Variable::Affixed(affix) => self.affix(affix),
+		}
+	}
+
+	fn exps<'e>(&mut self, elist: impl IntoIterator<Item = &'e Expression>) {
+		for ex in elist {
+			self.exp(ex);
+		}
+	}
+
+	fn exp(&mut self, ex: &Expression) {
+		match ex {
+			Expression::Value(val) => self.value(val),
+			Expression::BinaryExpression(ex) => {
+				self.exp(&ex.left);
+				self.exp(&ex.right);
+			}
+			Expression::UnaryExpression(ex) => self.exp(&ex.ex),
+			Expression::Error(_) => {}
+		}
+	}
+
+	fn value(&mut self, val: &Value) {
+		match val {
+			Value::Nil(_)
+			| Value::False(_)
+			| Value::True(_)
+			| Value::Numeral(..)
+			| Value::LiteralString(_)
+			| Value::VarArgs(_) => {}
+			Value::AnonFunctionDefinition(def) => self.funcbody(&def.fbody, None),
+			Value::Variable(var) => self.variable(var),
+			Value::FunctionCall(call) => {
+				self.affix(&call.affix);
+				self.call(&call.call);
+			}
+			Value::ParenExpression(ex, _) => self.exp(ex),
+			Value::TableConstructor(table) => self.table(table),
+		}
+	}
+
+	fn table(&mut self, table: &TableConstructor) {
+		for field in table.oflist.iter().flatten() {
+			match field {
+				Field::BracketField(field) => {
+					self.exp(&field.tabexp);
+					self.exp(&field.val);
+				}
+				Field::NameField(field) => self.exp(&field.val),
+				Field::Expression(ex) => self.exp(ex),
+			}
+		}
+	}
+
+	fn affix(&mut self, affix: &Affix) {
+		match &affix.pfix {
+			Prefix::Name(name) => self.singlevar(name),
+			Prefix::ParenExpression(ex, _) => self.exp(ex),
+		}
+		for suffix in &affix.suflist {
+			match suffix {
+				Suffix::Call(call) => self.call(call),
+				Suffix::Index(Index::Expression(ex, _)) => self.exp(ex),
+				Suffix::Index(Index::Member(_)) => {}
+			}
+		}
+	}
+
+	fn call(&mut self, call: &Call) {
+		match &call.argu {
+			Arguments::ClosedExpressionList(elist, _) => self.exps(elist.iter().flatten()),
+			Arguments::TableConstructor(table) => self.table(table),
+			Arguments::LiteralString(_) => {}
+		}
+	}
+}
@@ -1,3 +1,5 @@
+use crate::dialect::Dialect;
+
 macro_rules! keyword {
 	($($name:ident = $val:literal),*) => {
 		$(pub const $name: &str = $val;)*
@@ -16,10 +18,28 @@ keyword! {
 }
 
 pub fn is_keyword(input: &str) -> bool {
+	matches!(
+		input,
+		KAND | KBREAK
+			| KDO | KELSE
+			| KELSEIF | KEND
+			| KFALSE | KFOR
+			| KFUNCTION
+			| KGOTO | KIF
+			| KIN | KLOCAL
+			| KNIL | KNOT
+			| KOR | KREPEAT
+			| KRETURN | KTHEN
+			| KTRUE | KUNTIL
+			| KWHILE
+	)
+}
+
+/// The first version of Lua where `input` is a keyword. Only `goto` is newer
+/// than Lua 5.1; any other keyword is as old as the dialects go.
+pub fn keyword_since(input: &str) -> Dialect {
 	match input {
-		KAND | KBREAK | KDO | KELSE | KELSEIF | KEND | KFALSE | KFOR | KFUNCTION | KGOTO | KIF
-		| KIN | KLOCAL | KNIL | KNOT | KOR | KREPEAT | KRETURN | KTHEN | KTRUE | KUNTIL
-		| KWHILE => true,
-		_ => false,
+		KGOTO => Dialect::Lua52,
+		_ => Dialect::Lua51,
 	}
 }
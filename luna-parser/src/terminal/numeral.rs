@@ -0,0 +1,197 @@
+//! # Numeric Conversion
+//!
+//! Converts strings to [Numeral]s following `luaO_str2num` in `lobject.c`.
+//! The lexer uses this for numerals in source code, and it accepts exactly
+//! what `tonumber` accepts for strings (surrounding whitespace included).
+
+use luna_ast::terminal::Numeral;
+
+/// Maximum number of significant digits to read in a hexadecimal float
+/// (`MAXSIGDIG`). Further digits only adjust the exponent.
+const MAXSIGDIG: usize = 30;
+
+/// Whitespace as defined by `lisspace`.
+fn is_space(c: u8) -> bool {
+	matches!(c, b' ' | b'\t' | b'\n' | b'\x0B' | b'\x0C' | b'\r')
+}
+
+fn trim(s: &[u8]) -> &[u8] {
+	let start = s.iter().position(|&c| !is_space(c)).unwrap_or(s.len());
+	let end = s
+		.iter()
+		.rposition(|&c| !is_space(c))
+		.map_or(start, |i| i + 1);
+	&s[start..end]
+}
+
+/// Splits an optional leading `-` (or `+`) from `s`.
+fn sign(s: &[u8]) -> (bool, &[u8]) {
+	match s.split_first() {
+		Some((b'-', rest)) => (true, rest),
+		Some((b'+', rest)) => (false, rest),
+		_ => (false, s),
+	}
+}
+
+/// Strips a leading `0x` or `0X` from `s`.
+fn hex_prefix(s: &[u8]) -> Option<&[u8]> {
+	match s {
+		[b'0', b'x' | b'X', rest @ ..] => Some(rest),
+		_ => None,
+	}
+}
+
+fn hex_value(c: u8) -> Option<u32> {
+	(c as char).to_digit(16)
+}
+
+/// Converts `s` to a number, trying an integer first and then a float.
+///
+/// Returns `None` if `s` isn't a valid numeral.
+pub fn str2num(s: &[u8]) -> Option<Numeral> {
+	str2int(s)
+		.map(Numeral::Integer)
+		.or_else(|| str2d(s).map(Numeral::Float))
+}
+
+/// Converts `s` to an integer (`l_str2int`).
+///
+/// Hexadecimal integers wrap around on overflow. Decimal integers that
+/// overflow are rejected, so they can be read as floats instead.
+pub fn str2int(s: &[u8]) -> Option<i64> {
+	let (neg, s) = sign(trim(s));
+	let mut a: u64 = 0;
+
+	if let Some(digits) = hex_prefix(s) {
+		if digits.is_empty() {
+			return None;
+		}
+		for &c in digits {
+			a = a.wrapping_mul(16).wrapping_add(hex_value(c)? as u64);
+		}
+	} else {
+		const MAXBY10: u64 = i64::MAX as u64 / 10;
+		const MAXLASTD: u64 = i64::MAX as u64 % 10;
+
+		if s.is_empty() {
+			return None;
+		}
+		for &c in s {
+			if !c.is_ascii_digit() {
+				return None;
+			}
+			let d = (c - b'0') as u64;
+			// Overflow? Accept `-9223372036854775808` as the one exception.
+			if a >= MAXBY10 && (a > MAXBY10 || d > MAXLASTD + neg as u64) {
+				return None;
+			}
+			a = a * 10 + d;
+		}
+	}
+
+	let a = a as i64;
+	Some(if neg { a.wrapping_neg() } else { a })
+}
+
+/// Converts `s` to a float (`l_str2d`).
+///
+/// Rejects `inf` and `nan`, which aren't numerals in Lua.
+pub fn str2d(s: &[u8]) -> Option<f64> {
+	if s.iter().any(|&c| c == b'n' || c == b'N') {
+		return None;
+	}
+
+	let s = trim(s);
+	if hex_prefix(sign(s).1).is_some() {
+		strx2number(s)
+	} else {
+		// `f64::from_str` takes the same decimal forms as `strtod`
+		// (`3.`, `.5`, `1e-10`)
+		std::str::from_utf8(s).ok()?.parse().ok()
+	}
+}
+
+/// Converts a hexadecimal float, like `0x1.8p3` (`lua_strx2number`).
+fn strx2number(s: &[u8]) -> Option<f64> {
+	let (neg, s) = sign(s);
+	let mut s = hex_prefix(s)?;
+
+	let mut r = 0.0f64;
+	// Exponent correction for digits after the dot and ignored digits
+	let mut e: i64 = 0;
+	let mut sigdig = 0;
+	let mut nosigdig = 0;
+	let mut hasdot = false;
+
+	while let Some((&c, rest)) = s.split_first() {
+		if c == b'.' {
+			if hasdot {
+				break;
+			}
+			hasdot = true;
+		} else if let Some(d) = hex_value(c) {
+			if sigdig == 0 && d == 0 {
+				// Leading zeros aren't significant
+				nosigdig += 1;
+			} else {
+				sigdig += 1;
+				if sigdig <= MAXSIGDIG {
+					r = r * 16.0 + d as f64;
+				} else {
+					// Too many digits; ignore, but still count for the exponent
+					e += 1;
+				}
+			}
+			if hasdot {
+				e -= 1;
+			}
+		} else {
+			break;
+		}
+		s = rest;
+	}
+
+	if nosigdig + sigdig == 0 {
+		return None;
+	}
+
+	// Each hexadecimal digit is worth four binary digits
+	e *= 4;
+
+	if let Some((b'p' | b'P', rest)) = s.split_first() {
+		let (expneg, rest) = sign(rest);
+		if !rest.first().is_some_and(u8::is_ascii_digit) {
+			return None;
+		}
+		let mut exp: i64 = 0;
+		s = rest;
+		while let Some((&c, rest)) = s.split_first() {
+			if !c.is_ascii_digit() {
+				break;
+			}
+			exp = exp.saturating_mul(10).saturating_add((c - b'0') as i64);
+			s = rest;
+		}
+		e = e.saturating_add(if expneg { -exp } else { exp });
+	}
+
+	if !s.is_empty() {
+		return None;
+	}
+
+	let r = ldexp(r, e);
+	Some(if neg { -r } else { r })
+}
+
+/// Computes `x * 2^e` without overflowing the intermediate power of two.
+fn ldexp(mut x: f64, mut e: i64) -> f64 {
+	while e > f64::MAX_EXP as i64 && x != 0.0 && x.is_finite() {
+		x *= 2f64.powi(f64::MAX_EXP - 1);
+		e -= (f64::MAX_EXP - 1) as i64;
+	}
+	while e < f64::MIN_EXP as i64 && x != 0.0 && x.is_finite() {
+		x *= 2f64.powi(f64::MIN_EXP - 1);
+		e -= (f64::MIN_EXP - 1) as i64;
+	}
+	x * 2f64.powi(e.clamp(i32::MIN as i64, i32::MAX as i64) as i32)
+}
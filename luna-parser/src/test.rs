@@ -1,2 +1,40 @@
+use crate::{
+	input::{Input, State},
+	lex::tokenize,
+	IRes, In,
+};
+
+mod api;
+mod build;
+mod code;
+mod comment;
+mod cst;
+mod depth;
+mod dialect;
+mod error;
 mod exp;
+mod fold;
 mod function;
+mod lex;
+mod load;
+mod lower;
+mod numeral;
+mod print;
+mod recovery;
+mod resolve;
+mod serde;
+mod span;
+mod string;
+mod validate;
+mod visit;
+
+/// Runs `parser` over the tokens of `src`, returning its output if it consumed
+/// everything except the end of the stream.
+pub(crate) fn parse<O>(src: &str, mut parser: impl FnMut(In) -> IRes<O>) -> Option<O> {
+	let tokens = tokenize(src.as_bytes()).ok()?;
+	let state = State::default();
+	match parser(Input::new(&tokens, &state)) {
+		Ok((rest, out)) if rest.len() == 1 => Some(out),
+		_ => None,
+	}
+}
@@ -0,0 +1,75 @@
+use luna_ast::{
+	expression::{Expression, Value},
+	statement::Statement,
+};
+
+use crate::{parse_chunk, parse_expression, parse_statement, ParseOptions};
+
+#[test]
+fn expression() {
+	let options = ParseOptions::default();
+	let parse = parse_expression(b"1 + f(x)", &options).unwrap();
+	assert!(matches!(parse.tree, Expression::BinaryExpression(_)));
+	assert!(parse.errors.is_empty());
+
+	let err = parse_expression(b"1 + 2 3", &options).unwrap_err();
+	assert_eq!(err.message(b"1 + 2 3"), "<eof> expected near '3'");
+}
+
+#[test]
+fn statement() {
+	let options = ParseOptions::default();
+	let parse = parse_statement(b"local x <const> = 1", &options).unwrap();
+	assert!(matches!(
+		parse.tree,
+		Statement::LocalDefinitionWithAttribute(_)
+	));
+
+	// Only one statement is allowed
+	assert!(parse_statement(b"f() g()", &options).is_err());
+}
+
+#[test]
+fn recovering() {
+	let options = ParseOptions::new().recovering();
+	let parse = parse_expression(b"1 + ", &options).unwrap();
+	let Expression::BinaryExpression(add) = parse.tree else {
+		panic!("expected an addition, got {:?}", parse.tree);
+	};
+	assert!(matches!(*add.right, Expression::Error(_)));
+	assert_eq!(parse.errors.len(), 1);
+
+	let parse = parse_statement(b"x = 1 y", &options).unwrap();
+	assert!(matches!(parse.tree, Statement::Assignment(_)));
+	assert_eq!(
+		parse.errors[0].message(b"x = 1 y"),
+		"<eof> expected near 'y'"
+	);
+}
+
+#[test]
+fn options() {
+	let src = b"-- note\nx = 1 + + 2";
+	let options = ParseOptions::new().chunk_name("init.lua").keep_comments();
+	let err = parse_chunk(src, &options).unwrap_err();
+	assert_eq!(
+		options.render(&err, src).lines().next(),
+		Some("init.lua:2: unexpected symbol near '+'")
+	);
+
+	let parse = parse_chunk(b"-- note\nreturn x", &options).unwrap();
+	assert_eq!(parse.comments.len(), 1);
+	assert!(parse_chunk(b"return x", &ParseOptions::default())
+		.unwrap()
+		.comments
+		.is_empty());
+
+	let src = "return località".as_bytes();
+	assert!(parse_chunk(src, &ParseOptions::default()).is_err());
+	let parse = parse_chunk(src, &ParseOptions::new().unicode_names()).unwrap();
+	let ret = parse.tree.0.oret.unwrap();
+	assert!(matches!(
+		&ret.oelist.unwrap()[0],
+		Expression::Value(val) if matches!(**val, Value::Variable(_))
+	));
+}
@@ -0,0 +1,157 @@
+use luna_ast::{
+	assert_spanless_eq,
+	build::*,
+	operation::{BinaryOperation::*, UnaryOperation::*},
+	print::{print_chunk, PrintOptions},
+	Chunk,
+};
+
+use crate::{chunk as parse, code::compile};
+
+/// Asserts that `built` is the tree of `src`, and prints back as it.
+fn check(built: Chunk, src: &str) {
+	assert_spanless_eq!(parse(src).unwrap(), built);
+	assert_eq!(print_chunk(&built, &PrintOptions::new()), src);
+}
+
+#[test]
+fn expressions() {
+	check(
+		chunk(vec![local(
+			&["x", "y"],
+			vec![
+				bin(
+					num(1),
+					Add,
+					bin(num(2), Multiply, paren(un(Negate, var("a")))),
+				),
+				bin(paren(bin(nil(), Or, boolean(false))), Concat, string("s")),
+			],
+		)]),
+		"local x, y = 1 + 2 * (-a), (nil or false) .. \"s\"\n",
+	);
+	check(
+		chunk(vec![local(
+			&["t"],
+			vec![table(vec![
+				item(float(0.5)),
+				keyed("k", varargs()),
+				bracket(
+					num(1),
+					function(&["a"], true, block_return(vec![], vec![var("a")])),
+				),
+			])],
+		)]),
+		"local t = {\n\t0.5,\n\tk = ...,\n\t[1] = function(a, ...)\n\t\treturn a\n\tend\n}\n",
+	);
+}
+
+#[test]
+fn suffixes() {
+	check(
+		chunk(vec![
+			call_statement(method(field(index(var("a"), num(1)), "b"), "m", vec![])),
+			call_statement(call(call_string(var("f"), "s"), vec![num(1)])),
+			assign(vec![field(call(var("h"), vec![]), "x")], vec![nil()]),
+		]),
+		"a[1].b:m()\nf \"s\"(1)\nh().x = nil\n",
+	);
+	// Anything else is put in parentheses
+	check(
+		chunk(vec![call_statement(method(string("s"), "upper", vec![]))]),
+		"(\"s\"):upper()\n",
+	);
+	check(
+		chunk(vec![call_statement(call_table(paren(var("g")), vec![]))]),
+		"(g) {}\n",
+	);
+}
+
+#[test]
+fn statements() {
+	check(
+		chunk(vec![
+			local_attributes(&[("c", Some("const")), ("d", None)], vec![num(1)]),
+			if_then(
+				vec![
+					(var("c"), block(vec![goto("done")])),
+					(var("d"), block(vec![])),
+				],
+				Some(block(vec![do_block(block(vec![]))])),
+			),
+			while_do(boolean(true), block(vec![break_loop()])),
+			repeat_until(block(vec![]), boolean(true)),
+			for_range("i", num(1), num(10), Some(num(2)), block(vec![])),
+			for_in(
+				&["k", "v"],
+				vec![call(var("pairs"), vec![var("t")])],
+				block(vec![]),
+			),
+			function_statement(&["a", "b"], Some("m"), &[], false, block(vec![])),
+			local_function("f", &[], true, block_return(vec![], vec![])),
+			label("done"),
+		]),
+		"local c <const>, d = 1
+if c then
+	goto done
+elseif d then else
+	do end
+end
+while true do
+	break
+end
+repeat until true
+for i = 1, 10, 2 do end
+for k, v in pairs(t) do end
+function a.b:m() end
+local function f(...)
+	return
+end
+::done::
+",
+	);
+}
+
+#[test]
+#[should_panic]
+fn assign_to_call() {
+	assign(vec![call(var("f"), vec![])], vec![nil()]);
+}
+
+#[test]
+fn compile_built() {
+	// Built trees resolve and compile like parsed ones, though all their
+	// spans are the same
+	let src = "local x = 1 \
+		local function f(a, ...) return a + x, ... end \
+		for i = 1, 2 do x = f(i) end \
+		return x";
+	let built = Chunk(block_return(
+		vec![
+			local(&["x"], vec![num(1)]),
+			local_function(
+				"f",
+				&["a"],
+				true,
+				block_return(vec![], vec![bin(var("a"), Add, var("x")), varargs()]),
+			),
+			for_range(
+				"i",
+				num(1),
+				num(2),
+				None,
+				block(vec![assign(
+					vec![var("x")],
+					vec![call(var("f"), vec![var("i")])],
+				)]),
+			),
+		],
+		vec![var("x")],
+	));
+	let parsed = parse(src).unwrap();
+	assert_spanless_eq!(parsed, built);
+	assert_eq!(
+		compile(&built, b"", "=test").unwrap(),
+		compile(&parsed, src.as_bytes(), "=test").unwrap()
+	);
+}
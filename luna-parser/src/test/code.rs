@@ -0,0 +1,371 @@
+use luna_vm::{
+	proto::{Constant, Proto},
+	OpCode,
+};
+
+use crate::{chunk, code::compile, error::ErrorKind, MAX_DEPTH};
+
+fn proto(src: &str) -> Proto {
+	compile(&chunk(src).unwrap(), src.as_bytes(), "=test").unwrap()
+}
+
+/// The instructions of `proto`, without the `VARARGPREP` and `RETURN` every
+/// main function has.
+fn code(proto: &Proto) -> Vec<String> {
+	let code = &proto.code[1..proto.code.len() - 1];
+	code.iter().map(|inst| format!("{inst:?}")).collect()
+}
+
+#[test]
+fn loads() {
+	let p = proto("local a, b, c, d, e = 1, 2.5, 100000, 'x', 3.0");
+	assert_eq!(
+		code(&p),
+		[
+			"LOADI     0 1",
+			"LOADK     1 0",
+			"LOADK     2 1",
+			"LOADK     3 2",
+			"LOADF     4 3"
+		]
+	);
+	assert_eq!(
+		p.constants,
+		[
+			Constant::Float(2.5),
+			Constant::Integer(100000),
+			Constant::String(b"x".to_vec())
+		]
+	);
+}
+
+#[test]
+fn arithmetic() {
+	let p = proto("local a; a = a + 1; a = a * 2.0; a = 2 - a; return 1 + 2, 7 // 0");
+	assert_eq!(
+		code(&p),
+		[
+			"LOADNIL   0 0 0",
+			// Small integers and constants are operands
+			"ADDI      0 0 128",
+			"MMBINI    0 128 6",
+			"MULK      0 0 0",
+			"MMBINK    0 0 8",
+			// Only the second operand of a subtraction can be
+			"LOADI     1 2",
+			"SUB       0 1 0",
+			"MMBIN     1 0 7",
+			// Folded, but not when it raises an error
+			"LOADI     1 3",
+			"LOADI     2 7",
+			"IDIVK     2 2 1",
+			"MMBINK    2 1 12",
+			"RETURN    1 3 1",
+		]
+	);
+}
+
+#[test]
+fn jumps() {
+	let p = proto("local a, b; if a < 1 and b then a = 1 else b = 2 end");
+	assert_eq!(
+		code(&p),
+		[
+			"LOADNIL   0 1 0",
+			"LTI       0 128 0",
+			"JMP       4",
+			"TEST      1 0 0",
+			"JMP       2",
+			"LOADI     0 1",
+			"JMP       1",
+			"LOADI     1 2",
+		]
+	);
+
+	// The jump back goes straight to the end, like the `break` it lands on
+	let p = proto("while true do break end");
+	assert_eq!(code(&p), ["JMP       1", "JMP       0"]);
+}
+
+#[test]
+fn values() {
+	// `and` and `or` copy the value they keep, unless it's never the result
+	let p = proto("local a, b; local c = a and b or 1");
+	assert_eq!(
+		code(&p),
+		[
+			"LOADNIL   0 1 0",
+			"TEST      0 0 0",
+			"JMP       2",
+			"TESTSET   2 1 0k",
+			"JMP       1",
+			"LOADI     2 1",
+		]
+	);
+}
+
+#[test]
+fn tables() {
+	let p = proto("local t = {1, 2, x = 3, ...}");
+	assert_eq!(
+		code(&p),
+		[
+			"NEWTABLE  0 1 2",
+			"EXTRAARG  0",
+			"LOADI     1 1",
+			"LOADI     2 2",
+			"SETFIELD  0 0 1k",
+			"VARARG    3 0 0",
+			"SETLIST   0 0 0",
+		]
+	);
+
+	// List items are stored 50 at a time
+	let items = vec!["0"; 60].join(", ");
+	let p = proto(&format!("local t = {{{items}}}"));
+	let setlists: Vec<_> = p
+		.code
+		.iter()
+		.filter(|inst| format!("{inst:?}").starts_with("SETLIST"))
+		.collect();
+	assert_eq!(
+		setlists
+			.iter()
+			.map(|inst| format!("{inst:?}"))
+			.collect::<Vec<_>>(),
+		["SETLIST   0 50 0", "SETLIST   0 10 50"]
+	);
+}
+
+#[test]
+fn closures() {
+	let p = proto(
+		"local x
+		local function f(a, ...) return x, a, ... end
+		return f(...)",
+	);
+	assert_eq!(
+		code(&p),
+		[
+			"LOADNIL   0 0 0",
+			"CLOSURE   1 0",
+			"MOVE      2 1 0",
+			"VARARG    3 0 0",
+			// Captured locals are closed on the way out
+			"TAILCALL  2 0 1k",
+			"RETURN    2 0 1k",
+		]
+	);
+
+	let f = &p.protos[0];
+	assert_eq!(
+		(f.numparams, f.is_vararg, f.linedefined, f.lastlinedefined),
+		(1, true, 2, 2)
+	);
+	assert_eq!(f.upvalues[0].name.as_deref(), Some("x"));
+	assert!(f.upvalues[0].instack);
+	assert_eq!(
+		f.code
+			.iter()
+			.map(|inst| format!("{inst:?}"))
+			.collect::<Vec<_>>(),
+		[
+			"VARARGPREP 1 0 0",
+			"GETUPVAL  1 0 0",
+			"MOVE      2 0 0",
+			"VARARG    3 0 0",
+			"RETURN    1 0 2",
+			"RETURN    1 1 2",
+		]
+	);
+}
+
+#[test]
+fn numeric_for() {
+	let p = proto("local s = 0; for i = 1, 10, 2 do s = s + i end");
+	assert_eq!(
+		code(&p),
+		[
+			"LOADI     0 0",
+			"LOADI     1 1",
+			"LOADI     2 10",
+			"LOADI     3 2",
+			"FORPREP   1 2",
+			"ADD       0 0 4",
+			"MMBIN     0 4 6",
+			"FORLOOP   1 3",
+		]
+	);
+}
+
+#[test]
+fn generic_for() {
+	// The closing value of the loop is closed on the way out
+	let p = proto("local t; for k, v in next, t do t = v end");
+	assert_eq!(
+		code(&p),
+		[
+			"LOADNIL   0 0 0",
+			"GETTABUP  1 0 0",
+			"MOVE      2 0 0",
+			"LOADNIL   3 1 0",
+			"TFORPREP  1 1",
+			"MOVE      0 6 0",
+			"TFORCALL  1 0 2",
+			"TFORLOOP  1 3",
+			"CLOSE     1 0 0",
+		]
+	);
+}
+
+#[test]
+fn breaks() {
+	let p = proto("for i = 1, 2 do break end");
+	assert_eq!(
+		code(&p),
+		[
+			"LOADI     0 1",
+			"LOADI     1 2",
+			"LOADI     2 1",
+			"FORPREP   0 1",
+			"JMP       1",
+			"FORLOOP   0 2",
+		]
+	);
+
+	let p = proto("local t; for k, v in next, t do break end");
+	assert_eq!(
+		code(&p),
+		[
+			"LOADNIL   0 0 0",
+			"GETTABUP  1 0 0",
+			"MOVE      2 0 0",
+			"LOADNIL   3 1 0",
+			"TFORPREP  1 1",
+			"JMP       2",
+			"TFORCALL  1 0 2",
+			"TFORLOOP  1 3",
+			"CLOSE     1 0 0",
+		]
+	);
+
+	// Leaving the scope of a to-be-closed local closes it at the label
+	let p = proto("while true do local x <close> = nil; break end");
+	assert_eq!(
+		code(&p),
+		[
+			"LOADNIL   0 0 0",
+			"TBC       0 0 0",
+			"JMP       2",
+			"CLOSE     0 0 0",
+			"JMP       -5",
+			"CLOSE     0 0 0",
+		]
+	);
+}
+
+#[test]
+fn gotos() {
+	// Jumping back out of the scope of a local closes it
+	let p = proto("::top:: local x; if x then goto top end");
+	assert_eq!(
+		code(&p),
+		[
+			"LOADNIL   0 0 0",
+			"TEST      0 0 0",
+			"JMP       2",
+			"CLOSE     0 0 0",
+			"JMP       -5",
+		]
+	);
+
+	// A label at the end of its block is outside the scope of its locals
+	let p = proto("do goto done; local y = 1 ::done:: end");
+	assert_eq!(code(&p), ["JMP       1", "LOADI     0 1"]);
+}
+
+#[test]
+fn repeat_upvalues() {
+	// Repeating closes the captured locals of the body, and so does leaving
+	let p = proto("repeat local x; local f = function() return x end until x");
+	assert_eq!(
+		code(&p),
+		[
+			"LOADNIL   0 0 0",
+			"CLOSURE   1 0",
+			"TEST      0 0 0",
+			"JMP       2",
+			"CLOSE     0 0 0",
+			"JMP       2",
+			"CLOSE     0 0 0",
+			"JMP       -8",
+		]
+	);
+}
+
+#[test]
+fn to_be_closed() {
+	let p = proto("do local x <close> = nil; local y = 1 end");
+	assert_eq!(
+		code(&p),
+		[
+			"LOADNIL   0 0 0",
+			"TBC       0 0 0",
+			"LOADI     1 1",
+			"CLOSE     0 0 0"
+		]
+	);
+
+	// A call can't be a tail call in the scope of one
+	let p = proto("local x <close> = nil; return f()");
+	assert_eq!(
+		code(&p),
+		[
+			"LOADNIL   0 0 0",
+			"TBC       0 0 0",
+			"GETTABUP  1 0 0",
+			"CALL      1 1 0",
+			"RETURN    1 0 1k",
+		]
+	);
+}
+
+#[test]
+fn long_chains() {
+	let src = format!("local x; return x{}", " + x".repeat(MAX_DEPTH - 3));
+	let p = proto(&src);
+	let adds = p
+		.code
+		.iter()
+		.filter(|inst| inst.opcode() == Some(OpCode::Add));
+	assert_eq!(adds.count(), MAX_DEPTH - 3);
+
+	// Longer ones never get here, rather than overflow the stack
+	let src = format!("local x; return x{}", " + x".repeat(100_000));
+	assert_eq!(
+		chunk(&src).unwrap_err().kind,
+		ErrorKind::TooManySyntaxLevels
+	);
+}
+
+#[test]
+fn limits() {
+	let locals = (0..201)
+		.map(|i| format!("local a{i}"))
+		.collect::<Vec<_>>()
+		.join("\n");
+	let e = compile(&chunk(&locals).unwrap(), locals.as_bytes(), "=test").unwrap_err();
+	assert_eq!(
+		e.kind,
+		ErrorKind::TooMany {
+			what: "local variables",
+			limit: 200,
+			line: 0
+		}
+	);
+
+	let values = vec!["1"; 300].join(", ");
+	let src = format!("return {values}");
+	let e = compile(&chunk(&src).unwrap(), src.as_bytes(), "=test").unwrap_err();
+	assert_eq!(e.kind, ErrorKind::TooManyRegisters);
+}
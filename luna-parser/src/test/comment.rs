@@ -0,0 +1,86 @@
+use luna_ast::{
+	comment::{Comment, CommentKind},
+	span::Span,
+	statement::Statement,
+};
+
+use crate::{
+	chunk,
+	lex::{tokenize, tokenize_with_comments, TokenKind},
+	parse_chunk, ParseOptions,
+};
+
+fn kinds(src: &str) -> Vec<TokenKind> {
+	tokenize(src.as_bytes())
+		.unwrap()
+		.into_iter()
+		.map(|tok| tok.kind)
+		.collect()
+}
+
+fn texts<'a>(comments: impl Iterator<Item = &'a Comment>) -> Vec<&'a [u8]> {
+	comments.map(|c| c.text.as_slice()).collect()
+}
+
+#[test]
+fn skipped() {
+	assert_eq!(kinds("-- nothing here"), vec![TokenKind::Eof]);
+	assert_eq!(
+		kinds("--[[ a\nb ]] -"),
+		vec![TokenKind::Minus, TokenKind::Eof]
+	);
+	assert_eq!(kinds("--[==[ ]] ]=] ]==]"), vec![TokenKind::Eof]);
+	// Not a long bracket, so the comment runs to the end of the line
+	assert_eq!(kinds("--[= x\n-"), vec![TokenKind::Minus, TokenKind::Eof]);
+	assert_eq!(kinds("--\n-"), vec![TokenKind::Minus, TokenKind::Eof]);
+	assert!(tokenize(b"--[[ unfinished").is_err());
+}
+
+#[test]
+fn collected() {
+	let (tokens, comments) = tokenize_with_comments(b"--a\nx --[=[b]=]").unwrap();
+	assert_eq!(tokens.len(), 2);
+	assert_eq!(
+		comments.iter().cloned().collect::<Vec<_>>(),
+		vec![
+			Comment {
+				kind: CommentKind::Line,
+				text: b"a".to_vec(),
+				span: Span::new(0, 3),
+			},
+			Comment {
+				kind: CommentKind::Long(1),
+				text: b"b".to_vec(),
+				span: Span::new(6, 15),
+			},
+		]
+	);
+
+	let (_, comments) = tokenize_with_comments(b"x -- a").unwrap();
+	assert_eq!(comments.len(), 1);
+	assert_eq!(tokenize_with_comments(b"x").unwrap().1.len(), 0);
+}
+
+#[test]
+fn ignored_by_parser() {
+	let src = "-- doc\nlocal x = 1 -- one\n--[[ two ]]\nprint(x)";
+	assert!(chunk(src).is_ok());
+}
+
+#[test]
+fn attached() {
+	let src = "-- the answer\n--[[ really ]]\nlocal x = 42 -- trailing\n\nprint(x)\n-- end";
+	let options = ParseOptions::new().keep_comments();
+	let parse = parse_chunk(src.as_bytes(), &options).unwrap();
+	let (chunk, comments) = (parse.tree, parse.comments);
+	let [local, print]: &[Statement; 2] = chunk.0.stlist.as_slice().try_into().unwrap();
+
+	assert_eq!(
+		texts(comments.leading(local)),
+		vec![&b" the answer"[..], b" really "]
+	);
+	assert_eq!(texts(comments.trailing(local)), vec![&b" trailing"[..]]);
+	assert!(comments.leading(print).next().is_none());
+	assert!(comments.trailing(print).next().is_none());
+	assert_eq!(comments.len(), 4);
+}
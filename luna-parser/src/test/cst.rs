@@ -0,0 +1,145 @@
+use luna_ast::{assert_spanless_eq, comment::CommentKind, terminal::Name};
+
+use crate::{
+	cst::{SyntaxElement, SyntaxKind, SyntaxNode, TriviaKind},
+	lex::TokenKind,
+	parse_chunk, parse_cst, ParseOptions,
+};
+
+const SOURCE: &str = "-- leading comment\n\
+	local  x<const> = 1 + ( 2*3 ) -- trailing\n\
+	\n\
+	--[==[\nlong\r\ncomment ]==]\n\
+	function t.a:b(...)\treturn {x ; [1]=2,y=3,} end\n\
+	if x then goto done elseif x then ; else x = -x end ::done::\n\
+	print 'hi' ;\n\
+	\t-- end of file";
+
+#[test]
+fn round_trip() {
+	let options = ParseOptions::new();
+	for src in [SOURCE, "", "  \n", "return", "x = 1 --[[ a\nb ]] y = 2\n"] {
+		let cst = parse_cst(src.as_bytes(), &options).unwrap().tree;
+		assert_eq!(cst.to_bytes(), src.as_bytes(), "{src:?}");
+	}
+
+	// Source the lexer skips is kept too
+	let src = "x = @ 1 $$ 'bad \\q escape' y\nz = 2";
+	let parse = parse_cst(src.as_bytes(), &options.recovering()).unwrap();
+	assert_eq!(parse.tree.to_bytes(), src.as_bytes());
+	assert!(!parse.errors.is_empty());
+}
+
+#[test]
+fn trivia() {
+	let cst = parse_cst(SOURCE.as_bytes(), &ParseOptions::new())
+		.unwrap()
+		.tree;
+	let tokens = cst.root().tokens();
+
+	let local = tokens[0];
+	assert_eq!(local.kind, TokenKind::Local);
+	assert_eq!(
+		local.leading[0].kind,
+		TriviaKind::Comment(CommentKind::Line)
+	);
+	assert_eq!(local.leading[1].text, b"\n");
+
+	// The trailing comment stays with the line it's on
+	let close = tokens.iter().find(|t| t.kind == TokenKind::RParen).unwrap();
+	let kinds: Vec<_> = close.trailing.iter().map(|t| t.kind).collect();
+	assert_eq!(
+		kinds,
+		[
+			TriviaKind::Whitespace,
+			TriviaKind::Comment(CommentKind::Line)
+		]
+	);
+
+	let eof = tokens.last().unwrap();
+	assert_eq!(eof.kind, TokenKind::Eof);
+	assert_eq!(eof.leading.last().unwrap().text, b"-- end of file");
+}
+
+#[test]
+fn nodes() {
+	let cst = parse_cst(
+		b"local f = function(a) return a + 1 end",
+		&ParseOptions::new(),
+	)
+	.unwrap()
+	.tree;
+	let root = cst.root();
+	assert_eq!(root.kind, SyntaxKind::Chunk);
+
+	fn kinds(node: &SyntaxNode, out: &mut Vec<SyntaxKind>) {
+		out.push(node.kind);
+		for child in &node.children {
+			if let SyntaxElement::Node(node) = child {
+				kinds(node, out);
+			}
+		}
+	}
+	let mut out = Vec::new();
+	kinds(root, &mut out);
+	assert_eq!(
+		out,
+		[
+			SyntaxKind::Chunk,
+			SyntaxKind::Block,
+			SyntaxKind::LocalDefinitionWithAttribute,
+			SyntaxKind::AttributeName,
+			SyntaxKind::AnonFunctionDefinition,
+			SyntaxKind::FunctionBody,
+			SyntaxKind::Block,
+			SyntaxKind::ReturnStatement,
+			SyntaxKind::BinaryExpression,
+		]
+	);
+	let SyntaxElement::Node(block) = &root.children[0] else {
+		panic!("expected the block");
+	};
+	assert_eq!(block.text(), b"local f = function(a) return a + 1 end");
+}
+
+#[test]
+fn to_ast() {
+	let options = ParseOptions::new().keep_comments();
+	let cst = parse_cst(SOURCE.as_bytes(), &options).unwrap().tree;
+	let derived = cst.to_ast(&options).unwrap();
+	let parsed = parse_chunk(SOURCE.as_bytes(), &options).unwrap();
+	assert_eq!(derived.tree, parsed.tree);
+	assert_eq!(derived.comments, parsed.comments);
+}
+
+#[test]
+fn edits() {
+	let options = ParseOptions::new();
+	let mut cst = parse_cst(b"local x = 1 -- one\nprint(x)", &options)
+		.unwrap()
+		.tree;
+
+	fn rename(node: &mut SyntaxNode) {
+		for child in &mut node.children {
+			match child {
+				SyntaxElement::Node(node) => rename(node),
+				SyntaxElement::Token(token) if token.text == b"x" => {
+					token.kind = TokenKind::Name(Name {
+						value: "count".to_owned(),
+						span: token.span,
+					});
+					token.text = b"count".to_vec();
+				}
+				SyntaxElement::Token(_) => {}
+			}
+		}
+	}
+	rename(cst.root_mut());
+
+	let src = b"local count = 1 -- one\nprint(count)";
+	assert_eq!(cst.to_bytes(), src);
+	assert_spanless_eq!(
+		cst.to_ast(&options).unwrap().tree,
+		parse_chunk(src, &options).unwrap().tree
+	);
+}
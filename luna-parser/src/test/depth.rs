@@ -0,0 +1,49 @@
+use crate::{chunk, parse_chunk, ParseOptions, MAX_DEPTH};
+
+/// `open` and `close` wrapped `n` times around `inner`, after `prefix`.
+fn nest(prefix: &str, open: &str, inner: &str, close: &str, n: usize) -> String {
+	format!("{prefix}{}{inner}{}", open.repeat(n), close.repeat(n))
+}
+
+fn message(src: &str) -> String {
+	chunk(src).unwrap_err().message(src.as_bytes())
+}
+
+#[test]
+fn too_deep() {
+	let too_many = "chunk has too many syntax levels";
+	assert_eq!(message(&nest("x = ", "(", "1", ")", 1000)), too_many);
+	assert_eq!(message(&nest("x = ", "{", "1", "}", 1000)), too_many);
+	assert_eq!(message(&nest("", "do ", "", " end", 1000)), too_many);
+	assert_eq!(message(&format!("x = {}1", "- ".repeat(1000))), too_many);
+	assert_eq!(message(&nest("", "f(", "1", ")", 1000)), too_many);
+	assert_eq!(
+		message(&nest("x = ", "function() return ", "1", " end", 1000)),
+		too_many
+	);
+}
+
+#[test]
+fn long_chains() {
+	// Left-associative operators nest the tree without nesting the source
+	let chain = |n: usize| format!("x = 1{}", " + 1".repeat(n));
+	assert_eq!(message(&chain(100_000)), "chunk has too many syntax levels");
+	assert!(chunk(&chain(MAX_DEPTH - 3)).is_ok());
+	assert!(chunk(&chain(MAX_DEPTH - 2)).is_err());
+}
+
+#[test]
+fn within_limit() {
+	// Each level of parentheses is one expression deeper, inside a statement
+	assert!(chunk(&nest("x = ", "(", "1", ")", MAX_DEPTH - 2)).is_ok());
+	assert!(chunk(&nest("x = ", "{", "1", "}", MAX_DEPTH - 2)).is_ok());
+	assert!(chunk(&nest("", "do ", "", " end", MAX_DEPTH)).is_ok());
+}
+
+#[test]
+fn configurable() {
+	let src = nest("x = ", "(", "1", ")", 20);
+	let parse = |max_depth| parse_chunk(src.as_bytes(), &ParseOptions::new().max_depth(max_depth));
+	assert!(parse(30).is_ok());
+	assert!(parse(10).is_err());
+}
@@ -0,0 +1,152 @@
+use luna_ast::{
+	expression::{Expression, Value},
+	statement::Statement,
+	terminal::LiteralString,
+};
+
+use crate::{parse_chunk, Dialect, ParseOptions};
+
+/// The error message for `src` in `dialect`, if it doesn't parse.
+fn error(dialect: Dialect, src: &str) -> Option<String> {
+	let options = ParseOptions::new().dialect(dialect);
+	let err = parse_chunk(src.as_bytes(), &options).err()?;
+	Some(err.message(src.as_bytes()))
+}
+
+#[test]
+fn features() {
+	let cases = [
+		(
+			"x = a // b",
+			Dialect::Lua53,
+			"integer division requires Lua 5.3 near '//'",
+		),
+		(
+			"x = a & b",
+			Dialect::Lua53,
+			"bitwise operators require Lua 5.3 near '&'",
+		),
+		(
+			"x = ~a",
+			Dialect::Lua53,
+			"bitwise operators require Lua 5.3 near '~'",
+		),
+		(
+			"x = a << 1",
+			Dialect::Lua53,
+			"bitwise operators require Lua 5.3 near '<<'",
+		),
+		(
+			"local x <const> = 1",
+			Dialect::Lua54,
+			"attributes require Lua 5.4 near '<'",
+		),
+		(
+			"::top::",
+			Dialect::Lua52,
+			"labels require Lua 5.2 near '::'",
+		),
+		(
+			"goto top",
+			Dialect::Lua52,
+			"goto requires Lua 5.2 near 'goto'",
+		),
+	];
+	for (src, since, msg) in cases {
+		for dialect in [
+			Dialect::Lua51,
+			Dialect::Lua52,
+			Dialect::Lua53,
+			Dialect::Lua54,
+		] {
+			match error(dialect, src) {
+				None => assert!(dialect >= since, "{src} parsed as {dialect}"),
+				Some(err) => {
+					assert!(dialect < since, "{src} failed as {dialect}: {err}");
+					assert_eq!(err, msg);
+				}
+			}
+		}
+	}
+}
+
+#[test]
+fn goto_name() {
+	// Only a keyword from Lua 5.2 on
+	let options = ParseOptions::new().dialect(Dialect::Lua51);
+	assert!(parse_chunk(b"goto = 1; print(goto)", &options).is_ok());
+	assert_eq!(
+		error(Dialect::Lua52, "goto = 1").unwrap(),
+		"<name> expected near '='"
+	);
+}
+
+#[test]
+fn lua51_blocks() {
+	assert_eq!(
+		error(Dialect::Lua51, "f();;").unwrap(),
+		"empty statements require Lua 5.2 near ';'"
+	);
+	assert_eq!(
+		error(Dialect::Lua51, "; f()").unwrap(),
+		"empty statements require Lua 5.2 near ';'"
+	);
+	assert_eq!(
+		error(Dialect::Lua51, "while x do break f() end").unwrap(),
+		"'end' expected near 'f'"
+	);
+	assert_eq!(
+		error(Dialect::Lua51, "repeat break return until x").unwrap(),
+		"'until' expected near 'return'"
+	);
+	assert_eq!(
+		error(Dialect::Lua51, "f(); g(); while x do break; end"),
+		None
+	);
+	assert_eq!(
+		error(Dialect::Lua52, "; f();; while x do break f() end"),
+		None
+	);
+}
+
+#[test]
+fn escapes() {
+	let string = |dialect, src: &str| {
+		let options = ParseOptions::new().dialect(dialect);
+		let chunk = parse_chunk(src.as_bytes(), &options).ok()?.tree;
+		let Some(Statement::Assignment(assign)) = chunk.0.stlist.first() else {
+			return None;
+		};
+		let Expression::Value(value) = &assign.elist[0] else {
+			return None;
+		};
+		match &**value {
+			Value::LiteralString(LiteralString { value, .. }) => Some(value.clone()),
+			_ => None,
+		}
+	};
+
+	// Lua 5.1 lets unknown escapes stand for themselves
+	assert_eq!(string(Dialect::Lua51, r#"s = "\x41\q""#).unwrap(), b"x41q");
+	assert_eq!(string(Dialect::Lua52, r#"s = "\x41\z  B""#).unwrap(), b"AB");
+	assert_eq!(string(Dialect::Lua52, r#"s = "\u{41}""#), None);
+	assert_eq!(string(Dialect::Lua53, r#"s = "\u{41}""#).unwrap(), b"A");
+}
+
+#[test]
+fn hex_floats() {
+	assert_eq!(error(Dialect::Lua51, "x = 0xff"), None);
+	assert_eq!(
+		error(Dialect::Lua51, "x = 0x1p4").unwrap(),
+		"malformed number near '0x1p'"
+	);
+	assert_eq!(
+		error(Dialect::Lua51, "x = 0x.8").unwrap(),
+		"malformed number near '0x'"
+	);
+	assert_eq!(
+		error(Dialect::Lua51, "x = 0xA.8").unwrap(),
+		"unexpected symbol near '.8'"
+	);
+	assert_eq!(error(Dialect::Lua52, "x = 0x1p4 + 0x.8 + 0xA.8"), None);
+}
@@ -0,0 +1,136 @@
+use crate::{chunk, error::EXCERPT_RADIUS};
+
+/// The Lua-style message for the error in `src`.
+fn message(src: &str) -> String {
+	chunk(src).unwrap_err().message(src.as_bytes())
+}
+
+#[test]
+fn lexer() {
+	assert_eq!(message("x = \"abc"), "unfinished string near <eof>");
+	assert_eq!(message("x = \"abc\n\""), "unfinished string near '\"abc'");
+	assert_eq!(
+		message("x = \"a\\qb\""),
+		"invalid escape sequence near '\"a\\q'"
+	);
+	assert_eq!(
+		message("x = \"\\300\""),
+		"decimal escape too large near '\"\\300\"'"
+	);
+	assert_eq!(
+		message("x = \"\\xg0\""),
+		"hexadecimal digit expected near '\"\\xg'"
+	);
+	assert_eq!(message("x = \"\\u48\""), "missing '{' near '\"\\u4'");
+	assert_eq!(message("x = \"\\u{48\""), "missing '}' near '\"\\u{48\"'");
+	assert_eq!(
+		message("x = \"\\u{80000000}\""),
+		"UTF-8 value too large near '\"\\u{80000000'"
+	);
+	assert_eq!(message("x = 3x"), "malformed number near '3x'");
+	assert_eq!(message("x = @"), "unexpected symbol near '@'");
+	assert_eq!(
+		message("x = [[\n\nabc"),
+		"unfinished long string (starting at line 1) near <eof>"
+	);
+	assert_eq!(
+		message("\n--[==[ abc ]]"),
+		"unfinished long comment (starting at line 2) near <eof>"
+	);
+}
+
+#[test]
+fn unexpected() {
+	assert_eq!(message("x = 1 +"), "unexpected symbol near <eof>");
+	assert_eq!(message("x = )"), "unexpected symbol near ')'");
+	assert_eq!(message("f(,)"), "unexpected symbol near ','");
+	assert_eq!(message("local x = 1 )"), "unexpected symbol near ')'");
+	assert_eq!(message("return -"), "unexpected symbol near <eof>");
+}
+
+#[test]
+fn syntax_error() {
+	assert_eq!(message("x"), "syntax error near <eof>");
+	assert_eq!(message("x + 1"), "syntax error near '+'");
+	assert_eq!(message("f() = 1"), "syntax error near '='");
+	assert_eq!(message("a, (b) = 1, 2"), "syntax error near '='");
+}
+
+#[test]
+fn expected() {
+	assert_eq!(message("for x do end"), "'=' or 'in' expected near 'do'");
+	assert_eq!(message("for i = 1 do end"), "',' expected near 'do'");
+	assert_eq!(message("goto 1"), "<name> expected near '1'");
+	assert_eq!(
+		message("function f(a, 1) end"),
+		"<name> or '...' expected near '1'"
+	);
+	assert_eq!(message("local x <const = 1"), "'>' expected near '='");
+	assert_eq!(message("a, b"), "'=' expected near <eof>");
+	assert_eq!(message("return 1 2"), "<eof> expected near '2'");
+	assert_eq!(message("end"), "<eof> expected near 'end'");
+	assert_eq!(message("if x end"), "'then' expected near 'end'");
+	assert_eq!(message("local 1 = 2"), "<name> expected near '1'");
+	assert_eq!(message("local"), "<name> expected near <eof>");
+	assert_eq!(message("a.b:c = 1"), "function arguments expected near '='");
+	assert_eq!(message("x = a:b"), "function arguments expected near <eof>");
+}
+
+#[test]
+fn unclosed() {
+	assert_eq!(message("while true do x()"), "'end' expected near <eof>");
+	assert_eq!(
+		message("function f()\n\treturn 1\n"),
+		"'end' expected (to close 'function' at line 1) near <eof>"
+	);
+	assert_eq!(
+		message("repeat\n\tx()\n"),
+		"'until' expected (to close 'repeat' at line 1) near <eof>"
+	);
+	assert_eq!(message("f(1 2)"), "')' expected near '2'");
+	assert_eq!(
+		message("t = {\n1, 2\nx = 1"),
+		"'}' expected (to close '{' at line 1) near 'x'"
+	);
+}
+
+#[test]
+fn expected_set() {
+	let err = chunk("for x do end").unwrap_err();
+	assert_eq!(err.input.start, 6);
+	assert_eq!(err.expected.len(), 2);
+}
+
+#[test]
+fn render() {
+	let src = "local t = {}\nt.x = = 1\n";
+	let err = chunk(src).unwrap_err();
+
+	assert_eq!(
+		err.render("input", src.as_bytes()),
+		"input:2: unexpected symbol near '='\n  |\n2 | t.x = = 1\n  |       ^"
+	);
+}
+
+#[test]
+fn render_long_line() {
+	let terms = "1 + ".repeat(20);
+	let src = format!("x = {terms}@ {terms}");
+	let err = chunk(&src).unwrap_err();
+	assert_eq!(
+		err.render("input", src.as_bytes()),
+		format!(
+			"input:1: unexpected symbol near '@'\n  |\n1 | ...{}@ {}...\n  | {:pad$}^",
+			&terms[..EXCERPT_RADIUS],
+			&terms[..EXCERPT_RADIUS - 2],
+			"",
+			pad = EXCERPT_RADIUS + 3,
+		)
+	);
+
+	// The excerpt stays the same size however long the line is
+	let terms = "1 + ".repeat(50_000);
+	let src = format!("x = {terms}@ {terms}");
+	let rendered = chunk(&src).unwrap_err().render("input", src.as_bytes());
+	assert!(rendered.len() < 4 * EXCERPT_RADIUS + 100);
+}
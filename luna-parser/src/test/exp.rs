@@ -1,53 +1,103 @@
 use luna_ast::{
-	expression::{BinaryExpression, Expression, Value},
-	operation::BinaryOperation,
+	assert_spanless_eq,
+	build::{bin, num},
+	expression::{Expression, Value},
+	operation::BinaryOperation::{Add, Subtract},
+	span::{Span, Spanned},
 	terminal::Numeral,
+	variable::Variable,
 };
 
-use crate::parse::expression::{exp, value};
+use crate::{
+	parse::expression::{exp, value},
+	test::parse,
+};
 
 #[test]
 fn knil() {
-	assert_eq!(value("nil"), Ok(("", Value::Nil)));
+	assert_spanless_eq!(parse("nil", value), Some(Value::Nil(Span::default())));
 }
 
 #[test]
 fn kfalse() {
-	assert_eq!(value("false"), Ok(("", Value::False)));
+	assert_spanless_eq!(parse("false", value), Some(Value::False(Span::default())));
 }
 
 #[test]
 fn ktrue() {
-	assert_eq!(value("true"), Ok(("", Value::True)));
+	assert_spanless_eq!(parse("true", value), Some(Value::True(Span::default())));
 }
 
 #[test]
 fn operators() {
-	assert_eq!(
-		exp("1+2"),
-		Ok((
-			"",
-			Expression::BinaryExpression(BinaryExpression::AsExpression {
-				left: Box::new(Value::Numeral(Numeral::Integer(1))),
-				op: BinaryOperation::Add,
-				right: Box::new(Expression::BinaryExpression(BinaryExpression::AsValue(
-					Box::new(Value::Numeral(Numeral::Integer(2)))
-				)))
-			})
-		))
-	);
+	assert_spanless_eq!(parse("1+2", exp), Some(bin(num(1), Add, num(2))));
+	assert_spanless_eq!(parse("3-2", exp), Some(bin(num(3), Subtract, num(2))));
+}
+
+/// Renders the operator structure of `ex` as an s-expression.
+fn sexp(ex: &Expression) -> String {
+	match ex {
+		Expression::Value(val) => match &**val {
+			Value::Numeral(Numeral::Integer(i), _) => i.to_string(),
+			Value::Variable(Variable::Name(name)) => name.value.clone(),
+			Value::ParenExpression(ex, _) => sexp(ex),
+			val => format!("{val:?}"),
+		},
+		Expression::BinaryExpression(ex) => {
+			format!("({:?} {} {})", ex.op, sexp(&ex.left), sexp(&ex.right))
+		}
+		Expression::UnaryExpression(ex) => format!("({:?} {})", ex.op, sexp(&ex.ex)),
+		Expression::Error(_) => "<error>".to_owned(),
+	}
+}
+
+fn tree(src: &str) -> String {
+	sexp(&parse(src, exp).unwrap())
+}
+
+#[test]
+fn left_associative() {
+	assert_eq!(tree("1-2-3"), "(Subtract (Subtract 1 2) 3)");
+	assert_eq!(tree("8/4*2"), "(Multiply (Divide 8 4) 2)");
+	assert_eq!(tree("a or b or c"), "(Or (Or a b) c)");
+}
 
+#[test]
+fn right_associative() {
+	assert_eq!(tree("2^3^2"), "(Power 2 (Power 3 2))");
+	assert_eq!(tree("a..b..c"), "(Concat a (Concat b c))");
+}
+
+#[test]
+fn precedence() {
+	assert_eq!(tree("1+2*3 == 7"), "(IsEqual (Add 1 (Multiply 2 3)) 7)");
+	assert_eq!(tree("a or b and c"), "(Or a (And b c))");
+	assert_eq!(tree("a < b == c"), "(IsEqual (LessThan a b) c)");
 	assert_eq!(
-		exp("3-2"),
-		Ok((
-			"",
-			Expression::BinaryExpression(BinaryExpression::AsExpression {
-				left: Box::new(Value::Numeral(Numeral::Integer(3))),
-				op: BinaryOperation::Subtract,
-				right: Box::new(Expression::BinaryExpression(BinaryExpression::AsValue(
-					Box::new(Value::Numeral(Numeral::Integer(2)))
-				)))
-			})
-		))
+		tree("1 | 2 ~ 3 & 4"),
+		"(BitwiseOr 1 (BitwiseXor 2 (BitwiseAnd 3 4)))"
 	);
+	assert_eq!(tree("1 << 2 .. 3"), "(BitwiseLeftShift 1 (Concat 2 3))");
+	assert_eq!(tree("a .. b + c"), "(Concat a (Add b c))");
+	assert_eq!(tree("(1+2)*3"), "(Multiply (Add 1 2) 3)");
+}
+
+#[test]
+fn unary() {
+	assert_eq!(tree("-x^2"), "(Negate (Power x 2))");
+	assert_eq!(tree("not a == b"), "(IsEqual (Not a) b)");
+	assert_eq!(tree("-a * -b"), "(Multiply (Negate a) (Negate b))");
+	assert_eq!(tree("2^-3"), "(Power 2 (Negate 3))");
+	assert_eq!(tree("#t + ~x"), "(Add (Length t) (BitwiseNot x))");
+}
+
+#[test]
+fn operator_spans() {
+	let ex = parse("1 + 2 * 3", exp).unwrap();
+	let Expression::BinaryExpression(add) = ex else {
+		panic!("expected a binary expression")
+	};
+
+	assert_eq!(add.span, Span::new(0, 9));
+	assert_eq!(add.right.span(), Span::new(4, 9));
 }
@@ -0,0 +1,142 @@
+use luna_ast::{
+	fold::{fold_chunk, fold_expression, Constant},
+	print::{print_chunk, print_expression, PrintOptions},
+};
+
+use crate::{
+	chunk, parse_expression,
+	resolve::{resolve, LocalId, LocalKind},
+	ParseOptions, MAX_DEPTH,
+};
+
+/// `src` with its constants folded, printed back.
+fn folded(src: &str) -> String {
+	let mut ex = parse_expression(src.as_bytes(), &ParseOptions::new())
+		.unwrap()
+		.tree;
+	fold_expression(&mut ex);
+	print_expression(&ex, &PrintOptions::new())
+}
+
+/// The value `src` folds to.
+fn value(src: &str) -> Option<Constant> {
+	let mut ex = parse_expression(src.as_bytes(), &ParseOptions::new())
+		.unwrap()
+		.tree;
+	fold_expression(&mut ex);
+	Constant::of(&ex)
+}
+
+#[test]
+fn arithmetic() {
+	assert_eq!(folded("1 + 2 * 3"), "7");
+	assert_eq!(folded("(2 ^ 10) // 3"), "341.0");
+	assert_eq!(value("7 // -2"), Some(Constant::Integer(-4)));
+	assert_eq!(value("7 % -2"), Some(Constant::Integer(-1)));
+	assert_eq!(value("-7.5 % 2"), Some(Constant::Float(0.5)));
+	assert_eq!(value("1 / 2"), Some(Constant::Float(0.5)));
+	// Integers wrap around
+	assert_eq!(
+		value("0x7fffffffffffffff + 1"),
+		Some(Constant::Integer(i64::MIN))
+	);
+	assert_eq!(
+		value("(-0x7fffffffffffffff - 1) // -1"),
+		Some(Constant::Integer(i64::MIN))
+	);
+	assert_eq!(
+		value("-(-0x7fffffffffffffff - 1)"),
+		Some(Constant::Integer(i64::MIN))
+	);
+
+	// Errors and results Lua doesn't fold are left for run time
+	for src in [
+		"1 // 0", "1 % 0", "1 / 0", "0.0 * -1", "-0.0", "1 - 1.0", "'1' + 2", "#'abc'",
+	] {
+		assert_eq!(value(src), None, "{src}");
+	}
+	assert_eq!(folded("x + 1 * 2"), "x + 2");
+}
+
+#[test]
+fn bitwise() {
+	assert_eq!(value("1 << 4 | 3 & ~0"), Some(Constant::Integer(19)));
+	assert_eq!(value("2.0 ~ 3"), Some(Constant::Integer(1)));
+	assert_eq!(value("1 << 64"), Some(Constant::Integer(0)));
+	assert_eq!(value("-1 >> 60"), Some(Constant::Integer(15)));
+	assert_eq!(value("1 >> -2"), Some(Constant::Integer(4)));
+	// Floats without an integer value raise an error
+	assert_eq!(value("1.5 | 0"), None);
+	assert_eq!(value("2^63 | 0"), None);
+}
+
+#[test]
+fn comparison() {
+	assert_eq!(value("1 == 1.0"), Some(Constant::Boolean(true)));
+	assert_eq!(value("'1' == 1"), Some(Constant::Boolean(false)));
+	assert_eq!(value("nil ~= false"), Some(Constant::Boolean(true)));
+	assert_eq!(value("1 < 1.5"), Some(Constant::Boolean(true)));
+	assert_eq!(value("2^53 >= 2^53 + 1"), Some(Constant::Boolean(true)));
+	// Past 2^53, integers and floats compare exactly
+	assert_eq!(
+		value("0x7fffffffffffffff < 2^63"),
+		Some(Constant::Boolean(true))
+	);
+	assert_eq!(value("(1 << 53) + 1 > 2^53"), Some(Constant::Boolean(true)));
+	// String order depends on the locale
+	assert_eq!(value("'a' < 'b'"), None);
+
+	assert_eq!(value("not nil"), Some(Constant::Boolean(true)));
+	assert_eq!(value("nil and f()"), Some(Constant::Nil));
+	assert_eq!(value("1 or f()"), Some(Constant::Integer(1)));
+	assert_eq!(folded("false or f()"), "false or f()");
+}
+
+#[test]
+fn concatenation() {
+	assert_eq!(
+		value("'a' .. 'b' .. 1"),
+		Some(Constant::String(b"ab1".to_vec()))
+	);
+	// Float formatting is up to the C library
+	assert_eq!(value("'a' .. 1.5"), None);
+}
+
+#[test]
+fn long_chains() {
+	// The longest chain that parses folds from its deepest operand up
+	let mut tree = chunk(&format!("x = 1{}", " + 1".repeat(MAX_DEPTH - 3))).unwrap();
+	fold_chunk(&mut tree);
+	let sum = MAX_DEPTH - 2;
+	assert_eq!(
+		print_chunk(&tree, &PrintOptions::new()),
+		format!("x = {sum}\n")
+	);
+}
+
+#[test]
+fn dead_branches() {
+	let mut tree = chunk(
+		"if false then a() elseif x then b() elseif 1 then c() else d() end
+		while nil do e() end
+		if nil then f() else g() end
+		if false then h() end
+		while true do break end",
+	)
+	.unwrap();
+	fold_chunk(&mut tree);
+	assert_eq!(
+		print_chunk(&tree, &PrintOptions::new()),
+		"if x then\n\tb()\nelse\n\tc()\nend\ndo\n\tg()\nend\nwhile true do\n\tbreak\nend\n"
+	);
+}
+
+#[test]
+fn compile_time_constants() {
+	// The resolver folds initializers, like `localstat`
+	let tree = chunk("local a <const> = -1 local b <const> = 2^-1").unwrap();
+	let res = resolve(&tree);
+	assert_eq!(res.local(LocalId(0)).kind, LocalKind::CompileTimeConstant);
+	assert_eq!(res.local(LocalId(0)).constant, Some(Constant::Integer(-1)));
+	assert_eq!(res.local(LocalId(1)).constant, Some(Constant::Float(0.5)));
+}
@@ -1,6 +1,20 @@
-use crate::parse::function::functioncall;
+use crate::{
+	parse::affix::{suffixedexp, Suffixed},
+	test::parse,
+};
 
 #[test]
 fn call() {
-	println!("{:?}", functioncall("fun()"));
+	assert!(matches!(
+		parse("fun()", suffixedexp),
+		Some(Suffixed::FunctionCall(_))
+	));
+	assert!(matches!(
+		parse("a.b:c 'x' [1]", suffixedexp),
+		Some(Suffixed::Variable(_))
+	));
+	assert!(matches!(
+		parse("(a)", suffixedexp),
+		Some(Suffixed::ParenExpression(..))
+	));
 }
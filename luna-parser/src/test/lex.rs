@@ -0,0 +1,148 @@
+use luna_ast::{
+	span::Span,
+	terminal::{Name, Numeral},
+};
+
+use crate::{
+	error::ErrorKind,
+	lex::{tokenize, Lexer, Token, TokenKind},
+};
+
+fn name(value: &str, start: usize) -> TokenKind {
+	TokenKind::Name(Name {
+		value: value.into(),
+		span: Span::new(start, start + value.len()),
+	})
+}
+
+fn kinds(src: &str) -> Vec<TokenKind> {
+	tokenize(src.as_bytes())
+		.unwrap()
+		.into_iter()
+		.map(|tok| tok.kind)
+		.collect()
+}
+
+#[test]
+fn keyword_boundary() {
+	assert_eq!(
+		kinds("android and orbit"),
+		vec![
+			name("android", 0),
+			TokenKind::And,
+			name("orbit", 12),
+			TokenKind::Eof,
+		]
+	);
+}
+
+#[test]
+fn identifiers() {
+	assert_eq!(
+		kinds("_G my_var x1 __index end_ endx"),
+		vec![
+			name("_G", 0),
+			name("my_var", 3),
+			name("x1", 10),
+			name("__index", 13),
+			name("end_", 21),
+			name("endx", 26),
+			TokenKind::Eof,
+		]
+	);
+	// A name can't start with a digit
+	assert!(tokenize(b"1x").is_err());
+}
+
+#[test]
+fn unicode_names() {
+	let src = "località = 1";
+	assert_eq!(
+		tokenize(src.as_bytes()).unwrap_err().kind,
+		ErrorKind::UnexpectedSymbol
+	);
+
+	let kinds: Vec<_> = Lexer::new(src.as_bytes())
+		.unicode_names()
+		.map(|tok| tok.unwrap().kind)
+		.collect();
+	assert_eq!(kinds[0], name("località", 0));
+
+	// Only letters start a name
+	let mut lexer = Lexer::new("x²".as_bytes()).unicode_names();
+	assert_eq!(lexer.next().unwrap().unwrap().kind, name("x²", 0));
+	let mut lexer = Lexer::new("²x".as_bytes()).unicode_names();
+	assert!(lexer.next().unwrap().is_err());
+}
+
+#[test]
+fn operators() {
+	use TokenKind::*;
+
+	assert_eq!(
+		kinds("a.b..c...//=/==~=~<<=<>>=>::"),
+		vec![
+			name("a", 0),
+			Dot,
+			name("b", 2),
+			DoubleDot,
+			name("c", 5),
+			TripleDot,
+			DoubleSlash,
+			Equals,
+			Slash,
+			IsEqual,
+			NotEqual,
+			Tilde,
+			LShift,
+			Equals,
+			Less,
+			RShift,
+			Equals,
+			Greater,
+			DoubleColon,
+			Eof,
+		]
+	);
+}
+
+#[test]
+fn spans() {
+	let tokens = tokenize(b"local x =\r\n  42").unwrap();
+
+	assert_eq!(
+		tokens,
+		vec![
+			Token {
+				kind: TokenKind::Local,
+				span: Span::new(0, 5),
+				line: 1
+			},
+			Token {
+				kind: name("x", 6),
+				span: Span::new(6, 7),
+				line: 1
+			},
+			Token {
+				kind: TokenKind::Equals,
+				span: Span::new(8, 9),
+				line: 1
+			},
+			Token {
+				kind: TokenKind::Numeral(Numeral::Integer(42)),
+				span: Span::new(13, 15),
+				line: 2
+			},
+			Token {
+				kind: TokenKind::Eof,
+				span: Span::new(15, 15),
+				line: 2
+			},
+		]
+	);
+}
+
+#[test]
+fn unfinished_string() {
+	assert!(tokenize(b"x = \"abc\n\"").is_err());
+}
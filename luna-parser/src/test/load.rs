@@ -0,0 +1,111 @@
+use std::io::{self, BufReader, Read};
+
+use luna_ast::{
+	expression::{Expression, Value},
+	span::{Span, Spanned},
+	statement::Statement,
+};
+
+use crate::{
+	chunk_bytes, chunk_from_reader, chunk_from_script,
+	error::LoadError,
+	load::{header_break, header_len, is_binary},
+	zio::Zio,
+};
+
+/// The bytes of the string assigned by the first statement of a chunk.
+fn assigned_string(stat: &Statement) -> Vec<u8> {
+	let Statement::Assignment(assign) = stat else {
+		panic!("expected an assignment, got {stat:?}");
+	};
+	let Expression::Value(val) = &assign.elist[0] else {
+		panic!("expected a value, got {:?}", assign.elist[0]);
+	};
+	let Value::LiteralString(string) = &**val else {
+		panic!("expected a string, got {val:?}");
+	};
+	string.value.clone()
+}
+
+#[test]
+fn non_utf8_bytes() {
+	let chunk = chunk_bytes(b"x = '\xff\xfe'").unwrap();
+	assert_eq!(assigned_string(&chunk.0.stlist[0]), b"\xff\xfe");
+}
+
+#[test]
+fn reader() {
+	let src: &[u8] = b"x = 'abc'\nf(x)";
+	let chunk = chunk_from_reader(BufReader::new(src)).unwrap();
+	assert_eq!(chunk, chunk_bytes(src).unwrap());
+}
+
+#[test]
+fn reader_function() {
+	// Pieces split in the middle of tokens, as a `lua_Reader` might
+	let mut pieces = vec![&b"x = 'a"[..], b"bc'\nf(", b"x)", b""].into_iter();
+	let zio = Zio::new(move || pieces.next().map(<[u8]>::to_vec));
+	let chunk = chunk_from_reader(zio).unwrap();
+	assert_eq!(chunk, chunk_bytes(b"x = 'abc'\nf(x)").unwrap());
+}
+
+#[test]
+fn errors() {
+	let err = chunk_from_reader(&b"x = = 1"[..]).unwrap_err();
+	assert_eq!(err.to_string(), "unexpected symbol near '='");
+	assert_eq!(
+		err.render("test").lines().next(),
+		Some("test:1: unexpected symbol near '='")
+	);
+
+	struct Broken;
+	impl Read for Broken {
+		fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+			Err(io::Error::other("broken pipe"))
+		}
+	}
+	assert!(matches!(chunk_from_reader(Broken), Err(LoadError::Io(_))));
+}
+
+#[test]
+fn script_header() {
+	assert_eq!(header_len(b"x = 1"), 0);
+	assert_eq!(header_len(b"\xEF\xBB\xBFx = 1"), 3);
+	assert_eq!(header_len(b"#!/usr/bin/env lua\nx = 1"), 18);
+	assert_eq!(header_len(b"\xEF\xBB\xBF# comment\r\nx = 1"), 12);
+	assert_eq!(header_len(b"#!lua"), 5);
+
+	// What separates a `#` line from the chunk, for tools that write it back
+	assert_eq!(header_break(b"\xEF\xBB\xBFx = 1\n"), b"");
+	assert_eq!(header_break(b"\xEF\xBB\xBF# comment\r\nx = 1"), b"\r\n");
+	assert_eq!(header_break(b"#!/usr/bin/env lua\nx = 1"), b"\n");
+	assert_eq!(header_break(b"#!lua"), b"");
+
+	let src: &[u8] = b"\xEF\xBB\xBF#!/usr/bin/env lua\nf()";
+	let chunk = chunk_from_script(src).unwrap();
+	// Spans still point into the whole file
+	assert_eq!(chunk.0.stlist[0].span(), Span::new(22, 25));
+}
+
+#[test]
+fn script_line_numbers() {
+	let src: &[u8] = b"#!/usr/bin/env lua\nx = = 1";
+	let err = chunk_from_script(src).unwrap_err();
+	assert_eq!(
+		err.render("test").lines().next(),
+		Some("test:2: unexpected symbol near '='")
+	);
+}
+
+#[test]
+fn binary_chunk() {
+	assert!(is_binary(b"\x1bLua\x54\x00"));
+	assert!(is_binary(b"#!/usr/bin/env lua\n\x1bLua"));
+	assert!(!is_binary(b"x = '\x1b'"));
+	assert!(matches!(
+		chunk_from_script(&b"\x1bLua\x54\x00"[..]),
+		Err(LoadError::BinaryChunk)
+	));
+	// Plain chunks aren't files, so nothing is skipped
+	assert!(chunk_from_reader(&b"#!/usr/bin/env lua\nf()"[..]).is_err());
+}
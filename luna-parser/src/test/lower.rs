@@ -0,0 +1,140 @@
+use luna_ast::{
+	ir::{Call, Expression, Index, Let, Statement, TableField, Target, Temp},
+	lower::lower,
+	terminal::Numeral,
+};
+
+use crate::chunk;
+
+/// The statements of `src`, lowered.
+fn stats(src: &str) -> Vec<Statement> {
+	lower(&chunk(src).unwrap()).0.body.stats
+}
+
+/// The value of a lowered string, or of the name it refers to.
+fn text(ex: &Expression) -> &str {
+	match ex {
+		Expression::String(s) => std::str::from_utf8(&s.value).unwrap(),
+		Expression::Name(name) => &name.value,
+		ex => panic!("expected a string or name, found {ex:?}"),
+	}
+}
+
+/// The table and key of an index, as text.
+fn index(ex: &Expression) -> (&Expression, &str) {
+	let Expression::Index(index) = ex else {
+		panic!("expected an index, found {ex:?}");
+	};
+	(&index.table, text(&index.key))
+}
+
+#[test]
+fn method_call() {
+	let stats = stats("a.b.c:m(1)");
+	let [Statement::Call(Expression::Let(call))] = &stats[..] else {
+		panic!("expected a method call, found {stats:?}");
+	};
+	let Let { temp, value, body } = &**call;
+	assert_eq!(*temp, Temp(0));
+
+	// The object is evaluated once, into the temporary
+	let (ab, c) = index(value);
+	let (a, b) = index(ab);
+	assert_eq!((text(a), b, c), ("a", "b", "c"));
+
+	let Expression::Call(call) = body else {
+		panic!("expected a call, found {body:?}");
+	};
+	let Call { function, args, .. } = &**call;
+	let (object, m) = index(function);
+	assert_eq!((object, m), (&Expression::Temp(Temp(0)), "m"));
+	assert!(matches!(
+		&args[..],
+		[
+			Expression::Temp(Temp(0)),
+			Expression::Numeral(Numeral::Integer(1), _)
+		]
+	));
+}
+
+#[test]
+fn method_definition() {
+	let stats = stats("function a.b:f(x) return self:g(x) end");
+	let [Statement::Assign {
+		targets, values, ..
+	}] = &stats[..]
+	else {
+		panic!("expected an assignment, found {stats:?}");
+	};
+	let [Target::Index(Index { table, key, .. })] = &targets[..] else {
+		panic!("expected a field, found {targets:?}");
+	};
+	let (a, b) = index(table);
+	assert_eq!((text(a), b, text(key)), ("a", "b", "f"));
+
+	let [Expression::Function(function)] = &values[..] else {
+		panic!("expected a function, found {values:?}");
+	};
+	let params: Vec<_> = function.params.iter().map(|p| p.value.as_str()).collect();
+	assert_eq!(params, ["self", "x"]);
+	// Temporaries are numbered for each function
+	assert_eq!(function.temps, 1);
+	assert!(matches!(
+		&function.body.stats[..],
+		[Statement::Return { values, .. }] if matches!(&values[..], [Expression::Let(_)])
+	));
+}
+
+#[test]
+fn call_sugar() {
+	let stats = stats("f'str' g{1, x = 2, [3] = 4} local function h() end");
+	let [Statement::Call(Expression::Call(f)), Statement::Call(Expression::Call(g)), Statement::LocalFunction { .. }] =
+		&stats[..]
+	else {
+		panic!("expected two calls and a local function, found {stats:?}");
+	};
+	assert!(matches!(&f.args[..], [Expression::String(s)] if s.value == b"str"));
+
+	let [Expression::Table(table)] = &g.args[..] else {
+		panic!("expected a table, found {:?}", g.args);
+	};
+	let keys: Vec<_> = table
+		.fields
+		.iter()
+		.map(|field| match field {
+			TableField::Positional(_) => None,
+			TableField::Keyed { key, .. } => Some(key),
+		})
+		.collect();
+	assert!(matches!(
+		&keys[..],
+		[
+			None,
+			Some(Expression::String(_)),
+			Some(Expression::Numeral(..))
+		]
+	));
+}
+
+#[test]
+fn parentheses() {
+	let stats = stats("return (f()), (...), (x), (1 + 2)");
+	let [Statement::Return { values, .. }] = &stats[..] else {
+		panic!("expected a return, found {stats:?}");
+	};
+	assert!(matches!(
+		&values[..],
+		[
+			Expression::Truncate(..),
+			Expression::Truncate(..),
+			Expression::Name(_),
+			Expression::Binary(_),
+		]
+	));
+
+	// `end` statements are dropped, and returns end their blocks
+	let stats = self::stats("; do ; return end");
+	assert!(
+		matches!(&stats[..], [Statement::Do(bl)] if matches!(&bl.stats[..], [Statement::Return { .. }]))
+	);
+}
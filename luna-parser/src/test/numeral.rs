@@ -0,0 +1,80 @@
+use luna_ast::terminal::Numeral::{self, Float, Integer};
+
+use crate::{
+	lex::{tokenize, TokenKind},
+	terminal::numeral::str2num,
+};
+
+fn lex(src: &str) -> Option<Numeral> {
+	match tokenize(src.as_bytes()).ok()?.first()?.kind {
+		TokenKind::Numeral(ref num) => Some(num.clone()),
+		_ => None,
+	}
+}
+
+#[test]
+fn decimal() {
+	assert_eq!(lex("3"), Some(Integer(3)));
+	assert_eq!(lex("3."), Some(Float(3.0)));
+	assert_eq!(lex(".5"), Some(Float(0.5)));
+	assert_eq!(lex("2.5"), Some(Float(2.5)));
+	assert_eq!(lex("250.0e-2"), Some(Float(2.5)));
+	assert_eq!(lex("0.25E1"), Some(Float(2.5)));
+	assert_eq!(lex("34e1"), Some(Float(340.0)));
+	assert_eq!(lex("1e+10"), Some(Float(1e10)));
+}
+
+#[test]
+fn hexadecimal() {
+	assert_eq!(lex("0xff"), Some(Integer(255)));
+	assert_eq!(lex("0XBEBADA"), Some(Integer(0xBEBADA)));
+	assert_eq!(lex("0x0.1E"), Some(Float(0.1171875)));
+	assert_eq!(lex("0xA23p-4"), Some(Float(162.1875)));
+	assert_eq!(
+		lex("0X1.921FB54442D18P+1"),
+		Some(Float(std::f64::consts::PI))
+	);
+	assert_eq!(lex("0x1.8p3"), Some(Float(12.0)));
+	assert_eq!(lex("0x.8"), Some(Float(0.5)));
+}
+
+#[test]
+fn overflow() {
+	// Hexadecimal integers wrap around
+	assert_eq!(lex("0xFFFFFFFFFFFFFFFF"), Some(Integer(-1)));
+	assert_eq!(lex("0x10000000000000001"), Some(Integer(1)));
+	// Decimal integers become floats
+	assert_eq!(lex("9223372036854775807"), Some(Integer(i64::MAX)));
+	assert_eq!(
+		lex("9223372036854775808"),
+		Some(Float(9223372036854775808.0))
+	);
+	assert_eq!(str2num(b"-9223372036854775808"), Some(Integer(i64::MIN)));
+	assert_eq!(
+		str2num(b"-9223372036854775809"),
+		Some(Float(-9223372036854775809.0))
+	);
+}
+
+#[test]
+fn malformed() {
+	for src in [
+		"3..2", "1e", "0x", "0xp1", "1.2.3", "3x", "0x1p", "12a", "08z",
+	] {
+		assert!(tokenize(src.as_bytes()).is_err(), "{src}");
+	}
+}
+
+#[test]
+fn conversion() {
+	assert_eq!(str2num(b"  10  "), Some(Integer(10)));
+	assert_eq!(str2num(b"\t-0x10\n"), Some(Integer(-16)));
+	assert_eq!(str2num(b"-.5"), Some(Float(-0.5)));
+	assert_eq!(str2num(b"1e500"), Some(Float(f64::INFINITY)));
+	assert_eq!(str2num(b"0x1p-1074"), Some(Float(f64::from_bits(1))));
+	assert_eq!(str2num(b"inf"), None);
+	assert_eq!(str2num(b"nan"), None);
+	assert_eq!(str2num(b"1 2"), None);
+	assert_eq!(str2num(b""), None);
+	assert_eq!(str2num(b"- 1"), None);
+}
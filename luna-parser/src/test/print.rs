@@ -0,0 +1,148 @@
+use luna_ast::{
+	assert_spanless_eq,
+	expression::Expression,
+	print::{format_source, print_chunk, print_expression, PrintOptions, Quote},
+};
+
+use crate::{chunk, parse_chunk, parse_expression, ParseOptions};
+
+/// Source that covers every kind of statement and expression.
+const SRC: &str = r#"
+local a <const>, b = 1, 2.5
+local function f(x, y, ...) return x + y * 2, ... end
+function t.a.b:m(self2) return self end
+function g(...) end
+x, y.z, w[1] = f(1, 2), - -3, not not true;
+;
+do local _ = #t end
+while a < b and not c do a = a + 1 break end
+repeat local q = q // 2 until q <= 0
+if a then b() elseif c then d() else e() end
+if a then end
+for i = 1, 10, 2 do print(i) end
+for k, v in pairs(t) do goto continue ::continue:: end
+s = "a\"b'c\\\n\0\1\255é" .. [[long]] .. 'x'
+r = (f())(x):m{1, 2, [3] = 4, k = v; "s"}.f "str"
+n = 0xff, 1e100, 0.1, 1 // 2, 2^-3^2, -2^2, (-2)^2, 1 - (2 - 3), (1 .. 2) .. 3
+u = ~a & b | c ~ d << 1 >> 2, a or b and c == d ~= e, {}
+(f)()
+return nil, false, ..., function() end
+"#;
+
+fn round_trip(src: &str, options: &PrintOptions) -> String {
+	let tree = chunk(src).unwrap();
+	let printed = print_chunk(&tree, options);
+	let reparsed = chunk(&printed).unwrap_or_else(|e| {
+		panic!("printed source doesn't parse: {e}\n{printed}");
+	});
+	assert_spanless_eq!(tree, reparsed);
+	// Printing is idempotent
+	assert_eq!(print_chunk(&reparsed, options), printed);
+	printed
+}
+
+#[test]
+fn round_trips() {
+	round_trip(SRC, &PrintOptions::default());
+	round_trip(
+		SRC,
+		&PrintOptions::new()
+			.indent("  ")
+			.quote(Quote::Single)
+			.trailing_separators()
+			.width(20),
+	);
+}
+
+#[test]
+fn layout() {
+	let src = "local t = {1, 2} while x do if y then break end end function f() end";
+	assert_eq!(
+		round_trip(src, &PrintOptions::default()),
+		"local t = { 1, 2 }\nwhile x do\n\tif y then\n\t\tbreak\n\tend\nend\nfunction f() end\n"
+	);
+
+	let src = "t = {alpha = 1, beta = 2}";
+	let options = PrintOptions::new().width(10).trailing_separators();
+	assert_eq!(
+		round_trip(src, &options),
+		"t = {\n\talpha = 1,\n\tbeta = 2,\n}\n"
+	);
+}
+
+#[test]
+fn parentheses() {
+	let cases = [
+		("(1 + 2) * 3", "(1 + 2) * 3"),
+		("1 + (2 * 3)", "1 + (2 * 3)"),
+		("1 + 2 * 3", "1 + 2 * 3"),
+		("a .. b .. c", "a .. b .. c"),
+		("- - x", "- -x"),
+		("2 ^ - 3", "2 ^ -3"),
+	];
+	let options = ParseOptions::default();
+	for (src, printed) in cases {
+		let exp = parse_expression(src.as_bytes(), &options).unwrap().tree;
+		assert_eq!(print_expression(&exp, &PrintOptions::default()), printed);
+	}
+
+	// Trees that weren't parsed get the parentheses they need
+	let mut exp = parse_expression(b"x * -y", &options).unwrap().tree;
+	let Expression::BinaryExpression(mul) = &mut exp else {
+		unreachable!()
+	};
+	*mul.left = parse_expression(b"1 + 2", &options).unwrap().tree;
+	let Expression::UnaryExpression(neg) = &mut *mul.right else {
+		unreachable!()
+	};
+	*neg.ex = parse_expression(b"y ^ 2", &options).unwrap().tree;
+	assert_eq!(
+		print_expression(&exp, &PrintOptions::default()),
+		"(1 + 2) * -y ^ 2"
+	);
+
+	// Negative integers are negations, in parentheses where they need them
+	let src = "0xffffffffffffffff ^ 2 + 2 ^ 0xfffffffffffffffe - - 0xffffffffffffffff";
+	let exp = parse_expression(src.as_bytes(), &options).unwrap().tree;
+	assert_eq!(
+		print_expression(&exp, &PrintOptions::default()),
+		"(-1) ^ 2 + 2 ^ -2 - - -1"
+	);
+	// Only the smallest one is left in hexadecimal, as it has no positive
+	// counterpart
+	let exp = parse_expression(b"0x8000000000000000", &options)
+		.unwrap()
+		.tree;
+	assert_eq!(
+		print_expression(&exp, &PrintOptions::default()),
+		"0x8000000000000000"
+	);
+
+	let mut tree = chunk("f() ; (g)()").unwrap();
+	tree.0.stlist.remove(1);
+	assert_eq!(
+		print_chunk(&tree, &PrintOptions::default()),
+		"f();\n(g)()\n"
+	);
+}
+
+#[test]
+fn comments() {
+	let src = "-- header\n\nlocal x = 1 -- one\n\n--[[ two ]]\nf(x, -- arg\n  2)\nt = {\n\t-- first\n\ta = 1, -- a\n}\n\nwhile x do\n\t-- inside\nend\n-- the end\n";
+	let options = ParseOptions::new().keep_comments();
+	let parse = parse_chunk(src.as_bytes(), &options).unwrap();
+	let printed = format_source(
+		&parse.tree,
+		&parse.comments,
+		src.as_bytes(),
+		&PrintOptions::default(),
+	);
+	assert_eq!(
+		printed,
+		"-- header\n\nlocal x = 1 -- one\n\n--[[ two ]]\nf(x, 2) -- arg\nt = {\n\t-- first\n\ta = 1 -- a\n}\n\nwhile x do\n\t-- inside\nend\n-- the end\n"
+	);
+
+	let reparsed = parse_chunk(printed.as_bytes(), &options).unwrap();
+	assert_spanless_eq!(parse.tree, reparsed.tree);
+	assert_eq!(reparsed.comments.len(), parse.comments.len());
+}
@@ -0,0 +1,149 @@
+use luna_ast::{
+	expression::Expression,
+	span::{Span, Spanned},
+	statement::Statement,
+	Chunk,
+};
+
+use crate::{chunk, error::Error, parse_chunk, ParseOptions};
+
+/// The chunk and errors of `src`, recovering from errors.
+fn chunk_recovering(src: &str) -> (Chunk, Vec<Error<Span>>) {
+	let parse = parse_chunk(src.as_bytes(), &ParseOptions::new().recovering()).unwrap();
+	(parse.tree, parse.errors)
+}
+
+/// The chunk and the Lua-style messages of every error in `src`.
+fn recover(src: &str) -> (Chunk, Vec<String>) {
+	let (chunk, errors) = chunk_recovering(src);
+	let messages = errors.iter().map(|e| e.message(src.as_bytes())).collect();
+	(chunk, messages)
+}
+
+#[test]
+fn valid_source_has_no_errors() {
+	let src = "local x = 1\nif x then print(x) end\nreturn x";
+	let (recovered, errors) = chunk_recovering(src);
+	assert!(errors.is_empty());
+	assert_eq!(Some(recovered), chunk(src).ok());
+}
+
+#[test]
+fn multiple_errors() {
+	let (chunk, errors) = recover("x = )\nlocal y = 1\ngoto 3\nf(y)");
+	assert_eq!(
+		errors,
+		["unexpected symbol near ')'", "<name> expected near '3'"]
+	);
+
+	let stlist = &chunk.0.stlist;
+	assert_eq!(stlist.len(), 5);
+	// The expression is missing, then the `)` is skipped as a statement of its own
+	let Statement::Assignment(assign) = &stlist[0] else {
+		panic!("expected an assignment, got {:?}", stlist[0]);
+	};
+	assert!(matches!(assign.elist[0], Expression::Error(_)));
+	assert!(matches!(stlist[1], Statement::Error(_)));
+	assert!(matches!(
+		stlist[2],
+		Statement::LocalDefinitionWithAttribute(_)
+	));
+	assert!(matches!(stlist[3], Statement::Error(_)));
+	assert!(matches!(stlist[4], Statement::FunctionCall(_)));
+}
+
+#[test]
+fn skips_to_next_statement() {
+	let src = "a b c\nlocal x = 1";
+	let (chunk, errors) = recover(src);
+	assert_eq!(errors, ["syntax error near 'b'"]);
+
+	let Statement::Error(span) = chunk.0.stlist[0] else {
+		panic!("expected an error node, got {:?}", chunk.0.stlist[0]);
+	};
+	assert_eq!(&src[span.start..span.end], "a b c");
+	assert!(matches!(
+		chunk.0.stlist[1],
+		Statement::LocalDefinitionWithAttribute(_)
+	));
+}
+
+#[test]
+fn missing_closer() {
+	let (chunk, errors) = recover("while x do\n  f()\n\nlocal t = {1, 2");
+	// Only the first error at the end of the source is reported
+	assert_eq!(errors, ["'}' expected near <eof>"]);
+	let [Statement::While(w)] = &chunk.0.stlist[..] else {
+		panic!("expected a while loop, got {:?}", chunk.0.stlist);
+	};
+	assert_eq!(w.bl.stlist.len(), 2);
+}
+
+#[test]
+fn stray_block_end() {
+	let (chunk, errors) = recover("f()\nend\ng()");
+	assert_eq!(errors, ["<eof> expected near 'end'"]);
+	assert!(matches!(
+		&chunk.0.stlist[..],
+		[
+			Statement::FunctionCall(_),
+			Statement::Error(_),
+			Statement::FunctionCall(_)
+		]
+	));
+}
+
+#[test]
+fn lexer_errors() {
+	let (chunk, errors) = recover("x = \"a\\qb\"\ny = @\nz = 1");
+	assert_eq!(
+		errors,
+		[
+			"invalid escape sequence near '\"a\\q'",
+			"unexpected symbol near '@'",
+		]
+	);
+	// Bad tokens become error nodes, and the statements after them still parse
+	assert!(matches!(
+		chunk.0.stlist.last(),
+		Some(Statement::Assignment(_))
+	));
+	assert!(chunk
+		.0
+		.stlist
+		.iter()
+		.any(|stat| matches!(stat, Statement::Error(_))));
+}
+
+#[test]
+fn strict_parse_stops_at_first() {
+	let err: Error<_> = chunk("x = )\ngoto 3").unwrap_err();
+	assert_eq!(err.message(b"x = )\ngoto 3"), "unexpected symbol near ')'");
+}
+
+#[test]
+fn same_line_statements() {
+	// The statements after an error on its line are kept, whatever starts them
+	let cases = [
+		("x = = 1; y = 2", "y = 2"),
+		("x = = 1 local y = 2", "local y = 2"),
+		("x = = 1 y = 2", "y = 2"),
+		("f(1 2) g()", "g()"),
+		("x = = 1 t.y, z = 2, 3", "t.y, z = 2, 3"),
+	];
+	for (src, kept) in cases {
+		let (chunk, errors) = recover(src);
+		assert_eq!(errors.len(), 1, "{src}: {errors:?}");
+		let span = chunk.0.stlist.last().unwrap().span();
+		assert_eq!(&src[span.start..span.end], kept);
+	}
+
+	// A name that can't start a statement is skipped with the rest
+	let src = "x = = 1, y z\nw = 1";
+	let (chunk, errors) = recover(src);
+	assert_eq!(errors.len(), 1);
+	let Statement::Error(span) = chunk.0.stlist[1] else {
+		panic!("expected an error node, got {:?}", chunk.0.stlist[1]);
+	};
+	assert_eq!(&src[span.start..span.end], "= 1, y z");
+}
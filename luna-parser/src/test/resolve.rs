@@ -0,0 +1,217 @@
+use luna_ast::{span::Span, terminal::Name, visit::Visit, visit_mut::VisitMut};
+
+use crate::{
+	chunk,
+	resolve::{resolve, Binding, Constant, Env, FunctionId, LocalId, LocalKind, Resolution},
+	MAX_DEPTH,
+};
+
+/// The binding of each occurrence of `name` in `src`, in source order.
+fn bindings(res: &Resolution, src: &str, name: &str) -> Vec<Binding> {
+	let mut spans: Vec<_> = res
+		.bindings()
+		.filter(|(span, _)| &src[span.start..span.end] == name)
+		.collect();
+	spans.sort_by_key(|(span, _)| span.start);
+	spans.into_iter().map(|(_, binding)| binding).collect()
+}
+
+#[test]
+fn locals_and_globals() {
+	let src = "local a = b; do local a = a end; a = print";
+	let tree = chunk(src).unwrap();
+	let res = resolve(&tree);
+	let env = Binding::Global(Env::Upvalue(0));
+	assert_eq!(
+		bindings(&res, src, "a"),
+		[
+			Binding::Local(LocalId(0)),
+			Binding::Local(LocalId(1)),
+			Binding::Local(LocalId(0)),
+			Binding::Local(LocalId(0)),
+		]
+	);
+	assert_eq!(bindings(&res, src, "b"), [env]);
+	assert_eq!(bindings(&res, src, "print"), [env]);
+	assert_eq!(res.local(LocalId(1)).register, Some(1));
+}
+
+#[test]
+fn upvalues() {
+	let src = "local x, y = 1, 2
+		local function f()
+			return function() return x + y + z end, x
+		end";
+	let tree = chunk(src).unwrap();
+	let res = resolve(&tree);
+	assert_eq!(res.functions().len(), 3);
+	assert!(res.local(LocalId(0)).captured);
+
+	// `f` captures `x` and `y` from registers, and `_ENV` from the main
+	// chunk's upvalue, for the inner function to capture from it
+	let f = res.function(FunctionId(1));
+	let names: Vec<_> = f
+		.upvalues
+		.iter()
+		.map(|up| (up.name.as_str(), up.instack, up.index))
+		.collect();
+	assert_eq!(names, [("x", true, 0), ("y", true, 1), ("_ENV", false, 0)]);
+	let inner = res.function(FunctionId(2));
+	let names: Vec<_> = inner
+		.upvalues
+		.iter()
+		.map(|up| (up.name.as_str(), up.instack, up.index))
+		.collect();
+	assert_eq!(
+		names,
+		[("x", false, 0), ("y", false, 1), ("_ENV", false, 2)]
+	);
+	assert_eq!(inner.upvalues[0].local, Some(LocalId(0)));
+	assert_eq!(bindings(&res, src, "z"), [Binding::Global(Env::Upvalue(2))]);
+	assert_eq!(
+		bindings(&res, src, "x"),
+		[
+			Binding::Local(LocalId(0)),
+			Binding::Upvalue(0),
+			Binding::Upvalue(0),
+		]
+	);
+}
+
+#[test]
+fn attributes() {
+	let src = "local a <const>, b <const> = 1, 'two'
+		local c <close> = nil
+		local d <const> = {}
+		local e <const> = (b)
+		local f, g <const> = 1
+		return function() return a, b, e end";
+	let tree = chunk(src).unwrap();
+	let res = resolve(&tree);
+	let kinds: Vec<_> = res
+		.locals()
+		.iter()
+		.map(|l| (l.name.as_str(), l.kind, l.register))
+		.collect();
+	assert_eq!(
+		kinds,
+		[
+			("a", LocalKind::Const, Some(0)),
+			("b", LocalKind::CompileTimeConstant, None),
+			("c", LocalKind::ToClose, Some(1)),
+			("d", LocalKind::Const, Some(2)),
+			("e", LocalKind::CompileTimeConstant, None),
+			("f", LocalKind::Regular, Some(3)),
+			("g", LocalKind::Const, Some(4)),
+		]
+	);
+	assert_eq!(
+		res.local(LocalId(4)).constant,
+		Some(Constant::String(b"two".to_vec()))
+	);
+
+	// Compile-time constants aren't captured
+	let closure = res.function(FunctionId(1));
+	assert_eq!(closure.upvalues.len(), 1);
+	assert_eq!(bindings(&res, src, "e")[1], Binding::Local(LocalId(4)));
+}
+
+#[test]
+fn scopes() {
+	let src = "local _ENV = {}
+		for i = 1, 2 do local j = i end
+		for k, v in pairs(t) do end
+		repeat local r until r
+		function t.a.b:m(p) return self, p end
+		x = 1";
+	let tree = chunk(src).unwrap();
+	let res = resolve(&tree);
+	let registers: Vec<_> = res
+		.locals()
+		.iter()
+		.map(|l| (l.name.as_str(), l.register))
+		.collect();
+	assert_eq!(
+		registers,
+		[
+			("_ENV", Some(0)),
+			("i", Some(4)),
+			("j", Some(5)),
+			("k", Some(5)),
+			("v", Some(6)),
+			("r", Some(1)),
+			("self", Some(0)),
+			("p", Some(1)),
+		]
+	);
+
+	// Globals go through the local `_ENV`
+	let env = Binding::Global(Env::Local(LocalId(0)));
+	assert_eq!(bindings(&res, src, "x"), [env]);
+	assert_eq!(bindings(&res, src, "pairs"), [env]);
+	// `t` is a global in the function name; `a`, `b` and `m` are fields
+	assert_eq!(bindings(&res, src, "t"), [env, env]);
+	assert!(bindings(&res, src, "m").is_empty());
+	assert_eq!(bindings(&res, src, "r").len(), 2);
+
+	let loop_scope = res.local(LocalId(1)).scope;
+	let body = res.local(LocalId(2)).scope;
+	assert_eq!(res.scope(body).parent, Some(loop_scope));
+	assert_eq!(res.scope(loop_scope).parent, res.block_scope(&tree.0));
+	assert_eq!(res.function(FunctionId(1)).params, 2);
+}
+
+#[test]
+fn long_chains() {
+	// Every operand of the longest chain that parses is resolved
+	let src = format!("local x; y = x{}", " + x".repeat(MAX_DEPTH - 3));
+	let tree = chunk(&src).unwrap();
+	let res = resolve(&tree);
+	assert_eq!(
+		bindings(&res, &src, "x"),
+		vec![Binding::Local(LocalId(0)); MAX_DEPTH - 1]
+	);
+}
+
+/// Forgets where each name came from, like a tree built in code.
+struct ClearSpans;
+
+impl VisitMut for ClearSpans {
+	fn visit_name_mut(&mut self, name: &mut Name) {
+		name.span = Span::default();
+	}
+}
+
+/// The bindings of the names called `name`, in the order they're visited.
+struct Bindings<'r> {
+	res: &'r Resolution<'r>,
+	name: &'static str,
+	found: Vec<Option<Binding>>,
+}
+
+impl Visit for Bindings<'_> {
+	fn visit_name(&mut self, name: &Name) {
+		if name.value == self.name {
+			self.found.push(self.res.binding(name));
+		}
+	}
+}
+
+#[test]
+fn repeated_spans() {
+	// Names are told apart by where they are in the tree, not by their spans
+	let mut tree = chunk("local a = 1; do local a = 2; print(a) end; print(a)").unwrap();
+	ClearSpans.visit_chunk_mut(&mut tree);
+	let res = resolve(&tree);
+	let mut names = Bindings {
+		res: &res,
+		name: "a",
+		found: Vec::new(),
+	};
+	names.visit_chunk(&tree);
+	let (outer, inner) = (Binding::Local(LocalId(0)), Binding::Local(LocalId(1)));
+	assert_eq!(
+		names.found,
+		[Some(outer), Some(inner), Some(inner), Some(outer)]
+	);
+}
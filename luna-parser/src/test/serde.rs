@@ -0,0 +1,36 @@
+use luna_ast::Chunk;
+use serde_json::json;
+
+use crate::chunk;
+
+#[test]
+fn round_trip() {
+	let src = "local t <const> = {1, x = 'a\\0b', [f()] = ...}\n\
+		for i = 1, #t, 2 do t[i] = -i // 2.5 end\n\
+		goto done ::done:: return function(a, ...) end";
+	let tree = chunk(src).unwrap();
+	let json = serde_json::to_string(&tree).unwrap();
+	let back: Chunk = serde_json::from_str(&json).unwrap();
+	assert_eq!(back, tree);
+}
+
+#[test]
+fn layout() {
+	let tree = chunk("x = #y").unwrap();
+	let json = serde_json::to_value(&tree).unwrap();
+	let span = |start, end| json!({"start": start, "end": end});
+	let name = |value, start, end| json!({"value": value, "span": span(start, end)});
+	assert_eq!(
+		json["stlist"][0],
+		json!({"type": "Assignment", "value": {
+			"vlist": [{"type": "Name", "value": name("x", 0, 1)}],
+			"elist": [{"type": "UnaryExpression", "value": {
+				"op": "Length",
+				"ex": {"type": "Value", "value": {"type": "Variable", "value":
+					{"type": "Name", "value": name("y", 5, 6)}}},
+				"span": span(4, 6),
+			}}],
+			"span": span(0, 6),
+		}})
+	);
+}
@@ -0,0 +1,87 @@
+use luna_ast::{
+	affix::{Prefix, Suffix},
+	function::ParameterList,
+	span::{LineIndex, Span, Spanned},
+	statement::Statement,
+	variable::Variable,
+};
+
+use crate::chunk;
+
+#[test]
+fn statement_spans() {
+	let src = "local x = 1\nprint(x)\n;";
+	let chunk = chunk(src).unwrap();
+	let spans: Vec<Span> = chunk.0.stlist.iter().map(Spanned::span).collect();
+
+	assert_eq!(
+		spans,
+		vec![Span::new(0, 11), Span::new(12, 20), Span::new(21, 22)]
+	);
+	assert_eq!(&src[spans[1].range()], "print(x)");
+}
+
+#[test]
+fn nested_spans() {
+	let src = "while a do x = b end";
+	let chunk = chunk(src).unwrap();
+	let Statement::While(stat) = &chunk.0.stlist[0] else {
+		panic!("expected a while loop")
+	};
+
+	assert_eq!(stat.span, Span::new(0, 20));
+	assert_eq!(&src[stat.cond.span().range()], "a");
+	assert_eq!(&src[stat.bl.span.range()], "x = b");
+}
+
+#[test]
+fn delimited_spans() {
+	let src = "do end goto x ::x:: (a)[b] = y local z <const>, w = 1 function f(a, ...) end";
+	let chunk = chunk(src).unwrap();
+	let text = |node: &dyn Spanned| &src[node.span().range()];
+	let stats: Vec<_> = chunk.0.stlist.iter().map(|stat| text(stat)).collect();
+	assert_eq!(stats[..3], ["do end", "goto x", "::x::"]);
+
+	// Parentheses and brackets are part of what they enclose
+	let Statement::Assignment(assign) = &chunk.0.stlist[3] else {
+		panic!("expected an assignment")
+	};
+	let Variable::Affixed(affix) = &assign.vlist[0] else {
+		panic!("expected an index")
+	};
+	let (Prefix::ParenExpression(..), [Suffix::Index(index)]) = (&affix.pfix, &affix.suflist[..])
+	else {
+		panic!("expected a parenthesized prefix")
+	};
+	assert_eq!((text(&affix.pfix), text(index)), ("(a)", "[b]"));
+
+	let Statement::LocalDefinitionWithAttribute(local) = &chunk.0.stlist[4] else {
+		panic!("expected a local")
+	};
+	assert_eq!(text(&local.atlist[0].attr), "<const>");
+	assert_eq!(
+		local.atlist[1].attr.span(),
+		Span::empty(src.rfind(" = ").unwrap() + 1)
+	);
+
+	let Statement::FunctionDefinition(def) = &chunk.0.stlist[5] else {
+		panic!("expected a function")
+	};
+	let Some(ParameterList::NameListWithVarArgs(_, vargs)) = &def.fbody.oplist else {
+		panic!("expected a vararg function")
+	};
+	assert_eq!(vargs.span, Span::new(src.len() - 8, src.len() - 5));
+}
+
+#[test]
+fn line_index() {
+	let index = LineIndex::new(b"a\nbc\r\n\n\rd");
+
+	assert_eq!(index.lines(), 4);
+	assert_eq!(index.line_col(0), (1, 1));
+	assert_eq!(index.line_col(3), (2, 2));
+	assert_eq!(index.line_col(6), (3, 1));
+	assert_eq!(index.line_col(8), (4, 1));
+	assert_eq!(index.line_start(2), Some(2));
+	assert_eq!(index.line_start(5), None);
+}
@@ -0,0 +1,86 @@
+use crate::{
+	error::ErrorKind,
+	lex::{tokenize, TokenKind},
+};
+
+fn lex(src: &str) -> Vec<u8> {
+	match tokenize(src.as_bytes()).unwrap().remove(0).kind {
+		TokenKind::String(string) => string.value,
+		kind => panic!("expected a string, found {kind}"),
+	}
+}
+
+fn error(src: &str) -> ErrorKind {
+	tokenize(src.as_bytes()).unwrap_err().kind
+}
+
+#[test]
+fn short() {
+	assert_eq!(lex(r#""hello""#), b"hello");
+	assert_eq!(lex(r#"'say "hi"'"#), b"say \"hi\"");
+	assert_eq!(lex(r#""""#), b"");
+}
+
+#[test]
+fn escapes() {
+	assert_eq!(
+		lex(r#""\a\b\f\n\r\t\v\\\"\'""#),
+		b"\x07\x08\x0C\n\r\t\x0B\\\"'"
+	);
+	assert_eq!(lex("\"a\\\nb\""), b"a\nb");
+	assert_eq!(lex("\"a\\\r\nb\""), b"a\nb");
+	assert_eq!(lex(r#""\65\066\0677""#), b"AB\x437");
+	assert_eq!(lex(r#""\x41\xff\x00""#), b"A\xFF\x00");
+	assert_eq!(lex("\"a\\z  \n\t  b\""), b"ab");
+}
+
+#[test]
+fn utf8_escapes() {
+	assert_eq!(lex(r#""\u{48}\u{E9}\u{20AC}""#), "Hé€".as_bytes());
+	assert_eq!(lex(r#""\u{10FFFF}""#), "\u{10FFFF}".as_bytes());
+	// Surrogates and values past Unicode use the original UTF-8 encoding
+	assert_eq!(lex(r#""\u{D800}""#), b"\xED\xA0\x80");
+	assert_eq!(lex(r#""\u{7FFFFFFF}""#), b"\xFD\xBF\xBF\xBF\xBF\xBF");
+}
+
+#[test]
+fn bad_escapes() {
+	for (src, kind) in [
+		(r#""\q""#, ErrorKind::InvalidEscape),
+		(r#""\256""#, ErrorKind::DecimalEscapeTooLarge),
+		(r#""\x4""#, ErrorKind::HexDigitExpected),
+		(r#""\xg0""#, ErrorKind::HexDigitExpected),
+		(r#""\u48""#, ErrorKind::MissingBrace('{')),
+		(r#""\u{}""#, ErrorKind::HexDigitExpected),
+		(r#""\u{48""#, ErrorKind::MissingBrace('}')),
+		(r#""\u{80000000}""#, ErrorKind::Utf8ValueTooLarge),
+	] {
+		assert_eq!(error(src), kind, "{src}");
+	}
+	assert_eq!(error("\"abc\\"), ErrorKind::UnfinishedString);
+}
+
+#[test]
+fn long() {
+	assert_eq!(lex("[[hello]]"), b"hello");
+	assert_eq!(lex("[[\nfirst\r\nsecond\n]]"), b"first\nsecond\n");
+	assert_eq!(lex("[==[a]]b]=]c]==]"), b"a]]b]=]c");
+	assert_eq!(lex("[=[\\n]=]"), b"\\n");
+	assert_eq!(lex("[[]]"), b"");
+}
+
+#[test]
+fn long_errors() {
+	assert_eq!(
+		error("[==[abc]=]"),
+		ErrorKind::UnfinishedLongString { line: 1 }
+	);
+	assert_eq!(error("[=a"), ErrorKind::InvalidLongStringDelimiter);
+	assert_eq!(tokenize(b"[ x").unwrap()[0].kind, TokenKind::LBracket);
+}
+
+#[test]
+fn lines() {
+	let tokens = tokenize(b"[[\na\nb]] x").unwrap();
+	assert_eq!(tokens[1].line, 3);
+}
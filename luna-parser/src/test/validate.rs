@@ -0,0 +1,110 @@
+use crate::{chunk, validate::validate, MAX_DEPTH};
+
+/// The Lua-style messages of the compile time errors in `src`.
+fn errors(src: &str) -> Vec<String> {
+	let chunk = chunk(src).unwrap();
+	validate(&chunk)
+		.iter()
+		.map(|e| e.message(src.as_bytes()))
+		.collect()
+}
+
+#[test]
+fn valid() {
+	let src = "
+		local t <const> = {}
+		for i = 1, 10 do
+			if i % 2 == 0 then goto continue end
+			while true do break end
+			local x <close> = nil
+			::continue::
+		end
+		repeat local y = 1 until y
+		local function f(...) return ... end
+		do goto skip; local z; ::skip:: end
+		::top:: do goto top end
+		t.x = 1
+		return ...
+	";
+	assert_eq!(errors(src), Vec::<String>::new());
+}
+
+#[test]
+fn break_outside_loop() {
+	assert_eq!(errors("\nbreak"), ["break outside a loop at line 2"]);
+	// A function doesn't see the loop around it
+	assert_eq!(
+		errors("while x do f(function() break end) end"),
+		["break outside a loop at line 1"]
+	);
+}
+
+#[test]
+fn goto() {
+	assert_eq!(
+		errors("goto nowhere"),
+		["no visible label 'nowhere' for <goto> at line 1"]
+	);
+	// Labels in nested blocks aren't visible
+	assert_eq!(
+		errors("goto a\ndo ::a:: end"),
+		["no visible label 'a' for <goto> at line 1"]
+	);
+	assert_eq!(
+		errors("goto a\nlocal x = 1\n::a:: print(x)"),
+		["<goto a> at line 1 jumps into the scope of local 'x'"]
+	);
+	// Locals in a `repeat` are still in scope at its end
+	assert_eq!(
+		errors("repeat goto a; local x ::a:: until x"),
+		["<goto a> at line 1 jumps into the scope of local 'x'"]
+	);
+	assert_eq!(
+		errors("::a::\ndo ::a:: end"),
+		["label 'a' already defined on line 1"]
+	);
+}
+
+#[test]
+fn vararg() {
+	assert_eq!(
+		errors("function f() return ... end"),
+		["cannot use '...' outside a vararg function near '...'"]
+	);
+}
+
+#[test]
+fn attributes() {
+	assert_eq!(
+		errors("local x <static> = 1"),
+		["unknown attribute 'static'"]
+	);
+	assert_eq!(
+		errors("local a <close>, b <close> = f()"),
+		["multiple to-be-closed variables in local list"]
+	);
+	assert_eq!(
+		errors("local x <const> = 1; x = 2; function g() x = 3 end; local x = 4; x = 5"),
+		[
+			"attempt to assign to const variable 'x'",
+			"attempt to assign to const variable 'x'",
+		]
+	);
+	assert_eq!(
+		errors("local f <const> = nil\nfunction f() end"),
+		["attempt to assign to const variable 'f'"]
+	);
+}
+
+#[test]
+fn long_chains() {
+	// The deepest operand of the longest chain that parses is still checked
+	let src = format!(
+		"function f() return ...{} end",
+		" + 1".repeat(MAX_DEPTH - 3)
+	);
+	assert_eq!(
+		errors(&src),
+		["cannot use '...' outside a vararg function near '...'"]
+	);
+}
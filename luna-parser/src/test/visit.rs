@@ -0,0 +1,67 @@
+use luna_ast::{
+	expression::Expression,
+	terminal::Name,
+	visit::{self, Visit},
+	visit_mut::VisitMut,
+};
+
+use crate::chunk;
+
+/// Counts names and expressions, in the order they're visited.
+#[derive(Default)]
+struct Counter {
+	names: Vec<String>,
+	expressions: usize,
+}
+
+impl Visit for Counter {
+	fn visit_name(&mut self, name: &Name) {
+		self.names.push(name.value.clone());
+	}
+
+	fn visit_expression(&mut self, exp: &Expression) {
+		self.expressions += 1;
+		visit::walk_expression(self, exp);
+	}
+}
+
+struct Rename;
+
+impl VisitMut for Rename {
+	fn visit_name_mut(&mut self, name: &mut Name) {
+		name.value = name.value.to_uppercase();
+	}
+}
+
+const SRC: &str = "
+local function f(a, ...)
+	for i = a, #t, 2 do t[i] = { x = i, [i] = ... } end
+	return obj:m'str'
+end
+repeat ::top:: until g(function(b) return b end)
+";
+
+#[test]
+fn visit() {
+	let mut counter = Counter::default();
+	counter.visit_chunk(&chunk(SRC).unwrap());
+	assert_eq!(
+		counter.names,
+		["f", "a", "i", "a", "t", "t", "i", "x", "i", "i", "obj", "m", "top", "g", "b", "b"]
+	);
+	assert_eq!(counter.expressions, 13);
+}
+
+#[test]
+fn visit_mut() {
+	let mut tree = chunk(SRC).unwrap();
+	Rename.visit_chunk_mut(&mut tree);
+
+	let mut counter = Counter::default();
+	counter.visit_chunk(&tree);
+	assert!(counter
+		.names
+		.iter()
+		.all(|name| *name == name.to_uppercase()));
+	assert_eq!(counter.names[2..4], ["I", "A"]);
+}
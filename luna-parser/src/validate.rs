@@ -0,0 +1,439 @@
+//! # Validation
+//!
+//! Some rules of Lua aren't part of its grammar, and are only checked when a
+//! chunk is compiled: where `goto` and `break` can jump, where `...` can be
+//! used, and which variables are read-only. [validate] checks a parsed chunk
+//! for all of them, keeping track of labels and locals the way `lparser.c`
+//! does.
+
+use luna_ast::{
+	affix::{Affix, Call, Index, Prefix, Suffix},
+	attribute::AttributeNameList,
+	expression::{Expression, Value},
+	function::{Arguments, FunctionBody, ParameterList},
+	span::Span,
+	statement::Statement,
+	table::{Field, TableConstructor},
+	terminal::Name,
+	variable::Variable,
+	Block, Chunk,
+};
+
+use crate::error::{Error, ErrorKind};
+
+/// Checks the compile time rules of `chunk`, returning every error in source order.
+pub fn validate(chunk: &Chunk) -> Vec<Error<Span>> {
+	let mut validator = Validator::default();
+	// The main chunk is always a vararg function
+	validator.function(&[], true, &chunk.0);
+	validator.errors.sort_by_key(|e| e.input.start);
+	validator.errors
+}
+
+/// An active local variable (`Vardesc`).
+struct Local {
+	name: String,
+	/// Whether the variable is `<const>` or `<close>`
+	readonly: bool,
+}
+
+/// A label, or a pending `goto` (`Labeldesc`).
+struct LabelDesc {
+	name: String,
+	span: Span,
+	/// The number of active locals where it appears
+	nactvar: usize,
+}
+
+/// A block being checked (`BlockCnt`).
+struct BlockCnt {
+	/// The first label of the block
+	firstlabel: usize,
+	/// The first pending `goto` of the block
+	firstgoto: usize,
+	/// The number of active locals outside the block
+	nactvar: usize,
+	isloop: bool,
+}
+
+/// A function being checked (`FuncState`).
+#[derive(Default)]
+struct FuncState {
+	vararg: bool,
+	actvar: Vec<Local>,
+	blocks: Vec<BlockCnt>,
+	/// The visible labels
+	labels: Vec<LabelDesc>,
+	/// The gotos that haven't found their label yet
+	gotos: Vec<LabelDesc>,
+}
+
+#[derive(Default)]
+struct Validator {
+	/// The function being checked, after the ones it's nested in
+	funcs: Vec<FuncState>,
+	errors: Vec<Error<Span>>,
+}
+
+impl Validator {
+	fn fs(&mut self) -> &mut FuncState {
+		self.funcs.last_mut().expect("always inside a function")
+	}
+
+	fn error(&mut self, span: Span, kind: ErrorKind) {
+		self.errors.push(Error::new(span, kind));
+	}
+
+	fn function(&mut self, params: &[Name], vararg: bool, bl: &Block) {
+		self.funcs.push(FuncState {
+			vararg,
+			..Default::default()
+		});
+		self.enter_block(false);
+		for param in params {
+			self.declare(&param.value, false);
+		}
+		self.statements(bl, false);
+		self.leave_block();
+		self.funcs.pop();
+	}
+
+	fn funcbody(&mut self, fbody: &FunctionBody, method: bool) {
+		let mut params = Vec::new();
+		if method {
+			params.push(Name {
+				value: "self".to_owned(),
+				span: fbody.span,
+			});
+		}
+		let vararg = match &fbody.oplist {
+			None => false,
+			Some(ParameterList::NameList(nlist)) => {
+				params.extend_from_slice(nlist);
+				false
+			}
+			Some(ParameterList::NameListWithVarArgs(nlist, _)) => {
+				params.extend_from_slice(nlist);
+				true
+			}
+			Some(ParameterList::VarArgs(_)) => true,
+		};
+		self.function(&params, vararg, &fbody.bl);
+	}
+
+	fn enter_block(&mut self, isloop: bool) {
+		let fs = self.fs();
+		let bl = BlockCnt {
+			firstlabel: fs.labels.len(),
+			firstgoto: fs.gotos.len(),
+			nactvar: fs.actvar.len(),
+			isloop,
+		};
+		fs.blocks.push(bl);
+	}
+
+	fn leave_block(&mut self) {
+		let fs = self.fs();
+		let bl = fs.blocks.pop().expect("block was entered");
+		fs.labels.truncate(bl.firstlabel);
+		fs.actvar.truncate(bl.nactvar);
+
+		if fs.blocks.is_empty() {
+			// Nowhere left for pending gotos to go (`undefgoto`)
+			let pending: Vec<_> = fs.gotos.drain(bl.firstgoto..).collect();
+			for gt in pending {
+				self.error(gt.span, ErrorKind::UndefinedGoto(gt.name));
+			}
+		} else {
+			// Pending gotos leave the scope of the block's locals (`movegotosout`)
+			for gt in &mut fs.gotos[bl.firstgoto..] {
+				gt.nactvar = bl.nactvar;
+			}
+		}
+	}
+
+	fn block(&mut self, bl: &Block, isloop: bool) {
+		self.enter_block(isloop);
+		self.statements(bl, false);
+		self.leave_block();
+	}
+
+	/// Checks the statements of `bl`, in the current block. `until` is set for
+	/// the body of a `repeat` loop, where locals are still visible at the end.
+	fn statements(&mut self, bl: &Block, until: bool) {
+		for (i, stat) in bl.stlist.iter().enumerate() {
+			// A label followed by nothing but void statements, at the very end of
+			// the block, is outside the scope of the block's locals
+			let last = !until
+				&& bl.oret.is_none()
+				&& bl.stlist[i + 1..]
+					.iter()
+					.all(|stat| matches!(stat, Statement::End(_) | Statement::Label(_)));
+			self.statement(stat, last);
+		}
+		if let Some(ret) = &bl.oret {
+			self.exps(ret.oelist.iter().flatten());
+		}
+	}
+
+	fn statement(&mut self, stat: &Statement, last: bool) {
+		match stat {
+			Statement::End(_) | Statement::Error(_) => {}
+			Statement::Assignment(assign) => {
+				for var in &assign.vlist {
+					match var {
+						Variable::Name(name) => self.check_readonly(name),
+						Variable::Affixed(affix) => self.affix(affix),
+					}
+				}
+				self.exps(&assign.elist);
+			}
+			Statement::FunctionCall(call) => {
+				self.affix(&call.affix);
+				self.call(&call.call);
+			}
+			Statement::Label(label) => self.label(&label.0, last),
+			Statement::Break(span) => {
+				if !self.fs().blocks.iter().any(|bl| bl.isloop) {
+					self.error(*span, ErrorKind::BreakOutsideLoop);
+				}
+			}
+			Statement::Goto(name, _) => {
+				let fs = self.fs();
+				// Backward jumps are always fine
+				if !fs.labels.iter().any(|lb| lb.name == name.value) {
+					let nactvar = fs.actvar.len();
+					fs.gotos.push(LabelDesc {
+						name: name.value.clone(),
+						span: name.span,
+						nactvar,
+					});
+				}
+			}
+			Statement::Do(bl, _) => self.block(bl, false),
+			Statement::While(w) => {
+				self.exp(&w.cond);
+				self.enter_block(true);
+				self.block(&w.bl, false);
+				self.leave_block();
+			}
+			Statement::RepeatUntil(r) => {
+				self.enter_block(true);
+				self.enter_block(false);
+				self.statements(&r.bl, true);
+				// The condition can see the body's locals
+				self.exp(&r.cond);
+				self.leave_block();
+				self.leave_block();
+			}
+			Statement::IfTree(tree) => {
+				for ifb in std::iter::once(&tree.initial).chain(&tree.elseifs) {
+					self.exp(&ifb.cond);
+					self.block(&ifb.bl, false);
+				}
+				if let Some(bl) = &tree.otherwise {
+					self.block(bl, false);
+				}
+			}
+			Statement::ForExpression(f) => {
+				self.exp(f.range.start());
+				self.exp(f.range.end());
+				self.exps(&f.step);
+				self.enter_block(true);
+				self.declare(&f.name.value, false);
+				self.block(&f.bl, false);
+				self.leave_block();
+			}
+			Statement::ForList(f) => {
+				self.exps(&f.elist);
+				self.enter_block(true);
+				for name in &f.nlist {
+					self.declare(&name.value, false);
+				}
+				self.block(&f.bl, false);
+				self.leave_block();
+			}
+			Statement::FunctionDefinition(def) => {
+				// `function f()` assigns to `f`, but `function t.f()` doesn't
+				if let ([name], None) = (&def.fname.nlist[..], &def.fname.objname) {
+					self.check_readonly(name);
+				}
+				self.funcbody(&def.fbody, def.fname.objname.is_some());
+			}
+			Statement::LocalFunctionDefinition(def) => {
+				// The function can call itself
+				self.declare(&def.name.value, false);
+				self.funcbody(&def.fbody, false);
+			}
+			Statement::LocalDefinitionWithAttribute(def) => {
+				// The values can't see the new locals
+				self.exps(def.oelist.iter().flatten());
+				self.locals(&def.atlist);
+			}
+		}
+	}
+
+	fn label(&mut self, name: &Name, last: bool) {
+		let fs = self.fs();
+		// Visible labels can't be shadowed (`checkrepeated`)
+		if let Some(lb) = fs.labels.iter().find(|lb| lb.name == name.value) {
+			let kind = ErrorKind::DuplicateLabel {
+				label: name.value.clone(),
+				previous: lb.span,
+			};
+			self.error(name.span, kind);
+			return;
+		}
+
+		let bl = fs.blocks.last().expect("always inside a block");
+		let firstgoto = bl.firstgoto;
+		let nactvar = match last {
+			true => bl.nactvar,
+			false => fs.actvar.len(),
+		};
+		fs.labels.push(LabelDesc {
+			name: name.value.clone(),
+			span: name.span,
+			nactvar,
+		});
+
+		// Resolve the pending gotos of this block (`solvegotos`)
+		let mut errors = Vec::new();
+		let mut i = firstgoto;
+		while i < fs.gotos.len() {
+			if fs.gotos[i].name != name.value {
+				i += 1;
+				continue;
+			}
+			let gt = fs.gotos.remove(i);
+			if gt.nactvar < nactvar {
+				let kind = ErrorKind::JumpIntoScope {
+					label: gt.name,
+					local: fs.actvar[gt.nactvar].name.clone(),
+				};
+				errors.push(Error::new(gt.span, kind));
+			}
+		}
+		self.errors.extend(errors);
+	}
+
+	fn locals(&mut self, atlist: &AttributeNameList) {
+		let mut closes = 0;
+		for attname in atlist {
+			let readonly = match attname.attr.0.as_ref().map(|n| n.value.as_str()) {
+				None => false,
+				Some("const") => true,
+				Some("close") => {
+					closes += 1;
+					if closes == 2 {
+						self.error(attname.span, ErrorKind::MultipleToBeClosed);
+					}
+					true
+				}
+				Some(other) => {
+					let span = attname.attr.0.as_ref().map_or(attname.span, |n| n.span);
+					self.error(span, ErrorKind::UnknownAttribute(other.to_owned()));
+					false
+				}
+			};
+			self.declare(&attname.name.value, readonly);
+		}
+	}
+
+	fn declare(&mut self, name: &str, readonly: bool) {
+		self.fs().actvar.push(Local {
+			name: name.to_owned(),
+			readonly,
+		});
+	}
+
+	/// Reports an assignment to `name` if it's a read-only local, here or in
+	/// an enclosing function (`check_readonly`).
+	fn check_readonly(&mut self, name: &Name) {
+		let local = self
+			.funcs
+			.iter()
+			.rev()
+			.flat_map(|fs| fs.actvar.iter().rev())
+			.find(|local| local.name == name.value);
+		if local.is_some_and(|local| local.readonly) {
+			self.error(name.span, ErrorKind::AssignToConst(name.value.clone()));
+		}
+	}
+
+	fn exps<'e>(&mut self, elist: impl IntoIterator<Item = &'e Expression>) {
+		for ex in elist {
+			self.exp(ex);
+		}
+	}
+
+	fn exp(&mut self, ex: &Expression) {
+		match ex {
+			Expression::Value(val) => self.value(val),
+			Expression::BinaryExpression(ex) => {
+				self.exp(&ex.left);
+				self.exp(&ex.right);
+			}
+			Expression::UnaryExpression(ex) => self.exp(&ex.ex),
+			Expression::Error(_) => {}
+		}
+	}
+
+	fn value(&mut self, val: &Value) {
+		match val {
+			Value::Nil(_)
+			| Value::False(_)
+			| Value::True(_)
+			| Value::Numeral(..)
+			| Value::LiteralString(_) => {}
+			Value::VarArgs(va) => {
+				if !self.fs().vararg {
+					self.error(va.span, ErrorKind::VarargOutsideVararg);
+				}
+			}
+			Value::AnonFunctionDefinition(def) => self.funcbody(&def.fbody, false),
+			Value::Variable(Variable::Name(_)) => {}
+			Value::Variable(Variable::Affixed(affix)) => self.affix(affix),
+			Value::FunctionCall(call) => {
+				self.affix(&call.affix);
+				self.call(&call.call);
+			}
+			Value::ParenExpression(ex, _) => self.exp(ex),
+			Value::TableConstructor(table) => self.table(table),
+		}
+	}
+
+	fn table(&mut self, table: &TableConstructor) {
+		for field in table.oflist.iter().flatten() {
+			match field {
+				Field::BracketField(field) => {
+					self.exp(&field.tabexp);
+					self.exp(&field.val);
+				}
+				Field::NameField(field) => self.exp(&field.val),
+				Field::Expression(ex) => self.exp(ex),
+			}
+		}
+	}
+
+	fn affix(&mut self, affix: &Affix) {
+		if let Prefix::ParenExpression(ex, _) = &affix.pfix {
+			self.exp(ex);
+		}
+		for suffix in &affix.suflist {
+			match suffix {
+				Suffix::Call(call) => self.call(call),
+				Suffix::Index(Index::Expression(ex, _)) => self.exp(ex),
+				Suffix::Index(Index::Member(_)) => {}
+			}
+		}
+	}
+
+	fn call(&mut self, call: &Call) {
+		match &call.argu {
+			Arguments::ClosedExpressionList(elist, _) => self.exps(elist.iter().flatten()),
+			Arguments::TableConstructor(table) => self.table(table),
+			Arguments::LiteralString(_) => {}
+		}
+	}
+}
@@ -0,0 +1,70 @@
+//! # Buffered Streams
+//!
+//! Like `lzio.c`, turns a function that hands out a chunk piece by piece (a
+//! `lua_Reader`) into a stream the parser can load from.
+//!
+//! Unlike `lzio.c`, the parser doesn't pull from the stream as it goes:
+//! [chunk_from_reader](crate::chunk_from_reader) collects every piece first,
+//! and parses the source as a whole.
+
+use std::io::{self, BufRead, Read};
+
+/// A stream over the pieces returned by a reader function.
+///
+/// The stream ends when the function returns `None` or an empty piece, as
+/// `luaZ_fill` does.
+pub struct Zio<F> {
+	reader: F,
+	buf: Vec<u8>,
+	pos: usize,
+	done: bool,
+}
+
+impl<F> Zio<F>
+where
+	F: FnMut() -> Option<Vec<u8>>,
+{
+	pub fn new(reader: F) -> Self {
+		Self {
+			reader,
+			buf: Vec::new(),
+			pos: 0,
+			done: false,
+		}
+	}
+}
+
+impl<F> BufRead for Zio<F>
+where
+	F: FnMut() -> Option<Vec<u8>>,
+{
+	fn fill_buf(&mut self) -> io::Result<&[u8]> {
+		if self.pos == self.buf.len() && !self.done {
+			match (self.reader)() {
+				Some(piece) if !piece.is_empty() => {
+					self.buf = piece;
+					self.pos = 0;
+				}
+				_ => self.done = true,
+			}
+		}
+		Ok(&self.buf[self.pos..])
+	}
+
+	fn consume(&mut self, amt: usize) {
+		self.pos = (self.pos + amt).min(self.buf.len());
+	}
+}
+
+impl<F> Read for Zio<F>
+where
+	F: FnMut() -> Option<Vec<u8>>,
+{
+	fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+		let avail = self.fill_buf()?;
+		let n = avail.len().min(out.len());
+		out[..n].copy_from_slice(&avail[..n]);
+		self.consume(n);
+		Ok(n)
+	}
+}
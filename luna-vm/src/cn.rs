@@ -52,3 +52,16 @@ pub const POS_C: u8 = POS_B + SIZE_B;
 pub const POS_BX: u8 = POS_K;
 pub const POS_AX: u8 = POS_A;
 pub const POS_SJ: u8 = POS_A;
+
+// Instruction argument limits
+pub const MAXARG_A: u32 = (1 << SIZE_A) - 1;
+pub const MAXARG_B: u32 = (1 << SIZE_B) - 1;
+pub const MAXARG_C: u32 = (1 << SIZE_C) - 1;
+pub const MAXARG_BX: u32 = (1 << SIZE_BX) - 1;
+pub const MAXARG_AX: u32 = (1 << SIZE_AX) - 1;
+pub const MAXARG_SJ: u32 = (1 << SIZE_SJ) - 1;
+
+// Excess K of signed arguments
+pub const OFFSET_SBX: i32 = (MAXARG_BX >> 1) as i32;
+pub const OFFSET_SJ: i32 = (MAXARG_SJ >> 1) as i32;
+pub const OFFSET_SC: i32 = (MAXARG_C >> 1) as i32;
@@ -0,0 +1,182 @@
+//! # Instructions
+//!
+//! Encoding and decoding of instructions, like the macros in `lopcodes.h`.
+
+use crate::{
+	cn,
+	formats::{ASize, BSize, CSize, FormatKind, KSize},
+	mask::Mask,
+	ops::OpCode,
+};
+
+pub use crate::cn::{
+	MAXARG_A, MAXARG_AX, MAXARG_B, MAXARG_BX, MAXARG_C, MAXARG_SJ, OFFSET_SBX, OFFSET_SC, OFFSET_SJ,
+};
+
+// From `lopcodes.h`:
+/*===========================================================================
+  We assume that instructions are unsigned 32-bit integers.
+  All instructions have an opcode in the first 7 bits.
+  Instructions can have the following formats:
+
+		3 3 2 2 2 2 2 2 2 2 2 2 1 1 1 1 1 1 1 1 1 1 0 0 0 0 0 0 0 0 0 0
+		1 0 9 8 7 6 5 4 3 2 1 0 9 8 7 6 5 4 3 2 1 0 9 8 7 6 5 4 3 2 1 0
+iABC          C(8)     |      B(8)     |k|     A(8)      |   Op(7)     |
+iABx                Bx(17)               |     A(8)      |   Op(7)     |
+iAsBx              sBx (signed)(17)      |     A(8)      |   Op(7)     |
+iAx                           Ax(25)                     |   Op(7)     |
+isJ                           sJ (signed)(25)            |   Op(7)     |
+
+  A signed argument is represented in excess K: the represented value is
+  the written unsigned value minus K, where K is half the maximum for the
+  corresponding unsigned argument.
+===========================================================================*/
+
+/// A single virtual machine instruction.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Instruction(pub u32);
+
+impl Instruction {
+	/// `CREATE_ABCk`
+	pub fn create_abc(op: OpCode, a: ASize, b: BSize, c: CSize, k: KSize) -> Self {
+		Self(
+			(op as u32) << cn::POS_OP
+				| (a as u32) << cn::POS_A
+				| (b as u32) << cn::POS_B
+				| (c as u32) << cn::POS_C
+				| (k as u32) << cn::POS_K,
+		)
+	}
+
+	/// `CREATE_ABx`
+	pub fn create_abx(op: OpCode, a: ASize, bx: u32) -> Self {
+		debug_assert!(bx <= cn::MAXARG_BX);
+		Self((op as u32) << cn::POS_OP | (a as u32) << cn::POS_A | bx << cn::POS_BX)
+	}
+
+	/// `CREATE_ABx` with a signed argument.
+	pub fn create_asbx(op: OpCode, a: ASize, sbx: i32) -> Self {
+		Self::create_abx(op, a, (sbx + cn::OFFSET_SBX) as u32)
+	}
+
+	/// `CREATE_Ax`
+	pub fn create_ax(op: OpCode, ax: u32) -> Self {
+		debug_assert!(ax <= cn::MAXARG_AX);
+		Self((op as u32) << cn::POS_OP | ax << cn::POS_AX)
+	}
+
+	/// `CREATE_sJ`, with the `k` bit (used by `OP_JMP` when extended) clear.
+	pub fn create_sj(op: OpCode, sj: i32) -> Self {
+		let sj = (sj + cn::OFFSET_SJ) as u32;
+		debug_assert!(sj <= cn::MAXARG_SJ);
+		Self((op as u32) << cn::POS_OP | sj << cn::POS_SJ)
+	}
+
+	fn arg(self, position: u8, size: u8) -> u32 {
+		(self.0 >> position) & u32::mask1(size, 0)
+	}
+
+	fn set_arg(&mut self, position: u8, size: u8, value: u32) {
+		self.0 = (self.0 & u32::mask0(size, position))
+			| ((value << position) & u32::mask1(size, position));
+	}
+
+	/// The opcode, if it's a valid one.
+	pub fn opcode(self) -> Option<OpCode> {
+		OpCode::from_opcodeid(self.arg(cn::POS_OP, cn::SIZE_OP) as u8)
+	}
+
+	pub fn a(self) -> ASize {
+		self.arg(cn::POS_A, cn::SIZE_A) as ASize
+	}
+
+	pub fn b(self) -> BSize {
+		self.arg(cn::POS_B, cn::SIZE_B) as BSize
+	}
+
+	pub fn c(self) -> CSize {
+		self.arg(cn::POS_C, cn::SIZE_C) as CSize
+	}
+
+	/// `C`, as a signed argument.
+	pub fn sc(self) -> i32 {
+		self.c() as i32 - cn::OFFSET_SC
+	}
+
+	pub fn k(self) -> KSize {
+		self.arg(cn::POS_K, 1) != 0
+	}
+
+	pub fn bx(self) -> u32 {
+		self.arg(cn::POS_BX, cn::SIZE_BX)
+	}
+
+	pub fn sbx(self) -> i32 {
+		self.bx() as i32 - cn::OFFSET_SBX
+	}
+
+	pub fn ax(self) -> u32 {
+		self.arg(cn::POS_AX, cn::SIZE_AX)
+	}
+
+	pub fn sj(self) -> i32 {
+		self.arg(cn::POS_SJ, cn::SIZE_SJ) as i32 - cn::OFFSET_SJ
+	}
+
+	/// `SET_OPCODE`
+	pub fn set_opcode(&mut self, op: OpCode) {
+		self.set_arg(cn::POS_OP, cn::SIZE_OP, op as u32);
+	}
+
+	pub fn set_a(&mut self, a: ASize) {
+		self.set_arg(cn::POS_A, cn::SIZE_A, a as u32);
+	}
+
+	pub fn set_b(&mut self, b: BSize) {
+		self.set_arg(cn::POS_B, cn::SIZE_B, b as u32);
+	}
+
+	pub fn set_c(&mut self, c: CSize) {
+		self.set_arg(cn::POS_C, cn::SIZE_C, c as u32);
+	}
+
+	pub fn set_k(&mut self, k: KSize) {
+		self.set_arg(cn::POS_K, 1, k as u32);
+	}
+
+	pub fn set_bx(&mut self, bx: u32) {
+		self.set_arg(cn::POS_BX, cn::SIZE_BX, bx);
+	}
+
+	pub fn set_sbx(&mut self, sbx: i32) {
+		self.set_bx((sbx + cn::OFFSET_SBX) as u32);
+	}
+
+	pub fn set_sj(&mut self, sj: i32) {
+		self.set_arg(cn::POS_SJ, cn::SIZE_SJ, (sj + cn::OFFSET_SJ) as u32);
+	}
+}
+
+impl std::fmt::Debug for Instruction {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let Some(op) = self.opcode() else {
+			return write!(f, "Instruction({:#010x})", self.0);
+		};
+		write!(f, "{:<9} ", op.name())?;
+		match op.mode().format() {
+			Some(FormatKind::ABC) => write!(
+				f,
+				"{} {} {}{}",
+				self.a(),
+				self.b(),
+				self.c(),
+				if self.k() { "k" } else { "" }
+			),
+			Some(FormatKind::ABX) => write!(f, "{} {}", self.a(), self.bx()),
+			Some(FormatKind::ASBX) => write!(f, "{} {}", self.a(), self.sbx()),
+			Some(FormatKind::AX) => write!(f, "{}", self.ax()),
+			Some(FormatKind::ISJ) => write!(f, "{}", self.sj()),
+			None => Ok(()),
+		}
+	}
+}
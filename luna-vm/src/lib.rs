@@ -4,9 +4,13 @@
 // Only really useful for this crate, anyway.
 mod cn;
 mod mask;
-mod vm;
 
 mod ops;
 pub use ops::{OpCode, OpCodeId};
 
 pub mod formats;
+pub mod instruction;
+pub mod proto;
+
+#[cfg(test)]
+mod test;
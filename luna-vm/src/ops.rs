@@ -23,7 +23,7 @@ impl std::fmt::Debug for OpMode {
 pub type OpCodeId = u8;
 
 /// Possible operation codes for instructions on the Lua Virtual Machine.
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub enum OpCode {
 	Move,
 	LoadI,
@@ -0,0 +1,187 @@
+//! # Function Prototypes
+//!
+//! The compiled form of a Lua function, like `Proto` in `lobject.h`. The
+//! compiler builds prototypes, and the virtual machine makes closures out of
+//! them.
+
+use crate::instruction::Instruction;
+
+/// How many instructions can go without an absolute line number.
+pub const MAXIWTHABS: usize = 128;
+
+/// The line differences that fit in [Proto::lineinfo].
+const LIMLINEDIFF: isize = 0x80;
+
+/// The [Proto::lineinfo] entry of an instruction whose line is in
+/// [Proto::abslineinfo].
+pub const ABSLINEINFO: i8 = -0x80;
+
+/// A value in the constant table.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Constant {
+	Nil,
+	Boolean(bool),
+	Integer(i64),
+	Float(f64),
+	String(Vec<u8>),
+}
+
+/// The kind of a variable, as in `lparser.h`.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VariableKind {
+	#[default]
+	Regular = 0,
+	Const = 1,
+	ToClose = 2,
+	CompileTimeConstant = 3,
+}
+
+/// Where a closure finds one of its upvalues (`Upvaldesc`).
+#[derive(Clone, Debug, PartialEq)]
+pub struct UpvalueDesc {
+	/// Absent when debug information is stripped
+	pub name: Option<String>,
+	/// Whether the upvalue is a register of the enclosing function, rather
+	/// than one of its upvalues
+	pub instack: bool,
+	/// The index of the register or upvalue
+	pub idx: u8,
+	pub kind: VariableKind,
+}
+
+/// A local variable, for debug information (`LocVar`).
+#[derive(Clone, Debug, PartialEq)]
+pub struct LocVar {
+	pub varname: String,
+	/// The first instruction where the variable is active
+	pub startpc: usize,
+	/// The first instruction where the variable is dead
+	pub endpc: usize,
+}
+
+/// The line of an instruction, stored whole (`AbsLineInfo`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AbsLineInfo {
+	pub pc: usize,
+	pub line: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Proto {
+	/// The number of fixed parameters
+	pub numparams: u8,
+	pub is_vararg: bool,
+	/// The number of registers the function needs
+	pub maxstacksize: u8,
+	pub code: Vec<Instruction>,
+	pub constants: Vec<Constant>,
+	pub upvalues: Vec<UpvalueDesc>,
+	/// The functions defined inside this one
+	pub protos: Vec<Proto>,
+	/// The line of each instruction, as a difference from the line of the
+	/// one before it. Empty when debug information is stripped.
+	pub lineinfo: Vec<i8>,
+	pub abslineinfo: Vec<AbsLineInfo>,
+	pub locvars: Vec<LocVar>,
+	/// Absent when debug information is stripped
+	pub source: Option<String>,
+	/// The line of `function`, or 0 for a main function
+	pub linedefined: usize,
+	pub lastlinedefined: usize,
+}
+
+impl Proto {
+	/// The line of the instruction at `pc` (`luaG_getfuncline`), unless
+	/// there's no line information.
+	pub fn line(&self, pc: usize) -> Option<usize> {
+		if self.lineinfo.is_empty() {
+			return None;
+		}
+		let (basepc, baseline) = self.baseline(pc);
+		let start = basepc.map_or(0, |basepc| basepc + 1);
+		self.lineinfo[start..=pc]
+			.iter()
+			.try_fold(baseline, |line, &dif| line.checked_add_signed(dif as isize))
+	}
+
+	/// The nearest absolute line at or before `pc` (`getbaseline`), and the
+	/// instruction it's for. Without one, it's the line where the function
+	/// was defined, before its first instruction.
+	fn baseline(&self, pc: usize) -> (Option<usize>, usize) {
+		match self.abslineinfo.first() {
+			Some(first) if pc >= first.pc => {
+				// Every MAXIWTHABS instructions have at least one absolute line
+				let mut i = (pc / MAXIWTHABS).saturating_sub(1);
+				while i + 1 < self.abslineinfo.len() && pc >= self.abslineinfo[i + 1].pc {
+					i += 1;
+				}
+				let abs = self.abslineinfo[i];
+				(Some(abs.pc), abs.line)
+			}
+			_ => (None, self.linedefined),
+		}
+	}
+}
+
+/// Adds line information as instructions are added to a [Proto], like
+/// `savelineinfo` in `lcode.c`.
+#[derive(Clone, Debug)]
+pub struct LineWriter {
+	previousline: usize,
+	/// Instructions since the last absolute line
+	iwthabs: usize,
+}
+
+impl LineWriter {
+	/// A writer for the code of a function defined at `linedefined`.
+	pub fn new(linedefined: usize) -> Self {
+		Self {
+			previousline: linedefined,
+			iwthabs: 0,
+		}
+	}
+
+	/// Adds `inst` from `line` to the end of `proto`'s code, returning its
+	/// index.
+	pub fn push(&mut self, proto: &mut Proto, inst: Instruction, line: usize) -> usize {
+		let pc = proto.code.len();
+		proto.code.push(inst);
+		let mut linedif = line as isize - self.previousline as isize;
+		if linedif.abs() >= LIMLINEDIFF || self.iwthabs >= MAXIWTHABS {
+			proto.abslineinfo.push(AbsLineInfo { pc, line });
+			linedif = ABSLINEINFO as isize;
+			self.iwthabs = 0;
+		}
+		self.iwthabs += 1;
+		proto.lineinfo.push(linedif as i8);
+		self.previousline = line;
+		pc
+	}
+
+	/// Removes the last instruction of `proto`'s code, and its line
+	/// (`removelastinstruction`).
+	pub fn pop(&mut self, proto: &mut Proto) -> Option<Instruction> {
+		let inst = proto.code.pop()?;
+		match proto.lineinfo.pop() {
+			Some(ABSLINEINFO) => {
+				proto.abslineinfo.pop();
+				// The line before it is lost, so the next one is stored whole
+				self.iwthabs = MAXIWTHABS + 1;
+			}
+			Some(linedif) => {
+				self.previousline = self.previousline.wrapping_add_signed(-(linedif as isize));
+				self.iwthabs -= 1;
+			}
+			None => {}
+		}
+		Some(inst)
+	}
+
+	/// Changes the line of the last instruction of `proto` (`luaK_fixline`).
+	pub fn fix_line(&mut self, proto: &mut Proto, line: usize) {
+		if let Some(inst) = self.pop(proto) {
+			self.push(proto, inst, line);
+		}
+	}
+}
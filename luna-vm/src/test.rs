@@ -0,0 +1,2 @@
+mod instruction;
+mod proto;
@@ -0,0 +1,39 @@
+use crate::{instruction::Instruction, OpCode};
+
+#[test]
+fn abc() {
+	let mut inst = Instruction::create_abc(OpCode::Add, 1, 2, 255, true);
+	assert!(matches!(inst.opcode(), Some(OpCode::Add)));
+	assert_eq!((inst.a(), inst.b(), inst.c(), inst.k()), (1, 2, 255, true));
+
+	inst.set_b(7);
+	inst.set_k(false);
+	assert_eq!((inst.a(), inst.b(), inst.c(), inst.k()), (1, 7, 255, false));
+	assert_eq!(inst.sc(), 128);
+}
+
+#[test]
+fn signed() {
+	let inst = Instruction::create_asbx(OpCode::LoadI, 3, -65535);
+	assert_eq!((inst.a(), inst.sbx()), (3, -65535));
+	assert_eq!(
+		Instruction::create_asbx(OpCode::LoadI, 0, 65536).sbx(),
+		65536
+	);
+
+	let mut jump = Instruction::create_sj(OpCode::Jmp, -1);
+	assert_eq!(jump.sj(), -1);
+	jump.set_sj(0xffffff);
+	assert_eq!(jump.sj(), 0xffffff);
+	assert!(matches!(jump.opcode(), Some(OpCode::Jmp)));
+}
+
+#[test]
+fn debug() {
+	let inst = Instruction::create_abx(OpCode::LoadK, 0, 5);
+	assert_eq!(format!("{inst:?}"), "LOADK     0 5");
+	assert_eq!(
+		format!("{:?}", Instruction(0x7f)),
+		"Instruction(0x0000007f)"
+	);
+}
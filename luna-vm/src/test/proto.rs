@@ -0,0 +1,71 @@
+use crate::{
+	instruction::Instruction,
+	proto::{AbsLineInfo, LineWriter, Proto, ABSLINEINFO, MAXIWTHABS},
+	OpCode,
+};
+
+/// A prototype defined at `linedefined`, with an instruction from each line.
+fn proto(linedefined: usize, lines: &[usize]) -> Proto {
+	let mut proto = Proto {
+		linedefined,
+		..Proto::default()
+	};
+	let mut writer = LineWriter::new(linedefined);
+	for (pc, &line) in lines.iter().enumerate() {
+		let inst = Instruction::create_abc(OpCode::Move, 0, 0, 0, false);
+		assert_eq!(writer.push(&mut proto, inst, line), pc);
+	}
+	proto
+}
+
+#[test]
+fn relative_lines() {
+	let proto = proto(10, &[10, 11, 11, 9, 200]);
+	assert_eq!(proto.lineinfo, [0, 1, 0, -2, ABSLINEINFO]);
+	// Differences that don't fit in a byte are stored whole
+	assert_eq!(proto.abslineinfo, [AbsLineInfo { pc: 4, line: 200 }]);
+
+	let lines: Vec<_> = (0..5).map(|pc| proto.line(pc)).collect();
+	assert_eq!(lines, [Some(10), Some(11), Some(11), Some(9), Some(200)]);
+}
+
+#[test]
+fn absolute_lines() {
+	// Lines are stored whole at least every MAXIWTHABS instructions
+	let lines: Vec<_> = (0..1000).map(|pc| 1 + pc / 3).collect();
+	let proto = proto(0, &lines);
+	assert_eq!(proto.abslineinfo.len(), 1000 / MAXIWTHABS);
+	assert_eq!(proto.abslineinfo[0], AbsLineInfo { pc: 128, line: 43 });
+
+	for (pc, &line) in lines.iter().enumerate() {
+		assert_eq!(proto.line(pc), Some(line), "{pc}");
+	}
+}
+
+#[test]
+fn stripped() {
+	let mut proto = proto(1, &[1, 2]);
+	proto.lineinfo.clear();
+	proto.abslineinfo.clear();
+	assert_eq!(proto.line(0), None);
+}
+
+#[test]
+fn removed_lines() {
+	let mut proto = Proto::default();
+	let mut writer = LineWriter::new(0);
+	for line in [1, 2, 3] {
+		writer.push(&mut proto, Instruction(0), line);
+	}
+	writer.fix_line(&mut proto, 1);
+	assert_eq!(proto.lineinfo, [1, 1, -1]);
+	assert_eq!(proto.line(2), Some(1));
+
+	// The line before an absolute one is lost with it, so the next is absolute
+	writer.fix_line(&mut proto, 300);
+	assert_eq!(writer.pop(&mut proto), Some(Instruction(0)));
+	writer.push(&mut proto, Instruction(0), 3);
+	assert_eq!(proto.lineinfo, [1, 1, ABSLINEINFO]);
+	assert_eq!(proto.abslineinfo, [AbsLineInfo { pc: 2, line: 3 }]);
+	assert_eq!(proto.line(2), Some(3));
+}
@@ -2,35 +2,69 @@
 
 use std::{
 	env::args,
-	fs::read_to_string,
 	io::{stdin, BufRead, Write},
+	process::ExitCode,
 };
 
-use luna_parser::chunk;
+use luna_ast::Chunk;
+use luna_parser::{chunk, chunk_from_file};
 
-fn main() {
-	let mut args = args();
+const USAGE: &str = "Usage: luna [--dump-ast=json] [script]";
 
-	match args.len() {
-		1 => repl(),
-		2 => {
-			args.next(); // skip program name
-			let spath = args.next().expect("Expected script path");
-			script(&spath)
+/// How parsed chunks are shown.
+#[derive(Clone, Copy)]
+enum Dump {
+	Debug,
+	Json,
+}
+
+impl Dump {
+	fn print(self, chunk: &Chunk) {
+		match self {
+			Self::Debug => println!("{chunk:?}"),
+			Self::Json => println!("{}", serde_json::to_string(chunk).unwrap()),
 		}
-		_ => {
-			eprintln!("Usage: {} [script]", args.next().expect("prog"));
+	}
+}
+
+fn main() -> ExitCode {
+	let mut dump = Dump::Debug;
+	let mut path = None;
+
+	for arg in args().skip(1) {
+		match arg.as_str() {
+			"--dump-ast=json" => dump = Dump::Json,
+			_ if arg.starts_with('-') || path.is_some() => {
+				eprintln!("{USAGE}");
+				return ExitCode::FAILURE;
+			}
+			_ => path = Some(arg),
+		}
+	}
+
+	match path {
+		Some(path) => script(&path, dump),
+		None => {
+			repl(dump);
+			ExitCode::SUCCESS
 		}
 	}
 }
 
-fn script(path: &str) {
-	let data = read_to_string(path).expect("Couldn't open the script (Does it exist?)");
-	let chunk = chunk(&data);
-	println!("{chunk:?}")
+fn script(path: &str, dump: Dump) -> ExitCode {
+	match chunk_from_file(path) {
+		Ok(chunk) => {
+			dump.print(&chunk);
+			ExitCode::SUCCESS
+		}
+		Err(e) => {
+			eprintln!("luna: {}", e.render(path));
+			ExitCode::FAILURE
+		}
+	}
 }
 
-fn repl() {
+fn repl(dump: Dump) {
 	let mut io = stdin().lock();
 	let mut line = String::new();
 
@@ -46,7 +80,9 @@ fn repl() {
 			break;
 		}
 
-		let chunk = chunk(&line);
-		println!("{chunk:?}")
+		match chunk(&line) {
+			Ok(chunk) => dump.print(&chunk),
+			Err(e) => eprintln!("{}", e.render("stdin", line.as_bytes())),
+		}
 	}
 }
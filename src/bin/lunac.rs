@@ -1,3 +1,165 @@
-fn main() {
-	println!("Hello, world!");
+//! Compiles Lua scripts to bytecode, and lists the instructions like `luac -l`.
+
+use std::{
+	env::args,
+	fs,
+	io::{stdin, Read},
+	process::ExitCode,
+};
+
+use luna_parser::{code::compile, load, parse_chunk, ParseOptions};
+use luna_vm::proto::{Constant, Proto};
+
+const USAGE: &str = "Usage: lunac [-l] [-l] [script...]\n\
+	\x20 -l    list the instructions (twice to list constants, locals and upvalues too)\n\
+	\x20 -     read standard input";
+
+fn main() -> ExitCode {
+	let mut listing = 0;
+	let mut paths = Vec::new();
+
+	for arg in args().skip(1) {
+		match arg.as_str() {
+			"-l" => listing += 1,
+			"-h" | "--help" => {
+				println!("{USAGE}");
+				return ExitCode::SUCCESS;
+			}
+			"-" => paths.push(arg),
+			_ if arg.starts_with('-') => {
+				eprintln!("{USAGE}");
+				return ExitCode::FAILURE;
+			}
+			_ => paths.push(arg),
+		}
+	}
+	if paths.is_empty() {
+		eprintln!("{USAGE}");
+		return ExitCode::FAILURE;
+	}
+
+	let mut status = ExitCode::SUCCESS;
+	for path in &paths {
+		let (name, src) = match read(path) {
+			Ok(read) => read,
+			Err(e) => {
+				eprintln!("lunac: {e}");
+				status = ExitCode::FAILURE;
+				continue;
+			}
+		};
+		match script(&name, &src) {
+			Ok(proto) => {
+				if listing > 0 {
+					list(&proto, &name, listing > 1);
+				}
+			}
+			Err(e) => {
+				eprintln!("lunac: {e}");
+				status = ExitCode::FAILURE;
+			}
+		}
+	}
+	status
+}
+
+/// Reads the script at `path`, or standard input for `-`, along with the name
+/// errors give it.
+fn read(path: &str) -> Result<(String, Vec<u8>), String> {
+	let mut src = Vec::new();
+	let (name, read) = match path {
+		"-" => ("stdin", stdin().read_to_end(&mut src).map(|_| ())),
+		_ => (path, fs::read(path).map(|read| src = read)),
+	};
+	match read {
+		Ok(()) => Ok((name.to_owned(), src)),
+		Err(e) => Err(format!("cannot read {name}: {e}")),
+	}
+}
+
+/// Compiles the script `src`, skipping its header like `luaL_loadfilex`.
+fn script(name: &str, src: &[u8]) -> Result<Proto, String> {
+	if load::is_binary(src) {
+		return Err(format!("{name}: precompiled chunks are already compiled"));
+	}
+	let body = &src[load::header_len(src)..];
+	let options = ParseOptions::new().chunk_name(name);
+	let render = |e| options.render(&e, body);
+	let chunk = parse_chunk(body, &options).map_err(render)?.tree;
+	compile(&chunk, body, &format!("@{name}")).map_err(render)
+}
+
+/// Lists `proto` and the functions in it (`PrintFunction` in `luac.c`).
+fn list(proto: &Proto, name: &str, full: bool) {
+	let kind = match proto.linedefined {
+		0 => "main",
+		_ => "function",
+	};
+	let plural = |n: usize| if n == 1 { "" } else { "s" };
+	println!(
+		"\n{kind} <{name}:{},{}> ({} instruction{})",
+		proto.linedefined,
+		proto.lastlinedefined,
+		proto.code.len(),
+		plural(proto.code.len())
+	);
+	println!(
+		"{}{} param{}, {} slot{}, {} upvalue{}, {} local{}, {} constant{}, {} function{}",
+		proto.numparams,
+		if proto.is_vararg { "+" } else { "" },
+		plural(proto.numparams as usize),
+		proto.maxstacksize,
+		plural(proto.maxstacksize as usize),
+		proto.upvalues.len(),
+		plural(proto.upvalues.len()),
+		proto.locvars.len(),
+		plural(proto.locvars.len()),
+		proto.constants.len(),
+		plural(proto.constants.len()),
+		proto.protos.len(),
+		plural(proto.protos.len())
+	);
+	for (pc, inst) in proto.code.iter().enumerate() {
+		let line = proto
+			.line(pc)
+			.map_or("-".to_owned(), |line| line.to_string());
+		println!("\t{}\t[{line}]\t{inst:?}", pc + 1);
+	}
+
+	if full {
+		println!("constants ({}):", proto.constants.len());
+		for (i, k) in proto.constants.iter().enumerate() {
+			println!("\t{i}\t{}", constant(k));
+		}
+		println!("locals ({}):", proto.locvars.len());
+		for (i, var) in proto.locvars.iter().enumerate() {
+			println!(
+				"\t{i}\t{}\t{}\t{}",
+				var.varname,
+				var.startpc + 1,
+				var.endpc + 1
+			);
+		}
+		println!("upvalues ({}):", proto.upvalues.len());
+		for (i, up) in proto.upvalues.iter().enumerate() {
+			let name = up.name.as_deref().unwrap_or("-");
+			println!("\t{i}\t{name}\t{}\t{}", up.instack as u8, up.idx);
+		}
+	}
+
+	for proto in &proto.protos {
+		list(proto, name, full);
+	}
+}
+
+/// A constant the way `luac` shows it, with its type.
+fn constant(k: &Constant) -> String {
+	match k {
+		Constant::Nil => "N\tnil".to_owned(),
+		Constant::Boolean(b) => format!("B\t{b}"),
+		Constant::Integer(i) => format!("I\t{i}"),
+		Constant::Float(f) if f.fract() == 0.0 && f.is_finite() => format!("F\t{f:.1}"),
+		Constant::Float(f) => format!("F\t{f}"),
+		Constant::String(s) => format!("S\t{:?}", String::from_utf8_lossy(s)),
+	}
 }
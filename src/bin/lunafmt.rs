@@ -0,0 +1,136 @@
+//! Formats Lua source files in place, or checks that they're formatted.
+
+use std::{
+	env::args,
+	fs,
+	io::{stdin, stdout, Read, Write},
+	process::ExitCode,
+};
+
+use luna_ast::{
+	print::{format_source, PrintOptions, Quote},
+	span::SpanlessEq,
+};
+use luna_parser::{
+	load::{header_break, header_len},
+	parse_chunk, ParseOptions,
+};
+
+const USAGE: &str = "Usage: lunafmt [--check] [--indent <spaces>] [--width <columns>] \
+	[--single-quotes] [--trailing-commas] [file...]\n\
+	\x20 --width <columns>    split table constructors past this column (80)";
+
+fn main() -> ExitCode {
+	let mut args = args().skip(1);
+	let mut check = false;
+	let mut options = PrintOptions::new();
+	let mut paths = Vec::new();
+
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--check" => check = true,
+			"--indent" => match args.next().and_then(|n| n.parse().ok()) {
+				Some(n) => options = options.indent(" ".repeat(n)),
+				None => return usage(),
+			},
+			"--width" => match args.next().and_then(|n| n.parse().ok()) {
+				Some(n) => options = options.width(n),
+				None => return usage(),
+			},
+			"--single-quotes" => options = options.quote(Quote::Single),
+			"--trailing-commas" => options = options.trailing_separators(),
+			"-h" | "--help" => {
+				println!("{USAGE}");
+				return ExitCode::SUCCESS;
+			}
+			_ if arg.starts_with('-') => return usage(),
+			_ => paths.push(arg),
+		}
+	}
+
+	if paths.is_empty() {
+		return stdio(check, &options);
+	}
+
+	let mut status = ExitCode::SUCCESS;
+	for path in &paths {
+		let src = match fs::read(path) {
+			Ok(src) => src,
+			Err(e) => {
+				eprintln!("lunafmt: cannot read {path}: {e}");
+				status = ExitCode::FAILURE;
+				continue;
+			}
+		};
+		let formatted = match format(&src, path, &options) {
+			Ok(formatted) => formatted,
+			Err(e) => {
+				eprintln!("lunafmt: {e}");
+				status = ExitCode::FAILURE;
+				continue;
+			}
+		};
+		if formatted == src {
+			continue;
+		}
+		if check {
+			println!("{path}: not formatted");
+			status = ExitCode::FAILURE;
+		} else if let Err(e) = fs::write(path, formatted) {
+			eprintln!("lunafmt: cannot write {path}: {e}");
+			status = ExitCode::FAILURE;
+		}
+	}
+	status
+}
+
+fn usage() -> ExitCode {
+	eprintln!("{USAGE}");
+	ExitCode::FAILURE
+}
+
+/// Formats standard input to standard output.
+fn stdio(check: bool, options: &PrintOptions) -> ExitCode {
+	let mut src = Vec::new();
+	if let Err(e) = stdin().read_to_end(&mut src) {
+		eprintln!("lunafmt: cannot read stdin: {e}");
+		return ExitCode::FAILURE;
+	}
+	match format(&src, "stdin", options) {
+		Ok(formatted) if check => match formatted == src {
+			true => ExitCode::SUCCESS,
+			false => {
+				println!("stdin: not formatted");
+				ExitCode::FAILURE
+			}
+		},
+		Ok(formatted) => {
+			stdout().write_all(&formatted).unwrap();
+			ExitCode::SUCCESS
+		}
+		Err(e) => {
+			eprintln!("lunafmt: {e}");
+			ExitCode::FAILURE
+		}
+	}
+}
+
+/// Formats the Lua source `src`, keeping any `#` line at its start.
+fn format(src: &[u8], name: &str, options: &PrintOptions) -> Result<Vec<u8>, String> {
+	let header = header_len(src);
+	let body = &src[header..];
+	let parse_options = ParseOptions::new().chunk_name(name).keep_comments();
+	let parse = parse_chunk(body, &parse_options).map_err(|e| parse_options.render(&e, body))?;
+	let printed = format_source(&parse.tree, &parse.comments, body, options);
+
+	// Never write out source that means something else
+	let reparsed = parse_chunk(printed.as_bytes(), &parse_options);
+	if !reparsed.is_ok_and(|reparsed| reparsed.tree.spanless_eq(&parse.tree)) {
+		return Err(format!("{name}: formatting would change the program"));
+	}
+
+	let mut formatted = src[..header].to_vec();
+	formatted.extend_from_slice(header_break(src));
+	formatted.extend_from_slice(printed.as_bytes());
+	Ok(formatted)
+}